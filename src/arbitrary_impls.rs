@@ -0,0 +1,151 @@
+//! `arbitrary` implementations for types whose values have to uphold invariants.
+//!
+//! Types without invariants derive `Arbitrary` where they are defined.
+
+use crate::read::{Command, RawPayload};
+use crate::{
+    BusErrors, CanFdFrame, CanFrame, ErrorKind, ExtIdentifier, FdFlags, Identifier, SerialNumber,
+    Status, VersionString,
+};
+use arbitrary::{Arbitrary, Error, Result, Unstructured};
+
+impl<'a> Arbitrary<'a> for Identifier {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Identifier::from_raw(u.int_in_range(0..=0x7FF)?).ok_or(Error::IncorrectFormat)
+    }
+}
+
+impl<'a> Arbitrary<'a> for ExtIdentifier {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        ExtIdentifier::from_raw(u.int_in_range(0..=0x1FFF_FFFF)?).ok_or(Error::IncorrectFormat)
+    }
+}
+
+macro_rules! impl_flags {
+    ($($flags:ident),+) => {
+        $(
+            impl<'a> Arbitrary<'a> for $flags {
+                fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+                    Ok($flags::from_bits_truncate(u8::arbitrary(u)?))
+                }
+            }
+        )+
+    };
+}
+
+impl_flags!(Status, BusErrors, FdFlags);
+
+impl<'a> Arbitrary<'a> for SerialNumber {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        const ALPHANUMERIC: &[u8] =
+            b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+        let mut raw = [0; 4];
+        for byte in &mut raw {
+            *byte = *u.choose(ALPHANUMERIC)?;
+        }
+        SerialNumber::new(raw).ok_or(Error::IncorrectFormat)
+    }
+}
+
+impl<'a> Arbitrary<'a> for VersionString {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut raw = [0; VersionString::MAX_LENGTH];
+        let len = u.int_in_range(0..=VersionString::MAX_LENGTH)?;
+        for byte in &mut raw[..len] {
+            *byte = u.int_in_range(b' '..=b'~')?;
+        }
+        VersionString::from_bytes(&raw[..len]).ok_or(Error::IncorrectFormat)
+    }
+}
+
+/// Generates frames with 0 to 8 data bytes. Frames with 8 data bytes may have a DLC of 9 to 15.
+impl<'a> Arbitrary<'a> for CanFrame {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let len = u.int_in_range(0..=CanFrame::MAX_LENGTH)?;
+        let frame = CanFrame::from_slice(u.bytes(len)?).map_err(|_| Error::IncorrectFormat)?;
+        if len == CanFrame::MAX_LENGTH && u.arbitrary()? {
+            frame
+                .with_dlc(u.int_in_range(9..=15)?)
+                .ok_or(Error::IncorrectFormat)
+        } else {
+            Ok(frame)
+        }
+    }
+}
+
+impl<'a> Arbitrary<'a> for CanFdFrame {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let dlc = u.int_in_range(0..=15)?;
+        let len = CanFdFrame::dlc_to_len(dlc).ok_or(Error::IncorrectFormat)?;
+        CanFdFrame::from_slice(u.bytes(len)?).map_err(|_| Error::IncorrectFormat)
+    }
+}
+
+/// Generates printable payloads, which never contain the terminating `CR`.
+impl<'a> Arbitrary<'a> for RawPayload {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut raw = [0; RawPayload::MAX_LENGTH];
+        let len = u.int_in_range(0..=RawPayload::MAX_LENGTH)?;
+        for byte in &mut raw[..len] {
+            *byte = u.int_in_range(b' '..=b'~')?;
+        }
+        RawPayload::from_slice(&raw[..len]).ok_or(Error::IncorrectFormat)
+    }
+}
+
+// The functions below generate fields of derived types that are stored as plain integers, but
+// only have a limited range of valid values.
+
+/// Generates channel numbers of multi-channel devices (0 to 9).
+pub(crate) fn channel(u: &mut Unstructured<'_>) -> Result<u8> {
+    u.int_in_range(0..=9)
+}
+
+/// Generates data length codes of remote frames (0 to 15).
+pub(crate) fn dlc(u: &mut Unstructured<'_>) -> Result<u8> {
+    u.int_in_range(0..=15)
+}
+
+/// Generates bus load percentages (0 to 100).
+pub(crate) fn busload(u: &mut Unstructured<'_>) -> Result<u8> {
+    u.int_in_range(0..=100)
+}
+
+/// Generates millisecond timestamps, which wrap at 60000.
+pub(crate) fn millis(u: &mut Unstructured<'_>) -> Result<u16> {
+    u.int_in_range(0..=0xEA5F)
+}
+
+/// Generates codes of non-standard bitrates: printable characters that are not used by the
+/// standard bitrates `S0` to `S8`.
+pub(crate) fn bitrate_code(u: &mut Unstructured<'_>) -> Result<u8> {
+    u.int_in_range(b'9'..=b'~')
+}
+
+/// Generates the flags of CAN FD commands and notifications. Only `BRS` is conveyed by their
+/// opcode, so `ESI` is never set.
+pub(crate) fn fd_flags(u: &mut Unstructured<'_>) -> Result<FdFlags> {
+    Ok(if u.arbitrary()? {
+        FdFlags::BRS
+    } else {
+        FdFlags::empty()
+    })
+}
+
+/// Generates printable opcodes that are not used by any standard command.
+pub(crate) fn unknown_opcode(u: &mut Unstructured<'_>) -> Result<u8> {
+    let opcodes = || {
+        (b'!'..=b'~').filter(|&opcode| {
+            matches!(Command::decode(&[opcode, b'\r']),
+                Err(e) if e.kind() == ErrorKind::UnsupportedCommand)
+        })
+    };
+    let index = u.int_in_range(0..=opcodes().count() - 1)?;
+    opcodes().nth(index).ok_or(Error::IncorrectFormat)
+}
+
+/// Generates printable opcodes for vendor commands, which may replace standard commands.
+pub(crate) fn vendor_opcode(u: &mut Unstructured<'_>) -> Result<u8> {
+    u.int_in_range(b'!'..=b'~')
+}
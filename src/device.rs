@@ -0,0 +1,1126 @@
+//! High-level support for SLCAN device firmware.
+
+#[cfg(test)]
+mod tests;
+
+use crate::read::{Command, CommandBuf, DecodeOptions};
+use crate::write::{
+    Notification, NotificationBuf, Response, ResponseBuf, Timestamp, TimestampedNotification,
+};
+use crate::{
+    AutoStartup, BitTiming, Bitrate, CanFdFrame, CanFrame, ChannelState, DataBitrate, Error,
+    ExtIdentifier, FdFlags, FilterValue, Identifier, SerialNumber, Status, TimestampMode,
+};
+use core::convert::Infallible;
+use defmt::Format;
+
+#[cfg(target_has_atomic = "8")]
+use core::sync::atomic::{AtomicU8, Ordering};
+
+/// Device-side state machine that enforces the open/closed semantics of the SLCAN protocol.
+///
+/// `SlcanDevice` wraps a `CommandBuf` and keeps track of the state of the CAN channel. Commands
+/// that are invalid in the current state are rejected instead of being passed to the firmware. In
+/// particular:
+///
+/// * Setup commands (bitrate, filters, timestamps, auto-poll, serial baud rate) are only accepted
+///   while the channel is closed.
+/// * Status flags can only be read, and received frames only be polled, while the channel is open.
+/// * The channel can only be opened after a bitrate has been configured.
+/// * CAN frames can only be transmitted while the channel is open in normal mode.
+///
+/// Commands are considered to be executed as soon as they are yielded, so the state changes
+/// immediately.
+///
+/// Like `CommandBuf`, `SlcanDevice::new` only has room for classic CAN commands. Devices that
+/// support CAN FD have to be created with `SlcanDevice::new_fd`.
+///
+/// # Example
+///
+/// ```
+/// use mini_slcan::{ChannelState, ErrorKind, device::SlcanDevice};
+/// use mini_slcan::read::Command;
+///
+/// let mut device = SlcanDevice::new();
+/// let input = b"O\rS6\rO\r";
+/// device.tail_mut()[..input.len()].copy_from_slice(input);
+///
+/// let mut iter = device.advance_by(input.len() as u8);
+/// // `O` is rejected, since no bitrate was configured yet.
+/// assert_eq!(iter.next().unwrap().unwrap_err().kind(), ErrorKind::InvalidState);
+/// assert!(iter.next().unwrap().is_ok());
+/// assert_eq!(iter.next().unwrap().unwrap(), Command::Open);
+/// drop(iter);
+///
+/// assert_eq!(device.state(), ChannelState::Open);
+/// ```
+#[derive(Debug)]
+pub struct SlcanDevice<B = [u8; CommandBuf::LEN]> {
+    buf: CommandBuf<B>,
+    state: DeviceState,
+}
+
+#[derive(Copy, Clone, Debug, Format)]
+struct DeviceState {
+    channel: ChannelState,
+    timing: Option<BitTiming>,
+    acceptance_code: FilterValue,
+    acceptance_mask: FilterValue,
+    timestamps: TimestampMode,
+}
+
+impl DeviceState {
+    const fn new() -> Self {
+        Self {
+            channel: ChannelState::Closed,
+            timing: None,
+            acceptance_code: FilterValue::from_raw(0),
+            acceptance_mask: FilterValue::from_raw(0xFFFF_FFFF),
+            timestamps: TimestampMode::Off,
+        }
+    }
+
+    fn permits(&self, cmd: &Command) -> bool {
+        self.channel.permits(cmd, self.timing.is_some())
+    }
+
+    /// Applies the state change caused by executing `cmd`.
+    fn apply(&mut self, cmd: &Command) {
+        match *cmd {
+            Command::SetupWithBitrate { bitrate } => {
+                self.timing = Some(BitTiming::Bitrate(bitrate))
+            }
+            Command::SetupWithBtr { btr0, btr1 } => {
+                self.timing = Some(BitTiming::Btr { btr0, btr1 })
+            }
+            Command::SetAcceptanceCode(code) => self.acceptance_code = code,
+            Command::SetAcceptanceMask(mask) => self.acceptance_mask = mask,
+            Command::SetRxTimestamp { mode } => self.timestamps = mode,
+            Command::Open => self.channel = ChannelState::Open,
+            Command::OpenListenOnly => self.channel = ChannelState::ListenOnly,
+            Command::Close => self.channel = ChannelState::Closed,
+            _ => {}
+        }
+    }
+
+    fn execute(&mut self, cmd: Command) -> Result<Command, Error> {
+        if !self.permits(&cmd) {
+            return Err(Error::invalid_state());
+        }
+
+        self.apply(&cmd);
+        Ok(cmd)
+    }
+
+    fn persist(
+        &self,
+        auto_startup: AutoStartup,
+        storage: &mut dyn ConfigStorage,
+    ) -> Result<(), Error> {
+        let timing = self.timing.ok_or_else(Error::invalid_state)?;
+        storage
+            .save(&StoredConfig {
+                timing,
+                acceptance_code: self.acceptance_code,
+                acceptance_mask: self.acceptance_mask,
+                auto_startup,
+            })
+            .map_err(|Rejected| Error::rejected())
+    }
+
+    fn restore(&mut self, storage: &mut dyn ConfigStorage) -> Option<StoredConfig> {
+        let config = storage.load()?;
+        self.timing = Some(config.timing);
+        self.acceptance_code = config.acceptance_code;
+        self.acceptance_mask = config.acceptance_mask;
+        self.channel = match config.auto_startup {
+            AutoStartup::Off => ChannelState::Closed,
+            AutoStartup::OpenNormal => ChannelState::Open,
+            AutoStartup::OpenListenOnly => ChannelState::ListenOnly,
+        };
+        Some(config)
+    }
+}
+
+/// Device configuration that is persisted by the `SetAutoStartup` command (`Qn`).
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Format)]
+pub struct StoredConfig {
+    pub timing: BitTiming,
+    pub acceptance_code: FilterValue,
+    pub acceptance_mask: FilterValue,
+    /// Whether and how the channel is opened after power-up.
+    pub auto_startup: AutoStartup,
+}
+
+/// Non-volatile storage for the device configuration, such as flash or EEPROM.
+///
+/// A `ConfigStorage` is used to persist the configuration when the host sends a `SetAutoStartup`
+/// command, and to restore it on power-up (see `SlcanDevice::restore`).
+pub trait ConfigStorage {
+    /// Loads the stored configuration, or returns `None` if none has been stored.
+    fn load(&mut self) -> Option<StoredConfig>;
+
+    /// Stores `config`, replacing any previously stored configuration.
+    fn save(&mut self, config: &StoredConfig) -> Result<(), Rejected>;
+}
+
+impl SlcanDevice {
+    /// Creates a new `SlcanDevice` with a closed, unconfigured channel.
+    pub const fn new() -> Self {
+        Self::with_options(DecodeOptions::new())
+    }
+
+    /// Creates a new `SlcanDevice` that decodes commands using the given `DecodeOptions`.
+    pub const fn with_options(options: DecodeOptions) -> Self {
+        Self {
+            buf: CommandBuf::with_options(options),
+            state: DeviceState::new(),
+        }
+    }
+}
+
+impl SlcanDevice<[u8; CommandBuf::FD_LEN]> {
+    /// Creates a new `SlcanDevice` that accepts CAN FD commands.
+    pub const fn new_fd() -> Self {
+        Self::with_options_fd(DecodeOptions::new())
+    }
+
+    /// Creates a new `SlcanDevice` that accepts CAN FD commands and decodes commands using the
+    /// given `DecodeOptions`.
+    pub const fn with_options_fd(options: DecodeOptions) -> Self {
+        Self {
+            buf: CommandBuf::with_options_fd(options),
+            state: DeviceState::new(),
+        }
+    }
+}
+
+impl<B: AsRef<[u8]> + AsMut<[u8]>> SlcanDevice<B> {
+    /// Returns the current state of the CAN channel.
+    pub fn state(&self) -> ChannelState {
+        self.state.channel
+    }
+
+    /// Returns whether a bitrate has been configured.
+    pub fn is_configured(&self) -> bool {
+        self.state.timing.is_some()
+    }
+
+    /// Returns the bit timing configured by the host.
+    pub fn bit_timing(&self) -> Option<BitTiming> {
+        self.state.timing
+    }
+
+    /// Returns the acceptance code and mask configured by the host.
+    ///
+    /// By default, the acceptance code is 0 and the mask is `0xFFFFFFFF`, accepting all frames.
+    pub fn acceptance_filter(&self) -> (FilterValue, FilterValue) {
+        (self.state.acceptance_code, self.state.acceptance_mask)
+    }
+
+    /// Saves the current configuration to `storage`, as requested by a `SetAutoStartup` command.
+    ///
+    /// Returns an error of kind `InvalidState` if no bitrate has been configured, and of kind
+    /// `Rejected` if `storage` fails to save the configuration.
+    pub fn persist(
+        &self,
+        auto_startup: AutoStartup,
+        storage: &mut dyn ConfigStorage,
+    ) -> Result<(), Error> {
+        self.state.persist(auto_startup, storage)
+    }
+
+    /// Restores the configuration from `storage`, which should be done on power-up.
+    ///
+    /// If the stored configuration has auto-startup enabled, the channel is considered open
+    /// afterwards. The restored configuration is returned, so that the firmware can apply it to
+    /// the CAN peripheral.
+    pub fn restore(&mut self, storage: &mut dyn ConfigStorage) -> Option<StoredConfig> {
+        self.state.restore(storage)
+    }
+
+    /// Returns the timestamp mode configured by the host.
+    pub fn timestamp_mode(&self) -> TimestampMode {
+        self.state.timestamps
+    }
+
+    /// Returns the currently unused part of the buffer.
+    ///
+    /// See `CommandBuf::tail_mut`.
+    pub fn tail_mut(&mut self) -> &mut [u8] {
+        self.buf.tail_mut()
+    }
+
+    /// Marks `len` more bytes from the buffer's tail as consumed, and returns an iterator over all
+    /// `Command`s in the buffer that are valid in the current state.
+    ///
+    /// Commands that are invalid in the current state are yielded as errors of kind
+    /// `InvalidState`. Like commands that fail to decode, they must be answered with
+    /// `Response::Error`.
+    pub fn advance_by(&mut self, amount: u8) -> impl Iterator<Item = Result<Command, Error>> + '_ {
+        let state = &mut self.state;
+        self.buf
+            .advance_by(amount)
+            .map(move |res| res.and_then(|cmd| state.execute(cmd)))
+    }
+}
+
+impl Default for SlcanDevice {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returned by `SlcanHandler` callbacks to reject a command.
+///
+/// Rejected commands are answered with `Response::Error`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Format)]
+pub struct Rejected;
+
+/// Callbacks invoked by a `Dispatcher` for each command received from the host.
+///
+/// Every callback has a default implementation that rejects the command, so implementors only need
+/// to provide the callbacks for the functionality they support. Commands without a dedicated
+/// callback are passed to `on_other`.
+///
+/// Callbacks are only invoked for commands that are valid in the current channel state (see
+/// `SlcanDevice`).
+pub trait SlcanHandler {
+    /// Configures one of the predefined bitrates (`Sn`).
+    fn on_setup(&mut self, bitrate: Bitrate) -> Result<(), Rejected> {
+        let _ = bitrate;
+        Err(Rejected)
+    }
+
+    /// Configures custom bit timing using raw BTR0/BTR1 register values (`sxxyy`).
+    fn on_setup_btr(&mut self, btr0: u8, btr1: u8) -> Result<(), Rejected> {
+        let _ = (btr0, btr1);
+        Err(Rejected)
+    }
+
+    /// Configures the bitrate of the CAN FD data phase (`Yn`).
+    fn on_setup_data_bitrate(&mut self, bitrate: DataBitrate) -> Result<(), Rejected> {
+        let _ = bitrate;
+        Err(Rejected)
+    }
+
+    /// Opens the CAN channel in normal (`O`) or listen-only mode (`L`).
+    fn on_open(&mut self, listen_only: bool) -> Result<(), Rejected> {
+        let _ = listen_only;
+        Err(Rejected)
+    }
+
+    /// Closes the CAN channel (`C`).
+    fn on_close(&mut self) -> Result<(), Rejected> {
+        Err(Rejected)
+    }
+
+    /// Transmits a standard data frame (`t`).
+    fn on_tx(&mut self, identifier: Identifier, frame: &CanFrame) -> Result<(), Rejected> {
+        let _ = (identifier, frame);
+        Err(Rejected)
+    }
+
+    /// Transmits an extended data frame (`T`).
+    fn on_tx_ext(&mut self, identifier: ExtIdentifier, frame: &CanFrame) -> Result<(), Rejected> {
+        let _ = (identifier, frame);
+        Err(Rejected)
+    }
+
+    /// Transmits a standard remote frame (`r`).
+    fn on_tx_rtr(&mut self, identifier: Identifier, len: u8) -> Result<(), Rejected> {
+        let _ = (identifier, len);
+        Err(Rejected)
+    }
+
+    /// Transmits an extended remote frame (`R`).
+    fn on_tx_ext_rtr(&mut self, identifier: ExtIdentifier, len: u8) -> Result<(), Rejected> {
+        let _ = (identifier, len);
+        Err(Rejected)
+    }
+
+    /// Transmits a standard CAN FD frame (`d`, or `b` if `FdFlags::BRS` is set).
+    fn on_tx_fd(
+        &mut self,
+        identifier: Identifier,
+        frame: &CanFdFrame,
+        flags: FdFlags,
+    ) -> Result<(), Rejected> {
+        let _ = (identifier, frame, flags);
+        Err(Rejected)
+    }
+
+    /// Transmits an extended CAN FD frame (`D`, or `B` if `FdFlags::BRS` is set).
+    fn on_tx_ext_fd(
+        &mut self,
+        identifier: ExtIdentifier,
+        frame: &CanFdFrame,
+        flags: FdFlags,
+    ) -> Result<(), Rejected> {
+        let _ = (identifier, frame, flags);
+        Err(Rejected)
+    }
+
+    /// Configures the timestamp mode of received frames (`Zn`).
+    fn on_set_timestamps(&mut self, mode: TimestampMode) -> Result<(), Rejected> {
+        let _ = mode;
+        Err(Rejected)
+    }
+
+    /// Sets the acceptance code register (`Mxxxxxxxx`).
+    fn on_set_acceptance_code(&mut self, code: FilterValue) -> Result<(), Rejected> {
+        let _ = code;
+        Err(Rejected)
+    }
+
+    /// Sets the acceptance mask register (`mxxxxxxxx`).
+    fn on_set_acceptance_mask(&mut self, mask: FilterValue) -> Result<(), Rejected> {
+        let _ = mask;
+        Err(Rejected)
+    }
+
+    /// Returns the status flags (`F`).
+    fn on_read_status(&mut self) -> Result<Status, Rejected> {
+        Err(Rejected)
+    }
+
+    /// Returns the hardware and software version (`V`).
+    fn on_read_version(&mut self) -> Result<(u8, u8), Rejected> {
+        Err(Rejected)
+    }
+
+    /// Returns the serial number (`N`).
+    fn on_read_serial(&mut self) -> Result<SerialNumber, Rejected> {
+        Err(Rejected)
+    }
+
+    /// Returns the queue that frames to transmit are added to.
+    ///
+    /// If this returns a `TxQueue`, transmit commands are added to it instead of invoking `on_tx`
+    /// and the other transmit callbacks, and are rejected while the queue is full. The queue's
+    /// `TX_FIFO_FULL` flag is also added to the status returned by `on_read_status`.
+    ///
+    /// By default, this returns `None`.
+    fn tx_queue(&mut self) -> Option<&mut TxQueue> {
+        None
+    }
+
+    /// Returns the next message to send to the host unprompted.
+    ///
+    /// This is used by `Dispatcher::poll` to forward notifications about received CAN frames
+    /// (for example, from `RxQueue::pop_pending`). It is called repeatedly until it returns `None`,
+    /// which is the default.
+    fn next_outgoing(&mut self) -> Option<Outgoing> {
+        None
+    }
+
+    /// Returns the storage used to persist the configuration when a `SetAutoStartup` command is
+    /// received.
+    ///
+    /// If this returns `None` (the default), `SetAutoStartup` commands are rejected.
+    fn config_storage(&mut self) -> Option<&mut dyn ConfigStorage> {
+        None
+    }
+
+    /// Handles any command without a dedicated callback, and returns the response to send.
+    fn on_other(&mut self, cmd: Command) -> Result<Response, Rejected> {
+        let _ = cmd;
+        Err(Rejected)
+    }
+}
+
+/// Decodes commands and invokes the matching `SlcanHandler` callbacks.
+///
+/// `Dispatcher` wraps an `SlcanDevice`, so commands that are invalid in the current channel state
+/// are rejected without invoking any callback.
+/// Devices that support CAN FD have to be created with `Dispatcher::new_fd`.
+///
+/// # Example
+///
+/// ```
+/// use mini_slcan::Bitrate;
+/// use mini_slcan::device::{Dispatcher, Rejected, SlcanHandler};
+/// use mini_slcan::write::Response;
+///
+/// struct Handler;
+///
+/// impl SlcanHandler for Handler {
+///     fn on_setup(&mut self, _: Bitrate) -> Result<(), Rejected> {
+///         Ok(())
+///     }
+/// }
+///
+/// let mut handler = Handler;
+/// let mut dispatcher = Dispatcher::new();
+/// let input = b"S6\rV\r";
+/// dispatcher.tail_mut()[..input.len()].copy_from_slice(input);
+///
+/// let mut responses = dispatcher.advance_by(input.len() as u8, &mut handler);
+/// assert_eq!(responses.next(), Some(Response::Ack));
+/// assert_eq!(responses.next(), Some(Response::Error));
+/// assert_eq!(responses.next(), None);
+/// ```
+#[derive(Debug)]
+pub struct Dispatcher<B = [u8; CommandBuf::LEN]> {
+    device: SlcanDevice<B>,
+}
+
+impl Dispatcher {
+    /// Creates a new `Dispatcher` with a closed, unconfigured channel.
+    pub const fn new() -> Self {
+        Self::with_options(DecodeOptions::new())
+    }
+
+    /// Creates a new `Dispatcher` that decodes commands using the given `DecodeOptions`.
+    pub const fn with_options(options: DecodeOptions) -> Self {
+        Self {
+            device: SlcanDevice::with_options(options),
+        }
+    }
+}
+
+impl Dispatcher<[u8; CommandBuf::FD_LEN]> {
+    /// Creates a new `Dispatcher` that accepts CAN FD commands.
+    pub const fn new_fd() -> Self {
+        Self::with_options_fd(DecodeOptions::new())
+    }
+
+    /// Creates a new `Dispatcher` that accepts CAN FD commands and decodes commands using the
+    /// given `DecodeOptions`.
+    pub const fn with_options_fd(options: DecodeOptions) -> Self {
+        Self {
+            device: SlcanDevice::with_options_fd(options),
+        }
+    }
+}
+
+impl<B: AsRef<[u8]> + AsMut<[u8]>> Dispatcher<B> {
+    /// Returns the wrapped `SlcanDevice`, which tracks the channel state.
+    pub fn device(&self) -> &SlcanDevice<B> {
+        &self.device
+    }
+
+    /// Restores the configuration from `storage`, which should be done on power-up.
+    ///
+    /// See `SlcanDevice::restore`.
+    pub fn restore(&mut self, storage: &mut dyn ConfigStorage) -> Option<StoredConfig> {
+        self.device.restore(storage)
+    }
+
+    /// Returns the currently unused part of the buffer.
+    ///
+    /// See `CommandBuf::tail_mut`.
+    pub fn tail_mut(&mut self) -> &mut [u8] {
+        self.device.tail_mut()
+    }
+
+    /// Marks `len` more bytes from the buffer's tail as consumed, dispatches all commands in the
+    /// buffer to `handler`, and returns an iterator over the responses to send to the host.
+    ///
+    /// Commands are dispatched lazily, as the iterator is advanced. Commands that fail to decode,
+    /// are invalid in the current state, or are rejected by `handler` are answered with
+    /// `Response::Error`. Rejected commands do not change the channel state.
+    pub fn advance_by<'a, H: SlcanHandler>(
+        &'a mut self,
+        amount: u8,
+        handler: &'a mut H,
+    ) -> impl Iterator<Item = Response> + 'a {
+        let state = &mut self.device.state;
+        self.device.buf.advance_by(amount).map(move |res| {
+            let cmd = match res {
+                Ok(cmd) if state.permits(&cmd) => cmd,
+                _ => return Response::Error,
+            };
+
+            if let Command::SetAutoStartup(mode) = cmd {
+                return match handler.config_storage() {
+                    Some(storage) => match state.persist(mode, storage) {
+                        Ok(()) => Response::Ack,
+                        Err(_) => Response::Error,
+                    },
+                    None => Response::Error,
+                };
+            }
+
+            // The state only changes if the handler accepts the command.
+            let mut next = *state;
+            next.apply(&cmd);
+            let resp = dispatch(handler, cmd);
+            if resp != Response::Error {
+                *state = next;
+            }
+            resp
+        })
+    }
+
+    /// Performs one iteration of the device's main loop.
+    ///
+    /// This reads available bytes from `transport`, dispatches all complete commands to `handler`
+    /// and writes the responses back to `transport`. Afterwards, all messages returned by
+    /// `SlcanHandler::next_outgoing` are written to `transport`.
+    ///
+    /// Messages that can not be encoded (because they contain out-of-range values) are skipped.
+    pub fn poll<T: Transport, H: SlcanHandler>(
+        &mut self,
+        transport: &mut T,
+        handler: &mut H,
+    ) -> Result<(), T::Error> {
+        let amount = transport.read(self.tail_mut())?;
+
+        let mut resp_buf = ResponseBuf::new();
+        for resp in self.advance_by(amount as u8, handler) {
+            if let Ok(bytes) = resp.encode(&mut resp_buf) {
+                transport.write(bytes)?;
+            }
+        }
+
+        // The handler may return CAN FD notifications.
+        let mut notif_buf = NotificationBuf::new_fd();
+        while let Some(outgoing) = handler.next_outgoing() {
+            let encoded = match outgoing {
+                Outgoing::Response(resp) => resp.encode(&mut resp_buf),
+                Outgoing::Notification {
+                    notif,
+                    timestamp: None,
+                } => notif.encode(&mut notif_buf),
+                Outgoing::Notification {
+                    notif,
+                    timestamp: Some(timestamp),
+                } => {
+                    TimestampedNotification::with_timestamp(notif, timestamp).encode(&mut notif_buf)
+                }
+            };
+
+            if let Ok(bytes) = encoded {
+                transport.write(bytes)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs the device's main loop, by calling `poll` forever.
+    ///
+    /// This only returns when `transport` fails.
+    pub fn run<T: Transport, H: SlcanHandler>(
+        &mut self,
+        transport: &mut T,
+        handler: &mut H,
+    ) -> Result<Infallible, T::Error> {
+        loop {
+            self.poll(transport, handler)?;
+        }
+    }
+}
+
+impl Default for Dispatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A bidirectional byte stream connecting the device to the host, such as a UART or USB serial
+/// port.
+pub trait Transport {
+    type Error;
+
+    /// Reads received bytes into `buf` and returns how many bytes were read.
+    ///
+    /// This may return 0 if no bytes are available.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error>;
+
+    /// Writes all of `bytes` to the host.
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error>;
+}
+
+/// Invokes the `handler` callback for `cmd` and returns the response.
+fn dispatch<H: SlcanHandler>(handler: &mut H, cmd: Command) -> Response {
+    if is_transmit(&cmd) {
+        if let Some(queue) = handler.tx_queue() {
+            let result = queue.push(cmd.clone());
+            return Response::for_result(&cmd, result).unwrap_or(Response::Error);
+        }
+    }
+
+    let result = match &cmd {
+        Command::SetupWithBitrate { bitrate } => handler.on_setup(*bitrate),
+        Command::SetupWithBtr { btr0, btr1 } => handler.on_setup_btr(*btr0, *btr1),
+        Command::SetupDataBitrate { bitrate } => handler.on_setup_data_bitrate(*bitrate),
+        Command::Open => handler.on_open(false),
+        Command::OpenListenOnly => handler.on_open(true),
+        Command::Close => handler.on_close(),
+        Command::TxStandard { identifier, frame } => handler.on_tx(*identifier, frame),
+        Command::TxExt { identifier, frame } => handler.on_tx_ext(*identifier, frame),
+        Command::TxStandardRtr { identifier, len } => handler.on_tx_rtr(*identifier, *len),
+        Command::TxExtRtr { identifier, len } => handler.on_tx_ext_rtr(*identifier, *len),
+        Command::TxFd {
+            identifier,
+            frame,
+            flags,
+        } => handler.on_tx_fd(*identifier, frame, *flags),
+        Command::TxExtFd {
+            identifier,
+            frame,
+            flags,
+        } => handler.on_tx_ext_fd(*identifier, frame, *flags),
+        Command::SetRxTimestamp { mode } => handler.on_set_timestamps(*mode),
+        Command::SetAcceptanceCode(code) => handler.on_set_acceptance_code(*code),
+        Command::SetAcceptanceMask(mask) => handler.on_set_acceptance_mask(*mask),
+        Command::ReadStatus => {
+            let queue_status = handler.tx_queue().map_or(Status::empty(), |q| q.status());
+            return handler.on_read_status().map_or(Response::Error, |status| {
+                Response::Status(status | queue_status)
+            });
+        }
+        Command::ReadVersion => {
+            return handler.on_read_version().map_or(
+                Response::Error,
+                |(hardware_version, software_version)| Response::Version {
+                    hardware_version,
+                    software_version,
+                },
+            )
+        }
+        Command::ReadSerial => {
+            return handler
+                .on_read_serial()
+                .map_or(Response::Error, Response::Serial)
+        }
+        _ => return handler.on_other(cmd).unwrap_or(Response::Error),
+    };
+
+    // All commands handled above are answered without data, so `for_result` always returns a
+    // response for them.
+    Response::for_result(&cmd, result).unwrap_or(Response::Error)
+}
+
+/// A message sent by the device to the host.
+#[derive(Debug, Format)]
+pub enum Outgoing {
+    /// A response to a command.
+    Response(Response),
+
+    /// A notification about a received CAN frame or bus error.
+    Notification {
+        notif: Notification,
+        /// The timestamp of the notification, if timestamps are enabled.
+        timestamp: Option<Timestamp>,
+    },
+}
+
+/// Buffers received CAN frames according to the auto-poll setting (`Xn`).
+///
+/// When auto-poll is enabled (the default), received frames are forwarded to the host
+/// immediately. When it is disabled, they are queued until the host polls for them via `PollOne`
+/// (`P`) or `PollAll` (`A`).
+///
+/// # Example
+///
+/// ```
+/// use mini_slcan::device::{Outgoing, RxQueue};
+/// use mini_slcan::write::{Notification, Response};
+/// use mini_slcan::Identifier;
+///
+/// let notif = || Notification::Rx {
+///     identifier: Identifier::from_raw(0x123).unwrap(),
+///     frame: [0xAA].into(),
+/// };
+///
+/// let mut queue = RxQueue::new();
+/// queue.set_auto_poll(false);
+/// assert!(queue.receive(notif(), None).is_none());
+/// assert!(queue.receive(notif(), None).is_none());
+///
+/// let mut output = queue.poll_all();
+/// assert!(matches!(output.next(), Some(Outgoing::Notification { .. })));
+/// assert!(matches!(output.next(), Some(Outgoing::Notification { .. })));
+/// assert!(matches!(output.next(), Some(Outgoing::Response(Response::PollAllEnd))));
+/// assert!(output.next().is_none());
+/// ```
+#[derive(Debug, Format)]
+pub struct RxQueue {
+    frames: [Option<(Notification, Option<Timestamp>)>; RxQueue::CAPACITY],
+    /// Index of the oldest frame.
+    head: u8,
+    len: u8,
+    auto_poll: bool,
+    overrun: bool,
+}
+
+impl RxQueue {
+    /// The maximum number of frames that can be queued while auto-poll is disabled.
+    pub const CAPACITY: usize = 8;
+
+    /// Creates an empty `RxQueue` with auto-poll enabled.
+    pub const fn new() -> Self {
+        const EMPTY: Option<(Notification, Option<Timestamp>)> = None;
+        Self {
+            frames: [EMPTY; Self::CAPACITY],
+            head: 0,
+            len: 0,
+            auto_poll: true,
+            overrun: false,
+        }
+    }
+
+    /// Returns whether auto-poll is enabled, in which case received frames are not queued.
+    pub fn auto_poll(&self) -> bool {
+        self.auto_poll
+    }
+
+    /// Enables or disables auto-poll, as requested by the `SetAutoPoll` command.
+    ///
+    /// Frames that are queued when auto-poll is enabled are forwarded to the host before any newly
+    /// received frame. Since they can no longer be polled, `pop_pending` has to be called to
+    /// forward them even if no more frames are received.
+    pub fn set_auto_poll(&mut self, enabled: bool) {
+        self.auto_poll = enabled;
+    }
+
+    /// Returns the oldest queued frame if auto-poll is enabled.
+    ///
+    /// This forwards the frames that were queued while auto-poll was disabled, and is meant to be
+    /// called from `SlcanHandler::next_outgoing`. Returns `None` if no frame is queued or auto-poll
+    /// is disabled, in which case the host has to poll for the frames.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_slcan::device::{Outgoing, RxQueue};
+    /// use mini_slcan::write::Notification;
+    /// use mini_slcan::Identifier;
+    ///
+    /// let mut queue = RxQueue::new();
+    /// queue.set_auto_poll(false);
+    /// let notif = Notification::Rx {
+    ///     identifier: Identifier::from_raw(0x123).unwrap(),
+    ///     frame: [0xAA].into(),
+    /// };
+    /// assert!(queue.receive(notif, None).is_none());
+    /// assert!(queue.pop_pending().is_none());
+    ///
+    /// queue.set_auto_poll(true);
+    /// assert!(matches!(queue.pop_pending(), Some(Outgoing::Notification { .. })));
+    /// assert!(queue.pop_pending().is_none());
+    /// ```
+    pub fn pop_pending(&mut self) -> Option<Outgoing> {
+        if self.auto_poll {
+            self.pop()
+        } else {
+            None
+        }
+    }
+
+    /// Returns the number of queued frames.
+    pub fn len(&self) -> usize {
+        usize::from(self.len)
+    }
+
+    /// Returns whether no frames are queued.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns whether the queue is full, which should be reported to the host via
+    /// `Status::RX_FIFO_FULL`.
+    pub fn is_full(&self) -> bool {
+        self.len() == Self::CAPACITY
+    }
+
+    /// Returns whether a frame was dropped because the queue was full, and resets the flag.
+    ///
+    /// This should be reported to the host via `Status::DATA_OVERRUN`.
+    pub fn take_overrun(&mut self) -> bool {
+        core::mem::replace(&mut self.overrun, false)
+    }
+
+    /// Processes a notification about a received frame or bus error.
+    ///
+    /// If auto-poll is enabled, this returns the next notification to send to the host
+    /// immediately. Otherwise, the notification is queued and `None` is returned. When the queue
+    /// is full, the notification is dropped and the overrun flag is set.
+    pub fn receive(
+        &mut self,
+        notif: Notification,
+        timestamp: Option<Timestamp>,
+    ) -> Option<Outgoing> {
+        if self.auto_poll && self.is_empty() {
+            return Some(Outgoing::Notification { notif, timestamp });
+        }
+
+        if self.is_full() {
+            if !self.auto_poll {
+                self.overrun = true;
+                return None;
+            }
+
+            // Make room by forwarding the oldest queued frame.
+            let oldest = self.pop();
+            self.push(notif, timestamp);
+            return oldest;
+        }
+
+        self.push(notif, timestamp);
+        if self.auto_poll {
+            self.pop()
+        } else {
+            None
+        }
+    }
+
+    /// Handles the `PollOne` command (`P`).
+    ///
+    /// Returns the oldest queued frame, or `Response::Ack` if no frame is queued. If auto-poll is
+    /// enabled, polling is not allowed and `Response::Error` is returned.
+    pub fn poll_one(&mut self) -> Outgoing {
+        if self.auto_poll {
+            return Outgoing::Response(Response::Error);
+        }
+
+        self.pop().unwrap_or(Outgoing::Response(Response::Ack))
+    }
+
+    /// Handles the `PollAll` command (`A`).
+    ///
+    /// Returns an iterator over all queued frames, followed by `Response::PollAllEnd`. If auto-poll
+    /// is enabled, polling is not allowed and the iterator only yields `Response::Error`.
+    pub fn poll_all(&mut self) -> impl Iterator<Item = Outgoing> + '_ {
+        PollAll {
+            queue: self,
+            done: false,
+        }
+    }
+
+    fn push(&mut self, notif: Notification, timestamp: Option<Timestamp>) {
+        let index = (self.head + self.len) % Self::CAPACITY as u8;
+        self.frames[usize::from(index)] = Some((notif, timestamp));
+        self.len += 1;
+    }
+
+    fn pop(&mut self) -> Option<Outgoing> {
+        let (notif, timestamp) = self.frames[usize::from(self.head)].take()?;
+        self.head = (self.head + 1) % Self::CAPACITY as u8;
+        self.len -= 1;
+        Some(Outgoing::Notification { notif, timestamp })
+    }
+}
+
+impl Default for RxQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct PollAll<'a> {
+    queue: &'a mut RxQueue,
+    done: bool,
+}
+
+impl Iterator for PollAll<'_> {
+    type Item = Outgoing;
+
+    fn next(&mut self) -> Option<Outgoing> {
+        if self.done {
+            return None;
+        }
+
+        if self.queue.auto_poll {
+            self.done = true;
+            return Some(Outgoing::Response(Response::Error));
+        }
+
+        self.queue.pop().or_else(|| {
+            self.done = true;
+            Some(Outgoing::Response(Response::PollAllEnd))
+        })
+    }
+}
+
+/// Returns whether `cmd` requests the transmission of a CAN frame.
+fn is_transmit(cmd: &Command) -> bool {
+    matches!(
+        cmd,
+        Command::TxStandard { .. }
+            | Command::TxExt { .. }
+            | Command::TxStandardRtr { .. }
+            | Command::TxExtRtr { .. }
+            | Command::TxFd { .. }
+            | Command::TxExtFd { .. }
+    )
+}
+
+/// A bounded queue of frames waiting to be transmitted on the CAN bus.
+///
+/// The queue stores the transmit commands received from the host (`TxStandard`, `TxExt`,
+/// `TxStandardRtr`, `TxExtRtr`, `TxFd` and `TxExtFd`). When it is full, new commands are rejected
+/// and the `TX_FIFO_FULL` status flag is reported.
+///
+/// A `TxQueue` can be returned from `SlcanHandler::tx_queue` to let the `Dispatcher` fill it.
+///
+/// # Example
+///
+/// ```
+/// use mini_slcan::device::TxQueue;
+/// use mini_slcan::read::Command;
+/// use mini_slcan::Status;
+///
+/// let mut queue = TxQueue::new();
+/// while !queue.is_full() {
+///     queue.push(Command::decode(b"t1230\r").unwrap()).unwrap();
+/// }
+/// assert_eq!(queue.status(), Status::TX_FIFO_FULL);
+/// assert!(queue.push(Command::decode(b"t1230\r").unwrap()).is_err());
+///
+/// // Transmit the oldest frame to make room.
+/// let _frame = queue.pop().unwrap();
+/// assert_eq!(queue.status(), Status::empty());
+/// ```
+#[derive(Debug, Format)]
+pub struct TxQueue {
+    frames: [Option<Command>; TxQueue::CAPACITY],
+    /// Index of the oldest frame.
+    head: u8,
+    len: u8,
+}
+
+impl TxQueue {
+    /// The maximum number of queued frames.
+    pub const CAPACITY: usize = 8;
+
+    /// Creates an empty `TxQueue`.
+    pub const fn new() -> Self {
+        const EMPTY: Option<Command> = None;
+        Self {
+            frames: [EMPTY; Self::CAPACITY],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Returns the number of queued frames.
+    pub fn len(&self) -> usize {
+        usize::from(self.len)
+    }
+
+    /// Returns whether no frames are queued.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns whether the queue holds `CAPACITY` frames and rejects further ones.
+    pub fn is_full(&self) -> bool {
+        self.len() == Self::CAPACITY
+    }
+
+    /// Returns the status flags reflecting the state of this queue.
+    ///
+    /// This is `Status::TX_FIFO_FULL` while the queue is full, and empty otherwise.
+    pub fn status(&self) -> Status {
+        if self.is_full() {
+            Status::TX_FIFO_FULL
+        } else {
+            Status::empty()
+        }
+    }
+
+    /// Adds a transmit command to the end of the queue.
+    ///
+    /// If the queue is full, or if `cmd` is not a transmit command, `cmd` is returned back as an
+    /// error.
+    // Handing the command back is the point; there is no allocator to box it.
+    #[allow(clippy::result_large_err)]
+    pub fn push(&mut self, cmd: Command) -> Result<(), Command> {
+        if self.is_full() || !is_transmit(&cmd) {
+            return Err(cmd);
+        }
+
+        let index = (self.head + self.len) % Self::CAPACITY as u8;
+        self.frames[usize::from(index)] = Some(cmd);
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Returns the oldest queued transmit command without removing it.
+    pub fn peek(&self) -> Option<&Command> {
+        self.frames[usize::from(self.head)].as_ref()
+    }
+
+    /// Removes and returns the oldest queued transmit command.
+    pub fn pop(&mut self) -> Option<Command> {
+        let cmd = self.frames[usize::from(self.head)].take()?;
+        self.head = (self.head + 1) % Self::CAPACITY as u8;
+        self.len -= 1;
+        Some(cmd)
+    }
+}
+
+impl Default for TxQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Accumulates device events into `Status` flags.
+///
+/// Events like bus errors or lost arbitration are recorded via `set` as they occur, and are
+/// reported to the host (and cleared) when the host reads the status with the `ReadStatus`
+/// command (`F`).
+///
+/// All methods take `&self` and use atomic operations, so a `StatusTracker` can be stored in a
+/// `static` and updated from interrupt handlers. It is only available on targets that support
+/// atomic read-modify-write operations on bytes.
+///
+/// # Example
+///
+/// ```
+/// use mini_slcan::device::StatusTracker;
+/// use mini_slcan::Status;
+///
+/// static STATUS: StatusTracker = StatusTracker::new();
+///
+/// // In the CAN interrupt handler:
+/// STATUS.set(Status::ARBITRATION_LOST);
+/// STATUS.set(Status::BUS_ERROR);
+///
+/// // When handling the `F` command:
+/// assert_eq!(STATUS.take(), Status::ARBITRATION_LOST | Status::BUS_ERROR);
+/// assert_eq!(STATUS.take(), Status::empty());
+/// ```
+#[cfg(target_has_atomic = "8")]
+#[derive(Debug, Default)]
+pub struct StatusTracker {
+    flags: AtomicU8,
+}
+
+#[cfg(target_has_atomic = "8")]
+impl StatusTracker {
+    /// Creates a `StatusTracker` with no flags set.
+    pub const fn new() -> Self {
+        Self {
+            flags: AtomicU8::new(0),
+        }
+    }
+
+    /// Records the events in `flags`, in addition to the already recorded ones.
+    pub fn set(&self, flags: Status) {
+        // The flags don't guard any other memory, so `Relaxed` is sufficient.
+        self.flags.fetch_or(flags.bits(), Ordering::Relaxed);
+    }
+
+    /// Returns the recorded events without clearing them.
+    pub fn peek(&self) -> Status {
+        Status::from_bits_truncate(self.flags.load(Ordering::Relaxed))
+    }
+
+    /// Returns the recorded events and clears them, like the `ReadStatus` command does.
+    pub fn take(&self) -> Status {
+        Status::from_bits_truncate(self.flags.swap(0, Ordering::Relaxed))
+    }
+}
+
+#[cfg(target_has_atomic = "8")]
+impl Format for StatusTracker {
+    fn format(&self, fmt: &mut defmt::Formatter) {
+        self.peek().format(fmt)
+    }
+}
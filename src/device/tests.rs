@@ -0,0 +1,422 @@
+use super::*;
+use crate::{AutoStartup, BitTiming, Bitrate, ErrorKind, Identifier, SerialBaud, Status};
+
+fn feed<B: AsRef<[u8]> + AsMut<[u8]>>(
+    device: &mut SlcanDevice<B>,
+    input: &[u8],
+) -> Vec<Result<Command, ErrorKind>> {
+    device.tail_mut()[..input.len()].copy_from_slice(input);
+    device
+        .advance_by(input.len() as u8)
+        .map(|res| res.map_err(|e| e.kind()))
+        .collect()
+}
+
+#[test]
+fn states() {
+    let mut device = SlcanDevice::new();
+    assert_eq!(
+        feed(&mut device, b"t1230\rL\rC\rF\rZ1\rX0\rU1\r"),
+        vec![
+            Err(ErrorKind::InvalidState),
+            Err(ErrorKind::InvalidState),
+            Err(ErrorKind::InvalidState),
+            Err(ErrorKind::InvalidState),
+            Ok(Command::SetRxTimestamp {
+                mode: TimestampMode::Milliseconds
+            }),
+            Ok(Command::SetAutoPoll { enabled: false }),
+            Ok(Command::SetSerialBaudrate {
+                baud: SerialBaud::_115200
+            }),
+        ]
+    );
+    assert_eq!(device.timestamp_mode(), TimestampMode::Milliseconds);
+
+    assert_eq!(
+        feed(&mut device, b"S8\rL\rt1230\rP\rF\rX1\rU0\r"),
+        vec![
+            Ok(Command::SetupWithBitrate {
+                bitrate: Bitrate::_1mbit
+            }),
+            Ok(Command::OpenListenOnly),
+            Err(ErrorKind::InvalidState),
+            Ok(Command::PollOne),
+            Ok(Command::ReadStatus),
+            Err(ErrorKind::InvalidState),
+            Err(ErrorKind::InvalidState),
+        ]
+    );
+    assert!(device.is_configured());
+    assert_eq!(device.state(), ChannelState::ListenOnly);
+
+    assert_eq!(
+        feed(&mut device, b"S0\rO\rC\rO\r"),
+        vec![
+            Err(ErrorKind::InvalidState),
+            Err(ErrorKind::InvalidState),
+            Ok(Command::Close),
+            Ok(Command::Open),
+        ]
+    );
+    assert_eq!(device.state(), ChannelState::Open);
+
+    // Decoding errors are passed through.
+    assert_eq!(
+        feed(&mut device, b"x\rV\r"),
+        vec![Err(ErrorKind::UnsupportedCommand), Ok(Command::ReadVersion)]
+    );
+}
+
+#[derive(Default)]
+struct Storage(Option<StoredConfig>);
+
+impl ConfigStorage for Storage {
+    fn load(&mut self) -> Option<StoredConfig> {
+        self.0
+    }
+
+    fn save(&mut self, config: &StoredConfig) -> Result<(), Rejected> {
+        self.0 = Some(*config);
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Handler {
+    open: Option<bool>,
+    sent: Vec<(Identifier, CanFrame)>,
+    storage: Option<Storage>,
+    tx_queue: Option<TxQueue>,
+}
+
+impl SlcanHandler for Handler {
+    fn on_setup(&mut self, _: Bitrate) -> Result<(), Rejected> {
+        Ok(())
+    }
+
+    fn on_open(&mut self, listen_only: bool) -> Result<(), Rejected> {
+        if listen_only {
+            return Err(Rejected);
+        }
+        self.open = Some(listen_only);
+        Ok(())
+    }
+
+    fn on_tx(&mut self, identifier: Identifier, frame: &CanFrame) -> Result<(), Rejected> {
+        if frame.is_empty() {
+            return Err(Rejected);
+        }
+        self.sent.push((identifier, *frame));
+        Ok(())
+    }
+
+    fn on_read_version(&mut self) -> Result<(u8, u8), Rejected> {
+        Ok((1, 2))
+    }
+
+    fn on_set_acceptance_code(&mut self, _: FilterValue) -> Result<(), Rejected> {
+        Ok(())
+    }
+
+    fn on_read_status(&mut self) -> Result<Status, Rejected> {
+        Ok(Status::BUS_ERROR)
+    }
+
+    fn tx_queue(&mut self) -> Option<&mut TxQueue> {
+        self.tx_queue.as_mut()
+    }
+
+    fn config_storage(&mut self) -> Option<&mut dyn ConfigStorage> {
+        self.storage.as_mut().map(|s| s as _)
+    }
+}
+
+#[test]
+fn fd_commands() {
+    // The default buffer only has room for classic CAN commands.
+    assert_eq!(SlcanDevice::new().tail_mut().len(), CommandBuf::LEN);
+
+    let mut device = SlcanDevice::new_fd();
+    assert_eq!(device.tail_mut().len(), CommandBuf::FD_LEN);
+    let input = format!("S6\rO\rd123F{}\r", "AA".repeat(64));
+    let res = feed(&mut device, input.as_bytes());
+    assert_eq!(res.len(), 3);
+    assert_eq!(
+        res[2],
+        Ok(Command::TxFd {
+            identifier: Identifier::from_raw(0x123).unwrap(),
+            frame: CanFdFrame::from_slice(&[0xAA; 64]).unwrap(),
+            flags: FdFlags::empty(),
+        })
+    );
+}
+
+#[test]
+fn dispatcher() {
+    let mut handler = Handler::default();
+    let mut dispatcher = Dispatcher::new();
+    let mut responses = Vec::new();
+    for input in &[&b"O\rS4\rL\rO\rt1230\r"[..], b"t1231AA\rV\rN\rx\r"] {
+        dispatcher.tail_mut()[..input.len()].copy_from_slice(input);
+        responses.extend(dispatcher.advance_by(input.len() as u8, &mut handler));
+    }
+
+    assert_eq!(
+        responses,
+        [
+            Response::Error,
+            Response::Ack,
+            Response::Error,
+            Response::Ack,
+            Response::Error,
+            Response::TxAck,
+            Response::Version {
+                hardware_version: 1,
+                software_version: 2
+            },
+            Response::Error,
+            Response::Error,
+        ]
+    );
+    assert_eq!(handler.open, Some(false));
+    assert_eq!(
+        handler.sent,
+        [(Identifier::from_raw(0x123).unwrap(), CanFrame::from([0xAA]))]
+    );
+    assert_eq!(dispatcher.device().state(), ChannelState::Open);
+}
+
+fn rx(id: u16) -> Notification {
+    Notification::Rx {
+        identifier: Identifier::from_raw(id).unwrap(),
+        frame: CanFrame::new(),
+    }
+}
+
+fn rx_id(out: Option<Outgoing>) -> Option<u16> {
+    match out {
+        Some(Outgoing::Notification {
+            notif: Notification::Rx { identifier, .. },
+            ..
+        }) => Some(identifier.as_raw()),
+        _ => None,
+    }
+}
+
+#[test]
+fn rx_queue() {
+    let mut queue = RxQueue::new();
+    assert_eq!(rx_id(queue.receive(rx(1), None)), Some(1));
+    assert!(matches!(
+        queue.poll_one(),
+        Outgoing::Response(Response::Error)
+    ));
+
+    queue.set_auto_poll(false);
+    assert!(matches!(
+        queue.poll_one(),
+        Outgoing::Response(Response::Ack)
+    ));
+    for id in 0..RxQueue::CAPACITY as u16 + 1 {
+        assert!(queue.receive(rx(id), None).is_none());
+    }
+    assert!(queue.is_full());
+    assert!(queue.take_overrun());
+    assert!(!queue.take_overrun());
+
+    assert_eq!(rx_id(Some(queue.poll_one())), Some(0));
+    assert_eq!(rx_id(Some(queue.poll_one())), Some(1));
+
+    // Once auto-poll is re-enabled, queued frames are forwarded first.
+    queue.set_auto_poll(true);
+    assert_eq!(rx_id(queue.receive(rx(100), None)), Some(2));
+    assert_eq!(queue.len(), RxQueue::CAPACITY - 2);
+
+    queue.set_auto_poll(false);
+    let ids = queue
+        .poll_all()
+        .map(|out| rx_id(Some(out)))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        ids,
+        [Some(3), Some(4), Some(5), Some(6), Some(7), Some(100), None]
+    );
+    assert!(queue.is_empty());
+
+    // Frames queued before auto-poll is re-enabled are forwarded without new frames arriving.
+    for id in 0..3 {
+        assert!(queue.receive(rx(id), None).is_none());
+    }
+    assert!(queue.pop_pending().is_none());
+    queue.set_auto_poll(true);
+    assert_eq!(rx_id(queue.pop_pending()), Some(0));
+    assert_eq!(rx_id(queue.pop_pending()), Some(1));
+    assert_eq!(rx_id(queue.pop_pending()), Some(2));
+    assert!(queue.pop_pending().is_none());
+    assert!(queue.is_empty());
+}
+
+#[test]
+fn persist_config() {
+    fn dispatch(dispatcher: &mut Dispatcher, handler: &mut Handler, input: &[u8]) -> Vec<Response> {
+        dispatcher.tail_mut()[..input.len()].copy_from_slice(input);
+        dispatcher.advance_by(input.len() as u8, handler).collect()
+    }
+
+    let mut handler = Handler::default();
+    let mut dispatcher = Dispatcher::new();
+
+    // Without storage, or without a configured bitrate, `Q` is rejected.
+    assert_eq!(
+        dispatch(&mut dispatcher, &mut handler, b"Q1\r"),
+        [Response::Error]
+    );
+    handler.storage = Some(Storage::default());
+    assert_eq!(
+        dispatch(&mut dispatcher, &mut handler, b"Q1\r"),
+        [Response::Error]
+    );
+
+    assert_eq!(
+        dispatch(&mut dispatcher, &mut handler, b"S4\rM00000123\rQ2\r"),
+        [Response::Ack, Response::Ack, Response::Ack]
+    );
+    let config = StoredConfig {
+        timing: BitTiming::Bitrate(Bitrate::_125kbit),
+        acceptance_code: FilterValue::from_raw(0x123),
+        acceptance_mask: FilterValue::from_raw(0xFFFF_FFFF),
+        auto_startup: AutoStartup::OpenListenOnly,
+    };
+    assert_eq!(handler.storage.as_ref().unwrap().0, Some(config));
+
+    // On the next power-up, the configuration is restored.
+    let mut device = SlcanDevice::new();
+    assert_eq!(
+        device.restore(handler.storage.as_mut().unwrap()),
+        Some(config)
+    );
+    assert_eq!(device.state(), ChannelState::ListenOnly);
+    assert_eq!(device.bit_timing(), Some(config.timing));
+    assert_eq!(
+        device.acceptance_filter(),
+        (config.acceptance_code, config.acceptance_mask)
+    );
+    assert_eq!(SlcanDevice::new().restore(&mut Storage::default()), None);
+}
+
+#[test]
+fn tx_backpressure() {
+    let mut handler = Handler {
+        tx_queue: Some(TxQueue::new()),
+        ..Handler::default()
+    };
+    let mut dispatcher = Dispatcher::new();
+    let mut responses = Vec::new();
+    let input = b"S0\rO\r";
+    dispatcher.tail_mut()[..input.len()].copy_from_slice(input);
+    responses.extend(dispatcher.advance_by(input.len() as u8, &mut handler));
+
+    for _ in 0..TxQueue::CAPACITY + 1 {
+        let input = b"T000000000\r";
+        dispatcher.tail_mut()[..input.len()].copy_from_slice(input);
+        responses.extend(dispatcher.advance_by(input.len() as u8, &mut handler));
+    }
+    let input = b"F\r";
+    dispatcher.tail_mut()[..input.len()].copy_from_slice(input);
+    responses.extend(dispatcher.advance_by(input.len() as u8, &mut handler));
+
+    let mut expected = vec![Response::Ack, Response::Ack];
+    expected.extend((0..TxQueue::CAPACITY).map(|_| Response::ExtTxAck));
+    expected.push(Response::Error);
+    expected.push(Response::Status(Status::BUS_ERROR | Status::TX_FIFO_FULL));
+    assert_eq!(responses, expected);
+
+    // Frames were queued instead of being passed to `on_tx`.
+    assert!(handler.sent.is_empty());
+    let queue = handler.tx_queue.as_mut().unwrap();
+    assert!(matches!(queue.peek(), Some(Command::TxExt { .. })));
+    assert!(queue.pop().is_some());
+    assert_eq!(queue.status(), Status::empty());
+    assert_eq!(queue.push(Command::Open), Err(Command::Open));
+}
+
+#[test]
+fn status_tracker() {
+    let tracker = StatusTracker::new();
+    assert_eq!(tracker.take(), Status::empty());
+
+    tracker.set(Status::ERROR_WARNING);
+    tracker.set(Status::ERROR_PASSIVE | Status::ERROR_WARNING);
+    assert_eq!(
+        tracker.peek(),
+        Status::ERROR_WARNING | Status::ERROR_PASSIVE
+    );
+    assert_eq!(
+        tracker.take(),
+        Status::ERROR_WARNING | Status::ERROR_PASSIVE
+    );
+    assert_eq!(tracker.peek(), Status::empty());
+}
+
+#[test]
+fn poll_transport() {
+    struct Serial {
+        input: Vec<u8>,
+        output: Vec<u8>,
+    }
+
+    impl Transport for Serial {
+        type Error = ();
+
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, ()> {
+            if self.input.is_empty() {
+                return Err(());
+            }
+
+            let len = buf.len().min(self.input.len()).min(5);
+            buf[..len].copy_from_slice(&self.input[..len]);
+            self.input.drain(..len);
+            Ok(len)
+        }
+
+        fn write(&mut self, bytes: &[u8]) -> Result<(), ()> {
+            self.output.extend_from_slice(bytes);
+            Ok(())
+        }
+    }
+
+    struct RxHandler {
+        queue: RxQueue,
+    }
+
+    impl SlcanHandler for RxHandler {
+        fn on_set_timestamps(&mut self, _: TimestampMode) -> Result<(), Rejected> {
+            Ok(())
+        }
+
+        fn next_outgoing(&mut self) -> Option<Outgoing> {
+            match self.queue.poll_one() {
+                notif @ Outgoing::Notification { .. } => Some(notif),
+                Outgoing::Response(_) => None,
+            }
+        }
+    }
+
+    let mut handler = RxHandler {
+        queue: RxQueue::new(),
+    };
+    handler.queue.set_auto_poll(false);
+    handler.queue.receive(rx(0x123), None);
+    handler
+        .queue
+        .receive(rx(0x456), Some(Timestamp::Millis(0xABCD)));
+
+    let mut serial = Serial {
+        input: b"Z1\rV\r".to_vec(),
+        output: Vec::new(),
+    };
+    let mut dispatcher = Dispatcher::new();
+    assert_eq!(dispatcher.run(&mut serial, &mut handler), Err(()));
+    assert_eq!(serial.output, b"\r\x07t1230\rt4560ABCD\r");
+}
@@ -1,37 +1,373 @@
+use core::convert::TryFrom;
+use core::fmt;
 use defmt::Format;
 
+// The counts and offsets are stored as `u16` to keep `Error` small, since it is returned on every
+// decoding path. SLCAN messages are much shorter than that, larger values saturate.
 #[derive(Debug, Format)]
 pub struct Error {
     kind: ErrorKind,
+    /// For `Eof` errors, the minimum number of additional input bytes needed. For `Overflow`
+    /// errors, the number of discarded bytes. 0 if unknown.
+    bytes: u16,
+    /// Byte offset in the decoded input at which the error was detected.
+    offset: Option<u16>,
+    /// The field of the message that was being decoded.
+    field: Option<Field>,
+    /// The input byte that caused the error.
+    byte: Option<u8>,
 }
 
 impl Error {
-    pub(crate) fn decode() -> Self {
+    /// Creates an error of the given kind, without any additional information.
+    pub(crate) fn new(kind: ErrorKind) -> Self {
         Self {
-            kind: ErrorKind::Decode,
+            kind,
+            bytes: 0,
+            offset: None,
+            field: None,
+            byte: None,
         }
     }
 
+    pub(crate) fn decode() -> Self {
+        Self::new(ErrorKind::Decode)
+    }
+
     pub(crate) fn eof() -> Self {
+        Self::eof_needing(0)
+    }
+
+    /// Creates an `Eof` error indicating that at least `needed` more bytes are required.
+    pub(crate) fn eof_needing(needed: usize) -> Self {
         Self {
-            kind: ErrorKind::Eof,
+            bytes: saturate(needed),
+            ..Self::new(ErrorKind::Eof)
         }
     }
 
+    /// Creates an `Overflow` error for a buffer that discarded `discarded` bytes.
+    pub(crate) fn overflow(discarded: usize) -> Self {
+        Self {
+            bytes: saturate(discarded),
+            ..Self::new(ErrorKind::Overflow)
+        }
+    }
+
+    /// Creates a `MissingTerminator` error for a BELL found at `offset`, in the middle of a
+    /// message.
+    pub(crate) fn bell_in_message(offset: usize) -> Self {
+        Self::new(ErrorKind::MissingTerminator)
+            .with_byte(7)
+            .in_field(Field::Terminator)
+            .at(offset)
+    }
+
+    pub(crate) fn invalid_state() -> Self {
+        Self::new(ErrorKind::InvalidState)
+    }
+
+    pub(crate) fn rejected() -> Self {
+        Self::new(ErrorKind::Rejected)
+    }
+
+    /// Sets the offset at which the error was detected, unless it is already known.
+    pub(crate) fn at(mut self, offset: usize) -> Self {
+        self.offset.get_or_insert(saturate(offset));
+        self
+    }
+
+    /// Sets the field in which the error was detected, unless it is already known.
+    pub(crate) fn in_field(mut self, field: Field) -> Self {
+        self.field.get_or_insert(field);
+        self
+    }
+
+    /// Sets the input byte that caused the error.
+    pub(crate) fn with_byte(mut self, byte: u8) -> Self {
+        self.byte = Some(byte);
+        self
+    }
+
+    /// Shifts the offset of the error by `len`, for errors in input that followed a `len`-byte
+    /// prefix that was decoded separately.
+    pub(crate) fn after(mut self, len: usize) -> Self {
+        if let Some(offset) = &mut self.offset {
+            *offset = offset.saturating_add(saturate(len));
+        }
+        self
+    }
+
     #[inline]
     pub fn kind(&self) -> ErrorKind {
         self.kind
     }
+
+    /// Returns the byte offset in the decoded message at which the error was detected.
+    ///
+    /// For invalid hex digits and unexpected terminators, this is the offset of the offending
+    /// byte. For values that are out of range, it is the offset of the start of the field. For
+    /// `Eof` errors, it is the offset at which the input ended.
+    ///
+    /// This is only known for errors returned by decoders, and `None` otherwise.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_slcan::Field;
+    /// use mini_slcan::read::Command;
+    ///
+    /// let err = Command::decode(b"T1234X6781AA\r").unwrap_err();
+    /// assert_eq!(err.offset(), Some(5));
+    /// assert_eq!(err.field(), Some(Field::Identifier));
+    /// ```
+    #[inline]
+    pub fn offset(&self) -> Option<usize> {
+        self.offset.map(usize::from)
+    }
+
+    /// Returns the field of the message that was being decoded when the error was detected.
+    ///
+    /// This is only known for errors in some fields, and `None` otherwise.
+    #[inline]
+    pub fn field(&self) -> Option<Field> {
+        self.field
+    }
+
+    /// Returns the input byte that caused the error.
+    ///
+    /// This is known for invalid hex digits, unsupported opcodes, invalid parameter values, and for
+    /// bytes found where a terminator was expected or where none may appear. It is `None` for all
+    /// other errors.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_slcan::ErrorKind;
+    /// use mini_slcan::read::Command;
+    ///
+    /// let err = Command::decode(b"O\n").unwrap_err();
+    /// assert_eq!(err.kind(), ErrorKind::MissingTerminator);
+    /// assert_eq!(err.byte(), Some(b'\n'));
+    /// assert_eq!(err.to_string(), "missing terminator at offset 1 (got 0x0A)");
+    /// ```
+    #[inline]
+    pub fn byte(&self) -> Option<u8> {
+        self.byte
+    }
+
+    /// Returns the minimum number of additional input bytes needed to continue decoding.
+    ///
+    /// This is only known for some `Eof` errors returned by decoders, and `None` otherwise.
+    #[inline]
+    pub fn bytes_needed(&self) -> Option<usize> {
+        match self.kind {
+            ErrorKind::Eof if self.bytes != 0 => Some(self.bytes.into()),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of bytes that were discarded because of an `Overflow` error.
+    ///
+    /// Returns `None` for all other kinds of errors.
+    #[inline]
+    pub fn bytes_discarded(&self) -> Option<usize> {
+        match self.kind {
+            ErrorKind::Overflow => Some(self.bytes.into()),
+            _ => None,
+        }
+    }
+}
+
+/// Converts a byte count or offset to the `u16` stored in `Error`, saturating at `u16::MAX`.
+fn saturate(n: usize) -> u16 {
+    u16::try_from(n).unwrap_or(u16::MAX)
 }
 
 #[derive(Eq, PartialEq, Copy, Clone, Debug, Format)]
 #[non_exhaustive]
 pub enum ErrorKind {
     /// Input is malformed and does not adhere to the SLCAN specification.
+    ///
+    /// This is used for malformed input that is not covered by one of the more specific kinds
+    /// below, see `ErrorKind::is_malformed`.
     Decode,
 
+    /// A numeric field contains a character that is not a hex digit.
+    InvalidHexDigit,
+
+    /// A CAN identifier exceeds the range of standard (11-bit) or extended (29-bit) identifiers.
+    IdentifierOutOfRange,
+
+    /// A data length code is out of range, or a message is shorter or longer than its fields
+    /// require.
+    InvalidLength,
+
+    /// The message is not terminated by a `CR` where one was expected.
+    MissingTerminator,
+
+    /// A field has a value that is not defined for it, like an unknown bitrate code of the `S`
+    /// command or a channel number above 9.
+    InvalidValue,
+
+    /// The terminating `CR` is followed by more data.
+    TrailingData,
+
+    /// The opcode of the message is not supported.
+    UnsupportedCommand,
+
     /// More data is required to decode the input.
     Eof,
+
+    /// The operation is not permitted in the current state of the connection.
+    InvalidState,
+
+    /// The device responded to a command with an error (ASCII BELL).
+    Rejected,
+
+    /// A receive buffer filled up without containing a complete message, so its contents were
+    /// discarded.
+    ///
+    /// Since buffers are large enough for the longest valid message, this indicates that the
+    /// other side sent an overlong (or corrupted) line. `Error::bytes_discarded` returns how many
+    /// bytes were dropped.
+    Overflow,
+}
+
+impl ErrorKind {
+    /// Returns whether this kind indicates malformed input.
+    ///
+    /// This is the case for `Decode` and the more specific kinds describing how the input is
+    /// malformed, which a device usually answers with an error response.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_slcan::ErrorKind;
+    /// use mini_slcan::read::Command;
+    ///
+    /// let kind = Command::decode(b"t1X31AA\r").unwrap_err().kind();
+    /// assert_eq!(kind, ErrorKind::InvalidHexDigit);
+    /// assert!(kind.is_malformed());
+    /// assert!(!ErrorKind::Eof.is_malformed());
+    /// ```
+    pub fn is_malformed(self) -> bool {
+        match self {
+            ErrorKind::Decode
+            | ErrorKind::InvalidHexDigit
+            | ErrorKind::IdentifierOutOfRange
+            | ErrorKind::InvalidLength
+            | ErrorKind::InvalidValue
+            | ErrorKind::MissingTerminator
+            | ErrorKind::TrailingData
+            | ErrorKind::UnsupportedCommand => true,
+            ErrorKind::Eof
+            | ErrorKind::InvalidState
+            | ErrorKind::Rejected
+            | ErrorKind::Overflow => false,
+        }
+    }
+}
+
+/// A field of an SLCAN message, reported by `Error::field`.
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Format)]
+#[non_exhaustive]
+pub enum Field {
+    /// The channel number prefix of a message sent to or by a multi-channel device.
+    Channel,
+
+    /// The opcode at the start of the message.
+    Opcode,
+
+    /// A standard or extended CAN identifier.
+    Identifier,
+
+    /// A data length code.
+    Dlc,
+
+    /// The data bytes of a frame.
+    Data,
+
+    /// The terminating `CR`, which must end the message.
+    Terminator,
+}
+
+/// Formats the error kind, followed by the field, offset and byte at which it was detected if
+/// known.
+///
+/// # Examples
+///
+/// ```
+/// use mini_slcan::read::Command;
+///
+/// let err = Command::decode(b"T1234X6781AA\r").unwrap_err();
+/// assert_eq!(err.to_string(), "invalid hex digit in identifier at offset 5 (got 0x58)");
+///
+/// let err = Command::decode(b"t1").unwrap_err();
+/// assert_eq!(
+///     err.to_string(),
+///     "unexpected end of input in identifier at offset 2 (2 more bytes needed)",
+/// );
+/// ```
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.kind.fmt(f)?;
+        match (self.kind, self.field) {
+            // The kind already names the terminator.
+            (ErrorKind::MissingTerminator, Some(Field::Terminator))
+            | (ErrorKind::TrailingData, Some(Field::Terminator)) => {}
+            (_, Some(field)) => write!(f, " in {}", field)?,
+            (_, None) => {}
+        }
+        if let Some(offset) = self.offset {
+            write!(f, " at offset {}", offset)?;
+        }
+        if let Some(byte) = self.byte {
+            write!(f, " (got {:#04X})", byte)?;
+        }
+        if let Some(needed) = self.bytes_needed() {
+            let s = if needed == 1 { "" } else { "s" };
+            write!(f, " ({} more byte{} needed)", needed, s)?;
+        }
+        if let Some(discarded) = self.bytes_discarded() {
+            let s = if discarded == 1 { "" } else { "s" };
+            write!(f, " ({} byte{} discarded)", discarded, s)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ErrorKind::Decode => "malformed message",
+            ErrorKind::InvalidHexDigit => "invalid hex digit",
+            ErrorKind::IdentifierOutOfRange => "identifier out of range",
+            ErrorKind::InvalidLength => "invalid length",
+            ErrorKind::InvalidValue => "invalid value",
+            ErrorKind::MissingTerminator => "missing terminator",
+            ErrorKind::TrailingData => "trailing data after terminator",
+            ErrorKind::UnsupportedCommand => "unsupported command",
+            ErrorKind::Eof => "unexpected end of input",
+            ErrorKind::InvalidState => "operation not permitted in current state",
+            ErrorKind::Rejected => "rejected by device",
+            ErrorKind::Overflow => "receive buffer overflow",
+        })
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Field::Channel => "channel",
+            Field::Opcode => "opcode",
+            Field::Identifier => "identifier",
+            Field::Dlc => "DLC",
+            Field::Data => "data",
+            Field::Terminator => "terminator",
+        })
+    }
 }
 
-// TODO: impl Display+Debug, #[cfg] Error
+#[cfg(feature = "core-error")]
+impl core::error::Error for Error {}
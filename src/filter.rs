@@ -0,0 +1,47 @@
+//! Acceptance filter configuration.
+
+use defmt::Format;
+
+/// A 32-bit acceptance filter value, as used by the `M` and `m` commands.
+///
+/// The value corresponds to the 4 acceptance code or acceptance mask registers of an SJA1000 CAN
+/// controller, with the first register (ACR0/AMR0) stored in the most significant byte.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Format)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub struct FilterValue(u32);
+
+impl FilterValue {
+    /// Creates a `FilterValue` from its raw 32-bit representation.
+    pub const fn from_raw(raw: u32) -> Self {
+        Self(raw)
+    }
+
+    /// Creates a `FilterValue` from the 4 filter register values, first register first.
+    pub const fn from_registers(regs: [u8; 4]) -> Self {
+        Self(u32::from_be_bytes(regs))
+    }
+
+    pub const fn as_raw(&self) -> u32 {
+        self.0
+    }
+
+    /// Returns the 4 filter register values, first register first.
+    pub const fn registers(&self) -> [u8; 4] {
+        self.0.to_be_bytes()
+    }
+}
+
+/// Acceptance filter mode of the SJA1000 CAN controller.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Format)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub enum FilterMode {
+    /// Two shorter filters are applied to incoming frames (`W0`).
+    Dual,
+
+    /// A single long filter is applied to incoming frames (`W1`).
+    Single,
+}
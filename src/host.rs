@@ -0,0 +1,607 @@
+//! High-level support for host applications talking to an SLCAN device.
+
+#[cfg(feature = "tokio")]
+mod async_port;
+#[cfg(all(feature = "serialport", feature = "socketcan", target_os = "linux"))]
+mod bridge;
+#[cfg(feature = "tokio")]
+mod codec;
+#[cfg(feature = "serialport")]
+mod port;
+#[cfg(feature = "futures")]
+mod stream;
+#[cfg(test)]
+mod tests;
+#[cfg(any(feature = "futures", feature = "serialport", feature = "tokio"))]
+mod transport;
+
+#[cfg(feature = "tokio")]
+pub use self::async_port::AsyncSlcan;
+#[cfg(all(feature = "serialport", feature = "socketcan", target_os = "linux"))]
+pub use self::bridge::SocketCanBridge;
+#[cfg(feature = "tokio")]
+pub use self::codec::SlcanCodec;
+#[cfg(feature = "serialport")]
+pub use self::port::SlcanPort;
+#[cfg(feature = "futures")]
+pub use self::stream::{Notifications, SlcanStream};
+#[cfg(any(feature = "futures", feature = "serialport", feature = "tokio"))]
+pub use self::transport::PortError;
+
+use core::mem;
+
+use crate::read::{Command, DecodeOptions, Demux, Incoming, ResponseDecoder};
+use crate::write::{CommandEncodeBuf, Response};
+use crate::{
+    BitTiming, Bitrate, ChannelState, Error, FilterValue, SerialNumber, TimestampMode,
+    VersionString,
+};
+use defmt::Format;
+
+/// A state change that takes effect once the device acknowledges the pending command.
+#[derive(Copy, Clone, Debug, Format)]
+enum Transition {
+    None,
+    Configure,
+    SetTimestamps(TimestampMode),
+    SetState(ChannelState),
+}
+
+/// Connection state machine for host applications.
+///
+/// `SlcanHost` keeps track of the state of the device's CAN channel and refuses to send commands
+/// that are invalid in that state. In particular:
+///
+/// * Setup commands (bitrate, filters, timestamps, auto-poll, serial baud rate) may only be sent
+///   while the channel is closed.
+/// * Status flags can only be read, and received frames only be polled, while the channel is open.
+/// * The channel can only be opened after a bitrate has been configured.
+/// * CAN frames can only be transmitted while the channel is open in normal mode.
+/// * Only one command may be awaiting a response at a time.
+///
+/// State changes take effect when the device acknowledges the corresponding command, so every
+/// message received from the device has to be passed to `receive`.
+///
+/// # Example
+///
+/// ```
+/// use mini_slcan::{Bitrate, ChannelState, host::SlcanHost};
+/// use mini_slcan::read::Command;
+/// use mini_slcan::write::CommandEncodeBuf;
+///
+/// let mut host = SlcanHost::new();
+/// let mut buf = CommandEncodeBuf::new();
+///
+/// // Opening the channel before configuring a bitrate is refused.
+/// assert!(host.send(&Command::Open, &mut buf).is_err());
+///
+/// let bitrate = Command::SetupWithBitrate { bitrate: Bitrate::_500kbit };
+/// assert_eq!(host.send(&bitrate, &mut buf).unwrap(), b"S6\r");
+/// host.receive(b"\r").unwrap();
+///
+/// assert_eq!(host.send(&Command::Open, &mut buf).unwrap(), b"O\r");
+/// host.receive(b"\r").unwrap();
+/// assert_eq!(host.state(), ChannelState::Open);
+/// ```
+#[derive(Debug, Format)]
+pub struct SlcanHost {
+    state: ChannelState,
+    configured: bool,
+    demux: Demux,
+    pending: Transition,
+}
+
+impl SlcanHost {
+    /// Creates a new `SlcanHost`, assuming that the device's channel is closed and not
+    /// configured.
+    pub const fn new() -> Self {
+        Self::with_options(DecodeOptions::new())
+    }
+
+    /// Creates a new `SlcanHost` that decodes messages using the given `DecodeOptions`.
+    pub const fn with_options(options: DecodeOptions) -> Self {
+        Self {
+            state: ChannelState::Closed,
+            configured: false,
+            demux: Demux::with_options(options),
+            pending: Transition::None,
+        }
+    }
+
+    /// Returns the current state of the CAN channel.
+    pub fn state(&self) -> ChannelState {
+        self.state
+    }
+
+    /// Returns whether a bitrate has been configured.
+    pub fn is_configured(&self) -> bool {
+        self.configured
+    }
+
+    /// Returns whether the last command sent is still awaiting its response.
+    pub fn is_awaiting_response(&self) -> bool {
+        self.demux.is_awaiting_response()
+    }
+
+    /// Stops waiting for the response to the last command sent.
+    ///
+    /// This can be used when the device did not respond in time. Any state change caused by the
+    /// command is discarded.
+    pub fn cancel_pending(&mut self) {
+        self.demux.cancel_pending();
+        self.pending = Transition::None;
+    }
+
+    /// Encodes `cmd` into `buf` so that it can be sent to the device.
+    ///
+    /// Returns an error of kind `InvalidState` if `cmd` is not valid in the current state, or if
+    /// the previous command has not been answered yet.
+    pub fn send<'a>(
+        &mut self,
+        cmd: &Command,
+        buf: &'a mut CommandEncodeBuf,
+    ) -> Result<&'a [u8], Error> {
+        if self.is_awaiting_response() {
+            return Err(Error::invalid_state());
+        }
+
+        if !self.state.permits(cmd, self.configured) {
+            return Err(Error::invalid_state());
+        }
+
+        let transition = match cmd {
+            Command::SetupWithBitrate { .. } | Command::SetupWithBtr { .. } => {
+                Transition::Configure
+            }
+            Command::SetRxTimestamp { mode } => Transition::SetTimestamps(*mode),
+            Command::Open => Transition::SetState(ChannelState::Open),
+            Command::OpenListenOnly => Transition::SetState(ChannelState::ListenOnly),
+            Command::Close => Transition::SetState(ChannelState::Closed),
+            _ => Transition::None,
+        };
+
+        let encoded = cmd.encode(buf)?;
+        self.demux.command_sent(cmd);
+        self.pending = transition;
+        Ok(encoded)
+    }
+
+    /// Decodes a message received from the device, and updates the connection state.
+    ///
+    /// `msg` must include the terminating `CR` (or consist of a single BELL character).
+    pub fn receive(&mut self, msg: &[u8]) -> Result<Incoming, Error> {
+        let awaiting = self.is_awaiting_response();
+        let incoming = self.demux.classify(msg);
+
+        if awaiting && !self.is_awaiting_response() {
+            // The pending command was answered (or its response could not be decoded).
+            let transition = mem::replace(&mut self.pending, Transition::None);
+            if let Ok(Incoming::Response(Response::Ack)) = incoming {
+                match transition {
+                    Transition::None => {}
+                    Transition::Configure => self.configured = true,
+                    Transition::SetTimestamps(mode) => self.demux.set_timestamp_mode(mode),
+                    Transition::SetState(state) => self.state = state,
+                }
+            }
+        }
+
+        incoming
+    }
+}
+
+impl Default for SlcanHost {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A queue of commands awaiting a response from the device.
+///
+/// SLCAN devices answer commands in the order they were received. `CommandQueue` allows a host to
+/// send several commands without waiting for each response, and pairs every received response
+/// with the oldest outstanding command.
+///
+/// # Example
+///
+/// ```
+/// use mini_slcan::host::CommandQueue;
+/// use mini_slcan::read::Command;
+/// use mini_slcan::write::Response;
+///
+/// let mut queue = CommandQueue::new();
+/// queue.push(Command::ReadVersion).unwrap();
+/// queue.push(Command::Open).unwrap();
+///
+/// let (cmd, resp) = queue.receive(b"V1013\r").unwrap();
+/// assert_eq!(cmd, Command::ReadVersion);
+/// assert!(matches!(resp, Ok(Response::Version { .. })));
+///
+/// let (cmd, resp) = queue.receive(b"\x07").unwrap();
+/// assert_eq!(cmd, Command::Open);
+/// assert_eq!(resp.unwrap(), Response::Error);
+/// ```
+#[derive(Debug, Format)]
+pub struct CommandQueue {
+    commands: [Option<Command>; CommandQueue::CAPACITY],
+    /// Index of the oldest command.
+    head: u8,
+    len: u8,
+}
+
+impl CommandQueue {
+    /// The maximum number of outstanding commands.
+    pub const CAPACITY: usize = 8;
+
+    /// Creates a new, empty `CommandQueue`.
+    pub const fn new() -> Self {
+        const EMPTY: Option<Command> = None;
+        Self {
+            commands: [EMPTY; Self::CAPACITY],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Returns the number of outstanding commands.
+    pub fn len(&self) -> usize {
+        usize::from(self.len)
+    }
+
+    /// Returns whether no command is awaiting a response.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns whether `CAPACITY` commands are outstanding, so that `push` rejects further ones.
+    pub fn is_full(&self) -> bool {
+        self.len() == Self::CAPACITY
+    }
+
+    /// Returns the oldest outstanding command, which the next response will be paired with.
+    pub fn oldest(&self) -> Option<&Command> {
+        self.commands[usize::from(self.head)].as_ref()
+    }
+
+    /// Records that `cmd` was sent to the device.
+    ///
+    /// If the queue is full, `cmd` is returned back as an error.
+    // `Command` is large, but the caller needs it back and there is no allocator to box it.
+    #[allow(clippy::result_large_err)]
+    pub fn push(&mut self, cmd: Command) -> Result<(), Command> {
+        if self.is_full() {
+            return Err(cmd);
+        }
+
+        let index = (self.head + self.len) % Self::CAPACITY as u8;
+        self.commands[usize::from(index)] = Some(cmd);
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Removes the oldest outstanding command without pairing it with a response.
+    ///
+    /// This can be used when the device did not respond in time, or when a `PollOne` command was
+    /// answered with a CAN frame.
+    pub fn pop(&mut self) -> Option<Command> {
+        let cmd = self.commands[usize::from(self.head)].take()?;
+        self.head = (self.head + 1) % Self::CAPACITY as u8;
+        self.len -= 1;
+        Some(cmd)
+    }
+
+    /// Pairs a message received from the device with the oldest outstanding command.
+    ///
+    /// `msg` must include the terminating `CR` (or consist of a single BELL character). It is
+    /// decoded as a response to the oldest command using a `ResponseDecoder`.
+    ///
+    /// Returns `None` if `msg` is a notification, or if no command is outstanding. Notifications
+    /// are not paired with any command, so a `PollOne` command that is answered with a CAN frame
+    /// has to be removed via `pop`.
+    pub fn receive(&mut self, msg: &[u8]) -> Option<(Command, Result<Response, Error>)> {
+        let decoder = ResponseDecoder::for_command(self.oldest()?);
+        if decoder.is_notification(msg) {
+            return None;
+        }
+
+        let cmd = self.pop()?;
+        Some((cmd, decoder.decode(msg)))
+    }
+}
+
+impl Default for CommandQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Identification of an SLCAN device, obtained via `Probe`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Format)]
+pub struct DeviceInfo {
+    pub hardware_version: u8,
+    pub software_version: u8,
+    /// The detailed version string, if the device supports the `ReadDetailedVersion` command.
+    pub detailed_version: Option<VersionString>,
+    pub serial: SerialNumber,
+}
+
+#[derive(Copy, Clone, Debug, Format)]
+enum ProbeStep {
+    Version,
+    DetailedVersion {
+        hardware_version: u8,
+        software_version: u8,
+    },
+    Serial {
+        hardware_version: u8,
+        software_version: u8,
+        detailed_version: Option<VersionString>,
+    },
+    Done,
+}
+
+/// Identifies an SLCAN device by querying its version and serial number.
+///
+/// This sends the `ReadVersion` (`V`), `ReadDetailedVersion` (`v`) and `ReadSerial` (`N`)
+/// commands, one at a time, and collects the responses into a `DeviceInfo`. It does not require
+/// the CAN channel to be open.
+///
+/// # Example
+///
+/// ```
+/// use mini_slcan::host::Probe;
+/// use mini_slcan::write::CommandEncodeBuf;
+///
+/// let mut probe = Probe::new();
+/// let mut buf = CommandEncodeBuf::new();
+/// let replies: [&[u8]; 3] = [b"V1013\r", b"\x07", b"NA123\r"];
+/// let mut replies = replies.iter();
+///
+/// let info = loop {
+///     let cmd = probe.next_command().unwrap();
+///     let _bytes = cmd.encode(&mut buf).unwrap(); // send these to the device
+///     if let Some(info) = probe.receive(replies.next().unwrap()).unwrap() {
+///         break info;
+///     }
+/// };
+///
+/// assert_eq!(info.hardware_version, 0x10);
+/// assert_eq!(info.software_version, 0x13);
+/// assert!(info.detailed_version.is_none());
+/// ```
+#[derive(Copy, Clone, Debug, Format)]
+pub struct Probe {
+    step: ProbeStep,
+}
+
+impl Probe {
+    /// Creates a new `Probe`, starting with the `ReadVersion` command.
+    pub const fn new() -> Self {
+        Self {
+            step: ProbeStep::Version,
+        }
+    }
+
+    /// Returns the next command to send to the device, or `None` if probing has finished.
+    pub fn next_command(&self) -> Option<Command> {
+        match self.step {
+            ProbeStep::Version => Some(Command::ReadVersion),
+            ProbeStep::DetailedVersion { .. } => Some(Command::ReadDetailedVersion),
+            ProbeStep::Serial { .. } => Some(Command::ReadSerial),
+            ProbeStep::Done => None,
+        }
+    }
+
+    /// Processes a message received in response to the command returned by `next_command`.
+    ///
+    /// `msg` must include the terminating `CR` (or consist of a single BELL character).
+    /// Notifications are ignored.
+    ///
+    /// Returns the `DeviceInfo` once all responses have been received. If the device rejects the
+    /// `ReadVersion` or `ReadSerial` command, an error of kind `Rejected` is returned. Errors leave
+    /// the `Probe` unchanged, so the command can be retried.
+    pub fn receive(&mut self, msg: &[u8]) -> Result<Option<DeviceInfo>, Error> {
+        let decoder = match self.next_command() {
+            Some(cmd) => ResponseDecoder::for_command(&cmd),
+            None if Incoming::is_notification(msg) => return Ok(None),
+            None => return Err(Error::invalid_state()),
+        };
+        if decoder.is_notification(msg) {
+            return Ok(None);
+        }
+
+        let resp = decoder.decode(msg)?;
+
+        self.step = match (self.step, resp) {
+            (
+                ProbeStep::Version,
+                Response::Version {
+                    hardware_version,
+                    software_version,
+                },
+            ) => ProbeStep::DetailedVersion {
+                hardware_version,
+                software_version,
+            },
+            (
+                ProbeStep::DetailedVersion {
+                    hardware_version,
+                    software_version,
+                },
+                resp,
+            ) => ProbeStep::Serial {
+                hardware_version,
+                software_version,
+                detailed_version: match resp {
+                    Response::DetailedVersion(version) => Some(version),
+                    // The detailed version is an extension that many devices do not support.
+                    _ => None,
+                },
+            },
+            (
+                ProbeStep::Serial {
+                    hardware_version,
+                    software_version,
+                    detailed_version,
+                },
+                Response::Serial(serial),
+            ) => {
+                self.step = ProbeStep::Done;
+                return Ok(Some(DeviceInfo {
+                    hardware_version,
+                    software_version,
+                    detailed_version,
+                    serial,
+                }));
+            }
+            _ => return Err(Error::rejected()),
+        };
+
+        Ok(None)
+    }
+}
+
+impl Default for Probe {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Builder for the sequence of commands that configures and opens the CAN channel.
+///
+/// The commands are emitted in the order required by the SLCAN protocol: bit timing, acceptance
+/// filter, timestamp mode, and finally the command that opens the channel. Since setup commands are
+/// only accepted while the channel is closed, the sequence must be sent to a closed channel (this
+/// is enforced when sending the commands through `SlcanHost`).
+///
+/// # Example
+///
+/// ```
+/// use mini_slcan::{Bitrate, TimestampMode};
+/// use mini_slcan::host::Setup;
+///
+/// let setup = Setup::new()
+///     .bitrate(Bitrate::_500kbit)
+///     .timestamps(TimestampMode::Milliseconds)
+///     .listen_only(true);
+///
+/// let mut buf = [0; Setup::MAX_ENCODED_LEN];
+/// assert_eq!(setup.encode(&mut buf).unwrap(), b"S6\rZ1\rL\r");
+///
+/// // A bitrate is required.
+/// assert!(Setup::new().encode(&mut buf).is_err());
+/// ```
+#[derive(Copy, Clone, Debug, Format)]
+pub struct Setup {
+    timing: Option<BitTiming>,
+    filter: Option<(FilterValue, FilterValue)>,
+    timestamps: TimestampMode,
+    listen_only: bool,
+}
+
+impl Setup {
+    /// Maximum length of the encoded command sequence (`sxxyy\rMxxxxxxxx\rmxxxxxxxx\rZn\rO\r`).
+    pub const MAX_ENCODED_LEN: usize = 6 + 10 + 10 + 3 + 2;
+
+    /// Creates a `Setup` with no bit timing, no acceptance filter and timestamps disabled, which
+    /// opens the channel in normal mode.
+    pub const fn new() -> Self {
+        Self {
+            timing: None,
+            filter: None,
+            timestamps: TimestampMode::Off,
+            listen_only: false,
+        }
+    }
+
+    /// Configures one of the predefined bitrates (`Sn`).
+    ///
+    /// This replaces any bit timing configured via `btr`.
+    pub const fn bitrate(mut self, bitrate: Bitrate) -> Self {
+        self.timing = Some(BitTiming::Bitrate(bitrate));
+        self
+    }
+
+    /// Configures custom bit timing using raw BTR0/BTR1 register values (`sxxyy`).
+    ///
+    /// This replaces any bitrate configured via `bitrate`.
+    pub const fn btr(mut self, btr0: u8, btr1: u8) -> Self {
+        self.timing = Some(BitTiming::Btr { btr0, btr1 });
+        self
+    }
+
+    /// Configures the acceptance code and mask (`M` and `m`).
+    ///
+    /// By default, the device's acceptance filter is left unchanged.
+    pub const fn filter(mut self, code: FilterValue, mask: FilterValue) -> Self {
+        self.filter = Some((code, mask));
+        self
+    }
+
+    /// Configures the timestamp mode of received frames (`Zn`).
+    pub const fn timestamps(mut self, mode: TimestampMode) -> Self {
+        self.timestamps = mode;
+        self
+    }
+
+    /// Selects whether the channel is opened in listen-only mode (`L`) instead of normal mode (`O`).
+    pub const fn listen_only(mut self, listen_only: bool) -> Self {
+        self.listen_only = listen_only;
+        self
+    }
+
+    /// Returns an iterator over the commands to send, in order.
+    ///
+    /// Returns an error of kind `InvalidState` if no bitrate was configured.
+    pub fn commands(&self) -> Result<impl Iterator<Item = Command>, Error> {
+        let timing = match self.timing {
+            Some(BitTiming::Bitrate(bitrate)) => Command::SetupWithBitrate { bitrate },
+            Some(BitTiming::Btr { btr0, btr1 }) => Command::SetupWithBtr { btr0, btr1 },
+            None => return Err(Error::invalid_state()),
+        };
+        let (code, mask) = match self.filter {
+            Some((code, mask)) => (
+                Some(Command::SetAcceptanceCode(code)),
+                Some(Command::SetAcceptanceMask(mask)),
+            ),
+            None => (None, None),
+        };
+        let open = if self.listen_only {
+            Command::OpenListenOnly
+        } else {
+            Command::Open
+        };
+
+        Ok(core::iter::once(timing)
+            .chain(code)
+            .chain(mask)
+            .chain(core::iter::once(Command::SetRxTimestamp {
+                mode: self.timestamps,
+            }))
+            .chain(core::iter::once(open)))
+    }
+
+    /// Encodes the whole command sequence into `buf`.
+    ///
+    /// Returns an error of kind `InvalidState` if no bitrate was configured, or of kind `Eof` if
+    /// `buf` is too small. A buffer of `MAX_ENCODED_LEN` bytes is always large enough.
+    pub fn encode<'a>(&self, buf: &'a mut [u8]) -> Result<&'a [u8], Error> {
+        let mut used = 0;
+        for cmd in self.commands()? {
+            let mut cmd_buf = CommandEncodeBuf::new();
+            let encoded = cmd.encode(&mut cmd_buf)?;
+            buf.get_mut(used..used + encoded.len())
+                .ok_or_else(Error::eof)?
+                .copy_from_slice(encoded);
+            used += encoded.len();
+        }
+
+        Ok(&buf[..used])
+    }
+}
+
+impl Default for Setup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,258 @@
+//! Async host transport over `tokio-serial`.
+
+use super::transport::{self, Framer, PortError};
+use super::{DeviceInfo, Probe, Setup, SlcanHost};
+use crate::read::{Command, DecodeOptions, Incoming};
+use crate::write::{CommandEncodeBuf, Notification, Response, Timestamp};
+use crate::{Error, Frame};
+use futures_util::stream::{self, Stream};
+use std::collections::VecDeque;
+use std::io;
+use std::vec::Vec;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// An SLCAN device connected to a serial port, driven by `tokio`.
+///
+/// This is the async counterpart to `SlcanPort`: every method waits for the device's answer, and
+/// checks that the command is valid in the current state of the channel. Notifications that arrive
+/// while a method waits for a response are queued, and returned by later calls to `recv` or by the
+/// `notifications` stream.
+///
+/// There is no built-in timeout. Wrap calls in `tokio::time::timeout` instead, and call
+/// `cancel_pending` when a command's response does not arrive in time.
+///
+/// # Example
+///
+/// ```no_run
+/// use futures_util::StreamExt;
+/// use mini_slcan::{Bitrate, CanFrame, Frame, FrameKind, Identifier};
+/// use mini_slcan::host::{AsyncSlcan, Setup};
+///
+/// # async fn run() -> Result<(), mini_slcan::host::PortError> {
+/// let mut slcan = AsyncSlcan::open("/dev/ttyACM0")?;
+/// slcan.setup(&Setup::new().bitrate(Bitrate::_500kbit)).await?;
+///
+/// slcan.send_frame(&Frame {
+///     id: Identifier::new_const(0x123).into(),
+///     kind: FrameKind::Data(CanFrame::from([0xAA, 0xBB])),
+/// }).await?;
+///
+/// let notifications = slcan.notifications();
+/// futures_util::pin_mut!(notifications);
+/// while let Some(res) = notifications.next().await {
+///     let (notif, _timestamp) = res?;
+///     println!("{:?}", notif);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct AsyncSlcan<P = tokio_serial::SerialStream> {
+    port: P,
+    host: SlcanHost,
+    framer: Framer,
+    /// Messages received while waiting for a response.
+    queued: VecDeque<Result<Incoming, Error>>,
+}
+
+impl AsyncSlcan {
+    /// Baud rate used by `open`. USB devices usually ignore it.
+    pub const DEFAULT_BAUD_RATE: u32 = 115_200;
+
+    /// Opens the serial port at `path`, with a baud rate of `DEFAULT_BAUD_RATE`.
+    ///
+    /// This has to be called from within a `tokio` runtime. Use `tokio_serial::new` and
+    /// `AsyncSlcan::new` to open the port with different settings.
+    pub fn open(path: &str) -> Result<Self, PortError> {
+        let port =
+            tokio_serial::SerialStream::open(&tokio_serial::new(path, Self::DEFAULT_BAUD_RATE))
+                .map_err(io::Error::from)?;
+        Ok(Self::new(port))
+    }
+}
+
+impl<P: AsyncRead + AsyncWrite + Unpin> AsyncSlcan<P> {
+    /// Creates an `AsyncSlcan` communicating over `port`, assuming that the device's channel is
+    /// closed and not configured.
+    pub fn new(port: P) -> Self {
+        Self::with_options(port, DecodeOptions::new())
+    }
+
+    /// Creates an `AsyncSlcan` that decodes messages using the given `DecodeOptions`.
+    pub fn with_options(port: P, options: DecodeOptions) -> Self {
+        Self {
+            port,
+            host: SlcanHost::with_options(options),
+            framer: Framer::default(),
+            queued: VecDeque::new(),
+        }
+    }
+
+    /// Returns the `SlcanHost` tracking the state of the device's channel.
+    pub fn host(&self) -> &SlcanHost {
+        &self.host
+    }
+
+    /// Returns a mutable reference to the underlying port.
+    pub fn port_mut(&mut self) -> &mut P {
+        &mut self.port
+    }
+
+    /// Releases the underlying port.
+    pub fn free(self) -> P {
+        self.port
+    }
+
+    /// Stops waiting for the response to the last command sent.
+    ///
+    /// This has to be called when a future returned by one of the command methods is dropped
+    /// before it completes, for example because of a timeout. Otherwise, further commands are
+    /// refused.
+    pub fn cancel_pending(&mut self) {
+        self.host.cancel_pending();
+    }
+
+    /// Sends `cmd` to the device, and waits for its response.
+    ///
+    /// Errors are reported like in `SlcanPort::command`.
+    pub async fn command(&mut self, cmd: &Command) -> Result<Response, PortError> {
+        self.send(cmd).await?;
+        loop {
+            let msg = self.read_message_or_cancel().await?;
+            if let Some(res) = transport::command_response(&mut self.host, &mut self.queued, msg) {
+                return res;
+            }
+        }
+    }
+
+    /// Transmits `frame` on the CAN bus.
+    ///
+    /// The channel has to be open in normal mode.
+    pub async fn send_frame(&mut self, frame: &Frame) -> Result<(), PortError> {
+        self.command(&Command::from(*frame)).await?;
+        Ok(())
+    }
+
+    /// Configures and opens the CAN channel by sending the commands of `setup`.
+    ///
+    /// The channel has to be closed.
+    pub async fn setup(&mut self, setup: &Setup) -> Result<(), PortError> {
+        for cmd in setup.commands()? {
+            self.command(&cmd).await?;
+        }
+        Ok(())
+    }
+
+    /// Closes the CAN channel.
+    pub async fn close(&mut self) -> Result<(), PortError> {
+        self.command(&Command::Close).await?;
+        Ok(())
+    }
+
+    /// Identifies the device by querying its version and serial number.
+    ///
+    /// See `Probe` for details.
+    pub async fn probe(&mut self) -> Result<DeviceInfo, PortError> {
+        let mut probe = Probe::new();
+        while let Some(cmd) = probe.next_command() {
+            self.send(&cmd).await?;
+            let msg = loop {
+                let msg = self.read_message_or_cancel().await?;
+                match transport::probe_response(&mut self.host, &mut self.queued, msg) {
+                    Some(msg) => break msg,
+                    None => continue,
+                }
+            };
+
+            if let Some(info) = probe.receive(&msg)? {
+                return Ok(info);
+            }
+        }
+
+        // `Probe` returns the `DeviceInfo` when it runs out of commands, so this is not reached.
+        Err(Error::invalid_state().into())
+    }
+
+    /// Receives the next message from the device.
+    ///
+    /// Messages that were queued while waiting for a response are returned first.
+    pub async fn recv(&mut self) -> Result<Incoming, PortError> {
+        if let Some(incoming) = self.queued.pop_front() {
+            return Ok(incoming?);
+        }
+
+        let msg = self.read_message().await?;
+        Ok(self.host.receive(&msg?)?)
+    }
+
+    /// Returns a stream of the notifications received from the device.
+    ///
+    /// Responses that do not answer a command, for example late responses to a cancelled command,
+    /// are skipped. The stream ends after the port reports an I/O error.
+    pub fn notifications(
+        &mut self,
+    ) -> impl Stream<Item = Result<(Notification, Option<Timestamp>), PortError>> + '_ {
+        stream::unfold(Some(self), |this| async move {
+            let this = this?;
+            loop {
+                match this.recv().await {
+                    Ok(Incoming::Notification { notif, timestamp }) => {
+                        return Some((Ok((notif, timestamp)), Some(this)))
+                    }
+                    Ok(Incoming::Response(_)) => {}
+                    Err(e @ PortError::Io(_)) => return Some((Err(e), None)),
+                    Err(e) => return Some((Err(e), Some(this))),
+                }
+            }
+        })
+    }
+
+    async fn send(&mut self, cmd: &Command) -> Result<(), PortError> {
+        let mut buf = CommandEncodeBuf::new();
+        let bytes = self.host.send(cmd, &mut buf)?;
+        let res = match self.port.write_all(bytes).await {
+            Ok(()) => self.port.flush().await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = res {
+            self.host.cancel_pending();
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    async fn read_message_or_cancel(&mut self) -> io::Result<Result<Vec<u8>, Error>> {
+        let res = self.read_message().await;
+        if res.is_err() {
+            self.host.cancel_pending();
+        }
+        res
+    }
+
+    /// Reads the next message terminated by `CR` or BELL, including the terminator.
+    ///
+    /// Malformed input is reported via the inner `Result`.
+    async fn read_message(&mut self) -> io::Result<Result<Vec<u8>, Error>> {
+        loop {
+            if let Some(msg) = self.framer.next_message() {
+                return Ok(msg);
+            }
+
+            let mut chunk = [0; 64];
+            let len = self.port.read(&mut chunk).await?;
+            if len == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            self.framer.extend(&chunk[..len]);
+        }
+    }
+}
+
+impl<P> core::fmt::Debug for AsyncSlcan<P> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("AsyncSlcan")
+            .field("host", &self.host)
+            .field("framer", &self.framer)
+            .field("queued", &self.queued)
+            .finish()
+    }
+}
@@ -0,0 +1,171 @@
+//! Bridge between an SLCAN device and a SocketCAN interface.
+
+use super::{PortError, SlcanPort};
+use crate::read::Incoming;
+use crate::Frame;
+use core::convert::{Infallible, TryFrom};
+use socketcan::{CanAnyFrame, CanFdSocket, Socket};
+use std::boxed::Box;
+use std::io::{self, Read, Write};
+use std::time::Duration;
+
+/// Forwards frames between an SLCAN device and a SocketCAN interface.
+///
+/// Frames received by the device are written to the SocketCAN socket, and frames read from the
+/// socket are transmitted by the device. This makes the device usable by any SocketCAN tool, for
+/// example by bridging it to a virtual `vcan` interface.
+///
+/// The bridge does not configure the device. Open its channel via `port_mut`, for example with
+/// `SlcanPort::setup`, before forwarding frames.
+///
+/// Both directions are served by the same thread: the bridge alternates between waiting for a
+/// message from the device, which ends when the serial port's read timeout expires, and forwarding
+/// all frames that are waiting on the socket. The read timeout thus determines how long frames from
+/// the socket may be delayed.
+///
+/// Error frames are not forwarded in either direction.
+///
+/// # Example
+///
+/// ```no_run
+/// use mini_slcan::Bitrate;
+/// use mini_slcan::host::{Setup, SocketCanBridge};
+///
+/// # fn main() -> Result<(), mini_slcan::host::PortError> {
+/// let mut bridge = SocketCanBridge::open("/dev/ttyACM0", "vcan0")?;
+/// bridge
+///     .port_mut()
+///     .setup(&Setup::new().bitrate(Bitrate::_500kbit))?;
+/// bridge.run()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct SocketCanBridge<P = Box<dyn serialport::SerialPort>> {
+    port: SlcanPort<P>,
+    socket: CanFdSocket,
+}
+
+impl SocketCanBridge {
+    /// Read timeout of the serial port opened by `open`.
+    pub const POLL_TIMEOUT: Duration = Duration::from_millis(10);
+
+    /// Opens the serial port at `path` with a read timeout of `POLL_TIMEOUT`, and the SocketCAN
+    /// interface `ifname`.
+    pub fn open(path: &str, ifname: &str) -> Result<Self, PortError> {
+        let port = serialport::new(path, SlcanPort::DEFAULT_BAUD_RATE)
+            .timeout(Self::POLL_TIMEOUT)
+            .open()
+            .map_err(io::Error::from)?;
+        Self::new(SlcanPort::new(port), CanFdSocket::open(ifname)?)
+    }
+}
+
+impl<P: Read + Write> SocketCanBridge<P> {
+    /// Creates a bridge between `port` and `socket`.
+    ///
+    /// This puts `socket` into non-blocking mode. The underlying serial port should have a short
+    /// read timeout.
+    pub fn new(port: SlcanPort<P>, socket: CanFdSocket) -> Result<Self, PortError> {
+        socket.set_nonblocking(true)?;
+        Ok(Self { port, socket })
+    }
+
+    /// Returns a mutable reference to the `SlcanPort` the device is connected to.
+    pub fn port_mut(&mut self) -> &mut SlcanPort<P> {
+        &mut self.port
+    }
+
+    /// Returns a reference to the SocketCAN socket.
+    pub fn socket(&self) -> &CanFdSocket {
+        &self.socket
+    }
+
+    /// Releases the `SlcanPort` and the SocketCAN socket.
+    pub fn free(self) -> (SlcanPort<P>, CanFdSocket) {
+        (self.port, self.socket)
+    }
+
+    /// Forwards frames until an I/O error occurs.
+    ///
+    /// Malformed messages and frames rejected by the device are dropped.
+    pub fn run(&mut self) -> Result<Infallible, PortError> {
+        loop {
+            if let Err(e @ PortError::Io(_)) = self.forward() {
+                return Err(e);
+            }
+        }
+    }
+
+    /// Waits for the next message from the device and forwards it, then forwards all frames
+    /// waiting on the socket.
+    ///
+    /// Returns early if the device sends a malformed message or rejects a frame. Frames that the
+    /// device does not acknowledge before the port's read timeout expires are not retried, and
+    /// their late acknowledgement is skipped like any other response.
+    pub fn forward(&mut self) -> Result<(), PortError> {
+        forward(&mut self.port, &self.socket)
+    }
+}
+
+/// A socket that frames are forwarded to and from.
+///
+/// This is implemented by `CanFdSocket`, and by mock sockets in tests.
+pub(super) trait FrameSocket {
+    /// Reads the next frame, or fails with `WouldBlock` if none is waiting.
+    fn read_frame(&self) -> io::Result<CanAnyFrame>;
+
+    /// Writes `frame`, waiting for space in the socket's buffer if necessary.
+    fn write_frame(&self, frame: &CanAnyFrame) -> io::Result<()>;
+}
+
+impl FrameSocket for CanFdSocket {
+    fn read_frame(&self) -> io::Result<CanAnyFrame> {
+        Socket::read_frame(self)
+    }
+
+    fn write_frame(&self, frame: &CanAnyFrame) -> io::Result<()> {
+        Socket::write_frame_insist(self, frame)
+    }
+}
+
+/// Implements `SocketCanBridge::forward`.
+pub(super) fn forward<P: Read + Write, S: FrameSocket>(
+    port: &mut SlcanPort<P>,
+    socket: &S,
+) -> Result<(), PortError> {
+    match port.recv() {
+        Ok(Incoming::Notification { notif, .. }) => {
+            if let Some(frame) = notif.to_frame() {
+                socket.write_frame(&CanAnyFrame::from(frame))?;
+            }
+        }
+        Ok(Incoming::Response(_)) => {}
+        Err(PortError::Io(e)) if e.kind() == io::ErrorKind::TimedOut => {}
+        Err(e) => return Err(e),
+    }
+
+    loop {
+        let frame = match socket.read_frame() {
+            Ok(frame) => frame,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        if let Ok(frame) = Frame::try_from(frame) {
+            match port.send_frame(&frame) {
+                // The port's read timeout is short, so a slow acknowledgement must not stop the
+                // bridge.
+                Err(PortError::Io(e)) if e.kind() == io::ErrorKind::TimedOut => {}
+                res => res?,
+            }
+        }
+    }
+}
+
+impl<P> core::fmt::Debug for SocketCanBridge<P> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SocketCanBridge")
+            .field("port", &self.port)
+            .field("socket", &self.socket)
+            .finish()
+    }
+}
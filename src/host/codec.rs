@@ -0,0 +1,106 @@
+//! `tokio_util::codec` support.
+
+use super::transport::{self, PortError, Split};
+use super::SlcanHost;
+use crate::read::{Command, DecodeOptions, Incoming};
+use crate::write::CommandEncodeBuf;
+use crate::Error;
+use bytes::{Buf, BytesMut};
+use std::io;
+use tokio_util::codec::{Decoder, Encoder};
+
+/// A host-side codec for framed SLCAN transports.
+///
+/// `SlcanCodec` encodes `Command`s sent to the device, and decodes the messages it sends back into
+/// `Incoming` messages. Like `SlcanHost`, which it uses internally, it refuses to encode commands
+/// that are invalid in the current state of the channel, and decodes responses in the context of
+/// the last command sent.
+///
+/// Malformed messages are yielded as `Err` items instead of failing the decoder, so that a
+/// `Framed` stream keeps running after receiving them.
+///
+/// # Example
+///
+/// ```no_run
+/// use futures_util::{SinkExt, StreamExt};
+/// use mini_slcan::Bitrate;
+/// use mini_slcan::host::SlcanCodec;
+/// use mini_slcan::read::Command;
+/// use tokio_util::codec::Framed;
+///
+/// # async fn run() -> Result<(), mini_slcan::host::PortError> {
+/// let port = tokio_serial::SerialStream::open(&tokio_serial::new("/dev/ttyACM0", 115_200))
+///     .map_err(std::io::Error::from)?;
+/// let mut framed = Framed::new(port, SlcanCodec::new());
+///
+/// framed.send(Command::SetupWithBitrate { bitrate: Bitrate::_500kbit }).await?;
+/// while let Some(msg) = framed.next().await {
+///     println!("{:?}", msg?);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct SlcanCodec {
+    host: SlcanHost,
+}
+
+impl SlcanCodec {
+    /// Creates a new `SlcanCodec`, assuming that the device's channel is closed and not
+    /// configured.
+    pub const fn new() -> Self {
+        Self::with_options(DecodeOptions::new())
+    }
+
+    /// Creates a new `SlcanCodec` that decodes messages using the given `DecodeOptions`.
+    pub const fn with_options(options: DecodeOptions) -> Self {
+        Self {
+            host: SlcanHost::with_options(options),
+        }
+    }
+
+    /// Returns the `SlcanHost` tracking the state of the device's channel.
+    pub fn host(&self) -> &SlcanHost {
+        &self.host
+    }
+
+    /// Returns a mutable reference to the `SlcanHost`, for example to cancel a pending command
+    /// whose response did not arrive in time.
+    pub fn host_mut(&mut self) -> &mut SlcanHost {
+        &mut self.host
+    }
+}
+
+impl Decoder for SlcanCodec {
+    type Item = Result<Incoming, Error>;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        Ok(Some(match transport::split(src) {
+            Some(Split::Message(len)) => {
+                let msg = src.split_to(len);
+                self.host.receive(&msg)
+            }
+            Some(Split::Garbage(len)) => {
+                src.advance(len);
+                Err(Error::bell_in_message(len))
+            }
+            Some(Split::Overflow) => {
+                let discarded = src.len();
+                src.clear();
+                Err(Error::overflow(discarded))
+            }
+            None => return Ok(None),
+        }))
+    }
+}
+
+impl Encoder<Command> for SlcanCodec {
+    type Error = PortError;
+
+    fn encode(&mut self, cmd: Command, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let mut buf = CommandEncodeBuf::new();
+        dst.extend_from_slice(self.host.send(&cmd, &mut buf)?);
+        Ok(())
+    }
+}
@@ -0,0 +1,232 @@
+//! Blocking host transport over a serial port.
+
+use super::transport::{self, Framer, PortError};
+use super::{DeviceInfo, Probe, Setup, SlcanHost};
+use crate::read::{Command, DecodeOptions, Incoming};
+use crate::write::{CommandEncodeBuf, Response};
+use crate::{Error, Frame};
+use std::boxed::Box;
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::time::Duration;
+use std::vec::Vec;
+
+/// An SLCAN device connected to a serial port.
+///
+/// `SlcanPort` combines an `SlcanHost` with a serial port (or any other `Read + Write` byte
+/// stream). Every method blocks until the device has answered, and checks that the command is
+/// valid in the current state of the channel, just like `SlcanHost::send`.
+///
+/// Notifications that arrive while a method waits for a response are queued, and returned by later
+/// calls to `recv`.
+///
+/// # Example
+///
+/// ```no_run
+/// use mini_slcan::{Bitrate, CanFrame, Frame, FrameKind, Identifier};
+/// use mini_slcan::host::{Setup, SlcanPort};
+/// use mini_slcan::read::Incoming;
+///
+/// # fn main() -> Result<(), mini_slcan::host::PortError> {
+/// let mut port = SlcanPort::open("/dev/ttyACM0")?;
+/// port.setup(&Setup::new().bitrate(Bitrate::_500kbit))?;
+///
+/// port.send_frame(&Frame {
+///     id: Identifier::new_const(0x123).into(),
+///     kind: FrameKind::Data(CanFrame::from([0xAA, 0xBB])),
+/// })?;
+///
+/// loop {
+///     if let Incoming::Notification { notif, .. } = port.recv()? {
+///         println!("{:?}", notif);
+///     }
+/// }
+/// # }
+/// ```
+pub struct SlcanPort<P = Box<dyn serialport::SerialPort>> {
+    port: P,
+    host: SlcanHost,
+    framer: Framer,
+    /// Messages received while waiting for a response.
+    queued: VecDeque<Result<Incoming, Error>>,
+}
+
+impl SlcanPort {
+    /// Baud rate used by `open`. USB devices usually ignore it.
+    pub const DEFAULT_BAUD_RATE: u32 = 115_200;
+
+    /// Read timeout used by `open`.
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+    /// Opens the serial port at `path`, with a baud rate of `DEFAULT_BAUD_RATE` and a read timeout
+    /// of `DEFAULT_TIMEOUT`.
+    ///
+    /// Use `serialport::new` and `SlcanPort::new` to open the port with different settings.
+    pub fn open(path: &str) -> Result<Self, PortError> {
+        let port = serialport::new(path, Self::DEFAULT_BAUD_RATE)
+            .timeout(Self::DEFAULT_TIMEOUT)
+            .open()
+            .map_err(io::Error::from)?;
+        Ok(Self::new(port))
+    }
+}
+
+impl<P: Read + Write> SlcanPort<P> {
+    /// Creates an `SlcanPort` communicating over `port`, assuming that the device's channel is
+    /// closed and not configured.
+    pub fn new(port: P) -> Self {
+        Self::with_options(port, DecodeOptions::new())
+    }
+
+    /// Creates an `SlcanPort` that decodes messages using the given `DecodeOptions`.
+    pub fn with_options(port: P, options: DecodeOptions) -> Self {
+        Self {
+            port,
+            host: SlcanHost::with_options(options),
+            framer: Framer::default(),
+            queued: VecDeque::new(),
+        }
+    }
+
+    /// Returns the `SlcanHost` tracking the state of the device's channel.
+    pub fn host(&self) -> &SlcanHost {
+        &self.host
+    }
+
+    /// Returns a mutable reference to the underlying port, for example to change its timeout.
+    pub fn port_mut(&mut self) -> &mut P {
+        &mut self.port
+    }
+
+    /// Releases the underlying port.
+    pub fn free(self) -> P {
+        self.port
+    }
+
+    /// Sends `cmd` to the device, and waits for its response.
+    ///
+    /// Returns an error of kind `Rejected` if the device answers with an error (BELL), and of kind
+    /// `InvalidState` if `cmd` is not valid in the current state. If reading the response fails,
+    /// for example because the port timed out, the command is no longer awaited.
+    ///
+    /// `PollOne` is answered with `Response::Ack`, and the polled frame, if any, is returned by
+    /// `recv`.
+    pub fn command(&mut self, cmd: &Command) -> Result<Response, PortError> {
+        self.send(cmd)?;
+        loop {
+            let msg = self.read_message_or_cancel()?;
+            if let Some(res) = transport::command_response(&mut self.host, &mut self.queued, msg) {
+                return res;
+            }
+        }
+    }
+
+    /// Transmits `frame` on the CAN bus.
+    ///
+    /// The channel has to be open in normal mode.
+    pub fn send_frame(&mut self, frame: &Frame) -> Result<(), PortError> {
+        self.command(&Command::from(*frame))?;
+        Ok(())
+    }
+
+    /// Configures and opens the CAN channel by sending the commands of `setup`.
+    ///
+    /// The channel has to be closed.
+    pub fn setup(&mut self, setup: &Setup) -> Result<(), PortError> {
+        for cmd in setup.commands()? {
+            self.command(&cmd)?;
+        }
+        Ok(())
+    }
+
+    /// Closes the CAN channel.
+    pub fn close(&mut self) -> Result<(), PortError> {
+        self.command(&Command::Close)?;
+        Ok(())
+    }
+
+    /// Identifies the device by querying its version and serial number.
+    ///
+    /// See `Probe` for details.
+    pub fn probe(&mut self) -> Result<DeviceInfo, PortError> {
+        let mut probe = Probe::new();
+        while let Some(cmd) = probe.next_command() {
+            self.send(&cmd)?;
+            let msg = loop {
+                let msg = self.read_message_or_cancel()?;
+                match transport::probe_response(&mut self.host, &mut self.queued, msg) {
+                    Some(msg) => break msg,
+                    None => continue,
+                }
+            };
+
+            if let Some(info) = probe.receive(&msg)? {
+                return Ok(info);
+            }
+        }
+
+        // `Probe` returns the `DeviceInfo` when it runs out of commands, so this is not reached.
+        Err(Error::invalid_state().into())
+    }
+
+    /// Receives the next message from the device.
+    ///
+    /// Messages that were queued while waiting for a response are returned first. Otherwise, this
+    /// blocks until a message arrives, or until the port times out.
+    pub fn recv(&mut self) -> Result<Incoming, PortError> {
+        if let Some(incoming) = self.queued.pop_front() {
+            return Ok(incoming?);
+        }
+
+        let msg = self.read_message()?;
+        Ok(self.host.receive(&msg?)?)
+    }
+
+    fn send(&mut self, cmd: &Command) -> Result<(), PortError> {
+        let mut buf = CommandEncodeBuf::new();
+        let bytes = self.host.send(cmd, &mut buf)?;
+        if let Err(e) = self.port.write_all(bytes).and_then(|()| self.port.flush()) {
+            self.host.cancel_pending();
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    fn read_message_or_cancel(&mut self) -> io::Result<Result<Vec<u8>, Error>> {
+        let res = self.read_message();
+        if res.is_err() {
+            self.host.cancel_pending();
+        }
+        res
+    }
+
+    /// Reads the next message terminated by `CR` or BELL, including the terminator.
+    ///
+    /// Malformed input is reported via the inner `Result`.
+    fn read_message(&mut self) -> io::Result<Result<Vec<u8>, Error>> {
+        loop {
+            if let Some(msg) = self.framer.next_message() {
+                return Ok(msg);
+            }
+
+            let mut chunk = [0; 64];
+            let len = match self.port.read(&mut chunk) {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(len) => len,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            self.framer.extend(&chunk[..len]);
+        }
+    }
+}
+
+impl<P> core::fmt::Debug for SlcanPort<P> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SlcanPort")
+            .field("host", &self.host)
+            .field("framer", &self.framer)
+            .field("queued", &self.queued)
+            .finish()
+    }
+}
@@ -0,0 +1,237 @@
+//! Runtime-independent host transport over `futures-io`.
+
+use super::transport::{Framer, PortError};
+use super::SlcanHost;
+use crate::read::{Command, DecodeOptions, Incoming};
+use crate::write::{CommandEncodeBuf, Notification, Timestamp};
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use futures_core::{ready, Stream};
+use futures_io::{AsyncRead, AsyncWrite};
+use futures_sink::Sink;
+use std::io;
+use std::vec::Vec;
+
+/// An SLCAN device connected to a `futures-io` reader and writer.
+///
+/// `SlcanStream` is a `Sink` of `Command`s sent to the device, and a `Stream` of the `Incoming`
+/// messages it sends back. It does not depend on any particular async runtime, so it works with
+/// `async-std`, `smol`, or custom executors.
+///
+/// Like `SlcanHost`, which it uses internally, it refuses commands that are invalid in the current
+/// state of the channel, and decodes responses in the context of the last command sent. Since only
+/// one command can await its response at a time, the response has to be received from the stream
+/// before the next command is sent.
+///
+/// Malformed messages are yielded as `Err` items, after which the stream keeps running. The stream
+/// ends when the reader reaches EOF or reports an I/O error.
+///
+/// Unlike `AsyncSlcan`, which only streams notifications, `SlcanStream` yields responses as well,
+/// since they are the only way to learn the result of a command sent via the `Sink`. The
+/// `notifications` adapter skips them, for applications that only receive frames.
+///
+/// # Example
+///
+/// ```no_run
+/// use futures::io::{AsyncRead, AsyncWrite};
+/// use futures::{SinkExt, StreamExt};
+/// use mini_slcan::Bitrate;
+/// use mini_slcan::host::SlcanStream;
+/// use mini_slcan::read::{Command, Incoming};
+///
+/// # async fn run<R, W>(reader: R, writer: W) -> Result<(), mini_slcan::host::PortError>
+/// # where R: AsyncRead + Unpin, W: AsyncWrite + Unpin {
+/// let mut slcan = SlcanStream::new(reader, writer);
+///
+/// slcan.send(Command::SetupWithBitrate { bitrate: Bitrate::_500kbit }).await?;
+/// while let Some(msg) = slcan.next().await {
+///     if let Incoming::Notification { notif, .. } = msg? {
+///         println!("{:?}", notif);
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct SlcanStream<R, W> {
+    reader: R,
+    writer: W,
+    host: SlcanHost,
+    framer: Framer,
+    /// Encoded commands that have not been written yet.
+    tx: Vec<u8>,
+    /// Whether the reader has reached EOF or failed.
+    done: bool,
+}
+
+impl<R: AsyncRead + Unpin, W: AsyncWrite + Unpin> SlcanStream<R, W> {
+    /// Creates an `SlcanStream` that receives messages from `reader` and sends commands to
+    /// `writer`, assuming that the device's channel is closed and not configured.
+    pub fn new(reader: R, writer: W) -> Self {
+        Self::with_options(reader, writer, DecodeOptions::new())
+    }
+
+    /// Creates an `SlcanStream` that decodes messages using the given `DecodeOptions`.
+    pub fn with_options(reader: R, writer: W, options: DecodeOptions) -> Self {
+        Self {
+            reader,
+            writer,
+            host: SlcanHost::with_options(options),
+            framer: Framer::default(),
+            tx: Vec::new(),
+            done: false,
+        }
+    }
+
+    /// Returns the `SlcanHost` tracking the state of the device's channel.
+    pub fn host(&self) -> &SlcanHost {
+        &self.host
+    }
+
+    /// Returns a mutable reference to the `SlcanHost`, for example to cancel a pending command
+    /// whose response did not arrive in time.
+    pub fn host_mut(&mut self) -> &mut SlcanHost {
+        &mut self.host
+    }
+
+    /// Releases the underlying reader and writer.
+    ///
+    /// Commands that were not flushed yet are discarded.
+    pub fn free(self) -> (R, W) {
+        (self.reader, self.writer)
+    }
+
+    /// Returns a stream of the notifications received from the device.
+    ///
+    /// Like `AsyncSlcan::notifications`, this skips responses and ends after an I/O error.
+    pub fn notifications(&mut self) -> Notifications<'_, R, W> {
+        Notifications { stream: self }
+    }
+
+    /// Writes out the encoded commands.
+    ///
+    /// If writing fails, the last command is no longer awaited.
+    fn poll_write_tx(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), PortError>> {
+        while !self.tx.is_empty() {
+            let res = match ready!(Pin::new(&mut self.writer).poll_write(cx, &self.tx)) {
+                Ok(0) => Err(io::ErrorKind::WriteZero.into()),
+                res => res,
+            };
+            match res {
+                Ok(len) => {
+                    self.tx.drain(..len);
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    self.tx.clear();
+                    self.host.cancel_pending();
+                    return Poll::Ready(Err(e.into()));
+                }
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<R: AsyncRead + Unpin, W: AsyncWrite + Unpin> Stream for SlcanStream<R, W> {
+    type Item = Result<Incoming, PortError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(msg) = this.framer.next_message() {
+                let incoming = msg.and_then(|msg| this.host.receive(&msg));
+                return Poll::Ready(Some(incoming.map_err(PortError::from)));
+            }
+            if this.done {
+                return Poll::Ready(None);
+            }
+
+            let mut chunk = [0; 64];
+            match ready!(Pin::new(&mut this.reader).poll_read(cx, &mut chunk)) {
+                Ok(0) => this.done = true,
+                Ok(len) => this.framer.extend(&chunk[..len]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    this.done = true;
+                    this.host.cancel_pending();
+                    return Poll::Ready(Some(Err(e.into())));
+                }
+            }
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin, W: AsyncWrite + Unpin> Sink<Command> for SlcanStream<R, W> {
+    type Error = PortError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.get_mut().poll_write_tx(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, cmd: Command) -> Result<(), Self::Error> {
+        let this = self.get_mut();
+        let mut buf = CommandEncodeBuf::new();
+        this.tx.extend_from_slice(this.host.send(&cmd, &mut buf)?);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_tx(cx))?;
+        Pin::new(&mut this.writer)
+            .poll_flush(cx)
+            .map_err(PortError::from)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_tx(cx))?;
+        Pin::new(&mut this.writer)
+            .poll_close(cx)
+            .map_err(PortError::from)
+    }
+}
+
+/// A stream of the notifications received by an `SlcanStream`.
+///
+/// This is returned by `SlcanStream::notifications`.
+pub struct Notifications<'a, R, W> {
+    stream: &'a mut SlcanStream<R, W>,
+}
+
+impl<R: AsyncRead + Unpin, W: AsyncWrite + Unpin> Stream for Notifications<'_, R, W> {
+    type Item = Result<(Notification, Option<Timestamp>), PortError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let stream = &mut *self.get_mut().stream;
+        loop {
+            match ready!(Pin::new(&mut *stream).poll_next(cx)) {
+                Some(Ok(Incoming::Notification { notif, timestamp })) => {
+                    return Poll::Ready(Some(Ok((notif, timestamp))))
+                }
+                Some(Ok(Incoming::Response(_))) => {}
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+impl<R, W> core::fmt::Debug for Notifications<'_, R, W> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Notifications")
+            .field("stream", &self.stream)
+            .finish()
+    }
+}
+
+impl<R, W> core::fmt::Debug for SlcanStream<R, W> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SlcanStream")
+            .field("host", &self.host)
+            .field("framer", &self.framer)
+            .field("tx", &self.tx)
+            .field("done", &self.done)
+            .finish()
+    }
+}
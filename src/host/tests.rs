@@ -0,0 +1,695 @@
+use super::*;
+use crate::{Bitrate, CanFrame, ChannelState, ErrorKind, Identifier, SerialBaud};
+
+fn send(host: &mut SlcanHost, cmd: &Command) -> Result<Vec<u8>, ErrorKind> {
+    let mut buf = CommandEncodeBuf::new();
+    host.send(cmd, &mut buf)
+        .map(|bytes| bytes.to_vec())
+        .map_err(|e| e.kind())
+}
+
+fn tx() -> Command {
+    Command::TxStandard {
+        identifier: Identifier::from_raw(0x123).unwrap(),
+        frame: CanFrame::from([0xAA]),
+    }
+}
+
+#[test]
+fn states() {
+    let mut host = SlcanHost::new();
+    assert_eq!(host.state(), ChannelState::Closed);
+    assert_eq!(send(&mut host, &tx()), Err(ErrorKind::InvalidState));
+    assert_eq!(
+        send(&mut host, &Command::Open),
+        Err(ErrorKind::InvalidState)
+    );
+    assert_eq!(
+        send(&mut host, &Command::Close),
+        Err(ErrorKind::InvalidState)
+    );
+    assert_eq!(
+        send(&mut host, &Command::ReadStatus),
+        Err(ErrorKind::InvalidState)
+    );
+
+    // A refused setup command leaves the host unconfigured.
+    let setup = Command::SetupWithBitrate {
+        bitrate: Bitrate::_125kbit,
+    };
+    assert_eq!(send(&mut host, &setup).unwrap(), b"S4\r");
+    assert_eq!(send(&mut host, &setup), Err(ErrorKind::InvalidState));
+    assert!(matches!(
+        host.receive(b"\x07"),
+        Ok(Incoming::Response(Response::Error))
+    ));
+    assert!(!host.is_configured());
+
+    send(&mut host, &setup).unwrap();
+    host.receive(b"\r").unwrap();
+    assert!(host.is_configured());
+
+    send(&mut host, &Command::OpenListenOnly).unwrap();
+    host.receive(b"\r").unwrap();
+    assert_eq!(host.state(), ChannelState::ListenOnly);
+    assert_eq!(send(&mut host, &tx()), Err(ErrorKind::InvalidState));
+    assert_eq!(send(&mut host, &setup), Err(ErrorKind::InvalidState));
+    let auto_poll = Command::SetAutoPoll { enabled: true };
+    assert_eq!(send(&mut host, &auto_poll), Err(ErrorKind::InvalidState));
+    let baud = Command::SetSerialBaudrate {
+        baud: SerialBaud::_115200,
+    };
+    assert_eq!(send(&mut host, &baud), Err(ErrorKind::InvalidState));
+    assert_eq!(send(&mut host, &Command::ReadStatus).unwrap(), b"F\r");
+    host.receive(b"F00\r").unwrap();
+
+    send(&mut host, &Command::Close).unwrap();
+    host.receive(b"\r").unwrap();
+    assert_eq!(host.state(), ChannelState::Closed);
+
+    send(&mut host, &Command::Open).unwrap();
+    // Notifications may arrive before the response.
+    assert!(matches!(
+        host.receive(b"t1230\r"),
+        Ok(Incoming::Notification { .. })
+    ));
+    host.receive(b"\r").unwrap();
+    assert_eq!(host.state(), ChannelState::Open);
+    assert_eq!(send(&mut host, &tx()).unwrap(), b"t1231AA\r");
+    assert!(matches!(
+        host.receive(b"z\r"),
+        Ok(Incoming::Response(Response::TxAck))
+    ));
+
+    // A command that is never answered can be cancelled.
+    send(&mut host, &Command::Close).unwrap();
+    host.cancel_pending();
+    assert!(!host.is_awaiting_response());
+    assert_eq!(host.state(), ChannelState::Open);
+}
+
+#[test]
+fn timestamps() {
+    let mut host = SlcanHost::new();
+    send(
+        &mut host,
+        &Command::SetRxTimestamp {
+            mode: TimestampMode::Milliseconds,
+        },
+    )
+    .unwrap();
+    host.receive(b"\r").unwrap();
+
+    match host.receive(b"t12301234\r").unwrap() {
+        Incoming::Notification { timestamp, .. } => {
+            assert_eq!(timestamp, Some(crate::write::Timestamp::Millis(0x1234)))
+        }
+        other => panic!("unexpected message {:?}", other),
+    }
+}
+
+#[test]
+fn queue() {
+    let mut queue = CommandQueue::new();
+    assert!(queue.receive(b"\r").is_none());
+
+    for _ in 0..CommandQueue::CAPACITY {
+        queue.push(Command::ReadStatus).unwrap();
+    }
+    assert_eq!(queue.push(tx()), Err(tx()));
+
+    assert_eq!(queue.pop(), Some(Command::ReadStatus));
+    queue.push(tx()).unwrap();
+    assert!(queue.is_full());
+
+    for _ in 1..CommandQueue::CAPACITY {
+        assert!(queue.receive(b"t1230\r").is_none());
+        let (cmd, resp) = queue.receive(b"F00\r").unwrap();
+        assert_eq!(cmd, Command::ReadStatus);
+        assert_eq!(resp.unwrap(), Response::Status(crate::Status::empty()));
+    }
+
+    // Responses that don't match the command are reported as errors.
+    assert_eq!(queue.oldest(), Some(&tx()));
+    let (cmd, resp) = queue.receive(b"Z\r").unwrap();
+    assert_eq!(cmd, tx());
+    assert_eq!(resp.unwrap_err().kind(), ErrorKind::Decode);
+    assert!(queue.is_empty());
+    assert_eq!(queue.pop(), None);
+}
+
+#[test]
+#[cfg(feature = "usbtin")]
+fn queue_register_value() {
+    let mut queue = CommandQueue::new();
+    let cmd = Command::ReadRegister { register: 0x2A };
+    queue.push(cmd.clone()).unwrap();
+    queue.push(Command::ReadStatus).unwrap();
+
+    assert!(queue.receive(b"t1230\r").is_none());
+    let (sent, resp) = queue.receive(b"B3\r").unwrap();
+    assert_eq!(sent, cmd);
+    assert_eq!(resp.unwrap(), Response::RegisterValue(0xB3));
+
+    // Once the register read is answered, `B` starts a notification again.
+    assert!(queue.receive(b"B1231\r").is_none());
+    assert_eq!(queue.oldest(), Some(&Command::ReadStatus));
+}
+
+#[test]
+fn probe() {
+    let mut probe = Probe::new();
+    assert_eq!(probe.next_command(), Some(Command::ReadVersion));
+    assert_eq!(
+        probe.receive(b"\x07").unwrap_err().kind(),
+        ErrorKind::Rejected
+    );
+    assert_eq!(
+        probe.receive(b"N0000\r").unwrap_err().kind(),
+        ErrorKind::Decode
+    );
+    assert_eq!(probe.receive(b"V0102\r").unwrap(), None);
+
+    assert_eq!(probe.next_command(), Some(Command::ReadDetailedVersion));
+    assert_eq!(probe.receive(b"t1230\r").unwrap(), None);
+    assert_eq!(probe.receive(b"vfw 1.2.3\r").unwrap(), None);
+
+    assert_eq!(probe.next_command(), Some(Command::ReadSerial));
+    assert_eq!(
+        probe.receive(b"NAB12\r").unwrap(),
+        Some(DeviceInfo {
+            hardware_version: 1,
+            software_version: 2,
+            detailed_version: crate::VersionString::new("fw 1.2.3"),
+            serial: crate::SerialNumber::new(*b"AB12").unwrap(),
+        })
+    );
+    assert_eq!(probe.next_command(), None);
+    assert_eq!(
+        probe.receive(b"\r").unwrap_err().kind(),
+        ErrorKind::InvalidState
+    );
+}
+
+#[test]
+fn setup() {
+    let setup = Setup::new()
+        .bitrate(Bitrate::_1mbit)
+        .btr(0x03, 0x1C)
+        .filter(
+            crate::FilterValue::from_raw(0x12345678),
+            crate::FilterValue::from_raw(0xFFFFFFFF),
+        );
+    let mut buf = [0; Setup::MAX_ENCODED_LEN];
+    let encoded = setup.encode(&mut buf).unwrap();
+    assert_eq!(encoded, b"s031C\rM12345678\rmFFFFFFFF\rZ0\rO\r");
+    assert_eq!(encoded.len(), Setup::MAX_ENCODED_LEN);
+    assert_eq!(
+        setup.encode(&mut [0; 10]).unwrap_err().kind(),
+        ErrorKind::Eof
+    );
+
+    // The sequence is accepted by a closed `SlcanHost`, but not by an open one.
+    let mut host = SlcanHost::new();
+    for cmd in setup.commands().unwrap() {
+        send(&mut host, &cmd).unwrap();
+        host.receive(b"\r").unwrap();
+    }
+    assert_eq!(host.state(), ChannelState::Open);
+    let first = setup.commands().unwrap().next().unwrap();
+    assert_eq!(send(&mut host, &first), Err(ErrorKind::InvalidState));
+}
+
+#[cfg(feature = "serialport")]
+mod port {
+    use super::*;
+    use crate::read::Incoming;
+    use crate::write::Notification;
+    use std::collections::VecDeque;
+    use std::io;
+
+    /// A port that returns pre-recorded device output, and times out once it runs out.
+    struct FakePort {
+        rx: VecDeque<u8>,
+        tx: Vec<u8>,
+    }
+
+    impl io::Read for FakePort {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.rx.is_empty() {
+                return Err(io::ErrorKind::TimedOut.into());
+            }
+
+            // Return at most 3 bytes at a time, to exercise the reassembly of messages.
+            let len = buf.len().min(self.rx.len()).min(3);
+            for (dest, byte) in buf.iter_mut().zip(self.rx.drain(..len)) {
+                *dest = byte;
+            }
+            Ok(len)
+        }
+    }
+
+    impl io::Write for FakePort {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.tx.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn port() -> SlcanPort<FakePort> {
+        SlcanPort::new(FakePort {
+            rx: VecDeque::new(),
+            tx: Vec::new(),
+        })
+    }
+
+    fn device_sends(port: &mut SlcanPort<FakePort>, output: &[u8]) {
+        port.port_mut().rx.extend(output);
+    }
+
+    fn slcan_err<T: core::fmt::Debug>(res: Result<T, PortError>) -> ErrorKind {
+        match res {
+            Err(PortError::Slcan(e)) => e.kind(),
+            res => panic!("unexpected result: {:?}", res),
+        }
+    }
+
+    fn io_err<T: core::fmt::Debug>(res: Result<T, PortError>) -> io::ErrorKind {
+        match res {
+            Err(PortError::Io(e)) => e.kind(),
+            res => panic!("unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn setup_and_transmit() {
+        let mut port = port();
+        let frame = tx().to_frame().unwrap();
+        assert_eq!(slcan_err(port.send_frame(&frame)), ErrorKind::InvalidState);
+
+        device_sends(&mut port, b"\r\r\r");
+        port.setup(&Setup::new().bitrate(Bitrate::_500kbit))
+            .unwrap();
+        assert_eq!(port.host().state(), ChannelState::Open);
+
+        // A notification arriving before the response is queued.
+        device_sends(&mut port, b"t7FF0\rz\r");
+        port.send_frame(&frame).unwrap();
+        match port.recv().unwrap() {
+            Incoming::Notification { notif, timestamp } => {
+                assert!(matches!(notif, Notification::Rx { .. }));
+                assert_eq!(timestamp, None);
+            }
+            incoming => panic!("unexpected message: {:?}", incoming),
+        }
+        assert_eq!(io_err(port.recv()), io::ErrorKind::TimedOut);
+
+        device_sends(&mut port, b"\x07");
+        assert_eq!(slcan_err(port.send_frame(&frame)), ErrorKind::Rejected);
+
+        device_sends(&mut port, b"\r");
+        port.close().unwrap();
+        assert_eq!(port.host().state(), ChannelState::Closed);
+
+        assert_eq!(port.free().tx, b"S6\rZ0\rO\rt1231AA\rt1231AA\rC\r".to_vec());
+    }
+
+    #[test]
+    fn errors() {
+        let mut port = port();
+
+        // A timeout stops waiting for the response, so the next command can be sent.
+        assert_eq!(
+            io_err(port.command(&Command::ReadVersion)),
+            io::ErrorKind::TimedOut
+        );
+        assert!(!port.host().is_awaiting_response());
+
+        // Garbage before a BELL is reported separately from the BELL itself.
+        device_sends(&mut port, b"xx\x07");
+        assert_eq!(
+            slcan_err(port.command(&Command::ReadVersion)),
+            ErrorKind::Rejected
+        );
+        assert_eq!(slcan_err(port.recv()), ErrorKind::MissingTerminator);
+
+        // Overlong input is discarded.
+        device_sends(&mut port, &[b'x'; 300]);
+        device_sends(&mut port, b"V1013\r");
+        assert_eq!(
+            port.command(&Command::ReadVersion).unwrap(),
+            Response::Version {
+                hardware_version: 0x10,
+                software_version: 0x13,
+            }
+        );
+        assert_eq!(slcan_err(port.recv()), ErrorKind::Overflow);
+    }
+
+    #[test]
+    fn probe() {
+        let mut port = port();
+        device_sends(&mut port, b"V1013\r\x07t1230\rNA123\r");
+        let info = port.probe().unwrap();
+        assert_eq!(info.hardware_version, 0x10);
+        assert_eq!(info.detailed_version, None);
+        assert_eq!(info.serial, crate::SerialNumber::new(*b"A123").unwrap());
+        assert!(matches!(port.recv(), Ok(Incoming::Notification { .. })));
+        assert_eq!(port.free().tx, b"V\rv\rN\r".to_vec());
+    }
+
+    #[cfg(all(feature = "socketcan", target_os = "linux"))]
+    #[test]
+    fn bridge_forward() {
+        use crate::host::bridge::{forward, FrameSocket};
+        use crate::Frame;
+        use core::cell::RefCell;
+        use core::convert::TryFrom;
+        use socketcan::CanAnyFrame;
+
+        /// A non-blocking socket with pre-recorded frames to read.
+        #[derive(Default)]
+        struct FakeSocket {
+            rx: RefCell<VecDeque<CanAnyFrame>>,
+            tx: RefCell<Vec<Frame>>,
+        }
+
+        impl FrameSocket for FakeSocket {
+            fn read_frame(&self) -> io::Result<CanAnyFrame> {
+                self.rx
+                    .borrow_mut()
+                    .pop_front()
+                    .ok_or_else(|| io::ErrorKind::WouldBlock.into())
+            }
+
+            fn write_frame(&self, frame: &CanAnyFrame) -> io::Result<()> {
+                self.tx.borrow_mut().push(Frame::try_from(*frame).unwrap());
+                Ok(())
+            }
+        }
+
+        let mut port = port();
+        device_sends(&mut port, b"\r\r\r");
+        port.setup(&Setup::new().bitrate(Bitrate::_500kbit))
+            .unwrap();
+        let socket = FakeSocket::default();
+
+        // Nothing to forward.
+        forward(&mut port, &socket).unwrap();
+
+        // Received frames are written to the socket, and frames read from the socket are
+        // transmitted. The device acknowledges the first one, but the acknowledgement of the
+        // second one times out, which does not stop the bridge.
+        device_sends(&mut port, b"t7FF0\rz\r");
+        let frame = tx().to_frame().unwrap();
+        socket
+            .rx
+            .borrow_mut()
+            .extend(vec![CanAnyFrame::from(frame); 2]);
+        forward(&mut port, &socket).unwrap();
+        assert_eq!(
+            *socket.tx.borrow(),
+            [Notification::decode(b"t7FF0\r")
+                .unwrap()
+                .to_frame()
+                .unwrap()]
+        );
+        assert!(socket.rx.borrow().is_empty());
+
+        // The late acknowledgement is skipped.
+        device_sends(&mut port, b"z\r");
+        forward(&mut port, &socket).unwrap();
+        assert_eq!(socket.tx.borrow().len(), 1);
+
+        assert_eq!(port.free().tx, b"S6\rZ0\rO\rt1231AA\rt1231AA\r".to_vec());
+    }
+}
+
+#[cfg(feature = "tokio")]
+mod async_port {
+    use super::*;
+    use crate::read::Incoming;
+    use crate::write::Notification;
+    use futures_util::StreamExt;
+    use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt, DuplexStream};
+
+    /// Returns the host and device ends of a connection.
+    fn connect() -> (AsyncSlcan<DuplexStream>, DuplexStream) {
+        let (host, device) = duplex(1024);
+        (AsyncSlcan::new(host), device)
+    }
+
+    #[tokio::test]
+    async fn setup_and_transmit() {
+        let (mut slcan, mut device) = connect();
+        let frame = tx().to_frame().unwrap();
+        match slcan.send_frame(&frame).await {
+            Err(PortError::Slcan(e)) => assert_eq!(e.kind(), ErrorKind::InvalidState),
+            res => panic!("unexpected result: {:?}", res),
+        }
+
+        device.write_all(b"\r\r\r").await.unwrap();
+        slcan
+            .setup(&Setup::new().bitrate(Bitrate::_500kbit))
+            .await
+            .unwrap();
+        assert_eq!(slcan.host().state(), ChannelState::Open);
+
+        // A notification arriving before the response is queued.
+        device.write_all(b"t7FF0\rz\r").await.unwrap();
+        slcan.send_frame(&frame).await.unwrap();
+
+        // `PollOne` is answered by the polled frame.
+        device.write_all(b"t1230\r").await.unwrap();
+        assert_eq!(
+            slcan.command(&Command::PollOne).await.unwrap(),
+            Response::Ack
+        );
+
+        device.write_all(b"\x07").await.unwrap();
+        match slcan.send_frame(&frame).await {
+            Err(PortError::Slcan(e)) => assert_eq!(e.kind(), ErrorKind::Rejected),
+            res => panic!("unexpected result: {:?}", res),
+        }
+
+        // An unsolicited response is skipped by the notification stream.
+        let mut sent = [0; 26];
+        device.read_exact(&mut sent).await.unwrap();
+        assert_eq!(&sent, b"S6\rZ0\rO\rt1231AA\rP\rt1231AA\r");
+        device.write_all(b"z\rt1232AABB\r").await.unwrap();
+        drop(device);
+        let notifs = slcan
+            .notifications()
+            .map(|res| res.map(|(notif, _)| notif).map_err(|e| e.to_string()))
+            .collect::<Vec<_>>()
+            .await;
+        let rx = |raw, data: &[u8]| Notification::Rx {
+            identifier: Identifier::from_raw(raw).unwrap(),
+            frame: CanFrame::from_slice(data).unwrap(),
+        };
+        assert_eq!(
+            notifs,
+            [
+                Ok(rx(0x7FF, &[])),
+                Ok(rx(0x123, &[])),
+                Ok(rx(0x123, &[0xAA, 0xBB])),
+                Err("I/O error: unexpected end of file".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn probe() {
+        let (mut slcan, mut device) = connect();
+        device
+            .write_all(b"V1013\r\x07t1230\rNA123\r")
+            .await
+            .unwrap();
+        let info = slcan.probe().await.unwrap();
+        assert_eq!(info.hardware_version, 0x10);
+        assert_eq!(info.detailed_version, None);
+        assert_eq!(info.serial, crate::SerialNumber::new(*b"A123").unwrap());
+        assert!(matches!(
+            slcan.recv().await,
+            Ok(Incoming::Notification { .. })
+        ));
+
+        let mut sent = [0; 6];
+        device.read_exact(&mut sent).await.unwrap();
+        assert_eq!(&sent, b"V\rv\rN\r");
+    }
+}
+
+#[cfg(feature = "tokio")]
+mod codec {
+    use super::*;
+    use crate::read::Incoming;
+    use bytes::BytesMut;
+    use tokio_util::codec::{Decoder, Encoder};
+
+    fn decode_all(codec: &mut SlcanCodec, input: &[u8]) -> Vec<Result<Incoming, ErrorKind>> {
+        let mut src = BytesMut::from(input);
+        let mut msgs = Vec::new();
+        while let Some(msg) = codec.decode(&mut src).unwrap() {
+            msgs.push(msg.map_err(|e| e.kind()));
+        }
+        msgs
+    }
+
+    #[test]
+    fn encode_decode() {
+        let mut codec = SlcanCodec::new();
+        let mut dst = BytesMut::new();
+        match codec.encode(tx(), &mut dst) {
+            Err(PortError::Slcan(e)) => assert_eq!(e.kind(), ErrorKind::InvalidState),
+            res => panic!("unexpected result: {:?}", res),
+        }
+        assert!(dst.is_empty());
+
+        codec
+            .encode(
+                Command::SetupWithBitrate {
+                    bitrate: Bitrate::_500kbit,
+                },
+                &mut dst,
+            )
+            .unwrap();
+        assert_eq!(decode_all(&mut codec, b"\r").len(), 1);
+        codec.encode(Command::Open, &mut dst).unwrap();
+        assert!(matches!(
+            decode_all(&mut codec, b"\r")[..],
+            [Ok(Incoming::Response(Response::Ack))]
+        ));
+        assert_eq!(codec.host().state(), ChannelState::Open);
+
+        codec.encode(tx(), &mut dst).unwrap();
+        assert_eq!(&dst[..], b"S6\rO\rt1231AA\r");
+
+        // Responses are decoded in the context of the pending command. Incomplete messages are
+        // kept until the rest of them arrives.
+        let mut src = BytesMut::from(&b"t1230\rz\rt12"[..]);
+        assert!(matches!(
+            codec.decode(&mut src).unwrap(),
+            Some(Ok(Incoming::Notification { .. }))
+        ));
+        assert!(matches!(
+            codec.decode(&mut src).unwrap(),
+            Some(Ok(Incoming::Response(Response::TxAck)))
+        ));
+        assert!(codec.decode(&mut src).unwrap().is_none());
+        assert_eq!(&src[..], b"t12");
+        assert!(!codec.host().is_awaiting_response());
+    }
+
+    #[test]
+    fn malformed() {
+        let mut codec = SlcanCodec::new();
+        let msgs = decode_all(&mut codec, b"xx\x07\x07");
+        assert_eq!(msgs.len(), 3);
+        assert_eq!(msgs[0].as_ref().unwrap_err(), &ErrorKind::MissingTerminator);
+        assert!(matches!(msgs[1], Ok(Incoming::Response(Response::Error))));
+
+        let mut src = BytesMut::from(&[b'x'; 300][..]);
+        match codec.decode(&mut src).unwrap() {
+            Some(Err(e)) => {
+                assert_eq!(e.kind(), ErrorKind::Overflow);
+                assert_eq!(e.bytes_discarded(), Some(300));
+            }
+            res => panic!("unexpected result: {:?}", res),
+        }
+        assert!(src.is_empty());
+    }
+}
+
+#[cfg(feature = "futures")]
+mod stream {
+    use super::*;
+    use crate::read::Incoming;
+    use crate::write::Notification;
+    use futures::executor::block_on;
+    use futures::io::Cursor;
+    use futures::{SinkExt, StreamExt};
+
+    #[test]
+    fn send_receive() {
+        let device = Cursor::new(b"\r\rt1230\rz\rxx\x07".to_vec());
+        let mut slcan = SlcanStream::new(device, Vec::new());
+        block_on(async {
+            match slcan.send(tx()).await {
+                Err(PortError::Slcan(e)) => assert_eq!(e.kind(), ErrorKind::InvalidState),
+                res => panic!("unexpected result: {:?}", res),
+            }
+
+            slcan
+                .send(Command::SetupWithBitrate {
+                    bitrate: Bitrate::_500kbit,
+                })
+                .await
+                .unwrap();
+            assert!(matches!(
+                slcan.next().await,
+                Some(Ok(Incoming::Response(Response::Ack)))
+            ));
+            slcan.send(Command::Open).await.unwrap();
+            assert!(matches!(
+                slcan.next().await,
+                Some(Ok(Incoming::Response(Response::Ack)))
+            ));
+            assert_eq!(slcan.host().state(), ChannelState::Open);
+
+            // Notifications that arrive before the response are passed through.
+            slcan.send(tx()).await.unwrap();
+            assert!(matches!(
+                slcan.next().await,
+                Some(Ok(Incoming::Notification { .. }))
+            ));
+            assert!(matches!(
+                slcan.next().await,
+                Some(Ok(Incoming::Response(Response::TxAck)))
+            ));
+
+            match slcan.next().await {
+                Some(Err(PortError::Slcan(e))) => {
+                    assert_eq!(e.kind(), ErrorKind::MissingTerminator)
+                }
+                res => panic!("unexpected result: {:?}", res),
+            }
+            assert!(matches!(
+                slcan.next().await,
+                Some(Ok(Incoming::Response(Response::Error)))
+            ));
+            assert!(slcan.next().await.is_none());
+        });
+
+        let (_, written) = slcan.free();
+        assert_eq!(written, b"S6\rO\rt1231AA\r");
+    }
+
+    #[test]
+    fn notifications() {
+        let device = Cursor::new(b"\x07t1230\r\x07xx\rT000000010\r".to_vec());
+        let mut slcan = SlcanStream::new(device, Vec::new());
+        block_on(async {
+            let mut notifications = slcan.notifications();
+            assert!(matches!(
+                notifications.next().await,
+                Some(Ok((Notification::Rx { .. }, None)))
+            ));
+            match notifications.next().await {
+                Some(Err(PortError::Slcan(e))) => {
+                    assert_eq!(e.kind(), ErrorKind::UnsupportedCommand)
+                }
+                res => panic!("unexpected result: {:?}", res),
+            }
+            assert!(matches!(
+                notifications.next().await,
+                Some(Ok((Notification::RxExt { .. }, None)))
+            ));
+            assert!(notifications.next().await.is_none());
+        });
+    }
+}
@@ -0,0 +1,163 @@
+//! Functionality shared by the host transports.
+
+use crate::read::HOST_BUF_LEN;
+use crate::Error;
+use std::io;
+use std::vec::Vec;
+#[cfg(any(feature = "serialport", feature = "tokio"))]
+use {super::SlcanHost, crate::read::Incoming, crate::write::Response, std::collections::VecDeque};
+
+/// The next message in the bytes received from a device.
+pub(super) enum Split {
+    /// A complete message of the given length, including its terminating `CR` or BELL.
+    Message(usize),
+
+    /// A BELL in the middle of a message. The given number of bytes before it can not be decoded,
+    /// but the BELL is the next message.
+    Garbage(usize),
+
+    /// No valid message is this long. All received bytes have to be discarded.
+    Overflow,
+}
+
+/// Finds the next message terminated by `CR` or BELL in `rx`.
+///
+/// Returns `None` if more bytes are needed.
+pub(super) fn split(rx: &[u8]) -> Option<Split> {
+    match rx.iter().position(|b| *b == b'\r' || *b == 7) {
+        Some(end) if rx[end] == 7 && end != 0 => Some(Split::Garbage(end)),
+        Some(end) => Some(Split::Message(end + 1)),
+        None if rx.len() >= HOST_BUF_LEN => Some(Split::Overflow),
+        None => None,
+    }
+}
+
+/// Splits the bytes received from a device into messages terminated by `CR` or BELL.
+#[derive(Debug, Default)]
+pub(super) struct Framer {
+    /// Received bytes that do not form a complete message yet.
+    rx: Vec<u8>,
+}
+
+impl Framer {
+    /// Appends bytes received from the device.
+    pub(super) fn extend(&mut self, bytes: &[u8]) {
+        self.rx.extend_from_slice(bytes);
+    }
+
+    /// Removes the next message, including its terminator, from the received bytes.
+    ///
+    /// Returns `None` if more bytes are needed. Malformed input is reported as an error.
+    pub(super) fn next_message(&mut self) -> Option<Result<Vec<u8>, Error>> {
+        Some(match split(&self.rx)? {
+            Split::Message(len) => Ok(self.rx.drain(..len).collect()),
+            Split::Garbage(len) => {
+                self.rx.drain(..len);
+                Err(Error::bell_in_message(len))
+            }
+            Split::Overflow => {
+                let discarded = self.rx.len();
+                self.rx.clear();
+                Err(Error::overflow(discarded))
+            }
+        })
+    }
+}
+
+/// Processes a message received while waiting for the response to a command.
+///
+/// Messages that do not answer the command are added to `queued`, so that they are returned by
+/// `recv`, and `None` is returned. Otherwise, this returns the command's result. `PollOne` is
+/// answered with a notification, which is queued as well, and turned into `Response::Ack`.
+#[cfg(any(feature = "serialport", feature = "tokio"))]
+pub(super) fn command_response(
+    host: &mut SlcanHost,
+    queued: &mut VecDeque<Result<Incoming, Error>>,
+    msg: Result<Vec<u8>, Error>,
+) -> Option<Result<Response, PortError>> {
+    let incoming = msg.and_then(|msg| host.receive(&msg));
+    if host.is_awaiting_response() {
+        queued.push_back(incoming);
+        return None;
+    }
+
+    Some(match incoming {
+        Ok(Incoming::Response(Response::Error)) => Err(Error::rejected().into()),
+        Ok(Incoming::Response(resp)) => Ok(resp),
+        Ok(notif @ Incoming::Notification { .. }) => {
+            queued.push_back(Ok(notif));
+            Ok(Response::Ack)
+        }
+        Err(e) => Err(e.into()),
+    })
+}
+
+/// Processes a message received while waiting for the response to a command sent by `Probe`.
+///
+/// Like `command_response`, this queues messages that do not answer the command and returns
+/// `None`. The response itself is returned undecoded, since `Probe` decodes it.
+#[cfg(any(feature = "serialport", feature = "tokio"))]
+pub(super) fn probe_response(
+    host: &mut SlcanHost,
+    queued: &mut VecDeque<Result<Incoming, Error>>,
+    msg: Result<Vec<u8>, Error>,
+) -> Option<Vec<u8>> {
+    let msg = match msg {
+        Ok(msg) => msg,
+        Err(e) => {
+            queued.push_back(Err(e));
+            return None;
+        }
+    };
+
+    // This only updates the connection state.
+    let incoming = host.receive(&msg);
+    if host.is_awaiting_response() {
+        queued.push_back(incoming);
+        return None;
+    }
+
+    Some(msg)
+}
+
+/// Error returned by the host transports `SlcanPort`, `AsyncSlcan`, `SlcanStream` and
+/// `SlcanCodec`.
+#[derive(Debug)]
+pub enum PortError {
+    /// Opening, reading from or writing to the port failed.
+    Io(io::Error),
+
+    /// The device sent a malformed message or rejected a command, or the command is not valid in
+    /// the current state.
+    Slcan(Error),
+}
+
+impl From<io::Error> for PortError {
+    fn from(e: io::Error) -> Self {
+        PortError::Io(e)
+    }
+}
+
+impl From<Error> for PortError {
+    fn from(e: Error) -> Self {
+        PortError::Slcan(e)
+    }
+}
+
+impl core::fmt::Display for PortError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            PortError::Io(e) => write!(f, "I/O error: {}", e),
+            PortError::Slcan(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for PortError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PortError::Io(e) => Some(e),
+            PortError::Slcan(_) => None,
+        }
+    }
+}
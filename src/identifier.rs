@@ -1,24 +1,170 @@
 //! Defines CAN identifier types.
 
+use core::cmp::Ordering;
 use core::fmt;
+use core::ops::{BitAnd, BitOr};
 use defmt::Format;
 
 /// Standard 11-bit CAN identifier.
-#[derive(Copy, Clone, Eq, PartialEq, Format)]
+///
+/// Identifiers are ordered by their bus arbitration priority: lower identifiers win arbitration
+/// and compare as less than higher ones, so sorting a list of identifiers puts the highest
+/// priority first.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Format)]
 pub struct Identifier(u16);
 
 impl Identifier {
-    pub fn from_raw(raw: u16) -> Option<Self> {
-        if raw > 0x7FF {
+    pub const MAX_RAW: u16 = 0x7FF;
+
+    pub const fn from_raw(raw: u16) -> Option<Self> {
+        if raw > Self::MAX_RAW {
             None
         } else {
             Some(Self(raw))
         }
     }
 
-    pub fn as_raw(&self) -> u16 {
+    /// Creates an `Identifier` in a constant context.
+    ///
+    /// Panics (at compile time, when used in a `const`) if `raw` exceeds `MAX_RAW`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_slcan::Identifier;
+    ///
+    /// const HEARTBEAT: Identifier = Identifier::new_const(0x701);
+    /// assert_eq!(HEARTBEAT.as_raw(), 0x701);
+    /// ```
+    pub const fn new_const(raw: u16) -> Self {
+        assert!(raw <= Self::MAX_RAW, "standard identifier out of range");
+
+        Self(raw)
+    }
+
+    pub const fn as_raw(&self) -> u16 {
         self.0
     }
+
+    /// Returns whether this identifier matches `code` in all bits that are set in `mask`.
+    ///
+    /// Note that this uses the common convention of set mask bits being relevant for the match,
+    /// which is the inverse of the SJA1000 acceptance mask register.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_slcan::Identifier;
+    ///
+    /// const CODE: Identifier = Identifier::new_const(0x180);
+    /// const MASK: Identifier = Identifier::new_const(0x780);
+    ///
+    /// assert!(Identifier::new_const(0x185).matches(CODE, MASK));
+    /// assert!(!Identifier::new_const(0x205).matches(CODE, MASK));
+    /// assert_eq!(Identifier::new_const(0x185) & MASK, CODE);
+    /// ```
+    pub const fn matches(&self, code: Identifier, mask: Identifier) -> bool {
+        (self.0 ^ code.0) & mask.0 == 0
+    }
+}
+
+/// Helpers for CANopen COB-IDs, which consist of a 4-bit function code and a 7-bit node ID.
+impl Identifier {
+    /// Builds a COB-ID from a CANopen function code (0..=15) and node ID (0..=127).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_slcan::Identifier;
+    ///
+    /// let id = Identifier::from_canopen(0b1011, 0x05).unwrap();
+    /// assert_eq!(id, Identifier::canopen_sdo_response(0x05).unwrap());
+    /// assert_eq!(id.as_raw(), 0x585);
+    /// assert_eq!(id.canopen_function_code(), 0b1011);
+    /// assert_eq!(id.canopen_node_id(), 0x05);
+    ///
+    /// assert_eq!(Identifier::canopen_tpdo(2, 0x05).unwrap().as_raw(), 0x285);
+    /// assert!(Identifier::canopen_heartbeat(0).is_none());
+    /// ```
+    pub const fn from_canopen(function_code: u8, node_id: u8) -> Option<Self> {
+        if function_code > 0xF || node_id > 0x7F {
+            None
+        } else {
+            Some(Self((function_code as u16) << 7 | node_id as u16))
+        }
+    }
+
+    /// Returns the CANopen function code (the upper 4 bits).
+    pub const fn canopen_function_code(&self) -> u8 {
+        (self.0 >> 7) as u8
+    }
+
+    /// Returns the CANopen node ID (the lower 7 bits).
+    pub const fn canopen_node_id(&self) -> u8 {
+        (self.0 & 0x7F) as u8
+    }
+
+    /// The COB-ID of NMT (network management) commands.
+    pub const CANOPEN_NMT: Self = Self(0x000);
+
+    /// Returns the COB-ID of the SDO requests sent to node `node_id` (`0x600 + node_id`).
+    ///
+    /// Returns `None` if `node_id` is not in range 1..=127. The same applies to the other
+    /// node-specific COB-ID constructors.
+    pub const fn canopen_sdo_request(node_id: u8) -> Option<Self> {
+        canopen_node_cob(0x600, node_id)
+    }
+
+    /// Returns the COB-ID of the SDO responses sent by node `node_id` (`0x580 + node_id`).
+    pub const fn canopen_sdo_response(node_id: u8) -> Option<Self> {
+        canopen_node_cob(0x580, node_id)
+    }
+
+    /// Returns the COB-ID of transmit PDO `pdo` (1..=4) of node `node_id`.
+    pub const fn canopen_tpdo(pdo: u8, node_id: u8) -> Option<Self> {
+        if pdo < 1 || pdo > 4 {
+            return None;
+        }
+        canopen_node_cob(0x180 + 0x100 * (pdo as u16 - 1), node_id)
+    }
+
+    /// Returns the COB-ID of receive PDO `pdo` (1..=4) of node `node_id`.
+    pub const fn canopen_rpdo(pdo: u8, node_id: u8) -> Option<Self> {
+        if pdo < 1 || pdo > 4 {
+            return None;
+        }
+        canopen_node_cob(0x200 + 0x100 * (pdo as u16 - 1), node_id)
+    }
+
+    /// Returns the COB-ID of the heartbeat messages sent by node `node_id` (`0x700 + node_id`).
+    pub const fn canopen_heartbeat(node_id: u8) -> Option<Self> {
+        canopen_node_cob(0x700, node_id)
+    }
+}
+
+/// Returns the COB-ID `base + node_id`, if `node_id` is a valid CANopen node ID (1..=127).
+const fn canopen_node_cob(base: u16, node_id: u8) -> Option<Identifier> {
+    if node_id < 1 || node_id > 0x7F {
+        None
+    } else {
+        Some(Identifier(base + node_id as u16))
+    }
+}
+
+impl BitAnd for Identifier {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        Self(self.0 & rhs.0)
+    }
+}
+
+impl BitOr for Identifier {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
 }
 
 impl fmt::Debug for Identifier {
@@ -28,21 +174,178 @@ impl fmt::Debug for Identifier {
 }
 
 /// Extended 29-bit identifier.
-#[derive(Copy, Clone, Eq, PartialEq, Format)]
+///
+/// Like `Identifier`, extended identifiers are ordered by their bus arbitration priority.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Format)]
 pub struct ExtIdentifier(u32);
 
 impl ExtIdentifier {
-    pub fn from_raw(raw: u32) -> Option<Self> {
-        if raw > 0x1FFFFFFF {
+    pub const MAX_RAW: u32 = 0x1FFF_FFFF;
+
+    pub const fn from_raw(raw: u32) -> Option<Self> {
+        if raw > Self::MAX_RAW {
             None
         } else {
             Some(Self(raw))
         }
     }
 
-    pub fn as_raw(&self) -> u32 {
+    /// Creates an `ExtIdentifier` in a constant context.
+    ///
+    /// Panics (at compile time, when used in a `const`) if `raw` exceeds `MAX_RAW`.
+    pub const fn new_const(raw: u32) -> Self {
+        assert!(raw <= Self::MAX_RAW, "extended identifier out of range");
+
+        Self(raw)
+    }
+
+    pub const fn as_raw(&self) -> u32 {
         self.0
     }
+
+    /// Converts this identifier to a standard identifier with the same numeric value.
+    ///
+    /// Returns `None` if the value does not fit in 11 bits. This is the inverse of the
+    /// `From<Identifier>` implementation.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_slcan::{ExtIdentifier, Identifier};
+    ///
+    /// let id = ExtIdentifier::from(Identifier::new_const(0x123));
+    /// assert_eq!(id.as_raw(), 0x123);
+    /// assert_eq!(id.to_standard(), Some(Identifier::new_const(0x123)));
+    /// assert_eq!(ExtIdentifier::new_const(0x800).to_standard(), None);
+    /// assert_eq!(ExtIdentifier::new_const(0x1_0000).to_standard(), None);
+    /// ```
+    pub const fn to_standard(&self) -> Option<Identifier> {
+        if self.0 > Identifier::MAX_RAW as u32 {
+            None
+        } else {
+            Some(Identifier(self.0 as u16))
+        }
+    }
+
+    /// Returns whether this identifier matches `code` in all bits that are set in `mask`.
+    ///
+    /// See `Identifier::matches`.
+    pub const fn matches(&self, code: ExtIdentifier, mask: ExtIdentifier) -> bool {
+        (self.0 ^ code.0) & mask.0 == 0
+    }
+}
+
+/// Accessors for the fields of SAE J1939 identifiers.
+impl ExtIdentifier {
+    /// Builds a J1939 identifier.
+    ///
+    /// `destination` is the destination address of PDU1 messages (PF < 240); `None` addresses all
+    /// nodes (global address 255). PDU2 messages are broadcast, so `destination` has to be `None`
+    /// for them.
+    ///
+    /// Returns `None` if `priority` exceeds 7, `pgn` exceeds 18 bits, the lower byte of a PDU1
+    /// `pgn` is not zero, or a destination is specified for a PDU2 `pgn`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_slcan::ExtIdentifier;
+    ///
+    /// // EEC1 (PGN 61444) from the engine (source address 0), priority 3.
+    /// let id = ExtIdentifier::from_j1939(3, 61444, 0x00, None).unwrap();
+    /// assert_eq!(id.as_raw(), 0x0CF0_0400);
+    /// assert_eq!(id.j1939_priority(), 3);
+    /// assert_eq!(id.j1939_pgn(), 61444);
+    /// assert_eq!(id.j1939_source_address(), 0x00);
+    /// assert_eq!(id.j1939_destination(), None);
+    ///
+    /// // Request (PGN 59904) from address 0xF9, sent to address 0x00.
+    /// let id = ExtIdentifier::from_j1939(6, 59904, 0xF9, Some(0x00)).unwrap();
+    /// assert_eq!(id.as_raw(), 0x18EA_00F9);
+    /// assert_eq!(id.j1939_pgn(), 59904);
+    /// assert_eq!(id.j1939_destination(), Some(0x00));
+    /// ```
+    pub fn from_j1939(priority: u8, pgn: u32, source: u8, destination: Option<u8>) -> Option<Self> {
+        if priority > 7 || pgn > 0x3FFFF {
+            return None;
+        }
+
+        let ps = if is_pdu1(pgn) {
+            if pgn & 0xFF != 0 {
+                return None;
+            }
+            u32::from(destination.unwrap_or(0xFF))
+        } else {
+            if destination.is_some() {
+                return None;
+            }
+            pgn & 0xFF
+        };
+
+        let raw = u32::from(priority) << 26 | (pgn & !0xFF) << 8 | ps << 8 | u32::from(source);
+        Some(Self(raw))
+    }
+
+    /// Returns the 3-bit J1939 message priority (0 is the highest priority).
+    pub const fn j1939_priority(&self) -> u8 {
+        (self.0 >> 26) as u8 & 0b111
+    }
+
+    /// Returns the 18-bit J1939 parameter group number.
+    ///
+    /// For PDU1 messages, the destination address is not part of the PGN.
+    pub fn j1939_pgn(&self) -> u32 {
+        let pgn = (self.0 >> 8) & 0x3FFFF;
+        if is_pdu1(pgn) {
+            pgn & !0xFF
+        } else {
+            pgn
+        }
+    }
+
+    /// Returns the J1939 address of the sender.
+    pub const fn j1939_source_address(&self) -> u8 {
+        self.0 as u8
+    }
+
+    /// Returns the J1939 destination address of a PDU1 message, or `None` for PDU2 (broadcast)
+    /// messages.
+    pub fn j1939_destination(&self) -> Option<u8> {
+        if is_pdu1(self.0 >> 8) {
+            Some((self.0 >> 8) as u8)
+        } else {
+            None
+        }
+    }
+}
+
+/// Returns whether the J1939 PGN `pgn` uses the PDU1 format (PDU format field below 240), whose
+/// PDU specific field holds a destination address.
+fn is_pdu1(pgn: u32) -> bool {
+    ((pgn >> 8) as u8) < 240
+}
+
+impl BitAnd for ExtIdentifier {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        Self(self.0 & rhs.0)
+    }
+}
+
+impl BitOr for ExtIdentifier {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// Converts a standard identifier to an extended identifier with the same numeric value.
+impl From<Identifier> for ExtIdentifier {
+    fn from(id: Identifier) -> Self {
+        Self(id.0.into())
+    }
 }
 
 impl fmt::Debug for ExtIdentifier {
@@ -50,3 +353,151 @@ impl fmt::Debug for ExtIdentifier {
         write!(f, "0x{:08X}", self.0)
     }
 }
+
+/// A standard or extended CAN identifier.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Format)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub enum Id {
+    Standard(Identifier),
+    Extended(ExtIdentifier),
+}
+
+impl Id {
+    /// Returns the raw value of the identifier.
+    pub fn raw(&self) -> u32 {
+        match self {
+            Id::Standard(id) => id.as_raw().into(),
+            Id::Extended(id) => id.as_raw(),
+        }
+    }
+
+    /// Returns whether this is an extended 29-bit identifier.
+    pub fn is_extended(&self) -> bool {
+        matches!(self, Id::Extended(_))
+    }
+}
+
+/// Orders identifiers by their bus arbitration priority, with higher priorities comparing as
+/// less.
+///
+/// The 11 base bits of an extended identifier are arbitrated first. If they are equal to a
+/// standard identifier, the standard identifier wins. This is the same order used by
+/// `embedded_can::Id`.
+///
+/// # Example
+///
+/// ```
+/// use mini_slcan::{ExtIdentifier, Id, Identifier};
+///
+/// let std = Id::Standard(Identifier::new_const(0x100));
+/// let ext = Id::Extended(ExtIdentifier::new_const(0x100 << 18));
+/// assert!(std < ext);
+/// assert!(Id::Extended(ExtIdentifier::new_const(0)) < std);
+/// ```
+impl Ord for Id {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // (base identifier, IDE bit, extension bits), in order of transmission.
+        let arbitration_fields = |id: &Id| match *id {
+            Id::Standard(id) => (u32::from(id.0), 0, 0),
+            Id::Extended(id) => (id.0 >> 18, 1, id.0 & 0x3FFFF),
+        };
+
+        arbitration_fields(self).cmp(&arbitration_fields(other))
+    }
+}
+
+impl PartialOrd for Id {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl From<Identifier> for Id {
+    fn from(id: Identifier) -> Self {
+        Id::Standard(id)
+    }
+}
+
+impl From<ExtIdentifier> for Id {
+    fn from(id: ExtIdentifier) -> Self {
+        Id::Extended(id)
+    }
+}
+
+#[cfg(feature = "embedded-can")]
+impl From<embedded_can::StandardId> for Identifier {
+    fn from(id: embedded_can::StandardId) -> Self {
+        Self(id.as_raw())
+    }
+}
+
+#[cfg(feature = "embedded-can")]
+impl From<embedded_can::ExtendedId> for ExtIdentifier {
+    fn from(id: embedded_can::ExtendedId) -> Self {
+        Self(id.as_raw())
+    }
+}
+
+#[cfg(feature = "embedded-can")]
+impl From<embedded_can::Id> for Id {
+    fn from(id: embedded_can::Id) -> Self {
+        match id {
+            embedded_can::Id::Standard(id) => Id::Standard(id.into()),
+            embedded_can::Id::Extended(id) => Id::Extended(id.into()),
+        }
+    }
+}
+
+#[cfg(feature = "embedded-can")]
+impl From<Identifier> for embedded_can::StandardId {
+    fn from(id: Identifier) -> Self {
+        // `Identifier` is always in range.
+        embedded_can::StandardId::new(id.0).unwrap()
+    }
+}
+
+#[cfg(feature = "embedded-can")]
+impl From<ExtIdentifier> for embedded_can::ExtendedId {
+    fn from(id: ExtIdentifier) -> Self {
+        // `ExtIdentifier` is always in range.
+        embedded_can::ExtendedId::new(id.0).unwrap()
+    }
+}
+
+#[cfg(feature = "embedded-can")]
+impl From<Id> for embedded_can::Id {
+    fn from(id: Id) -> Self {
+        match id {
+            Id::Standard(id) => embedded_can::Id::Standard(id.into()),
+            Id::Extended(id) => embedded_can::Id::Extended(id.into()),
+        }
+    }
+}
+
+/// Converts a standard `embedded_can::Id`, handing back extended identifiers as the error.
+#[cfg(feature = "embedded-can")]
+impl core::convert::TryFrom<embedded_can::Id> for Identifier {
+    type Error = embedded_can::Id;
+
+    fn try_from(id: embedded_can::Id) -> Result<Self, Self::Error> {
+        match id {
+            embedded_can::Id::Standard(id) => Ok(id.into()),
+            embedded_can::Id::Extended(_) => Err(id),
+        }
+    }
+}
+
+/// Converts an extended `embedded_can::Id`, handing back standard identifiers as the error.
+#[cfg(feature = "embedded-can")]
+impl core::convert::TryFrom<embedded_can::Id> for ExtIdentifier {
+    type Error = embedded_can::Id;
+
+    fn try_from(id: embedded_can::Id) -> Result<Self, Self::Error> {
+        match id {
+            embedded_can::Id::Extended(id) => Ok(id.into()),
+            embedded_can::Id::Standard(_) => Err(id),
+        }
+    }
+}
@@ -4,21 +4,58 @@
 // Deny a few warnings in doctests, since rustdoc `allow`s many warnings by default
 #![doc(test(attr(deny(unused_imports, unused_must_use))))]
 #![warn(missing_debug_implementations, rust_2018_idioms)]
-#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
 
+// `derive(Arbitrary)` and the host transports use `std`.
+#[cfg(all(
+    any(
+        feature = "arbitrary",
+        feature = "futures",
+        feature = "serialport",
+        feature = "tokio"
+    ),
+    not(any(test, feature = "std"))
+))]
+extern crate std;
+
+#[macro_use]
+mod macros;
+
+#[cfg(feature = "arbitrary")]
+mod arbitrary_impls;
+pub mod device;
 mod error;
+mod filter;
+pub mod host;
 mod identifier;
 pub mod read;
 mod readme;
+#[cfg(feature = "serde")]
+mod serde_impls;
+mod signal;
+#[cfg(all(feature = "socketcan", target_os = "linux"))]
+mod socketcan_impls;
+#[cfg(feature = "testutil")]
+pub mod testutil;
+#[cfg(feature = "ufmt")]
+mod ufmt_impls;
 pub mod write;
 
-pub use self::error::{Error, ErrorKind};
-pub use self::identifier::{ExtIdentifier, Identifier};
+pub use self::error::{Error, ErrorKind, Field};
+pub use self::filter::{FilterMode, FilterValue};
+pub use self::identifier::{ExtIdentifier, Id, Identifier};
+pub use self::signal::ByteOrder;
 
+use crate::read::Command;
+use core::convert::TryFrom;
+use core::fmt;
 use core::ops::{Deref, DerefMut};
 use defmt::Format;
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Format)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
 pub enum Bitrate {
     _10kbit,
     _20kbit,
@@ -29,9 +66,24 @@ pub enum Bitrate {
     _500kbit,
     _800kbit,
     _1mbit,
+
+    /// A non-standard bitrate selected by `code` (`Sn`, with `n` being `code`).
+    ///
+    /// Non-standard bitrates are only decoded when registered via
+    /// `read::DecodeOptions::extra_bitrates`.
+    NonStandard {
+        #[cfg_attr(
+            feature = "serde",
+            serde(deserialize_with = "crate::serde_impls::bitrate_code")
+        )]
+        #[cfg_attr(feature = "arbitrary", arbitrary(with = crate::arbitrary_impls::bitrate_code))]
+        code: u8,
+        bps: u32,
+    },
 }
 
 impl Bitrate {
+    /// Returns the bitrate in kbit/s, rounded down.
     pub fn kbps(&self) -> u16 {
         match self {
             Bitrate::_10kbit => 10,
@@ -43,6 +95,160 @@ impl Bitrate {
             Bitrate::_500kbit => 500,
             Bitrate::_800kbit => 800,
             Bitrate::_1mbit => 1_000,
+            Bitrate::NonStandard { bps, .. } => (bps / 1_000) as u16,
+        }
+    }
+
+    /// Returns the bitrate in bit/s.
+    pub fn bps(&self) -> u32 {
+        match self {
+            Bitrate::NonStandard { bps, .. } => *bps,
+            _ => u32::from(self.kbps()) * 1_000,
+        }
+    }
+}
+
+/// Bitrate of the data phase of CAN FD frames, set with the `Yn` command.
+///
+/// The data bitrate is used for the data phase of frames sent with bit rate switching enabled
+/// (`b`/`B`). The arbitration phase always uses the nominal `Bitrate`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Format)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub enum DataBitrate {
+    _2mbit,
+    _4mbit,
+    _5mbit,
+    _8mbit,
+}
+
+impl DataBitrate {
+    /// Returns the bitrate in bit/s.
+    pub fn bps(&self) -> u32 {
+        match self {
+            DataBitrate::_2mbit => 2_000_000,
+            DataBitrate::_4mbit => 4_000_000,
+            DataBitrate::_5mbit => 5_000_000,
+            DataBitrate::_8mbit => 8_000_000,
+        }
+    }
+}
+
+/// CAN bit timing configuration, set with the `Sn` or `sxxyy` command.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Format)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum BitTiming {
+    /// One of the predefined bitrates (`Sn`).
+    Bitrate(Bitrate),
+
+    /// Raw BTR0/BTR1 register values (`sxxyy`).
+    Btr { btr0: u8, btr1: u8 },
+}
+
+/// Baud rate of the serial link between host and SLCAN device.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Format)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub enum SerialBaud {
+    _230400,
+    _115200,
+    _57600,
+    _38400,
+    _19200,
+    _9600,
+    _2400,
+}
+
+impl SerialBaud {
+    pub fn bps(&self) -> u32 {
+        match self {
+            SerialBaud::_230400 => 230_400,
+            SerialBaud::_115200 => 115_200,
+            SerialBaud::_57600 => 57_600,
+            SerialBaud::_38400 => 38_400,
+            SerialBaud::_19200 => 19_200,
+            SerialBaud::_9600 => 9_600,
+            SerialBaud::_2400 => 2_400,
+        }
+    }
+}
+
+/// Timestamp mode for received CAN frames, configured with the `Zn` command.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Format)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub enum TimestampMode {
+    /// Received frames are not timestamped (`Z0`).
+    Off,
+
+    /// Received frames carry a 16-bit millisecond timestamp that wraps at 60000 (`Z1`).
+    Milliseconds,
+
+    /// Received frames carry a 32-bit microsecond timestamp (`Z2`).
+    Microseconds,
+}
+
+/// Behavior of an SLCAN device after power-up, configured with the `Qn` command.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Format)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub enum AutoStartup {
+    /// The CAN channel stays closed until an `Open` command is received (`Q0`).
+    Off,
+
+    /// The CAN channel is automatically opened in normal mode (`Q1`).
+    OpenNormal,
+
+    /// The CAN channel is automatically opened in listen-only mode (`Q2`).
+    OpenListenOnly,
+}
+
+/// State of the CAN channel of an SLCAN device.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Format)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum ChannelState {
+    /// The channel is closed. Setup commands are only accepted in this state.
+    Closed,
+
+    /// The channel is open in normal mode.
+    Open,
+
+    /// The channel is open in listen-only mode. No frames can be transmitted.
+    ListenOnly,
+}
+
+impl ChannelState {
+    /// Returns whether `cmd` is valid while the channel is in this state.
+    ///
+    /// `configured` indicates whether a bitrate has been configured, which is required for opening
+    /// the channel.
+    pub(crate) fn permits(self, cmd: &Command, configured: bool) -> bool {
+        let closed = self == ChannelState::Closed;
+        match cmd {
+            Command::SetupWithBitrate { .. }
+            | Command::SetupWithBtr { .. }
+            | Command::SetupDataBitrate { .. }
+            | Command::SetRxTimestamp { .. }
+            | Command::SetAcceptanceCode(_)
+            | Command::SetAcceptanceMask(_)
+            | Command::SetFilterMode(_)
+            | Command::SetAutoPoll { .. }
+            | Command::SetSerialBaudrate { .. } => closed,
+            Command::Open | Command::OpenListenOnly => closed && configured,
+            Command::Close | Command::PollOne | Command::PollAll | Command::ReadStatus => !closed,
+            Command::TxStandard { .. }
+            | Command::TxExt { .. }
+            | Command::TxStandardRtr { .. }
+            | Command::TxExtRtr { .. }
+            | Command::TxFd { .. }
+            | Command::TxExtFd { .. } => self == ChannelState::Open,
+            _ => true,
         }
     }
 }
@@ -62,6 +268,52 @@ bitflags::bitflags! {
     }
 }
 
+bitflags::bitflags! {
+    /// CAN bus errors reported in an error frame notification.
+    #[derive(Format)]
+    pub struct BusErrors: u8 {
+        /// No acknowledgement was received for a transmitted frame (`a`).
+        const ACK = 1 << 0;
+        /// A dominant bit was transmitted, but a recessive bit was read back (`b`).
+        const BIT0 = 1 << 1;
+        /// A recessive bit was transmitted, but a dominant bit was read back (`B`).
+        const BIT1 = 1 << 2;
+        /// CRC mismatch (`c`).
+        const CRC = 1 << 3;
+        /// Form error (`f`).
+        const FORM = 1 << 4;
+        /// A received frame was lost due to a full receive buffer (`o`).
+        const RX_OVERRUN = 1 << 5;
+        /// A frame could not be transmitted due to a full transmit buffer (`O`).
+        const TX_OVERRUN = 1 << 6;
+        /// Bit stuffing error (`s`).
+        const STUFF = 1 << 7;
+    }
+}
+
+bitflags::bitflags! {
+    /// Flags of a CAN FD frame.
+    #[derive(Format, Default)]
+    pub struct FdFlags: u8 {
+        /// Bit rate switch: the data phase is transmitted with the data bitrate.
+        const BRS = 1 << 0;
+        /// Error state indicator: the transmitting node is error passive.
+        const ESI = 1 << 1;
+    }
+}
+
+/// Letters used to represent each `BusErrors` flag in an error frame notification.
+const BUS_ERROR_CODES: [(BusErrors, u8); 8] = [
+    (BusErrors::ACK, b'a'),
+    (BusErrors::BIT0, b'b'),
+    (BusErrors::BIT1, b'B'),
+    (BusErrors::CRC, b'c'),
+    (BusErrors::FORM, b'f'),
+    (BusErrors::RX_OVERRUN, b'o'),
+    (BusErrors::TX_OVERRUN, b'O'),
+    (BusErrors::STUFF, b's'),
+];
+
 /// 4-byte serial number of an SLCAN device.
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Format)]
 pub struct SerialNumber([u8; 4]);
@@ -84,21 +336,73 @@ impl SerialNumber {
             && raw[2].is_ascii_alphanumeric()
             && raw[3].is_ascii_alphanumeric();
 
-        if !valid {
-            let serial_number_contain_non_alphanumeric_characters = ();
+        assert!(valid, "serial number contains non-alphanumeric characters");
+
+        Self(raw)
+    }
+}
 
-            #[allow(unconditional_panic)]
-            [serial_number_contain_non_alphanumeric_characters][1];
+/// Detailed firmware version string of an SLCAN device.
+///
+/// This is reported in response to the `ReadDetailedVersion` command (`v`), and can hold up to
+/// `MAX_LENGTH` printable ASCII characters.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct VersionString {
+    bytes: [u8; Self::MAX_LENGTH],
+    len: u8,
+}
+
+impl VersionString {
+    pub const MAX_LENGTH: usize = 32;
+
+    /// Creates a `VersionString` from a string.
+    ///
+    /// Returns `None` if `s` is longer than `MAX_LENGTH` or contains characters that are not
+    /// printable ASCII.
+    pub fn new(s: &str) -> Option<Self> {
+        Self::from_bytes(s.as_bytes())
+    }
+
+    /// Creates a `VersionString` from raw bytes.
+    ///
+    /// Returns `None` if `raw` is longer than `MAX_LENGTH` or contains bytes that are not
+    /// printable ASCII characters.
+    pub fn from_bytes(raw: &[u8]) -> Option<Self> {
+        if raw.len() > Self::MAX_LENGTH || !raw.iter().all(|b| matches!(b, b' '..=b'~')) {
+            return None;
         }
 
-        Self(raw)
+        let mut bytes = [0; Self::MAX_LENGTH];
+        bytes[..raw.len()].copy_from_slice(raw);
+        Some(Self {
+            bytes,
+            len: raw.len() as u8,
+        })
+    }
+
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..usize::from(self.len)]
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        // Only printable ASCII characters are accepted by the constructors.
+        core::str::from_utf8(self.as_bytes()).unwrap()
+    }
+}
+
+impl Format for VersionString {
+    fn format(&self, fmt: &mut defmt::Formatter) {
+        self.as_str().format(fmt)
     }
 }
 
 #[derive(Eq, PartialEq, Copy, Clone, Debug, Default)]
 pub struct CanFrame {
     data: [u8; Self::MAX_LENGTH],
-    len: u8,
+    /// Data length code. Values 9 to 15 indicate a frame with 8 data bytes.
+    dlc: u8,
 }
 
 impl CanFrame {
@@ -108,23 +412,77 @@ impl CanFrame {
     pub const fn new() -> Self {
         Self {
             data: [0; Self::MAX_LENGTH],
-            len: 0,
+            dlc: 0,
         }
     }
 
+    /// Creates a frame containing `data`.
+    ///
+    /// Returns an error of kind `InvalidLength` if `data` is longer than `MAX_LENGTH`. This is
+    /// also available as a `TryFrom<&[u8]>` implementation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_slcan::CanFrame;
+    ///
+    /// let frame = CanFrame::from_slice(&[1, 2, 3]).unwrap();
+    /// assert_eq!(frame.data(), [1, 2, 3]);
+    ///
+    /// assert!(CanFrame::from_slice(&[0; 9]).is_err());
+    /// ```
+    pub fn from_slice(data: &[u8]) -> Result<Self, Error> {
+        if data.len() > Self::MAX_LENGTH {
+            return Err(Error::new(ErrorKind::InvalidLength));
+        }
+
+        let mut frame = Self::new();
+        frame.data[..data.len()].copy_from_slice(data);
+        frame.dlc = data.len() as u8;
+        Ok(frame)
+    }
+
     #[inline]
     pub fn len(&self) -> usize {
-        self.len.into()
+        usize::from(self.dlc).min(Self::MAX_LENGTH)
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.dlc == 0
+    }
+
+    /// Returns the raw data length code (DLC) of this frame.
+    ///
+    /// This is equal to `len()`, unless the DLC was set to a value between 9 and 15 via
+    /// `with_dlc`. Classic CAN treats those DLCs like 8.
+    #[inline]
+    pub fn dlc(&self) -> u8 {
+        self.dlc
+    }
+
+    /// Changes the raw data length code of a frame with 8 data bytes.
+    ///
+    /// Returns `None` if the frame does not contain 8 bytes of data or if `dlc` is not in range
+    /// 8..=15.
+    pub fn with_dlc(mut self, dlc: u8) -> Option<Self> {
+        if self.len() == Self::MAX_LENGTH && (8..=15).contains(&dlc) {
+            self.dlc = dlc;
+            Some(self)
+        } else {
+            None
+        }
     }
 
     #[inline]
     pub fn data(&self) -> &[u8] {
-        &self.data[..usize::from(self.len)]
+        &self.data[..self.len()]
     }
 
     #[inline]
     pub fn data_mut(&mut self) -> &mut [u8] {
-        &mut self.data[..usize::from(self.len)]
+        let len = self.len();
+        &mut self.data[..len]
     }
 
     /// Appends a byte to this CAN frame.
@@ -135,10 +493,79 @@ impl CanFrame {
             Err(Error::eof())
         } else {
             self.data[self.len()] = byte;
-            self.len += 1;
+            self.dlc += 1;
             Ok(())
         }
     }
+
+    /// Appends all bytes in `data` to this CAN frame.
+    ///
+    /// Returns an error of kind `InvalidLength` and leaves the frame unchanged if `data` does not
+    /// fit. Appending an empty slice preserves a DLC above 8.
+    pub fn extend_from_slice(&mut self, data: &[u8]) -> Result<(), Error> {
+        let len = self.len();
+        if data.len() > Self::MAX_LENGTH - len {
+            return Err(Error::new(ErrorKind::InvalidLength));
+        }
+
+        if !data.is_empty() {
+            self.data[len..len + data.len()].copy_from_slice(data);
+            self.dlc = (len + data.len()) as u8;
+        }
+        Ok(())
+    }
+
+    /// Shortens the frame to `len` bytes.
+    ///
+    /// Has no effect if the frame is already `len` bytes or shorter.
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.len() {
+            // Zero the removed bytes, so that they don't affect comparisons.
+            for byte in &mut self.data[len..] {
+                *byte = 0;
+            }
+            self.dlc = len as u8;
+        }
+    }
+
+    /// Removes all data from the frame.
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Reads the unsigned integer signal of `length` bits starting at `start_bit`.
+    ///
+    /// Returns `None` if `length` is 0 or larger than 64, or if the signal does not fit into the
+    /// frame data.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_slcan::{ByteOrder, CanFrame};
+    ///
+    /// let frame = CanFrame::from([0x34, 0x12, 0x12, 0x34]);
+    /// assert_eq!(frame.get_signal(0, 16, ByteOrder::LittleEndian), Some(0x1234));
+    /// assert_eq!(frame.get_signal(23, 16, ByteOrder::BigEndian), Some(0x1234));
+    /// assert_eq!(frame.get_signal(24, 16, ByteOrder::LittleEndian), None);
+    /// ```
+    pub fn get_signal(&self, start_bit: usize, length: usize, order: ByteOrder) -> Option<u64> {
+        signal::get(self.data(), start_bit, length, order)
+    }
+
+    /// Writes `value` into the signal of `length` bits starting at `start_bit`.
+    ///
+    /// Bits of `value` that do not fit into the signal are ignored. Returns an error of kind
+    /// `InvalidLength` and leaves the frame unchanged if `length` is 0 or larger than 64, or if the
+    /// signal does not fit into the frame data.
+    pub fn set_signal(
+        &mut self,
+        start_bit: usize,
+        length: usize,
+        order: ByteOrder,
+        value: u64,
+    ) -> Result<(), Error> {
+        signal::set(self.data_mut(), start_bit, length, order, value)
+    }
 }
 
 impl Deref for CanFrame {
@@ -155,12 +582,62 @@ impl DerefMut for CanFrame {
     }
 }
 
+impl TryFrom<&[u8]> for CanFrame {
+    type Error = Error;
+
+    fn try_from(data: &[u8]) -> Result<Self, Error> {
+        Self::from_slice(data)
+    }
+}
+
 impl Format for CanFrame {
     fn format(&self, fmt: &mut defmt::Formatter) {
         self.data().format(fmt)
     }
 }
 
+/// Formats the frame like `candump` does: the length in brackets, followed by the data bytes.
+///
+/// # Examples
+///
+/// ```
+/// use mini_slcan::CanFrame;
+///
+/// let frame = CanFrame::from([0xDE, 0xAD, 0xBE, 0xEF]);
+/// assert_eq!(frame.to_string(), "[4] DE AD BE EF");
+/// assert_eq!(format!("{:x}", frame), "deadbeef");
+/// assert_eq!(format!("{:X}", frame), "DEADBEEF");
+/// ```
+impl fmt::Display for CanFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}]", self.len())?;
+        for byte in self.data() {
+            write!(f, " {:02X}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// Formats the data bytes as a contiguous hex string.
+impl fmt::LowerHex for CanFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.data() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// Formats the data bytes as a contiguous hex string.
+impl fmt::UpperHex for CanFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.data() {
+            write!(f, "{:02X}", byte)?;
+        }
+        Ok(())
+    }
+}
+
 macro_rules! impl_from {
     ( $($len:literal),+ ) => {
         $(
@@ -170,7 +647,7 @@ macro_rules! impl_from {
                     data[..$len].copy_from_slice(&arr);
                     Self {
                         data,
-                        len: $len,
+                        dlc: $len,
                     }
                 }
             }
@@ -179,3 +656,369 @@ macro_rules! impl_from {
 }
 
 impl_from!(0, 1, 2, 3, 4, 5, 6, 7, 8);
+
+/// Data lengths of CAN FD frames, indexed by DLC.
+const FD_LENGTHS: [u8; 16] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 12, 16, 20, 24, 32, 48, 64];
+
+/// Data of a CAN FD frame (up to 64 bytes).
+///
+/// Unlike classic CAN frames, CAN FD frames can only have one of the lengths 0 to 8, 12, 16,
+/// 20, 24, 32, 48, or 64 bytes, each of which is selected by a data length code (DLC) between 0
+/// and 15.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct CanFdFrame {
+    data: [u8; Self::MAX_LENGTH],
+    /// Data length code (0-15).
+    dlc: u8,
+}
+
+impl CanFdFrame {
+    pub const MAX_LENGTH: usize = 64;
+
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            data: [0; Self::MAX_LENGTH],
+            dlc: 0,
+        }
+    }
+
+    /// Returns the number of data bytes in a CAN FD frame with the given DLC.
+    ///
+    /// Returns `None` if `dlc` is larger than 15.
+    pub fn dlc_to_len(dlc: u8) -> Option<usize> {
+        FD_LENGTHS
+            .get(usize::from(dlc))
+            .map(|&len| usize::from(len))
+    }
+
+    /// Returns the smallest DLC of a CAN FD frame that can hold `len` data bytes.
+    ///
+    /// If `len` is not a valid CAN FD frame length, it is rounded up to the next valid length.
+    /// Returns `None` if `len` exceeds `MAX_LENGTH`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_slcan::CanFdFrame;
+    ///
+    /// assert_eq!(CanFdFrame::len_to_dlc(8), Some(8));
+    /// assert_eq!(CanFdFrame::len_to_dlc(9), Some(9));
+    /// assert_eq!(CanFdFrame::dlc_to_len(9), Some(12));
+    /// assert_eq!(CanFdFrame::len_to_dlc(65), None);
+    /// ```
+    pub fn len_to_dlc(len: usize) -> Option<u8> {
+        FD_LENGTHS
+            .iter()
+            .position(|&valid| usize::from(valid) >= len)
+            .map(|dlc| dlc as u8)
+    }
+
+    /// Creates a zero-filled frame with the length encoded by `dlc`.
+    ///
+    /// Returns `None` if `dlc` is larger than 15.
+    pub fn from_dlc(dlc: u8) -> Option<Self> {
+        Self::dlc_to_len(dlc)?;
+        Some(Self {
+            data: [0; Self::MAX_LENGTH],
+            dlc,
+        })
+    }
+
+    /// Creates a frame containing `data`.
+    ///
+    /// Returns an error of kind `InvalidLength` if the length of `data` is not a valid CAN FD frame
+    /// length. This is also available as a `TryFrom<&[u8]>` implementation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_slcan::CanFdFrame;
+    ///
+    /// let frame = CanFdFrame::from_slice(&[0xAA; 12]).unwrap();
+    /// assert_eq!(frame.len(), 12);
+    /// assert_eq!(frame.dlc(), 9);
+    ///
+    /// assert!(CanFdFrame::from_slice(&[0xAA; 10]).is_err());
+    /// ```
+    pub fn from_slice(data: &[u8]) -> Result<Self, Error> {
+        let frame = Self::from_slice_padded(data)?;
+        if frame.len() == data.len() {
+            Ok(frame)
+        } else {
+            Err(Error::new(ErrorKind::InvalidLength))
+        }
+    }
+
+    /// Creates a frame containing `data`, padded with zeros to the next valid CAN FD frame
+    /// length.
+    ///
+    /// Returns an error of kind `InvalidLength` if `data` is longer than `MAX_LENGTH`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_slcan::CanFdFrame;
+    ///
+    /// let frame = CanFdFrame::from_slice_padded(&[0xAA; 10]).unwrap();
+    /// assert_eq!(frame.len(), 12);
+    /// assert_eq!(&frame[8..], [0xAA, 0xAA, 0, 0]);
+    ///
+    /// assert!(CanFdFrame::from_slice_padded(&[0xAA; 65]).is_err());
+    /// ```
+    pub fn from_slice_padded(data: &[u8]) -> Result<Self, Error> {
+        let mut frame = Self::len_to_dlc(data.len())
+            .and_then(Self::from_dlc)
+            .ok_or(Error::new(ErrorKind::InvalidLength))?;
+        frame.data[..data.len()].copy_from_slice(data);
+        Ok(frame)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        usize::from(FD_LENGTHS[usize::from(self.dlc)])
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.dlc == 0
+    }
+
+    /// Returns the data length code (DLC) of this frame.
+    #[inline]
+    pub fn dlc(&self) -> u8 {
+        self.dlc
+    }
+
+    #[inline]
+    pub fn data(&self) -> &[u8] {
+        &self.data[..self.len()]
+    }
+
+    #[inline]
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        let len = self.len();
+        &mut self.data[..len]
+    }
+
+    /// Reads the unsigned integer signal of `length` bits starting at `start_bit`.
+    ///
+    /// Returns `None` if `length` is 0 or larger than 64, or if the signal does not fit into the
+    /// frame data.
+    pub fn get_signal(&self, start_bit: usize, length: usize, order: ByteOrder) -> Option<u64> {
+        signal::get(self.data(), start_bit, length, order)
+    }
+
+    /// Writes `value` into the signal of `length` bits starting at `start_bit`.
+    ///
+    /// Bits of `value` that do not fit into the signal are ignored. Returns an error of kind
+    /// `InvalidLength` and leaves the frame unchanged if `length` is 0 or larger than 64, or if the
+    /// signal does not fit into the frame data.
+    pub fn set_signal(
+        &mut self,
+        start_bit: usize,
+        length: usize,
+        order: ByteOrder,
+        value: u64,
+    ) -> Result<(), Error> {
+        signal::set(self.data_mut(), start_bit, length, order, value)
+    }
+}
+
+impl Default for CanFdFrame {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for CanFdFrame {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.data()
+    }
+}
+
+impl DerefMut for CanFdFrame {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        self.data_mut()
+    }
+}
+
+impl Format for CanFdFrame {
+    fn format(&self, fmt: &mut defmt::Formatter) {
+        self.data().format(fmt)
+    }
+}
+
+impl TryFrom<&[u8]> for CanFdFrame {
+    type Error = Error;
+
+    fn try_from(data: &[u8]) -> Result<Self, Error> {
+        Self::from_slice(data)
+    }
+}
+
+impl From<CanFrame> for CanFdFrame {
+    fn from(frame: CanFrame) -> Self {
+        let mut data = [0; Self::MAX_LENGTH];
+        data[..frame.len()].copy_from_slice(frame.data());
+        Self {
+            data,
+            dlc: frame.len() as u8,
+        }
+    }
+}
+
+/// A classic CAN or CAN FD frame, together with its identifier.
+///
+/// This is the frame carried by the transmit `Command`s and the receive `Notification`s, and lets
+/// code that forwards frames between the host and the CAN bus handle all kinds of frames
+/// uniformly.
+///
+/// # Example
+///
+/// ```
+/// use mini_slcan::{Frame, FrameKind, Identifier};
+/// use mini_slcan::read::Command;
+///
+/// let frame = Command::decode(b"t1231AA\r").unwrap().to_frame().unwrap();
+/// assert_eq!(frame.id.raw(), 0x123);
+/// assert_eq!(frame.kind, FrameKind::Data([0xAA].into()));
+///
+/// let remote = Frame {
+///     id: Identifier::new_const(0x123).into(),
+///     kind: FrameKind::Remote(8),
+/// };
+/// assert_eq!(Command::from(remote), Command::decode(b"r1238\r").unwrap());
+/// ```
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Format)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub struct Frame {
+    pub id: Id,
+    pub kind: FrameKind,
+}
+
+/// A classic CAN or CAN FD frame, together with its identifier.
+///
+/// This is an alias of `Frame`, which replaced the original `AnyFrame` enum. It offers the same
+/// accessors (`data`, `dlc`, `is_extended`, `is_fd` and `is_rtr`), while the identifier is now
+/// stored in the `id` field.
+///
+/// # Example
+///
+/// ```
+/// use mini_slcan::AnyFrame;
+/// use mini_slcan::read::Command;
+///
+/// let frame: AnyFrame = Command::decode(b"T123456781AA\r").unwrap().to_frame().unwrap();
+/// assert_eq!(frame.id.raw(), 0x1234_5678);
+/// assert!(frame.is_extended());
+/// assert!(!frame.is_fd() && !frame.is_rtr());
+/// assert_eq!(frame.data(), [0xAA]);
+/// ```
+pub type AnyFrame = Frame;
+
+/// The type and payload of a `Frame`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Format)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub enum FrameKind {
+    /// A classic CAN data frame.
+    Data(CanFrame),
+
+    /// A classic CAN remote frame with the given data length code (0..=15).
+    Remote(
+        #[cfg_attr(feature = "serde", serde(deserialize_with = "crate::serde_impls::dlc"))]
+        #[cfg_attr(feature = "arbitrary", arbitrary(with = crate::arbitrary_impls::dlc))]
+        u8,
+    ),
+
+    /// A CAN FD frame.
+    Fd { frame: CanFdFrame, flags: FdFlags },
+}
+
+impl Frame {
+    /// Returns whether the frame uses an extended 29-bit identifier.
+    pub fn is_extended(&self) -> bool {
+        self.id.is_extended()
+    }
+
+    /// Returns whether this is a CAN FD frame.
+    pub fn is_fd(&self) -> bool {
+        matches!(self.kind, FrameKind::Fd { .. })
+    }
+
+    /// Returns whether this is a remote frame.
+    pub fn is_rtr(&self) -> bool {
+        matches!(self.kind, FrameKind::Remote(_))
+    }
+
+    /// Returns the data length code of the frame.
+    pub fn dlc(&self) -> u8 {
+        match &self.kind {
+            FrameKind::Data(frame) => frame.dlc(),
+            FrameKind::Remote(len) => *len,
+            FrameKind::Fd { frame, .. } => frame.dlc(),
+        }
+    }
+
+    /// Returns the data carried by the frame.
+    ///
+    /// Remote frames carry no data, so this returns an empty slice for them.
+    pub fn data(&self) -> &[u8] {
+        match &self.kind {
+            FrameKind::Data(frame) => frame.data(),
+            FrameKind::Remote(_) => &[],
+            FrameKind::Fd { frame, .. } => frame.data(),
+        }
+    }
+}
+
+/// Allows passing `Frame`s to and from CAN peripheral drivers.
+///
+/// The constructors create classic CAN frames. CAN FD frames report their data length code and up
+/// to 64 bytes of data.
+#[cfg(feature = "embedded-can")]
+impl embedded_can::Frame for Frame {
+    fn new(id: impl Into<embedded_can::Id>, data: &[u8]) -> Option<Self> {
+        Some(Self {
+            id: id.into().into(),
+            kind: FrameKind::Data(CanFrame::from_slice(data).ok()?),
+        })
+    }
+
+    fn new_remote(id: impl Into<embedded_can::Id>, dlc: usize) -> Option<Self> {
+        if dlc > 15 {
+            return None;
+        }
+
+        Some(Self {
+            id: id.into().into(),
+            kind: FrameKind::Remote(dlc as u8),
+        })
+    }
+
+    fn is_extended(&self) -> bool {
+        Frame::is_extended(self)
+    }
+
+    fn is_remote_frame(&self) -> bool {
+        self.is_rtr()
+    }
+
+    fn id(&self) -> embedded_can::Id {
+        self.id.into()
+    }
+
+    fn dlc(&self) -> usize {
+        Frame::dlc(self).into()
+    }
+
+    fn data(&self) -> &[u8] {
+        Frame::data(self)
+    }
+}
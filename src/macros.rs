@@ -0,0 +1,110 @@
+//! Macros for constructing frames and commands.
+
+/// Creates a [`Frame`](crate::Frame) with an identifier that is checked at compile time.
+///
+/// The identifier is standard unless prefixed with `ext`. Data frames take up to 8 bytes of data
+/// in brackets, remote frames take `rtr` followed by their data length code.
+///
+/// # Examples
+///
+/// ```
+/// use mini_slcan::{frame, FrameKind};
+///
+/// let frame = frame!(0x123, [0xDE, 0xAD]);
+/// assert_eq!(frame.id.raw(), 0x123);
+/// assert_eq!(frame.data(), [0xDE, 0xAD]);
+///
+/// let frame = frame!(ext 0x1234_5678, rtr 4);
+/// assert!(frame.is_extended());
+/// assert_eq!(frame.kind, FrameKind::Remote(4));
+/// ```
+///
+/// Identifiers that are out of range are rejected when compiling:
+///
+/// ```compile_fail
+/// # use mini_slcan::frame;
+/// let frame = frame!(0x800, []);
+/// ```
+// The checked values are associated constants of a local type, so that they are evaluated at
+// compile time without their names shadowing anything that `$id` might refer to.
+#[macro_export]
+macro_rules! frame {
+    (ext $id:expr, $($kind:tt)+) => {
+        $crate::Frame {
+            id: $crate::Id::Extended({
+                struct Check;
+                impl Check {
+                    const ID: $crate::ExtIdentifier = $crate::ExtIdentifier::new_const($id);
+                }
+                Check::ID
+            }),
+            kind: $crate::__frame_kind!($($kind)+),
+        }
+    };
+    ($id:expr, $($kind:tt)+) => {
+        $crate::Frame {
+            id: $crate::Id::Standard({
+                struct Check;
+                impl Check {
+                    const ID: $crate::Identifier = $crate::Identifier::new_const($id);
+                }
+                Check::ID
+            }),
+            kind: $crate::__frame_kind!($($kind)+),
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __frame_kind {
+    (rtr $len:expr) => {
+        $crate::FrameKind::Remote({
+            struct Check;
+            impl Check {
+                const LEN: u8 = {
+                    assert!($len <= 8, "remote frame DLC out of range");
+                    $len
+                };
+            }
+            Check::LEN
+        })
+    };
+    ([$($byte:expr),* $(,)?]) => {
+        $crate::FrameKind::Data($crate::CanFrame::from([$($byte),*]))
+    };
+}
+
+/// Creates a [`Command`](crate::read::Command).
+///
+/// `cmd!(Variant ...)` expands to `Command::Variant ...`, while `cmd!(tx ...)` creates a transmit
+/// command from the arguments of [`frame!`].
+///
+/// # Examples
+///
+/// ```
+/// use mini_slcan::{cmd, Bitrate, CanFrame, Identifier};
+/// use mini_slcan::read::Command;
+///
+/// assert_eq!(cmd!(Open), Command::Open);
+/// assert_eq!(
+///     cmd!(SetupWithBitrate { bitrate: Bitrate::_500kbit }),
+///     Command::SetupWithBitrate { bitrate: Bitrate::_500kbit },
+/// );
+/// assert_eq!(
+///     cmd!(tx 0x123, [1, 2, 3]),
+///     Command::TxStandard {
+///         identifier: Identifier::from_raw(0x123).unwrap(),
+///         frame: CanFrame::from([1, 2, 3]),
+///     },
+/// );
+/// ```
+#[macro_export]
+macro_rules! cmd {
+    (tx $($frame:tt)+) => {
+        $crate::read::Command::from($crate::frame!($($frame)+))
+    };
+    ($variant:ident $($args:tt)*) => {
+        $crate::read::Command::$variant $($args)*
+    };
+}
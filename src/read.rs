@@ -3,19 +3,49 @@
 #[cfg(test)]
 mod tests;
 
-use crate::{Bitrate, CanFrame, Error, ExtIdentifier, Identifier};
+use crate::write::{Notification, NotificationBuf, Response, ResponseBuf, Timestamp};
+use crate::{
+    AutoStartup, Bitrate, BusErrors, CanFdFrame, CanFrame, DataBitrate, Error, ErrorKind,
+    ExtIdentifier, FdFlags, Field, FilterMode, FilterValue, Frame, FrameKind, Id, Identifier,
+    SerialBaud, SerialNumber, Status, TimestampMode, VersionString,
+};
+use core::iter;
+use core::task::Poll;
 use defmt::Format;
 
 /// A command sent from the host to the SLCAN device.
-#[derive(Debug, Eq, PartialEq, Format)]
+#[derive(Clone, Debug, Eq, PartialEq, Format)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
 #[non_exhaustive]
 pub enum Command {
     SetupWithBitrate {
         bitrate: Bitrate,
     },
 
+    /// Sets up the CAN bit timing using raw BTR0/BTR1 register values (`sxxyy`).
+    SetupWithBtr {
+        btr0: u8,
+        btr1: u8,
+    },
+
+    /// Sets the bitrate of the data phase of CAN FD frames (`Yn`).
+    ///
+    /// This is not part of the original Lawicel protocol, but is implemented by CAN FD capable
+    /// devices.
+    SetupDataBitrate {
+        bitrate: DataBitrate,
+    },
+
     Open,
 
+    /// Opens the CAN channel in listen-only mode (`L`).
+    ///
+    /// In listen-only mode, the device does not acknowledge received frames and does not transmit
+    /// any frames.
+    OpenListenOnly,
+
     Close,
 
     TxStandard {
@@ -31,270 +61,3490 @@ pub enum Command {
 
     TxStandardRtr {
         identifier: Identifier,
+        #[cfg_attr(feature = "serde", serde(deserialize_with = "crate::serde_impls::dlc"))]
+        #[cfg_attr(feature = "arbitrary", arbitrary(with = crate::arbitrary_impls::dlc))]
         len: u8,
     },
 
     TxExtRtr {
         identifier: ExtIdentifier,
+        #[cfg_attr(feature = "serde", serde(deserialize_with = "crate::serde_impls::dlc"))]
+        #[cfg_attr(feature = "arbitrary", arbitrary(with = crate::arbitrary_impls::dlc))]
         len: u8,
     },
 
+    /// Transmits a standard CAN FD frame (`d`, or `b` with bit rate switching).
+    ///
+    /// This is not part of the original Lawicel protocol, but is implemented by CAN FD capable
+    /// devices like the CANable 2.0.
+    ///
+    /// The `ESI` flag is determined by the transmitting node, so it is never set by the decoder and
+    /// ignored when encoding.
+    TxFd {
+        identifier: Identifier,
+        frame: CanFdFrame,
+        #[cfg_attr(feature = "arbitrary", arbitrary(with = crate::arbitrary_impls::fd_flags))]
+        flags: FdFlags,
+    },
+
+    /// Transmits an extended CAN FD frame (`D`, or `B` with bit rate switching).
+    TxExtFd {
+        identifier: ExtIdentifier,
+        frame: CanFdFrame,
+        #[cfg_attr(feature = "arbitrary", arbitrary(with = crate::arbitrary_impls::fd_flags))]
+        flags: FdFlags,
+    },
+
+    /// Polls the device for a single received CAN frame (`P`).
+    PollOne,
+
+    /// Polls the device for all received CAN frames (`A`).
+    PollAll,
+
     ReadStatus,
+
+    /// Requests the transmit and receive error counters (`E`).
+    ///
+    /// This is not part of the original Lawicel protocol, but is implemented by several SLCAN
+    /// devices.
+    ReadErrorCounters,
+
+    /// Requests bus load statistics (`I`).
+    ///
+    /// This is not part of the original Lawicel protocol.
+    ReadBusload,
+
     ReadVersion,
+
+    /// Requests the detailed firmware version string (`v`).
+    ReadDetailedVersion,
+
     ReadSerial,
     SetRxTimestamp {
-        timestamp: bool,
+        mode: TimestampMode,
+    },
+
+    /// Enables or disables automatic forwarding of received CAN frames (`Xn`).
+    ///
+    /// When auto-poll is disabled, the host has to fetch received frames using the `PollOne` and
+    /// `PollAll` commands.
+    SetAutoPoll {
+        enabled: bool,
+    },
+
+    /// Sets the acceptance code register (`Mxxxxxxxx`).
+    SetAcceptanceCode(FilterValue),
+
+    /// Sets the acceptance mask register (`mxxxxxxxx`).
+    SetAcceptanceMask(FilterValue),
+
+    /// Selects the acceptance filter mode (`Wn`).
+    SetFilterMode(FilterMode),
+
+    /// Configures the behavior of the device after power-up (`Qn`).
+    SetAutoStartup(AutoStartup),
+
+    /// Changes the baud rate of the serial link (`Un`).
+    SetSerialBaudrate {
+        baud: SerialBaud,
+    },
+
+    /// Writes `value` to the MCP2515 register `register` (`Wrrvv`, USBtin only).
+    #[cfg(feature = "usbtin")]
+    WriteRegister {
+        register: u8,
+        value: u8,
+    },
+
+    /// Reads the MCP2515 register `register` (`Grr`, USBtin only).
+    #[cfg(feature = "usbtin")]
+    ReadRegister {
+        register: u8,
+    },
+
+    /// A command with an unrecognized opcode.
+    ///
+    /// This is only produced when enabled via `DecodeOptions::allow_unknown`.
+    Unknown {
+        #[cfg_attr(feature = "arbitrary", arbitrary(with = crate::arbitrary_impls::unknown_opcode))]
+        opcode: u8,
+        /// The bytes following the opcode, excluding the terminating `CR`.
+        payload: RawPayload,
+    },
+
+    /// A vendor-specific command registered via `DecodeOptions::vendor_commands`.
+    Vendor {
+        #[cfg_attr(feature = "arbitrary", arbitrary(with = crate::arbitrary_impls::vendor_opcode))]
+        opcode: u8,
+        /// The bytes following the opcode, excluding the terminating `CR`.
+        payload: RawPayload,
     },
 }
 
 impl Command {
-    pub const MAX_ENCODED_LEN: usize = 1 + 8 + 1 + 16 + 1; // Tiiiiiiiildddddddddddddddd\r
+    /// The maximum length of an encoded classic CAN command, reached by extended data frames with 8
+    /// data bytes.
+    pub const MAX_ENCODED_LEN: usize = 1 + 8 + 1 + 16 + 1; // Tiiiiiiiildd..dd\r
+
+    /// The maximum length of an encoded command, reached by CAN FD frames with 64 data bytes.
+    pub const MAX_FD_ENCODED_LEN: usize = 1 + 8 + 1 + 128 + 1; // Diiiiiiiildd..dd\r
 
     /// Decodes a command from an input string. The input must contain the terminating `CR`
     /// character (ASCII 13).
     pub fn decode(input: &[u8]) -> Result<Self, Error> {
-        let mut reader = Reader { input };
+        Self::decode_with(input, DecodeOptions::new())
+    }
 
+    /// Decodes a command from an input string, using the given `DecodeOptions`.
+    ///
+    /// The input must contain the terminating `CR` character (ASCII 13).
+    pub fn decode_with(input: &[u8], options: DecodeOptions) -> Result<Self, Error> {
+        let mut reader = Reader::with_options(input, options);
+        Self::read(&mut reader, options).map_err(|e| e.at(reader.pos()))
+    }
+
+    fn read(reader: &mut Reader<'_>, options: DecodeOptions) -> Result<Self, Error> {
         let op = reader.read_byte()?;
+        let cmd = match options.vendor_command(op) {
+            Some(vendor) => {
+                let payload = reader.read_raw_payload()?;
+                if !(vendor.validate)(payload.as_bytes()) {
+                    return Err(Error::decode().at(1));
+                }
+
+                Command::Vendor {
+                    opcode: op,
+                    payload,
+                }
+            }
+            None => Self::decode_op(op, reader, options)?,
+        };
+
+        reader.finish()?;
+        Ok(cmd)
+    }
+
+    /// Decodes the first command in `input`, which may be followed by more data.
+    ///
+    /// Returns the decoding result together with the number of bytes the command occupies,
+    /// including its terminating `CR`. The length is also returned when the command is invalid, so
+    /// that it can be skipped. If `input` does not contain a complete command, an `Eof` error and a
+    /// length of 0 are returned.
+    ///
+    /// This lets applications that manage their own buffers decode commands without copying them
+    /// into a `CommandBuf`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_slcan::read::Command;
+    ///
+    /// let mut input = &b"O\rxyz\rC\rS"[..];
+    /// let mut cmds = Vec::new();
+    /// loop {
+    ///     match Command::decode_partial(input) {
+    ///         (_, 0) => break,
+    ///         (result, consumed) => {
+    ///             cmds.push(result.ok());
+    ///             input = &input[consumed..];
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(cmds, [Some(Command::Open), None, Some(Command::Close)]);
+    /// assert_eq!(input, b"S");
+    /// ```
+    pub fn decode_partial(input: &[u8]) -> (Result<Self, Error>, usize) {
+        Self::decode_partial_with(input, DecodeOptions::new())
+    }
+
+    /// Like `decode_partial`, but uses the given `DecodeOptions`.
+    ///
+    /// If `DecodeOptions::allow_lf` is enabled, an `LF` following the terminating `CR` is included
+    /// in the returned length, and a leading `LF` is skipped.
+    pub fn decode_partial_with(
+        input: &[u8],
+        options: DecodeOptions,
+    ) -> (Result<Self, Error>, usize) {
+        let skip = match input.first() {
+            Some(b'\n') if options.allow_lf => 1,
+            _ => 0,
+        };
+        let rest = &input[skip..];
+        let mut len = match rest.iter().position(|b| options.is_terminator(*b)) {
+            Some(end) => end + 1,
+            None => return (Err(Error::eof_needing(1)), 0),
+        };
+        if options.allow_lf && rest[len - 1] == b'\r' && rest.get(len) == Some(&b'\n') {
+            len += 1;
+        }
+
+        (Self::decode_with(&rest[..len], options), skip + len)
+    }
+
+    /// Returns an iterator that decodes all commands in `input`.
+    ///
+    /// This is meant for applications that receive whole packets (for example over USB) that
+    /// consist of complete commands, and do not need the stateful buffering of `CommandBuf`.
+    /// Invalid commands yield an error and are skipped. If `input` ends with an incomplete
+    /// command, the last item is an `Eof` error.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_slcan::read::Command;
+    ///
+    /// let mut cmds = Command::decode_all(b"O\rxyz\rC\r");
+    /// assert_eq!(cmds.next().unwrap().unwrap(), Command::Open);
+    /// assert!(cmds.next().unwrap().is_err());
+    /// assert_eq!(cmds.next().unwrap().unwrap(), Command::Close);
+    /// assert!(cmds.next().is_none());
+    /// ```
+    pub fn decode_all(input: &[u8]) -> impl Iterator<Item = Result<Self, Error>> + '_ {
+        Self::decode_all_with(input, DecodeOptions::new())
+    }
+
+    /// Like `decode_all`, but uses the given `DecodeOptions`.
+    pub fn decode_all_with(
+        mut input: &[u8],
+        options: DecodeOptions,
+    ) -> impl Iterator<Item = Result<Self, Error>> + '_ {
+        iter::from_fn(move || {
+            if input.is_empty() || options.is_empty_line(input) {
+                return None;
+            }
+
+            let (result, len) = Self::decode_partial_with(input, options);
+            // An incomplete command is only reported once.
+            input = if len == 0 { &[] } else { &input[len..] };
+            Some(result)
+        })
+    }
+
+    /// Decodes the arguments of the standard command `op`, not including the terminating `CR`.
+    fn decode_op(op: u8, reader: &mut Reader<'_>, options: DecodeOptions) -> Result<Self, Error> {
         let cmd = match op {
             b'S' => {
-                let bitrate = match reader.read_byte()? {
-                    b'0' => Bitrate::_10kbit,
-                    b'1' => Bitrate::_20kbit,
-                    b'2' => Bitrate::_50kbit,
-                    b'3' => Bitrate::_100kbit,
-                    b'4' => Bitrate::_125kbit,
-                    b'5' => Bitrate::_250kbit,
-                    b'6' => Bitrate::_500kbit,
-                    b'7' => Bitrate::_800kbit,
-                    b'8' => Bitrate::_1mbit,
-                    _ => return Err(Error::decode()),
-                };
+                let bitrate = reader.read_param(|code| match code {
+                    b'0' => Some(Bitrate::_10kbit),
+                    b'1' => Some(Bitrate::_20kbit),
+                    b'2' => Some(Bitrate::_50kbit),
+                    b'3' => Some(Bitrate::_100kbit),
+                    b'4' => Some(Bitrate::_125kbit),
+                    b'5' => Some(Bitrate::_250kbit),
+                    b'6' => Some(Bitrate::_500kbit),
+                    b'7' => Some(Bitrate::_800kbit),
+                    b'8' => Some(Bitrate::_1mbit),
+                    code => options.extra_bitrate(code),
+                })?;
 
                 Command::SetupWithBitrate { bitrate }
             }
+            b's' => {
+                let btr0 = reader.read_hex_u8()?;
+                let btr1 = reader.read_hex_u8()?;
+
+                Command::SetupWithBtr { btr0, btr1 }
+            }
+            b'Y' => {
+                let bitrate = reader.read_param(|byte| match byte {
+                    b'2' => Some(DataBitrate::_2mbit),
+                    b'4' => Some(DataBitrate::_4mbit),
+                    b'5' => Some(DataBitrate::_5mbit),
+                    b'8' => Some(DataBitrate::_8mbit),
+                    _ => None,
+                })?;
+
+                Command::SetupDataBitrate { bitrate }
+            }
             b'O' => Command::Open,
+            b'L' => Command::OpenListenOnly,
             b'C' => Command::Close,
             b't' => {
-                let identifier = reader.read_hex_identifier()?;
-                let len = reader.read_hex_u4()?;
-                if len > 8 {
-                    return Err(Error::decode());
-                }
-                let frame = reader.read_frame(len)?;
+                let identifier = reader.read_identifier()?;
+                let dlc = reader.read_dlc(options)?;
+                let frame = reader.read_frame_data(dlc)?;
 
                 Command::TxStandard { identifier, frame }
             }
             b'T' => {
-                let identifier = reader.read_hex_ext_identifier()?;
-                let len = reader.read_hex_u4()?;
-                if len > 8 {
-                    return Err(Error::decode());
-                }
-                let frame = reader.read_frame(len)?;
+                let identifier = reader.read_ext_identifier()?;
+                let dlc = reader.read_dlc(options)?;
+                let frame = reader.read_frame_data(dlc)?;
 
                 Command::TxExt { identifier, frame }
             }
             b'r' => {
-                let identifier = reader.read_hex_identifier()?;
-                let len = reader.read_hex_u4()?;
-                if len > 8 {
-                    return Err(Error::decode());
-                }
+                let identifier = reader.read_identifier()?;
+                let len = reader.read_dlc(options)?;
 
                 Command::TxStandardRtr { identifier, len }
             }
             b'R' => {
-                let identifier = reader.read_hex_ext_identifier()?;
-                let len = reader.read_hex_u4()?;
-                if len > 8 {
-                    return Err(Error::decode());
-                }
+                let identifier = reader.read_ext_identifier()?;
+                let len = reader.read_dlc(options)?;
 
                 Command::TxExtRtr { identifier, len }
             }
+            b'd' | b'b' => {
+                let identifier = reader.read_identifier()?;
+                let frame = reader.read_fd_frame()?;
+
+                Command::TxFd {
+                    identifier,
+                    frame,
+                    flags: fd_flags(op),
+                }
+            }
+            b'D' | b'B' => {
+                let identifier = reader.read_ext_identifier()?;
+                let frame = reader.read_fd_frame()?;
+
+                Command::TxExtFd {
+                    identifier,
+                    frame,
+                    flags: fd_flags(op),
+                }
+            }
+            b'P' => Command::PollOne,
+            b'A' => Command::PollAll,
             b'F' => Command::ReadStatus,
+            b'E' => Command::ReadErrorCounters,
+            b'I' => Command::ReadBusload,
             b'V' => Command::ReadVersion,
+            b'v' => Command::ReadDetailedVersion,
             b'N' => Command::ReadSerial,
             b'Z' => {
-                let timestamp = match reader.read_byte()? {
-                    b'0' => false,
-                    b'1' => true,
-                    _ => return Err(Error::decode()),
-                };
+                let mode = reader.read_param(|byte| match byte {
+                    b'0' => Some(TimestampMode::Off),
+                    b'1' => Some(TimestampMode::Milliseconds),
+                    b'2' => Some(TimestampMode::Microseconds),
+                    _ => None,
+                })?;
 
-                Command::SetRxTimestamp { timestamp }
+                Command::SetRxTimestamp { mode }
             }
-            _ => return Err(Error::decode()),
-        };
+            b'X' => Command::SetAutoPoll {
+                enabled: reader.read_bool()?,
+            },
+            b'M' => Command::SetAcceptanceCode(reader.read_hex_filter_value()?),
+            b'm' => Command::SetAcceptanceMask(reader.read_hex_filter_value()?),
+            // `Wrrvv` and `Wn` share an opcode. `Wn` is terminated right after its single digit.
+            #[cfg(feature = "usbtin")]
+            b'W' if matches!(reader.input.get(1), Some(&byte) if !reader.is_terminator(byte)) => {
+                let register = reader.read_hex_u8()?;
+                let value = reader.read_hex_u8()?;
 
-        if reader.read_byte()? != b'\r' {
-            return Err(Error::decode());
-        }
+                Command::WriteRegister { register, value }
+            }
+            #[cfg(feature = "usbtin")]
+            b'G' => Command::ReadRegister {
+                register: reader.read_hex_u8()?,
+            },
+            b'W' => {
+                let mode = reader.read_param(|byte| match byte {
+                    b'0' => Some(FilterMode::Dual),
+                    b'1' => Some(FilterMode::Single),
+                    _ => None,
+                })?;
 
-        // Reject trailing undecoded data.
-        if !reader.input.is_empty() {
-            return Err(Error::decode());
-        }
+                Command::SetFilterMode(mode)
+            }
+            b'Q' => {
+                let mode = reader.read_param(|byte| match byte {
+                    b'0' => Some(AutoStartup::Off),
+                    b'1' => Some(AutoStartup::OpenNormal),
+                    b'2' => Some(AutoStartup::OpenListenOnly),
+                    _ => None,
+                })?;
+
+                Command::SetAutoStartup(mode)
+            }
+            b'U' => {
+                let baud = reader.read_param(|byte| match byte {
+                    b'0' => Some(SerialBaud::_230400),
+                    b'1' => Some(SerialBaud::_115200),
+                    b'2' => Some(SerialBaud::_57600),
+                    b'3' => Some(SerialBaud::_38400),
+                    b'4' => Some(SerialBaud::_19200),
+                    b'5' => Some(SerialBaud::_9600),
+                    b'6' => Some(SerialBaud::_2400),
+                    _ => None,
+                })?;
+
+                Command::SetSerialBaudrate { baud }
+            }
+            _ if options.allow_unknown => {
+                let payload = reader.read_raw_payload()?;
+
+                Command::Unknown {
+                    opcode: op,
+                    payload,
+                }
+            }
+            _ => {
+                return Err(Error::new(ErrorKind::UnsupportedCommand)
+                    .with_byte(op)
+                    .in_field(Field::Opcode)
+                    .at(0))
+            }
+        };
 
         Ok(cmd)
     }
-}
 
-/// A byte buffer that yields decoded `Command`s.
-///
-/// This is meant to be used by apps that receive bytewise data and want to decode `Command`s from
-/// that.
-#[derive(Default, Debug)]
-pub struct CommandBuf {
-    /// Invariant: `bytes[..used]` Never contains `\r`.
-    bytes: [u8; Command::MAX_ENCODED_LEN],
-    used: u8,
-}
+    /// Returns the CAN frame this command requests to transmit, if any.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_slcan::read::Command;
+    ///
+    /// let cmd = Command::decode(b"t1231AA\r").unwrap();
+    /// let frame = cmd.to_frame().unwrap();
+    /// assert_eq!(frame.id.raw(), 0x123);
+    /// assert_eq!(frame.data(), [0xAA]);
+    ///
+    /// assert!(Command::Open.to_frame().is_none());
+    /// ```
+    pub fn to_frame(&self) -> Option<Frame> {
+        let (id, kind) = match *self {
+            Command::TxStandard { identifier, frame } => {
+                (identifier.into(), FrameKind::Data(frame))
+            }
+            Command::TxExt { identifier, frame } => (identifier.into(), FrameKind::Data(frame)),
+            Command::TxStandardRtr { identifier, len } => {
+                (identifier.into(), FrameKind::Remote(len))
+            }
+            Command::TxExtRtr { identifier, len } => (identifier.into(), FrameKind::Remote(len)),
+            Command::TxFd {
+                identifier,
+                frame,
+                flags,
+            } => (identifier.into(), FrameKind::Fd { frame, flags }),
+            Command::TxExtFd {
+                identifier,
+                frame,
+                flags,
+            } => (identifier.into(), FrameKind::Fd { frame, flags }),
+            _ => return None,
+        };
 
-impl CommandBuf {
-    /// Creates a new, empty `CommandBuf`.
-    pub const fn new() -> Self {
-        Self {
-            bytes: [0; Command::MAX_ENCODED_LEN],
-            used: 0,
+        Some(Frame { id, kind })
+    }
+
+    /// Returns the opcode of this command, the first byte of its encoded form.
+    ///
+    /// The CAN FD transmit commands have different opcodes depending on whether `FdFlags::BRS` is
+    /// set.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_slcan::read::Command;
+    ///
+    /// assert_eq!(Command::Open.opcode(), b'O');
+    /// assert_eq!(Command::decode(b"r1230\r").unwrap().opcode(), b'r');
+    /// ```
+    pub fn opcode(&self) -> u8 {
+        match self {
+            Command::SetupWithBitrate { .. } => b'S',
+            Command::SetupWithBtr { .. } => b's',
+            Command::SetupDataBitrate { .. } => b'Y',
+            Command::Open => b'O',
+            Command::OpenListenOnly => b'L',
+            Command::Close => b'C',
+            Command::TxStandard { .. } => b't',
+            Command::TxExt { .. } => b'T',
+            Command::TxStandardRtr { .. } => b'r',
+            Command::TxExtRtr { .. } => b'R',
+            Command::TxFd { flags, .. } if flags.contains(FdFlags::BRS) => b'b',
+            Command::TxFd { .. } => b'd',
+            Command::TxExtFd { flags, .. } if flags.contains(FdFlags::BRS) => b'B',
+            Command::TxExtFd { .. } => b'D',
+            Command::PollOne => b'P',
+            Command::PollAll => b'A',
+            Command::ReadStatus => b'F',
+            Command::ReadErrorCounters => b'E',
+            Command::ReadBusload => b'I',
+            Command::ReadVersion => b'V',
+            Command::ReadDetailedVersion => b'v',
+            Command::ReadSerial => b'N',
+            Command::SetRxTimestamp { .. } => b'Z',
+            Command::SetAutoPoll { .. } => b'X',
+            Command::SetAcceptanceCode(_) => b'M',
+            Command::SetAcceptanceMask(_) => b'm',
+            Command::SetFilterMode(_) => b'W',
+            Command::SetAutoStartup(_) => b'Q',
+            Command::SetSerialBaudrate { .. } => b'U',
+            #[cfg(feature = "usbtin")]
+            Command::WriteRegister { .. } => b'W',
+            #[cfg(feature = "usbtin")]
+            Command::ReadRegister { .. } => b'G',
+            Command::Unknown { opcode, .. } | Command::Vendor { opcode, .. } => *opcode,
         }
     }
 
-    /// Returns the currently unused part of the buffer.
+    /// Returns the kind of response the device sends when it executes this command successfully.
     ///
-    /// The caller can copy new input bytes into the returned slice, and call `advance_by` to mark
-    /// them as part of the `CommandBuf`.
-    pub fn tail_mut(&mut self) -> &mut [u8] {
-        &mut self.bytes[usize::from(self.used)..]
+    /// Regardless of this, every command can be answered with `Response::Error`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_slcan::read::{Command, ResponseKind};
+    /// use mini_slcan::write::Response;
+    ///
+    /// let kind = Command::decode(b"t1230\r").unwrap().expected_response();
+    /// assert_eq!(kind, ResponseKind::TxAck);
+    /// assert!(kind.accepts(&Response::TxAck));
+    /// assert!(!kind.accepts(&Response::Ack));
+    /// ```
+    pub fn expected_response(&self) -> ResponseKind {
+        match self {
+            Command::TxStandard { .. } | Command::TxStandardRtr { .. } | Command::TxFd { .. } => {
+                ResponseKind::TxAck
+            }
+            Command::TxExt { .. } | Command::TxExtRtr { .. } | Command::TxExtFd { .. } => {
+                ResponseKind::ExtTxAck
+            }
+            Command::PollOne => ResponseKind::PollOne,
+            Command::PollAll => ResponseKind::PollAllEnd,
+            Command::ReadStatus => ResponseKind::Status,
+            Command::ReadErrorCounters => ResponseKind::ErrorCounters,
+            Command::ReadBusload => ResponseKind::Busload,
+            Command::ReadVersion => ResponseKind::Version,
+            Command::ReadDetailedVersion => ResponseKind::DetailedVersion,
+            Command::ReadSerial => ResponseKind::Serial,
+            #[cfg(feature = "usbtin")]
+            Command::ReadRegister { .. } => ResponseKind::RegisterValue,
+            Command::Unknown { .. } | Command::Vendor { .. } => ResponseKind::Any,
+            Command::SetupWithBitrate { .. }
+            | Command::SetupWithBtr { .. }
+            | Command::SetupDataBitrate { .. }
+            | Command::Open
+            | Command::OpenListenOnly
+            | Command::Close
+            | Command::SetRxTimestamp { .. }
+            | Command::SetAutoPoll { .. }
+            | Command::SetAcceptanceCode(_)
+            | Command::SetAcceptanceMask(_)
+            | Command::SetFilterMode(_)
+            | Command::SetAutoStartup(_)
+            | Command::SetSerialBaudrate { .. } => ResponseKind::Ack,
+            #[cfg(feature = "usbtin")]
+            Command::WriteRegister { .. } => ResponseKind::Ack,
+        }
     }
+}
 
-    fn is_full(&self) -> bool {
-        usize::from(self.used) == Command::MAX_ENCODED_LEN
+impl From<Frame> for Command {
+    /// Creates the command that transmits `frame`.
+    fn from(frame: Frame) -> Self {
+        match (frame.id, frame.kind) {
+            (Id::Standard(identifier), FrameKind::Data(frame)) => {
+                Command::TxStandard { identifier, frame }
+            }
+            (Id::Extended(identifier), FrameKind::Data(frame)) => {
+                Command::TxExt { identifier, frame }
+            }
+            (Id::Standard(identifier), FrameKind::Remote(len)) => {
+                Command::TxStandardRtr { identifier, len }
+            }
+            (Id::Extended(identifier), FrameKind::Remote(len)) => {
+                Command::TxExtRtr { identifier, len }
+            }
+            (Id::Standard(identifier), FrameKind::Fd { frame, flags }) => Command::TxFd {
+                identifier,
+                frame,
+                flags,
+            },
+            (Id::Extended(identifier), FrameKind::Fd { frame, flags }) => Command::TxExtFd {
+                identifier,
+                frame,
+                flags,
+            },
+        }
+    }
+}
+
+impl Notification {
+    /// Decodes a notification sent by an SLCAN device. The input must contain the terminating `CR`
+    /// character (ASCII 13).
+    ///
+    /// This is meant to be used by host applications that talk to an SLCAN device.
+    pub fn decode(input: &[u8]) -> Result<Self, Error> {
+        Self::decode_with(input, DecodeOptions::new())
     }
 
-    fn find_cr(&self, start: usize) -> Option<usize> {
-        self.bytes[start..usize::from(self.used)]
-            .iter()
-            .position(|b| *b == b'\r')
-            .map(|pos| pos + start)
+    /// Decodes a notification sent by an SLCAN device, using the given `DecodeOptions`.
+    ///
+    /// Of the `DecodeOptions`, only `allow_long_dlc`, `allow_lowercase_hex` and `allow_lf` affect
+    /// notification decoding.
+    pub fn decode_with(input: &[u8], options: DecodeOptions) -> Result<Self, Error> {
+        let mut reader = Reader::with_options(input, options);
+        let notif = reader
+            .read_notification(options)
+            .and_then(|notif| reader.finish().map(|()| notif));
+        notif.map_err(|e| e.at(reader.pos()))
     }
+}
 
-    /// Marks `len` more bytes from the buffer's tail as consumed, and returns an iterator over all
-    /// `Command`s in the buffer.
+impl Response {
+    /// Decodes a response sent by an SLCAN device.
     ///
-    /// When dropped, the returned iterator will remove the decoded bytes from the `CommandBuf`.
-    pub fn advance_by(&mut self, amount: u8) -> impl Iterator<Item = Result<Command, Error>> + '_ {
-        self.used += amount;
-        assert!(usize::from(self.used) <= Command::MAX_ENCODED_LEN);
+    /// The input must contain the terminating `CR` character (ASCII 13), except for
+    /// `Response::Error`, which is encoded as a single BELL character (ASCII 7).
+    ///
+    /// This is meant to be used by host applications that talk to an SLCAN device. Responses that
+    /// can only be told apart from others by knowing the command they respond to (such as the
+    /// USBtin `RegisterValue` response) are not decoded by this method.
+    pub fn decode(input: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader::new(input);
+        if reader.input == [7] {
+            return Ok(Response::Error);
+        }
 
-        CommandIter { buf: self, pos: 0 }
+        let resp = match reader.input.first() {
+            Some(b'\r') => Response::Ack,
+            _ => reader.read_response()?,
+        };
+        reader.finish()?;
+        Ok(resp)
     }
 }
 
-struct CommandIter<'a> {
-    buf: &'a mut CommandBuf,
-    pos: u8,
+/// Decodes and validates the response to a specific `Command`.
+///
+/// Some responses can not be decoded without knowing which command they respond to. Also, the
+/// host usually wants to make sure that a received response actually belongs to the command that
+/// was sent. `ResponseDecoder` handles both.
+///
+/// # Example
+///
+/// ```
+/// use mini_slcan::read::{Command, ResponseDecoder};
+/// use mini_slcan::write::Response;
+///
+/// let decoder = ResponseDecoder::for_command(&Command::Open);
+/// assert_eq!(decoder.decode(b"\r").unwrap(), Response::Ack);
+/// assert_eq!(decoder.decode(b"\x07").unwrap(), Response::Error);
+/// assert!(decoder.decode(b"z\r").is_err());
+/// ```
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Format)]
+pub struct ResponseDecoder {
+    expected: ResponseKind,
 }
 
-impl Iterator for CommandIter<'_> {
-    type Item = Result<Command, Error>;
+/// The kind of (successful) response a command elicits, as returned by
+/// `Command::expected_response`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Format)]
+#[non_exhaustive]
+pub enum ResponseKind {
+    /// `Response::Ack` (a plain `CR`).
+    Ack,
+    /// `Ack` if no frame is available, otherwise the polled frame (as a notification).
+    PollOne,
+    /// `Response::TxAck` (`z`).
+    TxAck,
+    /// `Response::ExtTxAck` (`Z`).
+    ExtTxAck,
+    /// `Response::PollAllEnd`, preceded by the polled frames (as notifications).
+    PollAllEnd,
+    /// `Response::Status` (`F`).
+    Status,
+    /// `Response::ErrorCounters` (`E`).
+    ErrorCounters,
+    /// `Response::Busload` (`I`).
+    Busload,
+    /// `Response::Version` (`V`).
+    Version,
+    /// `Response::DetailedVersion` (`v`).
+    DetailedVersion,
+    /// `Response::Serial` (`N`).
+    Serial,
+    /// `Response::RegisterValue` (USBtin only).
+    #[cfg(feature = "usbtin")]
+    RegisterValue,
+    /// Unknown or vendor-specific command; any response is accepted.
+    Any,
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let pos = usize::from(self.pos);
-        let end = match self.buf.find_cr(pos) {
-            Some(pos) => pos,
-            None if pos == 0 && self.buf.is_full() => {
-                // There is no `\r` in the entire buffer to terminate the received command. That
-                // means that the input is invalid, since the buffer can hold the longest command,
-                // including the trailing `\r`.
-                // Yield an error, and mark the whole buffer as consumed to make space for new data.
-                self.pos = Command::MAX_ENCODED_LEN as u8;
-                return Some(Err(Error::decode()));
-            }
-            None => return None,
-        };
+impl ResponseKind {
+    /// Returns whether `resp` is a response of this kind.
+    ///
+    /// `Response::Error` is not accepted by any kind except `Any`, even though every command can
+    /// fail.
+    pub fn accepts(&self, resp: &Response) -> bool {
+        match (self, resp) {
+            (ResponseKind::Any, _)
+            | (ResponseKind::Ack, Response::Ack)
+            | (ResponseKind::PollOne, Response::Ack)
+            | (ResponseKind::TxAck, Response::TxAck)
+            | (ResponseKind::ExtTxAck, Response::ExtTxAck)
+            | (ResponseKind::PollAllEnd, Response::PollAllEnd)
+            | (ResponseKind::Status, Response::Status(_))
+            | (ResponseKind::ErrorCounters, Response::ErrorCounters { .. })
+            | (ResponseKind::Busload, Response::Busload { .. })
+            | (ResponseKind::Version, Response::Version { .. })
+            | (ResponseKind::DetailedVersion, Response::DetailedVersion(_))
+            | (ResponseKind::Serial, Response::Serial(_)) => true,
+            #[cfg(feature = "usbtin")]
+            (ResponseKind::RegisterValue, Response::RegisterValue(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+impl ResponseDecoder {
+    /// Creates a `ResponseDecoder` that accepts the responses that are valid for `cmd`.
+    ///
+    /// `Response::Error` is valid for every command.
+    ///
+    /// The `PollOne` and `PollAll` commands are answered with the polled CAN frames, which have to
+    /// be decoded as `Notification`s. The `ResponseDecoder` for `PollOne` decodes the `Ack` that is
+    /// sent when no frame is available, and the one for `PollAll` decodes the final `PollAllEnd`.
+    pub fn for_command(cmd: &Command) -> Self {
+        Self {
+            expected: cmd.expected_response(),
+        }
+    }
+
+    /// Decodes `input` as a response to the command this decoder was created for.
+    ///
+    /// Returns an error if `input` is not a valid response to that command.
+    pub fn decode(&self, input: &[u8]) -> Result<Response, Error> {
+        if input == [7] {
+            return Ok(Response::Error);
+        }
 
-        let cmd = &self.buf.bytes[pos..end + 1];
-        self.pos += cmd.len() as u8;
+        #[cfg(feature = "usbtin")]
+        {
+            if self.expected == ResponseKind::RegisterValue {
+                let mut reader = Reader::new(input);
+                let value = reader.read_hex_u8()?;
+                reader.finish()?;
+                return Ok(Response::RegisterValue(value));
+            }
+        }
 
-        Some(Command::decode(cmd))
+        let resp = Response::decode(input)?;
+        if self.expected.accepts(&resp) {
+            Ok(resp)
+        } else {
+            Err(Error::decode())
+        }
     }
-}
 
-impl Drop for CommandIter<'_> {
-    fn drop(&mut self) {
-        let decoded_end = usize::from(self.pos);
-        self.buf.bytes.copy_within(decoded_end.., 0);
-        self.buf.used -= decoded_end as u8;
+    /// Returns whether `msg` is a notification rather than a response to the command this decoder
+    /// was created for.
+    ///
+    /// Unlike `Incoming::is_notification`, this takes the expected response into account: a USBtin
+    /// `RegisterValue` response like `B3` starts with a notification opcode, but is treated as a
+    /// response while a `ReadRegister` command is awaiting one.
+    pub fn is_notification(&self, msg: &[u8]) -> bool {
+        #[cfg(feature = "usbtin")]
+        {
+            if self.expected == ResponseKind::RegisterValue {
+                if let [hi, lo, b'\r'] = msg {
+                    if hi.is_ascii_hexdigit() && lo.is_ascii_hexdigit() {
+                        return false;
+                    }
+                }
+            }
+        }
+
+        Incoming::is_notification(msg)
     }
 }
 
-#[derive(Eq, PartialEq)]
-struct Reader<'a> {
-    input: &'a [u8],
+/// A message sent by an SLCAN device to the host.
+#[derive(Debug, Format)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum Incoming {
+    /// A response to a command sent by the host.
+    Response(Response),
+
+    /// A notification about a received CAN frame or bus error.
+    Notification {
+        notif: Notification,
+        /// The timestamp of the notification, if timestamps are enabled.
+        timestamp: Option<Timestamp>,
+    },
 }
 
-impl<'a> Reader<'a> {
-    fn read_byte(&mut self) -> Result<u8, Error> {
-        match self.input {
-            [] => Err(Error::eof()),
-            [b, rest @ ..] => {
-                self.input = rest;
-                Ok(*b)
-            }
-        }
+impl Incoming {
+    /// Returns whether `input` is a notification (as opposed to a response), based on its first
+    /// byte.
+    pub fn is_notification(input: &[u8]) -> bool {
+        matches!(
+            input.first(),
+            Some(b't')
+                | Some(b'T')
+                | Some(b'r')
+                | Some(b'R')
+                | Some(b'd')
+                | Some(b'D')
+                | Some(b'b')
+                | Some(b'B')
+                | Some(b'e')
+        )
     }
 
-    fn read_hex_digits(&mut self, digits: u8) -> Result<u32, Error> {
-        let mut val = 0;
+    /// Decodes a message sent by an SLCAN device.
+    ///
+    /// `timestamps` is the timestamp mode configured on the device, and determines whether and how
+    /// timestamps are decoded from notifications.
+    pub fn decode(
+        input: &[u8],
+        timestamps: TimestampMode,
+        options: DecodeOptions,
+    ) -> Result<Self, Error> {
+        match input.first() {
+            Some(b't') | Some(b'T') | Some(b'r') | Some(b'R') | Some(b'd') | Some(b'D')
+            | Some(b'b') | Some(b'B') | Some(b'e') => {
+                let mut reader = Reader::with_options(input, options);
+                let notif = reader.read_notification(options)?;
+                let timestamp = match timestamps {
+                    TimestampMode::Off => None,
+                    TimestampMode::Milliseconds => Some(Timestamp::Millis(reader.read_hex_u16()?)),
+                    TimestampMode::Microseconds => Some(Timestamp::Micros(reader.read_hex(8)?)),
+                };
+                reader.finish()?;
 
-        for _ in 0..digits {
-            val <<= 4;
-            val |= unhex(self.read_byte()?)? as u32;
+                Ok(Incoming::Notification { notif, timestamp })
+            }
+            _ => Ok(Incoming::Response(Response::decode(input)?)),
         }
+    }
+}
 
-        Ok(val)
+/// Classifies messages received by a host as notifications, responses, or errors.
+///
+/// An SLCAN device interleaves unsolicited notifications with the responses to commands sent by
+/// the host. `Demux` tells them apart, and uses the command that is awaiting a response (see
+/// `command_sent`) to decode and validate responses via a `ResponseDecoder`.
+///
+/// Only a single command may be awaiting a response at any time.
+#[derive(Copy, Clone, Debug, Format)]
+pub struct Demux {
+    timestamps: TimestampMode,
+    options: DecodeOptions,
+    pending: Option<ResponseDecoder>,
+}
+
+impl Demux {
+    /// Creates a new `Demux` with no command awaiting a response.
+    pub const fn new() -> Self {
+        Self::with_options(DecodeOptions::new())
     }
 
-    fn read_hex_u4(&mut self) -> Result<u8, Error> {
-        Ok(self.read_hex_digits(1)? as u8)
+    /// Creates a new `Demux` that decodes messages using the given `DecodeOptions`.
+    pub const fn with_options(options: DecodeOptions) -> Self {
+        Self {
+            timestamps: TimestampMode::Off,
+            options,
+            pending: None,
+        }
     }
 
-    fn read_hex_u8(&mut self) -> Result<u8, Error> {
-        Ok(self.read_hex_digits(2)? as u8)
+    /// Sets the timestamp mode used to decode notifications.
+    ///
+    /// This has to be kept in sync with the timestamp mode configured on the device via the
+    /// `SetRxTimestamp` command. By default, notifications are assumed to have no timestamp.
+    pub fn set_timestamp_mode(&mut self, mode: TimestampMode) {
+        self.timestamps = mode;
     }
 
-    fn read_hex_identifier(&mut self) -> Result<Identifier, Error> {
-        let raw = self.read_hex_digits(3)? as u16;
-        Identifier::from_raw(raw).ok_or(Error::decode())
+    /// Records that `cmd` was sent to the device, so that the next response is decoded as a
+    /// response to `cmd`.
+    pub fn command_sent(&mut self, cmd: &Command) {
+        self.pending = Some(ResponseDecoder::for_command(cmd));
     }
 
-    fn read_hex_ext_identifier(&mut self) -> Result<ExtIdentifier, Error> {
-        let raw = self.read_hex_digits(8)?;
-        ExtIdentifier::from_raw(raw).ok_or(Error::decode())
+    /// Returns whether a command is still awaiting its response.
+    pub fn is_awaiting_response(&self) -> bool {
+        self.pending.is_some()
     }
 
-    fn read_frame(&mut self, len: u8) -> Result<CanFrame, Error> {
-        assert!(len <= 8);
+    /// Stops waiting for the response to the last command sent.
+    ///
+    /// This can be used when the device did not respond in time. Subsequent responses will be
+    /// decoded without context.
+    pub fn cancel_pending(&mut self) {
+        self.pending = None;
+    }
 
-        let mut frame = CanFrame::new();
+    /// Classifies and decodes a single message received from the device.
+    ///
+    /// `msg` must include the terminating `CR` (or consist of a single BELL character).
+    ///
+    /// If a command is awaiting a response, response messages are decoded as a response to it, and
+    /// the command is considered answered. Otherwise, responses are decoded without context.
+    pub fn classify(&mut self, msg: &[u8]) -> Result<Incoming, Error> {
+        if self.is_notification(msg) {
+            if let Some(ResponseDecoder {
+                expected: ResponseKind::PollOne,
+            }) = self.pending
+            {
+                // The polled frame is the answer to `PollOne`.
+                self.pending = None;
+            }
 
-        for _ in 0..len {
-            let byte = self.read_hex_u8()?;
+            Incoming::decode(msg, self.timestamps, self.options)
+        } else {
+            match self.pending.take() {
+                Some(decoder) => decoder.decode(msg).map(Incoming::Response),
+                None => Response::decode(msg).map(Incoming::Response),
+            }
+        }
+    }
 
-            // Can never fail, because we limit `len` to 8 or less.
-            frame.push(byte).unwrap();
+    /// Returns whether `msg` is a notification, taking the response expected by the pending
+    /// command into account.
+    fn is_notification(&self, msg: &[u8]) -> bool {
+        match &self.pending {
+            Some(decoder) => decoder.is_notification(msg),
+            None => Incoming::is_notification(msg),
         }
+    }
+}
 
-        Ok(frame)
+impl Default for Demux {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Size of the `HostBuf` buffer, large enough to hold any `Response` or `Notification`, including
+/// CAN FD notifications.
+pub(crate) const HOST_BUF_LEN: usize = if ResponseBuf::LEN > NotificationBuf::FD_LEN {
+    ResponseBuf::LEN
+} else {
+    NotificationBuf::FD_LEN
+};
+
+/// A byte buffer that yields decoded `Incoming` messages.
+///
+/// This is the host-side counterpart to `CommandBuf`: it is meant to be used by host applications
+/// that receive bytewise data from an SLCAN device.
+#[derive(Debug)]
+pub struct HostBuf {
+    /// Invariant: `bytes[..used]` never contains `\r` or BELL.
+    bytes: [u8; HOST_BUF_LEN],
+    used: u8,
+    demux: Demux,
+}
+
+impl HostBuf {
+    /// Creates a new, empty `HostBuf`.
+    pub const fn new() -> Self {
+        Self::with_options(DecodeOptions::new())
+    }
+
+    /// Creates a new, empty `HostBuf` that decodes messages using the given `DecodeOptions`.
+    pub const fn with_options(options: DecodeOptions) -> Self {
+        Self {
+            bytes: [0; HOST_BUF_LEN],
+            used: 0,
+            demux: Demux::with_options(options),
+        }
+    }
+
+    /// Sets the timestamp mode used to decode notifications.
+    ///
+    /// This has to be kept in sync with the timestamp mode configured on the device via the
+    /// `SetRxTimestamp` command. By default, notifications are assumed to have no timestamp.
+    pub fn set_timestamp_mode(&mut self, mode: TimestampMode) {
+        self.demux.set_timestamp_mode(mode);
+    }
+
+    /// Records that `cmd` was sent to the device, so that its response can be decoded correctly.
+    ///
+    /// See `Demux::command_sent` for details.
+    pub fn command_sent(&mut self, cmd: &Command) {
+        self.demux.command_sent(cmd);
+    }
+
+    /// Returns the currently unused part of the buffer.
+    ///
+    /// The caller can copy new input bytes into the returned slice, and call `advance_by` to mark
+    /// them as part of the `HostBuf`.
+    pub fn tail_mut(&mut self) -> &mut [u8] {
+        &mut self.bytes[usize::from(self.used)..]
+    }
+
+    /// Marks `len` more bytes from the buffer's tail as consumed, and returns an iterator over all
+    /// `Incoming` messages in the buffer.
+    ///
+    /// When dropped, the returned iterator will remove the decoded bytes from the `HostBuf`.
+    ///
+    /// If `amount` exceeds the length of the slice returned by `tail_mut`, only the bytes that fit
+    /// into the buffer are marked as consumed, and the iterator first yields an
+    /// `ErrorKind::Overflow` error reporting the excess bytes as discarded.
+    pub fn advance_by(&mut self, amount: u8) -> impl Iterator<Item = Result<Incoming, Error>> + '_ {
+        let free = HOST_BUF_LEN as u8 - self.used;
+        self.used += amount.min(free);
+
+        HostIter {
+            buf: self,
+            pos: 0,
+            excess: amount.saturating_sub(free),
+        }
+    }
+}
+
+impl Default for HostBuf {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct HostIter<'a> {
+    buf: &'a mut HostBuf,
+    pos: u8,
+    /// Number of bytes passed to `advance_by` that did not fit into the buffer, and have not been
+    /// reported yet.
+    excess: u8,
+}
+
+impl Iterator for HostIter<'_> {
+    type Item = Result<Incoming, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.excess != 0 {
+            let excess = core::mem::replace(&mut self.excess, 0);
+            return Some(Err(Error::overflow(usize::from(excess))));
+        }
+
+        let pos = usize::from(self.pos);
+        let used = usize::from(self.buf.used);
+        let end = match self.buf.bytes[pos..used]
+            .iter()
+            .position(|b| *b == b'\r' || *b == 7)
+        {
+            Some(end) => pos + end,
+            None if pos == 0 && used == HOST_BUF_LEN => {
+                // No message fits the entire buffer, so the input is invalid. Discard all of it.
+                self.pos = HOST_BUF_LEN as u8;
+                return Some(Err(Error::overflow(HOST_BUF_LEN)));
+            }
+            None => return None,
+        };
+
+        let msg = &self.buf.bytes[pos..end + 1];
+        self.pos += msg.len() as u8;
+
+        if msg.len() > 1 && msg.last() == Some(&7) {
+            // A BELL in the middle of a message. The bytes before it can not be decoded.
+            return Some(Err(Error::bell_in_message(msg.len() - 1)));
+        }
+
+        Some(self.buf.demux.classify(msg))
+    }
+}
+
+impl Drop for HostIter<'_> {
+    fn drop(&mut self) {
+        let decoded_end = usize::from(self.pos);
+        self.buf.bytes.copy_within(decoded_end.., 0);
+        self.buf.used -= decoded_end as u8;
+    }
+}
+
+/// Options that control how `Command`s are decoded.
+///
+/// By default, only commands defined by the SLCAN protocol are accepted.
+#[derive(Copy, Clone, Debug, Default, Format)]
+pub struct DecodeOptions {
+    allow_unknown: bool,
+    allow_long_dlc: bool,
+    allow_lowercase_hex: bool,
+    allow_lf: bool,
+    vendor_commands: &'static [VendorCommand],
+    extra_bitrates: &'static [Bitrate],
+}
+
+impl DecodeOptions {
+    /// Creates the default set of `DecodeOptions`.
+    pub const fn new() -> Self {
+        Self {
+            allow_unknown: false,
+            allow_long_dlc: false,
+            allow_lowercase_hex: false,
+            allow_lf: false,
+            vendor_commands: &[],
+            extra_bitrates: &[],
+        }
+    }
+
+    /// Registers a table of non-standard bitrates accepted by the `Sn` command.
+    ///
+    /// Only `Bitrate::NonStandard` entries are considered. When a `Sn` command is received with a
+    /// code `n` that is not part of the standard table, the entry with matching `code` is returned
+    /// in `Command::SetupWithBitrate`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_slcan::Bitrate;
+    /// use mini_slcan::read::{Command, DecodeOptions};
+    ///
+    /// static EXTRA: &[Bitrate] = &[Bitrate::NonStandard { code: b'9', bps: 83_333 }];
+    ///
+    /// let options = DecodeOptions::new().extra_bitrates(EXTRA);
+    /// assert_eq!(
+    ///     Command::decode_with(b"S9\r", options).unwrap(),
+    ///     Command::SetupWithBitrate { bitrate: EXTRA[0] },
+    /// );
+    /// ```
+    pub const fn extra_bitrates(mut self, bitrates: &'static [Bitrate]) -> Self {
+        self.extra_bitrates = bitrates;
+        self
+    }
+
+    fn extra_bitrate(&self, code: u8) -> Option<Bitrate> {
+        self.extra_bitrates
+            .iter()
+            .copied()
+            .find(|bitrate| match bitrate {
+                Bitrate::NonStandard { code: c, .. } => *c == code,
+                _ => false,
+            })
+    }
+
+    /// Sets whether data length codes between 9 and 15 are accepted in transmit commands.
+    ///
+    /// Classic CAN frames with such a DLC carry 8 data bytes. The raw DLC is preserved in the
+    /// decoded `CanFrame` (see `CanFrame::dlc`) or in the `len` field of RTR commands.
+    ///
+    /// When disabled (the default), they are rejected with a decoding error.
+    pub const fn allow_long_dlc(mut self, allow: bool) -> Self {
+        self.allow_long_dlc = allow;
+        self
+    }
+
+    /// Sets whether the lowercase hex digits `a` to `f` are accepted in identifiers, data length
+    /// codes, data bytes and other numeric fields.
+    ///
+    /// The SLCAN protocol only uses uppercase hex digits, but some hosts (and people typing into
+    /// terminals) send lowercase ones. When disabled (the default), they are rejected with a
+    /// decoding error.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_slcan::read::{Command, DecodeOptions};
+    ///
+    /// assert!(Command::decode(b"t7ff1aa\r").is_err());
+    ///
+    /// let options = DecodeOptions::new().allow_lowercase_hex(true);
+    /// let cmd = Command::decode_with(b"t7ff1aa\r", options).unwrap();
+    /// assert_eq!(cmd, Command::decode(b"t7FF1AA\r").unwrap());
+    /// ```
+    pub const fn allow_lowercase_hex(mut self, allow: bool) -> Self {
+        self.allow_lowercase_hex = allow;
+        self
+    }
+
+    /// Registers a table of vendor-specific commands.
+    ///
+    /// Commands whose opcode is listed in `commands` are decoded as `Command::Vendor`. This takes
+    /// precedence over the standard SLCAN commands, so vendor commands may also be used to replace
+    /// the handling of a standard opcode.
+    pub const fn vendor_commands(mut self, commands: &'static [VendorCommand]) -> Self {
+        self.vendor_commands = commands;
+        self
+    }
+
+    fn vendor_command(&self, opcode: u8) -> Option<&'static VendorCommand> {
+        self.vendor_commands.iter().find(|cmd| cmd.opcode == opcode)
+    }
+
+    /// Sets whether commands with unrecognized opcodes are decoded as `Command::Unknown`.
+    ///
+    /// When disabled (the default), they are rejected with a decoding error.
+    pub const fn allow_unknown(mut self, allow: bool) -> Self {
+        self.allow_unknown = allow;
+        self
+    }
+
+    /// Sets whether commands may be terminated with `LF` or `CR LF` instead of `CR`.
+    ///
+    /// Terminal programs and some host libraries use these line endings. When enabled, `LF` is
+    /// treated like `CR`, and an `LF` directly following the `CR` is ignored. Buffers like
+    /// `CommandBuf` skip lines that only consist of an `LF`, so that the second half of a `CR LF`
+    /// pair does not produce an error.
+    ///
+    /// When disabled (the default), only `CR` terminates commands.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_slcan::read::{Command, CommandBuf, DecodeOptions};
+    ///
+    /// let options = DecodeOptions::new().allow_lf(true);
+    /// assert_eq!(Command::decode_with(b"O\r\n", options).unwrap(), Command::Open);
+    ///
+    /// let mut buf = CommandBuf::with_options(options);
+    /// let mut cmds = buf.feed(b"O\r\nC\n");
+    /// assert_eq!(cmds.next().unwrap().unwrap(), Command::Open);
+    /// assert_eq!(cmds.next().unwrap().unwrap(), Command::Close);
+    /// assert!(cmds.next().is_none());
+    /// ```
+    pub const fn allow_lf(mut self, allow: bool) -> Self {
+        self.allow_lf = allow;
+        self
+    }
+
+    /// Returns whether `byte` terminates a command.
+    fn is_terminator(&self, byte: u8) -> bool {
+        byte == b'\r' || (self.allow_lf && byte == b'\n')
+    }
+
+    /// Returns whether the terminated `line` is the remainder of a `CR LF` pair, which is skipped.
+    fn is_empty_line(&self, line: &[u8]) -> bool {
+        self.allow_lf && line == b"\n"
+    }
+}
+
+/// Describes a vendor-specific command that should be accepted by the decoder.
+///
+/// # Example
+///
+/// ```
+/// use mini_slcan::read::{Command, DecodeOptions, VendorCommand};
+///
+/// // `!` takes exactly 2 bytes of arguments.
+/// static VENDOR: &[VendorCommand] = &[VendorCommand::new(b'!', |payload| payload.len() == 2)];
+///
+/// let options = DecodeOptions::new().vendor_commands(VENDOR);
+/// match Command::decode_with(b"!42\r", options).unwrap() {
+///     Command::Vendor { opcode: b'!', payload } => assert_eq!(payload.as_bytes(), b"42"),
+///     _ => unreachable!(),
+/// }
+/// assert!(Command::decode_with(b"!4\r", options).is_err());
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct VendorCommand {
+    opcode: u8,
+    validate: fn(&[u8]) -> bool,
+}
+
+impl VendorCommand {
+    /// Creates a vendor command with opcode `opcode`.
+    ///
+    /// `validate` is invoked with the payload of the command (the bytes between the opcode and the
+    /// terminating `CR`), and has to return whether the payload is well-formed. If it returns
+    /// `false`, decoding fails with a decoding error.
+    pub const fn new(opcode: u8, validate: fn(&[u8]) -> bool) -> Self {
+        Self { opcode, validate }
+    }
+
+    /// Creates a vendor command with opcode `opcode` that accepts any payload.
+    pub const fn any_payload(opcode: u8) -> Self {
+        Self::new(opcode, |_| true)
+    }
+
+    pub const fn opcode(&self) -> u8 {
+        self.opcode
+    }
+}
+
+impl Format for VendorCommand {
+    fn format(&self, fmt: &mut defmt::Formatter) {
+        self.opcode.format(fmt)
+    }
+}
+
+/// The raw, undecoded payload of a `Command::Unknown` or `Command::Vendor`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct RawPayload {
+    bytes: [u8; Self::MAX_LENGTH],
+    len: u8,
+}
+
+impl RawPayload {
+    /// Maximum payload length (the opcode and the terminating `CR` are not part of the payload).
+    ///
+    /// This is sized for classic CAN commands, so longer payloads are rejected even when decoding
+    /// with a CAN FD sized buffer.
+    pub const MAX_LENGTH: usize = Command::MAX_ENCODED_LEN - 2;
+
+    /// Creates a payload from `raw`, or returns `None` if it is longer than `MAX_LENGTH`.
+    pub(crate) fn from_slice(raw: &[u8]) -> Option<Self> {
+        if raw.len() > Self::MAX_LENGTH {
+            return None;
+        }
+
+        let mut bytes = [0; Self::MAX_LENGTH];
+        bytes[..raw.len()].copy_from_slice(raw);
+        Some(Self {
+            bytes,
+            len: raw.len() as u8,
+        })
+    }
+
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..usize::from(self.len)]
+    }
+}
+
+impl Format for RawPayload {
+    fn format(&self, fmt: &mut defmt::Formatter) {
+        self.as_bytes().format(fmt)
+    }
+}
+
+/// A `Command` addressed to one channel of a multi-channel SLCAN device.
+///
+/// Devices with more than one CAN bus prefix every command with the number of the channel it
+/// refers to (`0t1230`, `1O`, etc.).
+#[derive(Clone, Debug, Eq, PartialEq, Format)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub struct ChanneledCommand {
+    /// The channel number, in range 0..=9.
+    #[cfg_attr(
+        feature = "serde",
+        serde(deserialize_with = "crate::serde_impls::channel")
+    )]
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = crate::arbitrary_impls::channel))]
+    pub channel: u8,
+    pub cmd: Command,
+}
+
+impl ChanneledCommand {
+    pub const MAX_ENCODED_LEN: usize = 1 + Command::MAX_ENCODED_LEN;
+    pub const MAX_FD_ENCODED_LEN: usize = 1 + Command::MAX_FD_ENCODED_LEN;
+
+    /// Decodes a channel-prefixed command from an input string. The input must contain the
+    /// terminating `CR` character (ASCII 13).
+    pub fn decode(input: &[u8]) -> Result<Self, Error> {
+        Self::decode_with(input, DecodeOptions::new())
+    }
+
+    /// Decodes a channel-prefixed command from an input string, using the given `DecodeOptions`.
+    pub fn decode_with(input: &[u8], options: DecodeOptions) -> Result<Self, Error> {
+        let mut reader = Reader::with_options(input, options);
+        let channel = reader.read_field(Field::Channel, |reader| {
+            reader.read_param(|digit| match digit {
+                b'0'..=b'9' => Some(digit - b'0'),
+                _ => None,
+            })
+        })?;
+        let cmd = Command::decode_with(reader.input, options).map_err(|e| e.after(1))?;
+
+        Ok(Self { channel, cmd })
+    }
+}
+
+/// Size of the default `CommandBuf` buffer, large enough to hold any classic CAN
+/// `ChanneledCommand`.
+const CMD_BUF_LEN: usize = ChanneledCommand::MAX_ENCODED_LEN;
+/// Size of the CAN FD `CommandBuf` buffer, large enough to hold any `ChanneledCommand`.
+const FD_CMD_BUF_LEN: usize = ChanneledCommand::MAX_FD_ENCODED_LEN;
+
+/// A byte buffer that yields decoded `Command`s.
+///
+/// This is meant to be used by apps that receive bytewise data and want to decode `Command`s from
+/// that.
+///
+/// By default, the buffer is stored inline and sized for classic CAN commands. `CommandBuf::new_fd`
+/// creates a `CommandBuf` that can also hold CAN FD commands, and `CommandBuf::with_buffer` one
+/// that uses caller-provided storage, for example a `&'static mut [u8]` placed in DMA-capable
+/// memory.
+#[derive(Debug)]
+pub struct CommandBuf<B = [u8; CMD_BUF_LEN]> {
+    bytes: B,
+    /// Number of bytes in `bytes` that hold received data.
+    used: usize,
+    options: DecodeOptions,
+    /// Whether invalid lines are discarded instead of yielding an error.
+    resync: bool,
+    /// Number of bytes discarded in resynchronization mode.
+    discarded: usize,
+}
+
+impl CommandBuf {
+    /// The size of the default inline buffer, which can hold any classic CAN `ChanneledCommand`.
+    pub const LEN: usize = CMD_BUF_LEN;
+
+    /// The size of the inline buffer of `new_fd`, which can hold any `ChanneledCommand`.
+    pub const FD_LEN: usize = FD_CMD_BUF_LEN;
+
+    /// Creates a new, empty `CommandBuf`.
+    pub const fn new() -> Self {
+        Self::with_options(DecodeOptions::new())
+    }
+
+    /// Creates a new, empty `CommandBuf` that decodes commands using the given `DecodeOptions`.
+    pub const fn with_options(options: DecodeOptions) -> Self {
+        Self {
+            bytes: [0; CMD_BUF_LEN],
+            used: 0,
+            options,
+            resync: false,
+            discarded: 0,
+        }
+    }
+}
+
+impl CommandBuf<[u8; FD_CMD_BUF_LEN]> {
+    /// Creates a new, empty `CommandBuf` that can hold CAN FD commands.
+    pub const fn new_fd() -> Self {
+        Self::with_options_fd(DecodeOptions::new())
+    }
+
+    /// Creates a new, empty `CommandBuf` that can hold CAN FD commands and decodes them using the
+    /// given `DecodeOptions`.
+    pub const fn with_options_fd(options: DecodeOptions) -> Self {
+        Self {
+            bytes: [0; FD_CMD_BUF_LEN],
+            used: 0,
+            options,
+            resync: false,
+            discarded: 0,
+        }
+    }
+}
+
+impl<B: AsRef<[u8]> + AsMut<[u8]>> CommandBuf<B> {
+    /// Creates a new, empty `CommandBuf` that stores received data in `bytes`.
+    ///
+    /// To be able to decode every classic CAN command, `bytes` should be at least `CommandBuf::LEN`
+    /// bytes long, or `CommandBuf::FD_LEN` bytes for CAN FD commands. Longer commands are rejected
+    /// with an `ErrorKind::Overflow` error.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_slcan::read::{Command, CommandBuf, DecodeOptions};
+    ///
+    /// let mut storage = [0; CommandBuf::LEN];
+    /// let mut buf = CommandBuf::with_buffer(&mut storage[..], DecodeOptions::new());
+    /// assert_eq!(buf.feed(b"O\r").next().unwrap().unwrap(), Command::Open);
+    /// ```
+    pub fn with_buffer(bytes: B, options: DecodeOptions) -> Self {
+        Self {
+            bytes,
+            used: 0,
+            options,
+            resync: false,
+            discarded: 0,
+        }
+    }
+
+    /// Enables or disables resynchronization mode.
+    ///
+    /// By default, every line that fails to decode yields an error. This is useful for replying
+    /// to every command, but line noise or a partial line received before a valid command also
+    /// produce errors then. In resynchronization mode, invalid lines (including overlong ones) are
+    /// instead discarded silently, up to and including their `CR`. The number of discarded bytes
+    /// can be retrieved with `take_discarded`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_slcan::read::{Command, CommandBuf};
+    ///
+    /// let mut buf = CommandBuf::new();
+    /// buf.set_resync(true);
+    ///
+    /// let mut cmds = buf.feed(b"\x00\xffO\rO\r");
+    /// assert_eq!(cmds.next().unwrap().unwrap(), Command::Open);
+    /// assert!(cmds.next().is_none());
+    /// drop(cmds);
+    ///
+    /// assert_eq!(buf.take_discarded(), 4);
+    /// assert_eq!(buf.take_discarded(), 0);
+    /// ```
+    pub fn set_resync(&mut self, resync: bool) {
+        self.resync = resync;
+    }
+
+    /// Returns the number of bytes discarded in resynchronization mode, and resets the count.
+    ///
+    /// The count saturates at `usize::MAX`.
+    pub fn take_discarded(&mut self) -> usize {
+        core::mem::replace(&mut self.discarded, 0)
+    }
+
+    /// Returns `result`, the result of decoding a `len`-byte line, unless resynchronization mode
+    /// discards it.
+    fn filter<T>(&mut self, len: usize, result: Result<T, Error>) -> Option<Result<T, Error>> {
+        match result {
+            Err(_) if self.resync => {
+                self.discarded = self.discarded.saturating_add(len);
+                None
+            }
+            result => Some(result),
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        self.bytes.as_ref().len()
+    }
+
+    /// Returns the currently unused part of the buffer.
+    ///
+    /// The caller can copy new input bytes into the returned slice, and call `advance_by` to mark
+    /// them as part of the `CommandBuf`.
+    pub fn tail_mut(&mut self) -> &mut [u8] {
+        let used = self.used;
+        &mut self.bytes.as_mut()[used..]
+    }
+
+    fn is_full(&self) -> bool {
+        self.used == self.capacity()
+    }
+
+    fn find_cr(&self, start: usize) -> Option<usize> {
+        self.bytes.as_ref()[start..self.used]
+            .iter()
+            .position(|b| self.options.is_terminator(*b))
+            .map(|pos| pos + start)
+    }
+
+    /// Marks `len` more bytes from the buffer's tail as consumed, and returns an iterator over all
+    /// `Command`s in the buffer.
+    ///
+    /// The decoded bytes are removed from the `CommandBuf` by `CommandIter::commit`, or when the
+    /// returned iterator is dropped.
+    ///
+    /// If `amount` exceeds the length of the slice returned by `tail_mut`, only the bytes that fit
+    /// into the buffer are marked as consumed, and the iterator first yields an
+    /// `ErrorKind::Overflow` error reporting the excess bytes as discarded.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_slcan::ErrorKind;
+    /// use mini_slcan::read::{Command, CommandBuf};
+    ///
+    /// let mut buf = CommandBuf::new();
+    /// let len = buf.tail_mut().len();
+    /// buf.tail_mut()[..2].copy_from_slice(b"O\r");
+    ///
+    /// let mut cmds = buf.advance_by(len as u8 + 3);
+    /// let err = cmds.next().unwrap().unwrap_err();
+    /// assert_eq!(err.kind(), ErrorKind::Overflow);
+    /// assert_eq!(err.bytes_discarded(), Some(3));
+    /// assert_eq!(cmds.next().unwrap().unwrap(), Command::Open);
+    /// ```
+    pub fn advance_by(&mut self, amount: u8) -> CommandIter<'_, Command, B> {
+        self.advance(amount, Command::decode_with)
+    }
+
+    /// Like `advance_by`, but decodes channel-prefixed `ChanneledCommand`s.
+    ///
+    /// This is meant to be used by devices with multiple CAN channels.
+    pub fn advance_by_channeled(&mut self, amount: u8) -> CommandIter<'_, ChanneledCommand, B> {
+        self.advance(amount, ChanneledCommand::decode_with)
+    }
+
+    /// Appends `input` to the buffer, and returns an iterator over all `Command`s in the buffer.
+    ///
+    /// `input` may be larger than the free space in the buffer. It is copied into the buffer piece
+    /// by piece, as the iterator makes space by decoding commands. Any input that has not been
+    /// processed when the iterator is dropped is discarded, so the iterator should be run to
+    /// completion.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_slcan::read::{Command, CommandBuf};
+    ///
+    /// let mut buf = CommandBuf::new();
+    /// let mut cmds = buf.feed(b"O\rC\rO");
+    /// assert_eq!(cmds.next().unwrap().unwrap(), Command::Open);
+    /// assert_eq!(cmds.next().unwrap().unwrap(), Command::Close);
+    /// assert!(cmds.next().is_none());
+    /// drop(cmds);
+    ///
+    /// // The incomplete command is completed by the next input.
+    /// assert_eq!(buf.feed(b"\r").next().unwrap().unwrap(), Command::Open);
+    /// ```
+    pub fn feed<'a>(
+        &'a mut self,
+        input: &'a [u8],
+    ) -> impl Iterator<Item = Result<Command, Error>> + 'a {
+        FeedIter {
+            buf: self,
+            input,
+            decode: Command::decode_with,
+        }
+    }
+
+    /// Like `feed`, but decodes channel-prefixed `ChanneledCommand`s.
+    pub fn feed_channeled<'a>(
+        &'a mut self,
+        input: &'a [u8],
+    ) -> impl Iterator<Item = Result<ChanneledCommand, Error>> + 'a {
+        FeedIter {
+            buf: self,
+            input,
+            decode: ChanneledCommand::decode_with,
+        }
+    }
+
+    /// Returns an iterator over the complete commands in the buffer, without removing them.
+    ///
+    /// This allows checking whether a high-priority command (like `C`) is pending before
+    /// processing the buffered commands in order. Invalid commands yield errors (unless
+    /// resynchronization mode is enabled), while an incomplete command at the end of the buffer is
+    /// not returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_slcan::read::{Command, CommandBuf};
+    ///
+    /// let mut buf = CommandBuf::new();
+    /// buf.tail_mut()[..5].copy_from_slice(b"O\rC\rO");
+    /// // Mark the input as received without decoding it.
+    /// drop(buf.advance_by(5));
+    ///
+    /// assert!(buf.peek().any(|cmd| cmd.ok() == Some(Command::Close)));
+    /// assert_eq!(buf.peek().count(), 2);
+    ///
+    /// // The commands are still in the buffer.
+    /// assert_eq!(buf.advance_by(0).count(), 2);
+    /// ```
+    pub fn peek(&self) -> impl Iterator<Item = Result<Command, Error>> + '_ {
+        self.peek_with(Command::decode_with)
+    }
+
+    /// Like `peek`, but decodes channel-prefixed `ChanneledCommand`s.
+    pub fn peek_channeled(&self) -> impl Iterator<Item = Result<ChanneledCommand, Error>> + '_ {
+        self.peek_with(ChanneledCommand::decode_with)
+    }
+
+    fn peek_with<'a, T: 'a>(
+        &'a self,
+        decode: fn(&[u8], DecodeOptions) -> Result<T, Error>,
+    ) -> impl Iterator<Item = Result<T, Error>> + 'a {
+        let options = self.options;
+        let resync = self.resync;
+        let mut rest = &self.bytes.as_ref()[..self.used];
+        iter::from_fn(move || loop {
+            let end = rest.iter().position(|b| options.is_terminator(*b))?;
+            let (line, tail) = rest.split_at(end + 1);
+            rest = tail;
+
+            if options.is_empty_line(line) {
+                continue;
+            }
+            match decode(line, options) {
+                Err(_) if resync => continue,
+                result => return Some(result),
+            }
+        })
+    }
+
+    /// Decodes and removes the first command in the buffer, if it contains a complete one.
+    fn next_command<T>(
+        &mut self,
+        decode: fn(&[u8], DecodeOptions) -> Result<T, Error>,
+    ) -> Option<Result<T, Error>> {
+        loop {
+            let (len, result) = match self.find_cr(0) {
+                Some(end) => {
+                    let line = &self.bytes.as_ref()[..=end];
+                    let result = if self.options.is_empty_line(line) {
+                        None
+                    } else {
+                        let result = decode(line, self.options);
+                        self.filter(end + 1, result)
+                    };
+                    (end + 1, result)
+                }
+                // See `CommandIter::next`.
+                None if self.is_full() => {
+                    let len = self.capacity();
+                    (len, self.filter(len, Err(Error::overflow(len))))
+                }
+                None => return None,
+            };
+
+            self.bytes.as_mut().copy_within(len.., 0);
+            self.used -= len;
+            if result.is_some() {
+                return result;
+            }
+        }
+    }
+
+    /// Appends a single byte to the buffer, and returns the decoded command if `byte` completes
+    /// one.
+    ///
+    /// This is meant for interrupt-driven receivers that get one byte at a time. It is an
+    /// alternative to `tail_mut` and `advance_by`, and should not be mixed with them.
+    ///
+    /// If the buffer fills up without receiving a `CR`, an `ErrorKind::Overflow` error is returned
+    /// and the buffered bytes are discarded.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_slcan::read::{Command, CommandBuf};
+    ///
+    /// let mut buf = CommandBuf::new();
+    /// assert!(buf.push_byte(b'O').is_none());
+    /// assert_eq!(buf.push_byte(b'\r').unwrap().unwrap(), Command::Open);
+    /// ```
+    pub fn push_byte(&mut self, byte: u8) -> Option<Result<Command, Error>> {
+        self.push(byte, Command::decode_with)
+    }
+
+    /// Like `push_byte`, but decodes channel-prefixed `ChanneledCommand`s.
+    pub fn push_byte_channeled(&mut self, byte: u8) -> Option<Result<ChanneledCommand, Error>> {
+        self.push(byte, ChanneledCommand::decode_with)
+    }
+
+    fn push<T>(
+        &mut self,
+        byte: u8,
+        decode: fn(&[u8], DecodeOptions) -> Result<T, Error>,
+    ) -> Option<Result<T, Error>> {
+        if self.is_full() {
+            // Only possible when mixed with `advance_by`. Treat it like an overlong command, and
+            // let `byte` start the next one unless it terminates the overlong command.
+            let len = self.capacity();
+            if self.options.is_terminator(byte) {
+                self.used = 0;
+                return self.filter(len + 1, Err(Error::overflow(len + 1)));
+            }
+            self.bytes.as_mut()[0] = byte;
+            self.used = 1;
+            return self.filter(len, Err(Error::overflow(len)));
+        }
+
+        let used = self.used;
+        self.bytes.as_mut()[used] = byte;
+
+        if self.options.is_terminator(byte) {
+            self.used = 0;
+            let line = &self.bytes.as_ref()[..=used];
+            if self.options.is_empty_line(line) {
+                None
+            } else {
+                let result = decode(line, self.options);
+                self.filter(used + 1, result)
+            }
+        } else {
+            self.used += 1;
+            if self.is_full() {
+                // The buffer can hold the longest command including its `CR`, so this command is
+                // invalid.
+                self.used = 0;
+                let len = self.capacity();
+                self.filter(len, Err(Error::overflow(len)))
+            } else {
+                None
+            }
+        }
+    }
+
+    fn advance<T>(
+        &mut self,
+        amount: u8,
+        decode: fn(&[u8], DecodeOptions) -> Result<T, Error>,
+    ) -> CommandIter<'_, T, B> {
+        let free = self.capacity() - self.used;
+        let amount = usize::from(amount);
+        self.used += amount.min(free);
+
+        CommandIter {
+            buf: self,
+            pos: 0,
+            excess: amount.saturating_sub(free),
+            decode,
+        }
+    }
+}
+
+impl Default for CommandBuf {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An iterator over the commands in a `CommandBuf`, returned by `CommandBuf::advance_by`.
+///
+/// Yielded commands stay in the buffer until `commit` is called or the iterator is dropped.
+#[derive(Debug)]
+pub struct CommandIter<'a, T, B: AsMut<[u8]>> {
+    buf: &'a mut CommandBuf<B>,
+    /// Number of bytes at the start of the buffer that were decoded, but not removed yet.
+    pos: usize,
+    /// Number of bytes passed to `advance_by` that did not fit into the buffer, and have not been
+    /// reported yet.
+    excess: usize,
+    decode: fn(&[u8], DecodeOptions) -> Result<T, Error>,
+}
+
+impl<T, B: AsMut<[u8]>> CommandIter<'_, T, B> {
+    /// Removes all commands yielded so far from the `CommandBuf`, and returns their total length
+    /// in bytes.
+    ///
+    /// Iteration can continue after calling this method. Dropping the iterator commits all
+    /// yielded commands as well, so calling `commit` is only necessary to remove commands from
+    /// the buffer before that.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_slcan::read::{Command, CommandBuf};
+    ///
+    /// let mut buf = CommandBuf::new();
+    /// buf.tail_mut()[..4].copy_from_slice(b"O\rC\r");
+    ///
+    /// let mut cmds = buf.advance_by(4);
+    /// assert_eq!(cmds.next().unwrap().unwrap(), Command::Open);
+    /// assert_eq!(cmds.commit(), 2);
+    /// assert_eq!(cmds.next().unwrap().unwrap(), Command::Close);
+    /// assert_eq!(cmds.commit(), 2);
+    /// assert!(cmds.next().is_none());
+    /// assert_eq!(cmds.commit(), 0);
+    /// ```
+    pub fn commit(&mut self) -> usize {
+        let decoded_end = self.pos;
+        self.buf.bytes.as_mut().copy_within(decoded_end.., 0);
+        self.buf.used -= decoded_end;
+        self.pos = 0;
+        decoded_end
+    }
+}
+
+impl<T, B: AsRef<[u8]> + AsMut<[u8]>> Iterator for CommandIter<'_, T, B> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.excess != 0 {
+            let excess = core::mem::replace(&mut self.excess, 0);
+            if let Some(result) = self.buf.filter(excess, Err(Error::overflow(excess))) {
+                return Some(result);
+            }
+        }
+
+        loop {
+            let pos = self.pos;
+            let end = match self.buf.find_cr(pos) {
+                Some(pos) => pos,
+                None if pos == 0 && self.buf.is_full() => {
+                    // There is no `\r` in the entire buffer to terminate the received command.
+                    // That means that the input is invalid, since the buffer can hold the longest
+                    // command, including the trailing `\r`.
+                    // Yield an error, and mark the whole buffer as consumed to make space for new
+                    // data.
+                    self.pos = self.buf.capacity();
+                    match self.buf.filter(self.pos, Err(Error::overflow(self.pos))) {
+                        Some(result) => return Some(result),
+                        None => continue,
+                    }
+                }
+                None => return None,
+            };
+
+            let cmd = &self.buf.bytes.as_ref()[pos..end + 1];
+            self.pos += cmd.len();
+
+            if !self.buf.options.is_empty_line(cmd) {
+                let result = (self.decode)(cmd, self.buf.options);
+                if let Some(result) = self.buf.filter(end + 1 - pos, result) {
+                    return Some(result);
+                }
+            }
+        }
+    }
+}
+
+struct FeedIter<'a, T, B> {
+    buf: &'a mut CommandBuf<B>,
+    /// Input that has not been copied into `buf` yet.
+    input: &'a [u8],
+    decode: fn(&[u8], DecodeOptions) -> Result<T, Error>,
+}
+
+impl<T, B: AsRef<[u8]> + AsMut<[u8]>> Iterator for FeedIter<'_, T, B> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(result) = self.buf.next_command(self.decode) {
+                return Some(result);
+            }
+
+            if self.input.is_empty() {
+                return None;
+            }
+
+            // No complete command in the buffer, but there is space to copy more input.
+            let tail = self.buf.tail_mut();
+            let len = tail.len().min(self.input.len());
+            tail[..len].copy_from_slice(&self.input[..len]);
+            self.buf.used += len;
+            self.input = &self.input[len..];
+        }
+    }
+}
+
+impl<T, B: AsMut<[u8]>> Drop for CommandIter<'_, T, B> {
+    fn drop(&mut self) {
+        self.commit();
+    }
+}
+
+/// A ring buffer that yields decoded `Command`s.
+///
+/// This works like `CommandBuf`, but never moves the buffered data. Decoded commands are removed by
+/// advancing the start of the buffered data instead, which avoids copying on every decoded command.
+/// In exchange, `tail_mut` may return less space than is available, since the free space can wrap
+/// around the end of the buffer.
+///
+/// Like `CommandBuf`, the default buffer is sized for classic CAN commands, and `CommandRing::new_fd`
+/// creates one that can also hold CAN FD commands.
+///
+/// # Example
+///
+/// ```
+/// use mini_slcan::read::{Command, CommandRing};
+///
+/// let mut ring = CommandRing::new();
+/// let tail = ring.tail_mut();
+/// tail[..4].copy_from_slice(b"O\rC\r");
+/// let cmds = ring.advance_by(4).collect::<Result<Vec<_>, _>>().unwrap();
+/// assert_eq!(cmds, [Command::Open, Command::Close]);
+/// ```
+#[derive(Debug)]
+pub struct CommandRing<B = [u8; CMD_BUF_LEN]> {
+    bytes: B,
+    /// Index of the first buffered byte.
+    head: usize,
+    /// Number of buffered bytes, starting at `head` and possibly wrapping around.
+    len: usize,
+    options: DecodeOptions,
+}
+
+impl CommandRing {
+    /// Creates a new, empty `CommandRing`.
+    pub const fn new() -> Self {
+        Self::with_options(DecodeOptions::new())
+    }
+
+    /// Creates a new, empty `CommandRing` that decodes commands using the given `DecodeOptions`.
+    pub const fn with_options(options: DecodeOptions) -> Self {
+        Self {
+            bytes: [0; CMD_BUF_LEN],
+            head: 0,
+            len: 0,
+            options,
+        }
+    }
+}
+
+impl CommandRing<[u8; FD_CMD_BUF_LEN]> {
+    /// Creates a new, empty `CommandRing` that can hold CAN FD commands.
+    pub const fn new_fd() -> Self {
+        Self::with_options_fd(DecodeOptions::new())
+    }
+
+    /// Creates a new, empty `CommandRing` that can hold CAN FD commands and decodes them using the
+    /// given `DecodeOptions`.
+    pub const fn with_options_fd(options: DecodeOptions) -> Self {
+        Self {
+            bytes: [0; FD_CMD_BUF_LEN],
+            head: 0,
+            len: 0,
+            options,
+        }
+    }
+}
+
+impl<B: AsRef<[u8]> + AsMut<[u8]>> CommandRing<B> {
+    /// Creates a new, empty `CommandRing` that stores received data in `bytes`.
+    ///
+    /// See `CommandBuf::with_buffer`.
+    pub fn with_buffer(bytes: B, options: DecodeOptions) -> Self {
+        Self {
+            bytes,
+            head: 0,
+            len: 0,
+            options,
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        self.bytes.as_ref().len()
+    }
+
+    /// Returns the unused part of the buffer that directly follows the buffered data.
+    ///
+    /// The caller can copy new input bytes into the returned slice, and call `advance_by` to mark
+    /// them as part of the `CommandRing`.
+    pub fn tail_mut(&mut self) -> &mut [u8] {
+        self.tail_mut2().0
+    }
+
+    /// Returns all unused parts of the buffer, in the order in which they will be filled.
+    ///
+    /// The first slice is the one returned by `tail_mut`. If the free space wraps around the end
+    /// of the buffer, the second slice holds the part at the start of the buffer, otherwise it is
+    /// empty. This matches the memory layout used by circular DMA transfers.
+    ///
+    /// Input may be written to both slices at once, and marked as consumed with a single call to
+    /// `advance_by`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_slcan::read::{Command, CommandRing, DecodeOptions};
+    ///
+    /// let mut storage = [0; 4];
+    /// let mut ring = CommandRing::with_buffer(&mut storage[..], DecodeOptions::new());
+    /// ring.tail_mut()[..3].copy_from_slice(b"O\rC");
+    /// assert_eq!(ring.advance_by(3).next().unwrap().unwrap(), Command::Open);
+    ///
+    /// let (first, second) = ring.tail_mut2();
+    /// assert_eq!((first.len(), second.len()), (1, 2));
+    /// first[0] = b'\r';
+    /// second[..2].copy_from_slice(b"L\r");
+    ///
+    /// let cmds = ring.advance_by(3).collect::<Result<Vec<_>, _>>().unwrap();
+    /// assert_eq!(cmds, [Command::Close, Command::OpenListenOnly]);
+    /// ```
+    pub fn tail_mut2(&mut self) -> (&mut [u8], &mut [u8]) {
+        let cap = self.capacity();
+        let start = (self.head + self.len) % cap.max(1);
+        let head = self.head;
+        let bytes = self.bytes.as_mut();
+
+        if self.len == cap {
+            (&mut [], &mut [])
+        } else if start >= head {
+            let (wrapped, tail) = bytes.split_at_mut(start);
+            (tail, &mut wrapped[..head])
+        } else {
+            (&mut bytes[start..head], &mut [])
+        }
+    }
+
+    /// Marks `amount` more bytes from the buffer's tail as consumed, and returns an iterator over
+    /// all `Command`s in the buffer.
+    ///
+    /// Commands are removed from the buffer as the iterator yields them.
+    ///
+    /// `amount` may exceed the length of the slice returned by `tail_mut`, as long as the input was
+    /// written to the slices returned by `tail_mut2`. If it exceeds the free space in the buffer,
+    /// only the bytes that fit are marked as consumed, and the iterator first yields an
+    /// `ErrorKind::Overflow` error reporting the excess bytes as discarded.
+    pub fn advance_by(&mut self, amount: u8) -> impl Iterator<Item = Result<Command, Error>> + '_ {
+        self.advance(amount, Command::decode_with)
+    }
+
+    /// Like `advance_by`, but decodes channel-prefixed `ChanneledCommand`s.
+    pub fn advance_by_channeled(
+        &mut self,
+        amount: u8,
+    ) -> impl Iterator<Item = Result<ChanneledCommand, Error>> + '_ {
+        self.advance(amount, ChanneledCommand::decode_with)
+    }
+
+    fn advance<T>(
+        &mut self,
+        amount: u8,
+        decode: fn(&[u8], DecodeOptions) -> Result<T, Error>,
+    ) -> RingIter<'_, T, B> {
+        let free = self.capacity() - self.len;
+        let amount = usize::from(amount);
+        self.len += amount.min(free);
+
+        RingIter {
+            ring: self,
+            excess: amount.saturating_sub(free),
+            decode,
+        }
+    }
+
+    /// Removes `len` bytes from the start of the buffered data.
+    fn consume(&mut self, len: usize) {
+        self.len -= len;
+        self.head = if self.len == 0 {
+            // Maximize the contiguous free space.
+            0
+        } else {
+            (self.head + len) % self.capacity()
+        };
+    }
+
+    /// Decodes and removes the first command in the buffer, if it contains a complete one.
+    fn next_command<T>(
+        &mut self,
+        decode: fn(&[u8], DecodeOptions) -> Result<T, Error>,
+    ) -> Option<Result<T, Error>> {
+        // Skip lines that only consist of the `LF` of a `CR LF` pair.
+        while self.len != 0
+            && self
+                .options
+                .is_empty_line(&[self.bytes.as_ref()[self.head]])
+        {
+            self.consume(1);
+        }
+
+        let cap = self.capacity();
+        let bytes = self.bytes.as_ref();
+        let cr = (0..self.len).find(|i| self.options.is_terminator(bytes[(self.head + i) % cap]));
+
+        let result = match cr {
+            Some(pos) => {
+                let len = pos + 1;
+                let result = if self.head + len <= cap {
+                    decode(&bytes[self.head..self.head + len], self.options)
+                } else if len <= FD_CMD_BUF_LEN {
+                    // The command wraps around, so it has to be copied to be decoded.
+                    let (first, second) = (&bytes[self.head..], &bytes[..len - (cap - self.head)]);
+                    let mut cmd = [0; FD_CMD_BUF_LEN];
+                    cmd[..first.len()].copy_from_slice(first);
+                    cmd[first.len()..len].copy_from_slice(second);
+                    decode(&cmd[..len], self.options)
+                } else {
+                    // Too long to be a valid command, like a line that overflows `CommandBuf`.
+                    Err(Error::overflow(len))
+                };
+
+                self.consume(len);
+                result
+            }
+            // See `CommandIter::next`.
+            None if self.len == cap && cap != 0 => {
+                self.consume(cap);
+                Err(Error::overflow(cap))
+            }
+            None => return None,
+        };
+
+        Some(result)
+    }
+}
+
+impl Default for CommandRing {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct RingIter<'a, T, B> {
+    ring: &'a mut CommandRing<B>,
+    /// Number of bytes passed to `advance_by` that did not fit into the buffer, and have not been
+    /// reported yet.
+    excess: usize,
+    decode: fn(&[u8], DecodeOptions) -> Result<T, Error>,
+}
+
+impl<T, B: AsRef<[u8]> + AsMut<[u8]>> Iterator for RingIter<'_, T, B> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.excess != 0 {
+            let excess = core::mem::replace(&mut self.excess, 0);
+            return Some(Err(Error::overflow(excess)));
+        }
+
+        self.ring.next_command(self.decode)
+    }
+}
+
+/// Maximum length of the opcode, identifier and DLC preceding the data of a transmit command.
+const STREAM_HEAD_LEN: usize = 1 + 8 + 1;
+
+/// A decoder that decodes `Command`s one byte at a time, without buffering entire lines.
+///
+/// Unlike `CommandBuf`, this decodes the data bytes of transmit commands as they arrive, so it only
+/// needs space for the decoded data instead of its hex representation. This makes it suitable for
+/// very constrained targets that receive input one byte at a time.
+///
+/// Each line yields one result, which is returned when its terminating `CR` is pushed. The raw
+/// payload of `Command::Unknown` and `Command::Vendor` is limited to `RawPayload::MAX_LENGTH`
+/// bytes, longer commands are rejected.
+///
+/// # Example
+///
+/// ```
+/// use core::task::Poll;
+/// use mini_slcan::CanFrame;
+/// use mini_slcan::read::{Command, StreamDecoder};
+///
+/// let mut decoder = StreamDecoder::new();
+/// for byte in b"t1232AABB" {
+///     assert!(decoder.push(*byte).is_pending());
+/// }
+///
+/// match decoder.push(b'\r') {
+///     Poll::Ready(Ok(Command::TxStandard { frame, .. })) => {
+///         assert_eq!(frame, CanFrame::from([0xAA, 0xBB]));
+///     }
+///     _ => unreachable!(),
+/// }
+/// ```
+#[derive(Debug)]
+pub struct StreamDecoder {
+    options: DecodeOptions,
+    state: StreamState,
+    /// Opcode, followed by the identifier and DLC of transmit commands.
+    head: [u8; STREAM_HEAD_LEN],
+    head_len: u8,
+    /// Decoded frame data of transmit commands, or the raw bytes following the opcode of all other
+    /// commands.
+    data: [u8; CanFdFrame::MAX_LENGTH],
+    /// Number of hex digits (for frame data) or bytes (otherwise) stored in `data`.
+    data_len: u8,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum StreamState {
+    /// Waiting for the opcode of the next command.
+    Idle,
+    /// Receiving the identifier and DLC of a transmit command.
+    Head,
+    /// Receiving `digits` hex digits of frame data.
+    Data { digits: u8 },
+    /// Receiving the arguments of any other command.
+    Raw,
+    /// The current line is invalid and is skipped up to its `CR`, which yields the described error.
+    Discard {
+        kind: ErrorKind,
+        field: Option<Field>,
+        offset: u8,
+        byte: Option<u8>,
+    },
+}
+
+impl StreamDecoder {
+    /// Creates a new `StreamDecoder`.
+    pub const fn new() -> Self {
+        Self::with_options(DecodeOptions::new())
+    }
+
+    /// Creates a new `StreamDecoder` that decodes commands using the given `DecodeOptions`.
+    pub const fn with_options(options: DecodeOptions) -> Self {
+        Self {
+            options,
+            state: StreamState::Idle,
+            head: [0; STREAM_HEAD_LEN],
+            head_len: 0,
+            data: [0; CanFdFrame::MAX_LENGTH],
+            data_len: 0,
+        }
+    }
+
+    /// Feeds a received byte to the decoder.
+    ///
+    /// Returns `Poll::Ready` with the decoded command (or an error) when `byte` terminates a line,
+    /// and `Poll::Pending` otherwise.
+    pub fn push(&mut self, byte: u8) -> Poll<Result<Command, Error>> {
+        if self.options.is_terminator(byte) {
+            if self.state == StreamState::Idle && self.options.is_empty_line(&[byte]) {
+                return Poll::Pending;
+            }
+
+            let result = self.finish(byte);
+            self.state = StreamState::Idle;
+            self.head_len = 0;
+            self.data_len = 0;
+            return Poll::Ready(result);
+        }
+
+        self.state = match self.state {
+            StreamState::Idle => {
+                self.head[0] = byte;
+                self.head_len = 1;
+                if Self::head_len(byte).is_some() && self.options.vendor_command(byte).is_none() {
+                    StreamState::Head
+                } else {
+                    StreamState::Raw
+                }
+            }
+            StreamState::Head => {
+                self.head[usize::from(self.head_len)] = byte;
+                self.head_len += 1;
+                if Some(usize::from(self.head_len)) == Self::head_len(self.head[0]) {
+                    match self.data_digits(byte) {
+                        Ok(digits) => StreamState::Data { digits },
+                        Err(e) => StreamState::Discard {
+                            kind: e.kind(),
+                            field: Some(Field::Dlc),
+                            offset: self.head_len - 1,
+                            byte: e.byte(),
+                        },
+                    }
+                } else {
+                    StreamState::Head
+                }
+            }
+            StreamState::Data { digits } if self.data_len == digits => StreamState::Discard {
+                kind: ErrorKind::MissingTerminator,
+                field: Some(Field::Terminator),
+                offset: self.head_len + self.data_len,
+                byte: Some(byte),
+            },
+            StreamState::Data { digits } => match unhex(byte, self.options.allow_lowercase_hex) {
+                Ok(nibble) => {
+                    let byte = &mut self.data[usize::from(self.data_len / 2)];
+                    *byte = match self.data_len % 2 {
+                        0 => nibble << 4,
+                        _ => *byte | nibble,
+                    };
+                    self.data_len += 1;
+                    StreamState::Data { digits }
+                }
+                Err(e) => StreamState::Discard {
+                    kind: e.kind(),
+                    field: Some(Field::Data),
+                    offset: self.head_len + self.data_len,
+                    byte: e.byte(),
+                },
+            },
+            StreamState::Raw if usize::from(self.data_len) < self.data.len() => {
+                self.data[usize::from(self.data_len)] = byte;
+                self.data_len += 1;
+                StreamState::Raw
+            }
+            StreamState::Raw => StreamState::Discard {
+                kind: ErrorKind::InvalidLength,
+                field: None,
+                offset: 1 + self.data_len,
+                byte: None,
+            },
+            state @ StreamState::Discard { .. } => state,
+        };
+
+        Poll::Pending
+    }
+
+    /// Returns the length of the opcode, identifier and DLC of the transmit command `op`, or
+    /// `None` if `op` does not transmit a data frame.
+    fn head_len(op: u8) -> Option<usize> {
+        match op {
+            b't' | b'd' | b'b' => Some(1 + 3 + 1),
+            b'T' | b'D' | b'B' => Some(1 + 8 + 1),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of hex digits of frame data that follow the DLC `dlc`.
+    fn data_digits(&self, dlc: u8) -> Result<u8, Error> {
+        let dlc = unhex(dlc, self.options.allow_lowercase_hex)?;
+        let len = match self.head[0] {
+            b't' | b'T' if dlc > 8 && !self.options.allow_long_dlc => {
+                return Err(Error::new(ErrorKind::InvalidLength))
+            }
+            b't' | b'T' => dlc.min(8),
+            // Can never fail, since `dlc` is a single hex digit.
+            _ => CanFdFrame::dlc_to_len(dlc).unwrap() as u8,
+        };
+
+        Ok(len * 2)
+    }
+
+    /// Checks the identifier of a transmit command whose header was received completely.
+    ///
+    /// This is done before reporting errors in later fields, to report the same error as
+    /// `Command::decode`.
+    fn check_head(&self) -> Result<(), Error> {
+        let op = self.head[0];
+        if Self::head_len(op) == Some(usize::from(self.head_len)) {
+            let mut reader = Reader::with_options(&self.head, self.options);
+            reader.read_byte()?;
+            if op.is_ascii_lowercase() {
+                reader.read_identifier()?;
+            } else {
+                reader.read_ext_identifier()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Decodes the current line after its `terminator` was received.
+    fn finish(&self, terminator: u8) -> Result<Command, Error> {
+        let op = self.head[0];
+        let data_len = usize::from(self.data_len);
+        match self.state {
+            StreamState::Idle => Command::decode_with(&[terminator], self.options),
+            StreamState::Head => {
+                let mut line = [0; STREAM_HEAD_LEN + 1];
+                let len = usize::from(self.head_len);
+                line[..len].copy_from_slice(&self.head[..len]);
+                line[len] = terminator;
+                Command::decode_with(&line[..=len], self.options)
+            }
+            StreamState::Raw => {
+                let mut line = [0; 1 + CanFdFrame::MAX_LENGTH + 1];
+                line[0] = op;
+                line[1..=data_len].copy_from_slice(&self.data[..data_len]);
+                line[data_len + 1] = terminator;
+                Command::decode_with(&line[..data_len + 2], self.options)
+            }
+            StreamState::Data { digits } if self.data_len == digits => {
+                let head = &self.head[..usize::from(self.head_len)];
+                let mut reader = Reader::with_options(head, self.options);
+                reader.read_byte()?;
+                let data = &self.data[..data_len / 2];
+                match op {
+                    b't' | b'T' => {
+                        let mut frame = CanFrame::from_slice(data)?;
+                        let dlc = unhex(head[head.len() - 1], self.options.allow_lowercase_hex)?;
+                        if dlc > 8 {
+                            frame = frame.with_dlc(dlc).ok_or(Error::decode())?;
+                        }
+
+                        Ok(if op == b't' {
+                            Command::TxStandard {
+                                identifier: reader.read_identifier()?,
+                                frame,
+                            }
+                        } else {
+                            Command::TxExt {
+                                identifier: reader.read_ext_identifier()?,
+                                frame,
+                            }
+                        })
+                    }
+                    _ => {
+                        let flags = fd_flags(op);
+                        let frame = CanFdFrame::from_slice(data)?;
+
+                        Ok(if op.is_ascii_lowercase() {
+                            Command::TxFd {
+                                identifier: reader.read_identifier()?,
+                                frame,
+                                flags,
+                            }
+                        } else {
+                            Command::TxExtFd {
+                                identifier: reader.read_ext_identifier()?,
+                                frame,
+                                flags,
+                            }
+                        })
+                    }
+                }
+            }
+            StreamState::Data { .. } => {
+                // The `CR` was received before all of the data.
+                self.check_head()?;
+                Err(Error::new(ErrorKind::InvalidLength)
+                    .with_byte(terminator)
+                    .in_field(Field::Data)
+                    .at(usize::from(self.head_len + self.data_len)))
+            }
+            StreamState::Discard {
+                kind,
+                field,
+                offset,
+                byte,
+            } => {
+                self.check_head()?;
+                let mut err = Error::new(kind).at(offset.into());
+                if let Some(field) = field {
+                    err = err.in_field(field);
+                }
+                if let Some(byte) = byte {
+                    err = err.with_byte(byte);
+                }
+                Err(err)
+            }
+        }
+    }
+}
+
+impl Default for StreamDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Decodes `Command`s from bytes received through a `heapless::spsc::Queue`.
+///
+/// This couples a `CommandBuf` with the consumer half of a byte queue, so that the receive
+/// interrupt only has to enqueue received bytes, while decoding and framing take place in the
+/// main loop.
+///
+/// Like `CommandBuf`, `CommandConsumer::new` only has room for classic CAN commands, and reports
+/// longer ones (like CAN FD frames) as `ErrorKind::Overflow`. Devices that support CAN FD have to
+/// use `CommandConsumer::new_fd`.
+///
+/// # Example
+///
+/// ```
+/// use heapless::spsc::Queue;
+/// use mini_slcan::read::{Command, CommandConsumer, DecodeOptions};
+///
+/// let mut queue: Queue<u8, 64> = Queue::new();
+/// let (mut producer, consumer) = queue.split();
+/// let mut cmds = CommandConsumer::new(consumer, DecodeOptions::new());
+///
+/// // In the UART interrupt:
+/// for byte in b"O\rC" {
+///     producer.enqueue(*byte).ok();
+/// }
+///
+/// // In the main loop:
+/// assert_eq!(cmds.next_command().unwrap().unwrap(), Command::Open);
+/// assert!(cmds.next_command().is_none());
+/// ```
+#[cfg(feature = "heapless")]
+pub struct CommandConsumer<'a, const N: usize, B = [u8; CMD_BUF_LEN]> {
+    bytes: heapless::spsc::Consumer<'a, u8, N>,
+    buf: CommandBuf<B>,
+}
+
+#[cfg(feature = "heapless")]
+impl<'a, const N: usize> CommandConsumer<'a, N> {
+    /// Creates a `CommandConsumer` that decodes the bytes dequeued from `bytes` using `options`.
+    pub fn new(bytes: heapless::spsc::Consumer<'a, u8, N>, options: DecodeOptions) -> Self {
+        Self {
+            bytes,
+            buf: CommandBuf::with_options(options),
+        }
+    }
+}
+
+#[cfg(feature = "heapless")]
+impl<'a, const N: usize> CommandConsumer<'a, N, [u8; FD_CMD_BUF_LEN]> {
+    /// Creates a `CommandConsumer` that can decode CAN FD commands from the bytes dequeued from
+    /// `bytes`, using `options`.
+    pub fn new_fd(bytes: heapless::spsc::Consumer<'a, u8, N>, options: DecodeOptions) -> Self {
+        Self {
+            bytes,
+            buf: CommandBuf::with_options_fd(options),
+        }
+    }
+}
+
+#[cfg(feature = "heapless")]
+impl<'a, const N: usize, B: AsRef<[u8]> + AsMut<[u8]>> CommandConsumer<'a, N, B> {
+    /// Returns a mutable reference to the internal `CommandBuf`, for example to enable
+    /// resynchronization mode.
+    pub fn buf_mut(&mut self) -> &mut CommandBuf<B> {
+        &mut self.buf
+    }
+
+    /// Dequeues received bytes until they complete a command, and returns the decoded command.
+    ///
+    /// Returns `None` when the queue is empty before a command is complete. The bytes of the
+    /// incomplete command are kept, and the command is returned by a later call once the rest of
+    /// it has been received.
+    pub fn next_command(&mut self) -> Option<Result<Command, Error>> {
+        while let Some(byte) = self.bytes.dequeue() {
+            if let Some(result) = self.buf.push_byte(byte) {
+                return Some(result);
+            }
+        }
+
+        None
+    }
+
+    /// Releases the consumer half of the queue.
+    pub fn free(self) -> heapless::spsc::Consumer<'a, u8, N> {
+        self.bytes
+    }
+}
+
+#[cfg(feature = "heapless")]
+impl<const N: usize, B: core::fmt::Debug> core::fmt::Debug for CommandConsumer<'_, N, B> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("CommandConsumer")
+            .field("buf", &self.buf)
+            .finish()
+    }
+}
+
+/// Creates a `CommandReader` that decodes commands from `reader` with the default
+/// `DecodeOptions`.
+///
+/// # Example
+///
+/// ```
+/// use mini_slcan::read::{self, Command};
+///
+/// // `&[u8]` implements `embedded_io::Read`. Firmware would pass its UART or USB driver instead.
+/// let mut cmds = read::from_reader(&b"O\rC\r"[..]);
+/// assert_eq!(cmds.next().unwrap().unwrap(), Command::Open);
+/// assert_eq!(cmds.next().unwrap().unwrap(), Command::Close);
+/// assert!(cmds.next().is_none());
+/// ```
+#[cfg(feature = "embedded-io")]
+pub fn from_reader<R: embedded_io::Read>(reader: R) -> CommandReader<R> {
+    from_reader_with(reader, DecodeOptions::new())
+}
+
+/// Creates a `CommandReader` that decodes commands from `reader` using `options`.
+#[cfg(feature = "embedded-io")]
+pub fn from_reader_with<R: embedded_io::Read>(
+    reader: R,
+    options: DecodeOptions,
+) -> CommandReader<R> {
+    CommandReader {
+        reader,
+        decoder: ChunkDecoder::new(options),
+    }
+}
+
+/// Creates a `CommandReader` that can decode CAN FD commands from `reader`, with the default
+/// `DecodeOptions`.
+#[cfg(feature = "embedded-io")]
+pub fn from_reader_fd<R: embedded_io::Read>(reader: R) -> CommandReader<R, [u8; FD_CMD_BUF_LEN]> {
+    from_reader_with_fd(reader, DecodeOptions::new())
+}
+
+/// Creates a `CommandReader` that can decode CAN FD commands from `reader`, using `options`.
+#[cfg(feature = "embedded-io")]
+pub fn from_reader_with_fd<R: embedded_io::Read>(
+    reader: R,
+    options: DecodeOptions,
+) -> CommandReader<R, [u8; FD_CMD_BUF_LEN]> {
+    CommandReader {
+        reader,
+        decoder: ChunkDecoder::new_fd(options),
+    }
+}
+
+/// Creates a `StdCommandReader` that decodes commands from `reader` with the default
+/// `DecodeOptions`.
+///
+/// # Example
+///
+/// ```
+/// use mini_slcan::read::{self, Command};
+/// use std::io::Cursor;
+///
+/// // Host programs would pass a `TcpStream`, a PTY or a file instead.
+/// let mut cmds = read::from_std_reader(Cursor::new(b"O\rC\r"));
+/// assert_eq!(cmds.next().unwrap().unwrap(), Command::Open);
+/// assert_eq!(cmds.next().unwrap().unwrap(), Command::Close);
+/// assert!(cmds.next().is_none());
+/// ```
+#[cfg(feature = "std")]
+pub fn from_std_reader<R: std::io::Read>(reader: R) -> StdCommandReader<R> {
+    from_std_reader_with(reader, DecodeOptions::new())
+}
+
+/// Creates a `StdCommandReader` that decodes commands from `reader` using `options`.
+#[cfg(feature = "std")]
+pub fn from_std_reader_with<R: std::io::Read>(
+    reader: R,
+    options: DecodeOptions,
+) -> StdCommandReader<R> {
+    StdCommandReader {
+        reader,
+        decoder: ChunkDecoder::new(options),
+    }
+}
+
+/// Creates a `StdCommandReader` that can decode CAN FD commands from `reader`, with the default
+/// `DecodeOptions`.
+#[cfg(feature = "std")]
+pub fn from_std_reader_fd<R: std::io::Read>(
+    reader: R,
+) -> StdCommandReader<R, [u8; FD_CMD_BUF_LEN]> {
+    from_std_reader_with_fd(reader, DecodeOptions::new())
+}
+
+/// Creates a `StdCommandReader` that can decode CAN FD commands from `reader`, using `options`.
+#[cfg(feature = "std")]
+pub fn from_std_reader_with_fd<R: std::io::Read>(
+    reader: R,
+    options: DecodeOptions,
+) -> StdCommandReader<R, [u8; FD_CMD_BUF_LEN]> {
+    StdCommandReader {
+        reader,
+        decoder: ChunkDecoder::new_fd(options),
+    }
+}
+
+/// Number of bytes a `CommandReader` or `StdCommandReader` requests from its reader at once.
+#[cfg(any(feature = "embedded-io", feature = "std"))]
+const READ_CHUNK_LEN: usize = 32;
+
+/// Decodes commands from the chunks of bytes returned by a reader.
+#[cfg(any(feature = "embedded-io", feature = "std"))]
+struct ChunkDecoder<B = [u8; CMD_BUF_LEN]> {
+    buf: CommandBuf<B>,
+    /// Bytes read from the reader that have not been passed to `buf` yet.
+    chunk: [u8; READ_CHUNK_LEN],
+    pos: usize,
+    len: usize,
+}
+
+#[cfg(any(feature = "embedded-io", feature = "std"))]
+impl ChunkDecoder {
+    fn new(options: DecodeOptions) -> Self {
+        Self::with_buf(CommandBuf::with_options(options))
+    }
+}
+
+#[cfg(any(feature = "embedded-io", feature = "std"))]
+impl ChunkDecoder<[u8; FD_CMD_BUF_LEN]> {
+    fn new_fd(options: DecodeOptions) -> Self {
+        Self::with_buf(CommandBuf::with_options_fd(options))
+    }
+}
+
+#[cfg(any(feature = "embedded-io", feature = "std"))]
+impl<B: AsRef<[u8]> + AsMut<[u8]>> ChunkDecoder<B> {
+    fn with_buf(buf: CommandBuf<B>) -> Self {
+        Self {
+            buf,
+            chunk: [0; READ_CHUNK_LEN],
+            pos: 0,
+            len: 0,
+        }
+    }
+
+    /// Decodes the next command, calling `read` whenever more bytes are needed.
+    ///
+    /// Returns `None` when `read` returns 0, which signals the end of the input.
+    fn next<E>(
+        &mut self,
+        mut read: impl FnMut(&mut [u8]) -> Result<usize, E>,
+    ) -> Option<Result<Command, ReadError<E>>> {
+        loop {
+            while self.pos < self.len {
+                let byte = self.chunk[self.pos];
+                self.pos += 1;
+                if let Some(result) = self.buf.push_byte(byte) {
+                    return Some(result.map_err(ReadError::Decode));
+                }
+            }
+
+            match read(&mut self.chunk) {
+                Ok(0) => return None,
+                Ok(len) => {
+                    self.pos = 0;
+                    self.len = len;
+                }
+                Err(e) => return Some(Err(ReadError::Io(e))),
+            }
+        }
+    }
+
+    fn pending(&self) -> &[u8] {
+        &self.chunk[self.pos..self.len]
+    }
+}
+
+/// Iterator over the commands read from an `embedded_io::Read` implementation.
+///
+/// Every call to `next` blocks until a complete command has been received. Iteration ends when
+/// the reader reports the end of its input. Bytes of an incomplete command at that point are
+/// discarded.
+///
+/// This is created by `from_reader` and `from_reader_with`, whose buffer is sized for classic CAN
+/// commands, and by `from_reader_fd` and `from_reader_with_fd`, which can also decode CAN FD
+/// commands.
+#[cfg(feature = "embedded-io")]
+pub struct CommandReader<R, B = [u8; CMD_BUF_LEN]> {
+    reader: R,
+    decoder: ChunkDecoder<B>,
+}
+
+#[cfg(feature = "embedded-io")]
+impl<R: embedded_io::Read, B: AsRef<[u8]> + AsMut<[u8]>> CommandReader<R, B> {
+    /// Returns a mutable reference to the internal `CommandBuf`, for example to enable
+    /// resynchronization mode.
+    pub fn buf_mut(&mut self) -> &mut CommandBuf<B> {
+        &mut self.decoder.buf
+    }
+
+    /// Releases the underlying reader.
+    ///
+    /// Bytes that were already read from it but not decoded yet are lost.
+    pub fn free(self) -> R {
+        self.reader
+    }
+}
+
+#[cfg(feature = "embedded-io")]
+impl<R: embedded_io::Read, B: AsRef<[u8]> + AsMut<[u8]>> Iterator for CommandReader<R, B> {
+    type Item = Result<Command, ReadError<R::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let reader = &mut self.reader;
+        self.decoder.next(|chunk| reader.read(chunk))
+    }
+}
+
+#[cfg(feature = "embedded-io")]
+impl<R, B: AsRef<[u8]> + AsMut<[u8]> + core::fmt::Debug> core::fmt::Debug for CommandReader<R, B> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("CommandReader")
+            .field("buf", &self.decoder.buf)
+            .field("pending", &self.decoder.pending())
+            .finish()
+    }
+}
+
+/// Iterator over the commands read from a `std::io::Read` implementation.
+///
+/// This works like `CommandReader`, and allows host programs to decode commands received via TCP
+/// sockets, PTYs or files. Reads that fail with `std::io::ErrorKind::Interrupted` are retried.
+///
+/// This is created by `from_std_reader` and `from_std_reader_with`, or by `from_std_reader_fd` and
+/// `from_std_reader_with_fd` for decoding CAN FD commands.
+#[cfg(feature = "std")]
+pub struct StdCommandReader<R, B = [u8; CMD_BUF_LEN]> {
+    reader: R,
+    decoder: ChunkDecoder<B>,
+}
+
+#[cfg(feature = "std")]
+impl<R: std::io::Read, B: AsRef<[u8]> + AsMut<[u8]>> StdCommandReader<R, B> {
+    /// Returns a mutable reference to the internal `CommandBuf`, for example to enable
+    /// resynchronization mode.
+    pub fn buf_mut(&mut self) -> &mut CommandBuf<B> {
+        &mut self.decoder.buf
+    }
+
+    /// Releases the underlying reader.
+    ///
+    /// Bytes that were already read from it but not decoded yet are lost.
+    pub fn free(self) -> R {
+        self.reader
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: std::io::Read, B: AsRef<[u8]> + AsMut<[u8]>> Iterator for StdCommandReader<R, B> {
+    type Item = Result<Command, ReadError<std::io::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let reader = &mut self.reader;
+        self.decoder.next(|chunk| loop {
+            match reader.read(chunk) {
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                res => return res,
+            }
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R, B: AsRef<[u8]> + AsMut<[u8]> + core::fmt::Debug> core::fmt::Debug
+    for StdCommandReader<R, B>
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("StdCommandReader")
+            .field("buf", &self.decoder.buf)
+            .field("pending", &self.decoder.pending())
+            .finish()
+    }
+}
+
+/// Error returned by a `CommandReader`, `StdCommandReader` or `SerialReader`.
+#[cfg(any(feature = "embedded-io", feature = "embedded-hal-nb", feature = "std"))]
+#[derive(Debug)]
+pub enum ReadError<E> {
+    /// The underlying reader or serial peripheral returned an error.
+    Io(E),
+
+    /// The received bytes could not be decoded as a command.
+    Decode(Error),
+}
+
+#[cfg(any(feature = "embedded-io", feature = "embedded-hal-nb", feature = "std"))]
+impl<E: core::fmt::Debug> core::fmt::Display for ReadError<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ReadError::Io(e) => write!(f, "I/O error: {:?}", e),
+            ReadError::Decode(e) => e.fmt(f),
+        }
+    }
+}
+
+/// Non-blocking command decoder for serial peripherals implementing
+/// `embedded_hal_nb::serial::Read`.
+///
+/// This fits the classic `nb` model: `read` returns `nb::Error::WouldBlock` while no complete
+/// command has been received, and is meant to be polled from a main loop or driven by
+/// `nb::block!`.
+///
+/// Like `CommandBuf`, `SerialReader::new` only has room for classic CAN commands, and reports longer
+/// ones (like CAN FD frames) as `ErrorKind::Overflow`. Devices that support CAN FD have to use
+/// `SerialReader::new_fd`.
+///
+/// # Example
+///
+/// ```
+/// use embedded_hal_nb::nb;
+/// use mini_slcan::read::{Command, DecodeOptions, SerialReader};
+/// # struct Uart<'a>(&'a [u8]);
+/// # impl embedded_hal_nb::serial::ErrorType for Uart<'_> {
+/// #     type Error = core::convert::Infallible;
+/// # }
+/// # impl embedded_hal_nb::serial::Read for Uart<'_> {
+/// #     fn read(&mut self) -> nb::Result<u8, Self::Error> {
+/// #         let (&byte, rest) = self.0.split_first().ok_or(nb::Error::WouldBlock)?;
+/// #         self.0 = rest;
+/// #         Ok(byte)
+/// #     }
+/// # }
+///
+/// let uart = Uart(b"O\rC");
+/// let mut reader = SerialReader::new(uart, DecodeOptions::new());
+/// assert_eq!(reader.read().unwrap(), Command::Open);
+/// assert!(matches!(reader.read(), Err(nb::Error::WouldBlock)));
+/// ```
+#[cfg(feature = "embedded-hal-nb")]
+pub struct SerialReader<S, B = [u8; CMD_BUF_LEN]> {
+    serial: S,
+    buf: CommandBuf<B>,
+}
+
+#[cfg(feature = "embedded-hal-nb")]
+impl<S: embedded_hal_nb::serial::Read> SerialReader<S> {
+    /// Creates a `SerialReader` that decodes the bytes read from `serial` using `options`.
+    pub fn new(serial: S, options: DecodeOptions) -> Self {
+        Self {
+            serial,
+            buf: CommandBuf::with_options(options),
+        }
+    }
+}
+
+#[cfg(feature = "embedded-hal-nb")]
+impl<S: embedded_hal_nb::serial::Read> SerialReader<S, [u8; FD_CMD_BUF_LEN]> {
+    /// Creates a `SerialReader` that can decode CAN FD commands from the bytes read from `serial`,
+    /// using `options`.
+    pub fn new_fd(serial: S, options: DecodeOptions) -> Self {
+        Self {
+            serial,
+            buf: CommandBuf::with_options_fd(options),
+        }
+    }
+}
+
+#[cfg(feature = "embedded-hal-nb")]
+impl<S: embedded_hal_nb::serial::Read, B: AsRef<[u8]> + AsMut<[u8]>> SerialReader<S, B> {
+    /// Returns a mutable reference to the internal `CommandBuf`, for example to enable
+    /// resynchronization mode.
+    pub fn buf_mut(&mut self) -> &mut CommandBuf<B> {
+        &mut self.buf
+    }
+
+    /// Reads bytes from the serial peripheral until they complete a command, and returns the
+    /// decoded command.
+    ///
+    /// Returns `nb::Error::WouldBlock` when the peripheral has no more data before a command is
+    /// complete. The bytes of the incomplete command are kept, and the command is returned by a
+    /// later call once the rest of it has been received.
+    pub fn read(&mut self) -> embedded_hal_nb::nb::Result<Command, ReadError<S::Error>> {
+        use embedded_hal_nb::nb;
+
+        loop {
+            let byte = self.serial.read().map_err(|e| e.map(ReadError::Io))?;
+            if let Some(result) = self.buf.push_byte(byte) {
+                return result.map_err(|e| nb::Error::Other(ReadError::Decode(e)));
+            }
+        }
+    }
+
+    /// Releases the serial peripheral.
+    pub fn free(self) -> S {
+        self.serial
+    }
+}
+
+#[cfg(feature = "embedded-hal-nb")]
+impl<S, B: core::fmt::Debug> core::fmt::Debug for SerialReader<S, B> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SerialReader")
+            .field("buf", &self.buf)
+            .finish()
+    }
+}
+
+/// Low-level decoder for SLCAN messages.
+///
+/// This is used by the decoders in this module, and can be used to parse vendor-specific messages
+/// that use the same formatting for hex numbers, identifiers and frames.
+///
+/// When the input ends in the middle of a field, the methods return an `Eof` error, and
+/// `Error::bytes_needed` returns how many more bytes (counted from the start of the field) are
+/// needed to read it. The `Reader` does not advance in that case.
+///
+/// # Example
+///
+/// ```
+/// use mini_slcan::read::Reader;
+///
+/// let mut reader = Reader::new(b"x12");
+/// assert_eq!(reader.read_byte().unwrap(), b'x');
+/// let err = reader.read_identifier().unwrap_err();
+/// assert_eq!(err.bytes_needed(), Some(1));
+///
+/// let mut reader = Reader::new(b"x1231AA\r");
+/// assert_eq!(reader.read_byte().unwrap(), b'x');
+/// assert_eq!(reader.read_identifier().unwrap().as_raw(), 0x123);
+/// assert_eq!(reader.read_frame().unwrap().data(), [0xAA]);
+/// reader.finish().unwrap();
+/// ```
+#[derive(Eq, PartialEq, Debug)]
+pub struct Reader<'a> {
+    input: &'a [u8],
+    /// Length of the original input, used to compute error offsets.
+    len: usize,
+    lowercase_hex: bool,
+    lf: bool,
+}
+
+impl<'a> Reader<'a> {
+    /// Creates a `Reader` that decodes `input`.
+    ///
+    /// By default, only uppercase hex digits are accepted, and only `CR` terminates lines.
+    pub fn new(input: &'a [u8]) -> Self {
+        Self {
+            input,
+            len: input.len(),
+            lowercase_hex: false,
+            lf: false,
+        }
+    }
+
+    /// Creates a `Reader` that reads hex digits as permitted by `options`.
+    fn with_options(input: &'a [u8], options: DecodeOptions) -> Self {
+        Self {
+            input,
+            len: input.len(),
+            lowercase_hex: options.allow_lowercase_hex,
+            lf: options.allow_lf,
+        }
+    }
+
+    /// Sets whether the lowercase hex digits `a` to `f` are accepted when reading numbers.
+    ///
+    /// When disabled (the default), only uppercase hex digits are accepted.
+    pub fn allow_lowercase_hex(mut self, allow: bool) -> Self {
+        self.lowercase_hex = allow;
+        self
+    }
+
+    /// Sets whether `LF` and `CR LF` are accepted as line terminators in addition to `CR`.
+    ///
+    /// When disabled (the default), only `CR` terminates lines.
+    pub fn allow_lf(mut self, allow: bool) -> Self {
+        self.lf = allow;
+        self
+    }
+
+    /// Returns the input that has not been read yet.
+    pub fn remaining(&self) -> &'a [u8] {
+        self.input
+    }
+
+    /// Returns whether `byte` terminates a line.
+    fn is_terminator(&self, byte: u8) -> bool {
+        byte == b'\r' || (self.lf && byte == b'\n')
+    }
+
+    /// Returns the number of bytes read so far.
+    fn pos(&self) -> usize {
+        self.len - self.input.len()
+    }
+
+    /// Invokes `f` to read `field`. Errors that are not attributed to a more precise location are
+    /// reported at the start of the field.
+    fn read_field<T>(
+        &mut self,
+        field: Field,
+        f: impl FnOnce(&mut Self) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let start = self.pos();
+        f(self).map_err(|e| e.in_field(field).at(start))
+    }
+
+    /// Invokes `f`, and resets the reader to its current position if `f` fails.
+    fn rewind_on_error<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let input = self.input;
+        let result = f(self);
+        if result.is_err() {
+            self.input = input;
+        }
+        result
+    }
+
+    /// Checks that the input continues with `digits` hex digits, without consuming them.
+    ///
+    /// Invalid digits are reported before missing ones, so that complete messages with too few
+    /// digits are rejected as malformed.
+    fn require_hex(&self, digits: usize) -> Result<(), Error> {
+        let available = self.input.len().min(digits);
+        for (i, digit) in self.input[..available].iter().enumerate() {
+            if self.is_terminator(*digit) {
+                // The message ends before the field does.
+                return Err(Error::new(ErrorKind::InvalidLength)
+                    .with_byte(*digit)
+                    .at(self.pos() + i));
+            }
+            unhex(*digit, self.lowercase_hex).map_err(|e| e.at(self.pos() + i))?;
+        }
+
+        if available < digits {
+            Err(Error::eof_needing(digits - available).at(self.pos() + available))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn read_byte(&mut self) -> Result<u8, Error> {
+        match self.input {
+            [] => Err(Error::eof_needing(1).at(self.pos())),
+            [b, rest @ ..] => {
+                self.input = rest;
+                Ok(*b)
+            }
+        }
+    }
+
+    /// Reads the terminating `CR` and ensures that no data follows it.
+    pub fn finish(&mut self) -> Result<(), Error> {
+        let pos = self.pos();
+        let result = match self.read_byte() {
+            Ok(b'\r') if self.lf && self.input == b"\n" => {
+                self.input = &[];
+                Ok(())
+            }
+            Ok(b'\r') => Ok(()),
+            Ok(b'\n') if self.lf => Ok(()),
+            Ok(b) => Err(Error::new(ErrorKind::MissingTerminator)
+                .with_byte(b)
+                .at(pos)),
+            Err(e) => Err(e),
+        };
+
+        // Reject trailing undecoded data.
+        let result = result.and_then(|()| match self.input {
+            [] => Ok(()),
+            [b, ..] => Err(Error::new(ErrorKind::TrailingData)
+                .with_byte(*b)
+                .at(self.pos())),
+        });
+        result.map_err(|e| e.in_field(Field::Terminator))
+    }
+
+    /// Reads a response that is not `Error` or `Ack`, not including the terminating `CR`.
+    fn read_response(&mut self) -> Result<Response, Error> {
+        let resp = match self.read_byte()? {
+            b'z' => Response::TxAck,
+            b'Z' => Response::ExtTxAck,
+            b'A' => Response::PollAllEnd,
+            b'F' => {
+                // Ignore reserved bits, so that the known flags can still be read from devices that
+                // set them.
+                let bits = self.read_hex_u8()?;
+                Response::Status(Status::from_bits_truncate(bits))
+            }
+            b'E' => {
+                let tec = self.read_hex_u8()?;
+                let rec = self.read_hex_u8()?;
+
+                Response::ErrorCounters { tec, rec }
+            }
+            b'I' => {
+                let load = self.read_hex_u8()?;
+                let rx_frames = self.read_hex(8)?;
+                let tx_frames = self.read_hex(8)?;
+
+                Response::Busload {
+                    load,
+                    rx_frames,
+                    tx_frames,
+                }
+            }
+            b'V' => {
+                let hardware_version = self.read_hex_u8()?;
+                let software_version = self.read_hex_u8()?;
+
+                Response::Version {
+                    hardware_version,
+                    software_version,
+                }
+            }
+            b'v' => {
+                let raw = self.read_until_cr()?;
+                let version = VersionString::from_bytes(raw).ok_or(Error::decode())?;
+
+                Response::DetailedVersion(version)
+            }
+            b'N' => {
+                let mut raw = [0; 4];
+                for byte in &mut raw {
+                    *byte = self.read_byte()?;
+                }
+
+                Response::Serial(SerialNumber::new(raw).ok_or(Error::decode())?)
+            }
+            op => {
+                return Err(Error::new(ErrorKind::UnsupportedCommand)
+                    .with_byte(op)
+                    .in_field(Field::Opcode)
+                    .at(0))
+            }
+        };
+
+        Ok(resp)
+    }
+
+    /// Reads a notification, not including the terminating `CR`.
+    fn read_notification(&mut self, options: DecodeOptions) -> Result<Notification, Error> {
+        let notif = match self.read_byte()? {
+            b't' => {
+                let identifier = self.read_identifier()?;
+                let dlc = self.read_dlc(options)?;
+                let frame = self.read_frame_data(dlc)?;
+
+                Notification::Rx { identifier, frame }
+            }
+            b'T' => {
+                let identifier = self.read_ext_identifier()?;
+                let dlc = self.read_dlc(options)?;
+                let frame = self.read_frame_data(dlc)?;
+
+                Notification::RxExt { identifier, frame }
+            }
+            b'r' => {
+                let identifier = self.read_identifier()?;
+                let len = self.read_dlc(options)?;
+
+                Notification::RxRtr { identifier, len }
+            }
+            b'R' => {
+                let identifier = self.read_ext_identifier()?;
+                let len = self.read_dlc(options)?;
+
+                Notification::RxExtRtr { identifier, len }
+            }
+            op @ b'd' | op @ b'b' => Notification::RxFd {
+                identifier: self.read_identifier()?,
+                frame: self.read_fd_frame()?,
+                flags: fd_flags(op),
+            },
+            op @ b'D' | op @ b'B' => Notification::RxExtFd {
+                identifier: self.read_ext_identifier()?,
+                frame: self.read_fd_frame()?,
+                flags: fd_flags(op),
+            },
+            b'e' => {
+                let count = self.read_hex_u4()?;
+                let mut errors = BusErrors::empty();
+                for _ in 0..count {
+                    errors |= self.read_param(|code| {
+                        crate::BUS_ERROR_CODES
+                            .iter()
+                            .find(|(_, c)| *c == code)
+                            .map(|(flag, _)| *flag)
+                    })?;
+                }
+
+                Notification::ErrorFrame(errors)
+            }
+            op => {
+                return Err(Error::new(ErrorKind::UnsupportedCommand)
+                    .with_byte(op)
+                    .in_field(Field::Opcode)
+                    .at(0))
+            }
+        };
+
+        Ok(notif)
+    }
+
+    /// Reads `1` or `0`.
+    ///
+    /// If the next byte is neither, it is not consumed.
+    pub fn read_bool(&mut self) -> Result<bool, Error> {
+        self.read_param(|byte| match byte {
+            b'0' => Some(false),
+            b'1' => Some(true),
+            _ => None,
+        })
+    }
+
+    /// Reads a single-character parameter, which `f` maps to its value.
+    ///
+    /// Bytes that `f` does not accept are not consumed, and are reported as `InvalidValue`, or as
+    /// `InvalidLength` if the message ends in place of the parameter.
+    fn read_param<T>(&mut self, f: impl FnOnce(u8) -> Option<T>) -> Result<T, Error> {
+        self.rewind_on_error(|reader| {
+            let pos = reader.pos();
+            let byte = reader.read_byte()?;
+            f(byte).ok_or_else(|| {
+                let kind = if reader.is_terminator(byte) {
+                    ErrorKind::InvalidLength
+                } else {
+                    ErrorKind::InvalidValue
+                };
+                Error::new(kind).with_byte(byte).at(pos)
+            })
+        })
+    }
+
+    /// Reads a number consisting of `digits` hex digits, most significant first.
+    ///
+    /// Returns an error of kind `InvalidLength` if `digits` is greater than 8, since the number
+    /// would not fit into a `u32`.
+    pub fn read_hex(&mut self, digits: u8) -> Result<u32, Error> {
+        if digits > 8 {
+            return Err(Error::new(ErrorKind::InvalidLength));
+        }
+        self.require_hex(digits.into())?;
+
+        let mut val = 0;
+
+        for _ in 0..digits {
+            val <<= 4;
+            val |= unhex(self.read_byte()?, self.lowercase_hex)? as u32;
+        }
+
+        Ok(val)
+    }
+
+    /// Reads a single hex digit.
+    pub fn read_hex_u4(&mut self) -> Result<u8, Error> {
+        Ok(self.read_hex(1)? as u8)
+    }
+
+    pub fn read_hex_u8(&mut self) -> Result<u8, Error> {
+        Ok(self.read_hex(2)? as u8)
+    }
+
+    pub fn read_hex_u16(&mut self) -> Result<u16, Error> {
+        Ok(self.read_hex(4)? as u16)
+    }
+
+    /// Reads a standard identifier consisting of 3 hex digits.
+    pub fn read_identifier(&mut self) -> Result<Identifier, Error> {
+        self.read_field(Field::Identifier, |reader| {
+            let raw = reader.read_hex(3)? as u16;
+            Identifier::from_raw(raw).ok_or(Error::new(ErrorKind::IdentifierOutOfRange))
+        })
+    }
+
+    /// Reads an extended identifier consisting of 8 hex digits.
+    pub fn read_ext_identifier(&mut self) -> Result<ExtIdentifier, Error> {
+        self.read_field(Field::Identifier, |reader| {
+            let raw = reader.read_hex(8)?;
+            ExtIdentifier::from_raw(raw).ok_or(Error::new(ErrorKind::IdentifierOutOfRange))
+        })
+    }
+
+    fn read_hex_filter_value(&mut self) -> Result<FilterValue, Error> {
+        Ok(FilterValue::from_raw(self.read_hex(8)?))
+    }
+
+    /// Reads all bytes up to (but excluding) the next `CR`.
+    fn read_raw_payload(&mut self) -> Result<RawPayload, Error> {
+        let raw = self.read_until_cr()?;
+        RawPayload::from_slice(raw).ok_or(Error::new(ErrorKind::InvalidLength))
+    }
+
+    /// Returns all bytes up to (but excluding) the next `CR`, and advances past them.
+    pub fn read_until_cr(&mut self) -> Result<&'a [u8], Error> {
+        let len = self
+            .input
+            .iter()
+            .position(|b| self.is_terminator(*b))
+            .ok_or(Error::eof_needing(1))?;
+        let (bytes, rest) = self.input.split_at(len);
+        self.input = rest;
+        Ok(bytes)
+    }
+
+    /// Reads a data length code, which may exceed 8 if `options` allow that.
+    fn read_dlc(&mut self, options: DecodeOptions) -> Result<u8, Error> {
+        self.read_field(Field::Dlc, |reader| {
+            let dlc = reader.read_hex_u4()?;
+            if dlc > 8 && !options.allow_long_dlc {
+                return Err(Error::new(ErrorKind::InvalidLength));
+            }
+
+            Ok(dlc)
+        })
+    }
+
+    /// Reads a single hex digit DLC in range 0..=8, followed by the frame data.
+    pub fn read_frame(&mut self) -> Result<CanFrame, Error> {
+        self.rewind_on_error(|reader| {
+            let dlc = reader.read_dlc(DecodeOptions::new())?;
+            reader.read_frame_data(dlc)
+        })
+    }
+
+    fn read_frame_data(&mut self, dlc: u8) -> Result<CanFrame, Error> {
+        assert!(dlc <= 15);
+        let digits = usize::from(dlc.min(8)) * 2;
+        self.read_field(Field::Data, |reader| reader.require_hex(digits))?;
+
+        let mut frame = CanFrame::new();
+
+        for _ in 0..dlc.min(8) {
+            let byte = self.read_hex_u8()?;
+
+            // Can never fail, because we read 8 bytes or less.
+            frame.push(byte).unwrap();
+        }
+
+        if dlc > 8 {
+            // Can never fail, since the frame holds 8 bytes and `dlc` is in range 9..=15.
+            frame = frame.with_dlc(dlc).unwrap();
+        }
+
+        Ok(frame)
+    }
+
+    /// Reads the single hex digit DLC and the data of a CAN FD frame.
+    pub fn read_fd_frame(&mut self) -> Result<CanFdFrame, Error> {
+        self.rewind_on_error(Self::read_fd_frame_inner)
+    }
+
+    fn read_fd_frame_inner(&mut self) -> Result<CanFdFrame, Error> {
+        let dlc = self.read_field(Field::Dlc, Self::read_hex_u4)?;
+
+        // Can never fail, since `dlc` is a single hex digit.
+        let mut frame = CanFdFrame::from_dlc(dlc).unwrap();
+        self.read_field(Field::Data, |reader| reader.require_hex(frame.len() * 2))?;
+        for byte in frame.data_mut() {
+            *byte = self.read_hex_u8()?;
+        }
+
+        Ok(frame)
+    }
+}
+
+/// Returns the flags of a CAN FD frame transmitted or received with the opcode `op`.
+///
+/// The uppercase and lowercase `b` opcodes indicate frames with bit rate switching.
+fn fd_flags(op: u8) -> FdFlags {
+    if op.eq_ignore_ascii_case(&b'b') {
+        FdFlags::BRS
+    } else {
+        FdFlags::empty()
     }
 }
 
-fn unhex(digit: u8) -> Result<u8, Error> {
+/// Decodes a hex digit, accepting lowercase digits only if `lowercase` is set.
+fn unhex(digit: u8, lowercase: bool) -> Result<u8, Error> {
     match digit {
         b'0'..=b'9' => Ok(digit - b'0'),
         b'A'..=b'F' => Ok(digit - b'A' + 10),
-        _ => Err(Error::decode()),
+        b'a'..=b'f' if lowercase => Ok(digit - b'a' + 10),
+        _ => Err(Error::new(ErrorKind::InvalidHexDigit).with_byte(digit)),
     }
 }
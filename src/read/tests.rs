@@ -1,22 +1,55 @@
 use super::*;
 use crate::error::ErrorKind;
+use crate::write::Response;
+use crate::{BusErrors, Field, Status};
 
 fn ok(input: &str, expected: Command) {
-    assert_eq!(Command::decode(input.as_bytes()).unwrap(), expected);
+    assert_eq!(Command::decode(input.as_bytes()).unwrap(), expected.clone());
+    if let Some(res) = stream_decode(input) {
+        assert_eq!(res.unwrap(), expected);
+    }
 }
 
 fn err(input: &str, expected: ErrorKind) {
-    assert_eq!(
-        Command::decode(input.as_bytes()).unwrap_err().kind(),
-        expected,
-    );
+    let err = Command::decode(input.as_bytes()).unwrap_err();
+    assert_eq!(err.kind(), expected);
+    if let Some(res) = stream_decode(input) {
+        let stream_err = res.unwrap_err();
+        assert_eq!(stream_err.kind(), expected, "{:?}", input);
+        assert_eq!(stream_err.byte(), err.byte(), "{:?}", input);
+    }
+}
+
+/// Decodes a single line with a `StreamDecoder`, if `input` is one.
+fn stream_decode(input: &str) -> Option<Result<Command, Error>> {
+    let (last, line) = input.as_bytes().split_last()?;
+    if *last != b'\r' || line.contains(&b'\r') {
+        return None;
+    }
+
+    let mut decoder = StreamDecoder::new();
+    for byte in line {
+        assert!(decoder.push(*byte).is_pending(), "{:?}", input);
+    }
+    match decoder.push(b'\r') {
+        Poll::Ready(res) => Some(res),
+        Poll::Pending => panic!("no result for {:?}", input),
+    }
 }
 
 #[test]
 fn decode_simple_cmds() {
     ok("O\r", Command::Open);
+    ok("L\r", Command::OpenListenOnly);
     ok("C\r", Command::Close);
-    err("C\n", ErrorKind::Decode);
+    ok("P\r", Command::PollOne);
+    ok("A\r", Command::PollAll);
+    ok("F\r", Command::ReadStatus);
+    ok("E\r", Command::ReadErrorCounters);
+    ok("I\r", Command::ReadBusload);
+    ok("V\r", Command::ReadVersion);
+    ok("v\r", Command::ReadDetailedVersion);
+    err("C\n", ErrorKind::MissingTerminator);
     err("C", ErrorKind::Eof);
     err("", ErrorKind::Eof);
 }
@@ -36,7 +69,67 @@ fn decode_setup_cmds() {
         },
     );
 
-    err("S9\r", ErrorKind::Decode);
+    err("S9\r", ErrorKind::InvalidValue);
+
+    static EXTRA: &[Bitrate] = &[
+        Bitrate::NonStandard {
+            code: b'9',
+            bps: 83_333,
+        },
+        Bitrate::NonStandard {
+            code: b'A',
+            bps: 33_333,
+        },
+    ];
+    let options = DecodeOptions::new().extra_bitrates(EXTRA);
+    assert_eq!(
+        Command::decode_with(b"S9\r", options).unwrap(),
+        Command::SetupWithBitrate { bitrate: EXTRA[0] },
+    );
+    assert_eq!(
+        Command::decode_with(b"SA\r", options).unwrap(),
+        Command::SetupWithBitrate { bitrate: EXTRA[1] },
+    );
+    assert_eq!(
+        Command::decode_with(b"S8\r", options).unwrap(),
+        Command::SetupWithBitrate {
+            bitrate: Bitrate::_1mbit,
+        },
+    );
+    assert_eq!(
+        Command::decode_with(b"SB\r", options).unwrap_err().kind(),
+        ErrorKind::InvalidValue,
+    );
+    assert_eq!(EXTRA[0].kbps(), 83);
+    assert_eq!(EXTRA[0].bps(), 83_333);
+    assert_eq!(Bitrate::_125kbit.bps(), 125_000);
+
+    ok(
+        "s031C\r",
+        Command::SetupWithBtr {
+            btr0: 0x03,
+            btr1: 0x1C,
+        },
+    );
+    err("s031\r", ErrorKind::InvalidLength);
+    err("s031C0\r", ErrorKind::MissingTerminator);
+    err("s03", ErrorKind::Eof);
+
+    ok(
+        "Y2\r",
+        Command::SetupDataBitrate {
+            bitrate: DataBitrate::_2mbit,
+        },
+    );
+    ok(
+        "Y8\r",
+        Command::SetupDataBitrate {
+            bitrate: DataBitrate::_8mbit,
+        },
+    );
+    err("Y3\r", ErrorKind::InvalidValue);
+    err("Y\r", ErrorKind::InvalidLength);
+    assert_eq!(DataBitrate::_5mbit.bps(), 5_000_000);
 }
 
 #[test]
@@ -62,9 +155,9 @@ fn decode_tx_cmds() {
             frame: [0xAA].into(),
         },
     );
-    err("t8000\r", ErrorKind::Decode);
-    err("t800\r", ErrorKind::Decode);
-    err("t80\r", ErrorKind::Decode);
+    err("t8000\r", ErrorKind::IdentifierOutOfRange);
+    err("t800\r", ErrorKind::IdentifierOutOfRange);
+    err("t80\r", ErrorKind::InvalidLength);
 
     ok(
         "T1FFFFFFF80001020304050607\r",
@@ -89,13 +182,74 @@ fn decode_tx_cmds() {
         },
     );
 
-    err("R1FFFFFFF9\r", ErrorKind::Decode);
+    err("R1FFFFFFF9\r", ErrorKind::InvalidLength);
+}
+
+#[test]
+fn decode_fd_cmds() {
+    ok(
+        "d1230\r",
+        Command::TxFd {
+            identifier: Identifier::from_raw(0x123).unwrap(),
+            frame: CanFdFrame::new(),
+            flags: FdFlags::empty(),
+        },
+    );
+    ok(
+        "b7FF9000102030405060708090A0B\r",
+        Command::TxFd {
+            identifier: Identifier::from_raw(0x7FF).unwrap(),
+            frame: CanFdFrame::from_slice(&[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]).unwrap(),
+            flags: FdFlags::BRS,
+        },
+    );
+
+    let input = format!("B1FFFFFFFF{}\r", "AA".repeat(64));
+    ok(
+        &input,
+        Command::TxExtFd {
+            identifier: ExtIdentifier::from_raw(0x1FFF_FFFF).unwrap(),
+            frame: CanFdFrame::from_slice(&[0xAA; 64]).unwrap(),
+            flags: FdFlags::BRS,
+        },
+    );
+    let expected = Command::TxExtFd {
+        identifier: ExtIdentifier::from_raw(0x1FFF_FFFF).unwrap(),
+        frame: CanFdFrame::from_slice(&[0xAA; 64]).unwrap(),
+        flags: FdFlags::BRS,
+    };
+    let mut buf = CommandBuf::new_fd();
+    let res = buf.feed(input.as_bytes()).collect::<Vec<_>>();
+    assert_eq!(res.len(), 1);
+    assert_eq!(res[0].as_ref().unwrap(), &expected);
+    let mut ring = CommandRing::new_fd();
+    ring.tail_mut()[..input.len()].copy_from_slice(input.as_bytes());
+    let res = ring.advance_by(input.len() as u8).collect::<Vec<_>>();
+    assert_eq!(res.len(), 1);
+    assert_eq!(res[0].as_ref().unwrap(), &expected);
+
+    // The default buffers only fit classic CAN commands.
+    let mut buf = CommandBuf::new();
+    let res = buf.feed(input.as_bytes()).next().unwrap();
+    assert_eq!(res.unwrap_err().kind(), ErrorKind::Overflow);
+    ok(
+        "D000000011AA\r",
+        Command::TxExtFd {
+            identifier: ExtIdentifier::from_raw(1).unwrap(),
+            frame: CanFdFrame::from_slice(&[0xAA]).unwrap(),
+            flags: FdFlags::empty(),
+        },
+    );
+
+    // DLC 9 means 12 data bytes, not 9.
+    err("d1239000102030405060708\r", ErrorKind::InvalidLength);
+    err("d800\r", ErrorKind::IdentifierOutOfRange);
 }
 
 #[test]
 fn mismatched_len() {
-    err("t7FF1\r", ErrorKind::Decode);
-    err("t7FF1AABB\r", ErrorKind::Decode);
+    err("t7FF1\r", ErrorKind::InvalidLength);
+    err("t7FF1AABB\r", ErrorKind::MissingTerminator);
 }
 
 fn cmdbuf_decode(chunks: &[&[u8]], res: &[Result<Command, ErrorKind>]) {
@@ -110,6 +264,48 @@ fn cmdbuf_decode(chunks: &[&[u8]], res: &[Result<Command, ErrorKind>]) {
             assert_eq!(&res, exp);
         }
     }
+    assert!(expected.next().is_none(), "too many expected results");
+
+    // Feeding the same input via `feed` yields the same results.
+    let mut expected = res.iter();
+    let mut buf = CommandBuf::new();
+    for chunk in chunks {
+        for res in buf.feed(chunk) {
+            let exp = expected.next().expect("too few expected results");
+            assert_eq!(&res.map_err(|e| e.kind()), exp);
+        }
+    }
+    assert!(expected.next().is_none(), "too many expected results");
+
+    // `CommandRing` yields the same results.
+    let mut expected = res.iter();
+    let mut ring = CommandRing::new();
+    for chunk in chunks {
+        let mut chunk = *chunk;
+        while !chunk.is_empty() {
+            let tail = ring.tail_mut();
+            let len = tail.len().min(chunk.len());
+            tail[..len].copy_from_slice(&chunk[..len]);
+            chunk = &chunk[len..];
+
+            for res in ring.advance_by(len as u8) {
+                let exp = expected.next().expect("too few expected results");
+                assert_eq!(&res.map_err(|e| e.kind()), exp);
+            }
+        }
+    }
+    assert!(expected.next().is_none(), "too many expected results");
+
+    // Feeding the same input bytewise yields the same results.
+    let mut expected = res.iter();
+    let mut buf = CommandBuf::new();
+    for byte in chunks.iter().flat_map(|chunk| chunk.iter()) {
+        if let Some(res) = buf.push_byte(*byte) {
+            let exp = expected.next().expect("too few expected results");
+            assert_eq!(&res.map_err(|e| e.kind()), exp);
+        }
+    }
+    assert!(expected.next().is_none(), "too many expected results");
 }
 
 #[test]
@@ -149,7 +345,16 @@ fn cmdbuf() {
     cmdbuf_decode(
         &[b"\rS0\r"],
         &[
-            Err(ErrorKind::Decode),
+            Err(ErrorKind::UnsupportedCommand),
+            Ok(Command::SetupWithBitrate {
+                bitrate: Bitrate::_10kbit,
+            }),
+        ],
+    );
+    cmdbuf_decode(
+        &[&[b'0'; CMD_BUF_LEN], b"S0\r"],
+        &[
+            Err(ErrorKind::Overflow),
             Ok(Command::SetupWithBitrate {
                 bitrate: Bitrate::_10kbit,
             }),
@@ -158,10 +363,1881 @@ fn cmdbuf() {
     cmdbuf_decode(
         &[b"INVALID\rS0", b"\r"],
         &[
-            Err(ErrorKind::Decode),
+            Err(ErrorKind::MissingTerminator),
             Ok(Command::SetupWithBitrate {
                 bitrate: Bitrate::_10kbit,
             }),
         ],
     );
+
+    // `feed` accepts input that is larger than the buffer.
+    let mut input = [0; CMD_BUF_LEN * 2];
+    for cmd in input.chunks_mut(2) {
+        cmd.copy_from_slice(b"C\r");
+    }
+    let mut buf = CommandBuf::new();
+    assert_eq!(
+        buf.feed(&input).filter(|cmd| cmd.is_ok()).count(),
+        CMD_BUF_LEN
+    );
+    assert_eq!(
+        buf.feed_channeled(b"1C\r").next().unwrap().unwrap(),
+        ChanneledCommand {
+            channel: 1,
+            cmd: Command::Close,
+        }
+    );
+
+    let mut buf = CommandBuf::new();
+    assert!(buf.push_byte_channeled(b'1').is_none());
+    assert!(buf.push_byte_channeled(b'O').is_none());
+    assert_eq!(
+        buf.push_byte_channeled(b'\r').unwrap().unwrap(),
+        ChanneledCommand {
+            channel: 1,
+            cmd: Command::Open,
+        }
+    );
+}
+
+#[test]
+fn cmdbuf_resync() {
+    let mut input = Vec::new();
+    input.extend_from_slice(b"O\rxyz\r\r");
+    input.extend_from_slice(&[b'?'; CommandBuf::LEN + 10]);
+    input.extend_from_slice(b"\rC\r");
+    let discarded = 4 + 1 + CommandBuf::LEN + 10 + 1;
+
+    let mut buf = CommandBuf::new();
+    buf.set_resync(true);
+    let res = buf.feed(&input).collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(res, [Command::Open, Command::Close]);
+    assert_eq!(buf.take_discarded(), discarded);
+    assert_eq!(buf.take_discarded(), 0);
+
+    let mut buf = CommandBuf::new();
+    buf.set_resync(true);
+    let res = input.iter().filter_map(|byte| buf.push_byte(*byte));
+    let res = res.collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(res, [Command::Open, Command::Close]);
+    assert_eq!(buf.take_discarded(), discarded);
+
+    let mut buf = CommandBuf::new();
+    buf.set_resync(true);
+    let mut res = Vec::new();
+    let mut rest = &input[..];
+    while !rest.is_empty() {
+        let tail = buf.tail_mut();
+        let len = tail.len().min(rest.len()).min(16);
+        tail[..len].copy_from_slice(&rest[..len]);
+        rest = &rest[len..];
+        res.extend(buf.advance_by(len as u8).map(Result::unwrap));
+    }
+    assert_eq!(res, [Command::Open, Command::Close]);
+    assert_eq!(buf.take_discarded(), discarded);
+
+    // Without resynchronization, every invalid line yields an error.
+    let mut buf = CommandBuf::new();
+    assert_eq!(buf.feed(&input).filter(Result::is_err).count(), 4);
+    assert_eq!(buf.take_discarded(), 0);
+}
+
+#[test]
+fn cmdbuf_push_after_full_advance() {
+    let mut buf = CommandBuf::new();
+    let len = buf.tail_mut().len();
+    buf.tail_mut().fill(b'x');
+    drop(buf.advance_by(len as u8));
+
+    // The first pushed byte reports the overlong command and starts the next one.
+    let err = buf.push_byte(b'O').unwrap().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Overflow);
+    assert_eq!(err.bytes_discarded(), Some(len));
+    assert_eq!(buf.push_byte(b'\r').unwrap().unwrap(), Command::Open);
+
+    // A terminator ends the overlong command instead.
+    buf.tail_mut().fill(b'x');
+    drop(buf.advance_by(len as u8));
+    let err = buf.push_byte(b'\r').unwrap().unwrap_err();
+    assert_eq!(err.bytes_discarded(), Some(len + 1));
+    assert!(buf.push_byte(b'C').is_none());
+    assert_eq!(buf.push_byte(b'\r').unwrap().unwrap(), Command::Close);
+}
+
+#[test]
+fn cmdbuf_peek() {
+    let input = b"O\rxyz\r1C\rS";
+    let mut buf = CommandBuf::new();
+    buf.tail_mut()[..input.len()].copy_from_slice(input);
+    drop(buf.advance_by(input.len() as u8));
+
+    let res = buf.peek().map(|res| res.map_err(|e| e.kind()));
+    assert_eq!(
+        res.collect::<Vec<_>>(),
+        [
+            Ok(Command::Open),
+            Err(ErrorKind::UnsupportedCommand),
+            Err(ErrorKind::UnsupportedCommand),
+        ],
+    );
+    assert_eq!(
+        buf.peek_channeled().nth(2).unwrap().unwrap(),
+        ChanneledCommand {
+            channel: 1,
+            cmd: Command::Close,
+        },
+    );
+
+    buf.set_resync(true);
+    assert_eq!(
+        buf.peek().collect::<Result<Vec<_>, _>>().unwrap(),
+        [Command::Open]
+    );
+
+    // Peeking does not consume anything.
+    assert_eq!(buf.advance_by(0).count(), 1);
+    assert_eq!(buf.take_discarded(), 7);
+    assert_eq!(buf.tail_mut().len(), CommandBuf::LEN - 1);
+}
+
+#[test]
+#[cfg(feature = "heapless")]
+fn command_consumer() {
+    let mut queue: heapless::spsc::Queue<u8, 8> = heapless::spsc::Queue::new();
+    let (mut producer, consumer) = queue.split();
+    let mut cmds = CommandConsumer::new(consumer, DecodeOptions::new());
+
+    // Commands can be longer than the queue, since they are buffered by the consumer.
+    for chunk in b"t1232AABB\rxyz\r".chunks(7) {
+        for byte in chunk {
+            producer.enqueue(*byte).unwrap();
+        }
+        if let Some(res) = cmds.next_command() {
+            assert_eq!(res.unwrap(), Command::decode(b"t1232AABB\r").unwrap());
+        }
+    }
+    assert_eq!(
+        cmds.next_command().unwrap().unwrap_err().kind(),
+        ErrorKind::UnsupportedCommand
+    );
+    assert!(cmds.next_command().is_none());
+
+    cmds.buf_mut().set_resync(true);
+    for byte in b"xyz\rO\r" {
+        producer.enqueue(*byte).unwrap();
+    }
+    assert_eq!(cmds.next_command().unwrap().unwrap(), Command::Open);
+    assert_eq!(cmds.buf_mut().take_discarded(), 4);
+
+    // CAN FD commands only fit into the buffer of `new_fd`.
+    let input = format!("d123F{}\r", "AA".repeat(64));
+    let mut queue: heapless::spsc::Queue<u8, 256> = heapless::spsc::Queue::new();
+    let (mut producer, consumer) = queue.split();
+    let mut cmds = CommandConsumer::new(consumer, DecodeOptions::new());
+    for byte in input.bytes() {
+        producer.enqueue(byte).unwrap();
+    }
+    let err = cmds.next_command().unwrap().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Overflow);
+
+    let mut queue: heapless::spsc::Queue<u8, 256> = heapless::spsc::Queue::new();
+    let (mut producer, consumer) = queue.split();
+    let mut cmds = CommandConsumer::new_fd(consumer, DecodeOptions::new());
+    for byte in input.bytes() {
+        producer.enqueue(byte).unwrap();
+    }
+    assert_eq!(
+        cmds.next_command().unwrap().unwrap(),
+        Command::decode(input.as_bytes()).unwrap()
+    );
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn std_command_reader() {
+    use std::io;
+
+    /// Interrupts every other read, and returns at most 3 bytes per successful read.
+    struct Flaky<'a> {
+        input: &'a [u8],
+        interrupt: bool,
+    }
+
+    impl io::Read for Flaky<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.interrupt = !self.interrupt;
+            if self.interrupt {
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+            if self.input.is_empty() {
+                return Err(io::ErrorKind::ConnectionReset.into());
+            }
+
+            let len = buf.len().min(self.input.len()).min(3);
+            buf[..len].copy_from_slice(&self.input[..len]);
+            self.input = &self.input[len..];
+            Ok(len)
+        }
+    }
+
+    let mut cmds = from_std_reader(Flaky {
+        input: b"t1232AABB\rxyz\rO\r",
+        interrupt: false,
+    });
+    assert_eq!(
+        cmds.next().unwrap().unwrap(),
+        Command::decode(b"t1232AABB\r").unwrap()
+    );
+    match cmds.next().unwrap() {
+        Err(ReadError::Decode(e)) => assert_eq!(e.kind(), ErrorKind::UnsupportedCommand),
+        res => panic!("unexpected result: {:?}", res),
+    }
+    assert_eq!(cmds.next().unwrap().unwrap(), Command::Open);
+    match cmds.next().unwrap() {
+        Err(ReadError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::ConnectionReset),
+        res => panic!("unexpected result: {:?}", res),
+    }
+
+    let options = DecodeOptions::new().allow_lf(true);
+    let mut cmds = from_std_reader_with(io::Cursor::new(b"O\nC\nt123"), options);
+    assert_eq!(cmds.next().unwrap().unwrap(), Command::Open);
+    assert_eq!(cmds.next().unwrap().unwrap(), Command::Close);
+    assert!(cmds.next().is_none());
+    assert_eq!(cmds.free().position(), 8);
+
+    // CAN FD commands only fit into the buffer of `from_std_reader_fd`.
+    let input = format!("B1FFFFFFFF{}\r", "55".repeat(64));
+    let mut cmds = from_std_reader(io::Cursor::new(&input));
+    match cmds.next().unwrap() {
+        Err(ReadError::Decode(e)) => assert_eq!(e.kind(), ErrorKind::Overflow),
+        res => panic!("unexpected result: {:?}", res),
+    }
+    let mut cmds = from_std_reader_fd(io::Cursor::new(&input));
+    assert_eq!(
+        cmds.next().unwrap().unwrap(),
+        Command::decode(input.as_bytes()).unwrap()
+    );
+    assert!(cmds.next().is_none());
+}
+
+#[test]
+#[cfg(feature = "embedded-hal-nb")]
+fn serial_reader() {
+    use embedded_hal_nb::nb;
+    use embedded_hal_nb::serial::ErrorKind as SerialError;
+
+    /// Yields the queued bytes, followed by `WouldBlock`. `!` signals an overrun error.
+    struct Serial<'a>(&'a [u8]);
+
+    impl embedded_hal_nb::serial::ErrorType for Serial<'_> {
+        type Error = SerialError;
+    }
+
+    impl embedded_hal_nb::serial::Read for Serial<'_> {
+        fn read(&mut self) -> nb::Result<u8, Self::Error> {
+            let (&byte, rest) = self.0.split_first().ok_or(nb::Error::WouldBlock)?;
+            self.0 = rest;
+            match byte {
+                b'!' => Err(nb::Error::Other(SerialError::Overrun)),
+                _ => Ok(byte),
+            }
+        }
+    }
+
+    let mut reader = SerialReader::new(Serial(b"t1232A"), DecodeOptions::new());
+    assert!(matches!(reader.read(), Err(nb::Error::WouldBlock)));
+    let serial = reader.free();
+    assert!(serial.0.is_empty());
+
+    let mut reader = SerialReader::new(Serial(b"t1232AABB\rx!\rO\r"), DecodeOptions::new());
+    assert_eq!(
+        reader.read().unwrap(),
+        Command::decode(b"t1232AABB\r").unwrap()
+    );
+    assert!(matches!(
+        reader.read(),
+        Err(nb::Error::Other(ReadError::Io(SerialError::Overrun)))
+    ));
+    match reader.read() {
+        Err(nb::Error::Other(ReadError::Decode(e))) => {
+            assert_eq!(e.kind(), ErrorKind::UnsupportedCommand)
+        }
+        res => panic!("unexpected result: {:?}", res),
+    }
+    assert_eq!(reader.read().unwrap(), Command::Open);
+    assert!(matches!(reader.read(), Err(nb::Error::WouldBlock)));
+
+    // CAN FD commands only fit into the buffer of `new_fd`.
+    let input = format!("D1FFFFFFFF{}\r", "55".repeat(64));
+    let mut reader = SerialReader::new(Serial(input.as_bytes()), DecodeOptions::new());
+    match reader.read() {
+        Err(nb::Error::Other(ReadError::Decode(e))) => assert_eq!(e.kind(), ErrorKind::Overflow),
+        res => panic!("unexpected result: {:?}", res),
+    }
+    let mut reader = SerialReader::new_fd(Serial(input.as_bytes()), DecodeOptions::new());
+    assert_eq!(
+        reader.read().unwrap(),
+        Command::decode(input.as_bytes()).unwrap()
+    );
+}
+
+#[test]
+#[cfg(feature = "embedded-io")]
+fn command_reader() {
+    /// Returns at most 3 bytes per `read`, then fails once the input is exhausted.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl embedded_io::ErrorType for Trickle<'_> {
+        type Error = embedded_io::ErrorKind;
+    }
+
+    impl embedded_io::Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            if self.0.is_empty() {
+                return Err(embedded_io::ErrorKind::BrokenPipe);
+            }
+
+            let len = buf.len().min(self.0.len()).min(3);
+            buf[..len].copy_from_slice(&self.0[..len]);
+            self.0 = &self.0[len..];
+            Ok(len)
+        }
+    }
+
+    let mut cmds = from_reader(Trickle(b"t1232AABB\rxyz\rO\r"));
+    assert_eq!(
+        cmds.next().unwrap().unwrap(),
+        Command::decode(b"t1232AABB\r").unwrap()
+    );
+    match cmds.next().unwrap() {
+        Err(ReadError::Decode(e)) => assert_eq!(e.kind(), ErrorKind::UnsupportedCommand),
+        res => panic!("unexpected result: {:?}", res),
+    }
+    assert_eq!(cmds.next().unwrap().unwrap(), Command::Open);
+    match cmds.next().unwrap() {
+        Err(ReadError::Io(e)) => assert_eq!(e, embedded_io::ErrorKind::BrokenPipe),
+        res => panic!("unexpected result: {:?}", res),
+    }
+
+    // Resynchronization mode of the internal buffer applies, and the iterator ends at EOF.
+    let mut cmds = from_reader_with(&b"xyz\rO\rC"[..], DecodeOptions::new());
+    cmds.buf_mut().set_resync(true);
+    assert_eq!(cmds.next().unwrap().unwrap(), Command::Open);
+    assert_eq!(cmds.buf_mut().take_discarded(), 4);
+    assert!(cmds.next().is_none());
+
+    // CAN FD commands only fit into the buffer of `from_reader_fd`.
+    let input = format!("d123F{}\r", "AA".repeat(64));
+    let mut cmds = from_reader(input.as_bytes());
+    match cmds.next().unwrap() {
+        Err(ReadError::Decode(e)) => assert_eq!(e.kind(), ErrorKind::Overflow),
+        res => panic!("unexpected result: {:?}", res),
+    }
+    let mut cmds = from_reader_fd(input.as_bytes());
+    assert_eq!(
+        cmds.next().unwrap().unwrap(),
+        Command::decode(input.as_bytes()).unwrap()
+    );
+    assert!(cmds.next().is_none());
+}
+
+#[test]
+fn cmdbuf_borrowed() {
+    let mut storage = [0; 8];
+    let mut buf = CommandBuf::with_buffer(&mut storage[..], DecodeOptions::new());
+    assert_eq!(buf.tail_mut().len(), 8);
+    buf.tail_mut()[..3].copy_from_slice(b"O\rC");
+    let cmds = buf.advance_by(3).collect::<Vec<_>>();
+    assert_eq!(cmds.len(), 1);
+    assert_eq!(cmds[0].as_ref().unwrap(), &Command::Open);
+    assert_eq!(buf.tail_mut().len(), 7);
+
+    // Commands that don't fit are rejected.
+    let results = buf
+        .feed(b"\rt1232AABB\rO\r")
+        .map(|res| res.map_err(|e| e.kind()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        results,
+        [
+            Ok(Command::Close),
+            Err(ErrorKind::Overflow),
+            Err(ErrorKind::InvalidLength),
+            Ok(Command::Open)
+        ]
+    );
+
+    let err = buf.feed(b"t1232AABB\r").next().unwrap().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Overflow);
+    assert_eq!(err.bytes_discarded(), Some(8));
+    assert_eq!(err.bytes_needed(), None);
+}
+
+#[test]
+fn command_ring_wraparound() {
+    fn push(ring: &mut CommandRing<&mut [u8]>, input: &[u8]) -> Vec<Command> {
+        let tail = ring.tail_mut();
+        tail[..input.len()].copy_from_slice(input);
+        ring.advance_by(input.len() as u8)
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    let mut storage = [0; 8];
+    let mut ring = CommandRing::with_buffer(&mut storage[..], DecodeOptions::new());
+    assert_eq!(
+        push(&mut ring, b"O\rC\rt1"),
+        [Command::Open, Command::Close]
+    );
+    assert_eq!(ring.tail_mut().len(), 2);
+    assert_eq!(push(&mut ring, b"23"), []);
+
+    // The free space wraps around to the start of the buffer.
+    assert_eq!(ring.tail_mut().len(), 4);
+    assert_eq!(
+        push(&mut ring, b"0\r"),
+        [Command::TxStandard {
+            identifier: Identifier::from_raw(0x123).unwrap(),
+            frame: CanFrame::new(),
+        }]
+    );
+
+    // Once empty, the whole buffer is available again.
+    assert_eq!(ring.tail_mut().len(), 8);
+
+    // Input written to both tail slices is decoded as one.
+    assert_eq!(
+        push(&mut ring, b"O\rC\rt12"),
+        [Command::Open, Command::Close]
+    );
+    let (first, second) = ring.tail_mut2();
+    assert_eq!((first.len(), second.len()), (1, 4));
+    first.copy_from_slice(b"3");
+    second[..4].copy_from_slice(b"0\rt1");
+    assert_eq!(ring.advance_by(5).count(), 1);
+
+    let (first, second) = ring.tail_mut2();
+    assert_eq!((first.len(), second.len()), (4, 2));
+    first.copy_from_slice(b"230\r");
+    second.copy_from_slice(b"O\r");
+    // Not decoded until the iterator is used.
+    drop(ring.advance_by(6));
+    let (first, second) = ring.tail_mut2();
+    assert!(first.is_empty() && second.is_empty());
+    assert_eq!(ring.advance_by(0).count(), 2);
+    assert_eq!(ring.tail_mut().len(), 8);
+
+    // Overlong lines that wrap around are reported as overflowing.
+    let mut storage = [0; 256];
+    let mut ring = CommandRing::with_buffer(&mut storage[..], DecodeOptions::new());
+    ring.tail_mut()[..3].copy_from_slice(b"O\rt");
+    assert_eq!(ring.advance_by(3).count(), 1);
+    let (first, second) = ring.tail_mut2();
+    assert_eq!((first.len(), second.len()), (253, 2));
+    first.fill(b'0');
+    second.copy_from_slice(b"0\r");
+    let err = ring.advance_by(255).next().unwrap().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Overflow);
+    assert_eq!(err.bytes_discarded(), Some(256));
+    assert_eq!(ring.tail_mut().len(), 256);
+}
+
+#[test]
+fn advance_past_capacity() {
+    fn kinds<T>(iter: impl Iterator<Item = Result<T, Error>>) -> Vec<Result<T, ErrorKind>> {
+        iter.map(|res| res.map_err(|e| e.kind())).collect()
+    }
+
+    let mut buf = CommandBuf::new();
+    buf.tail_mut()[..2].copy_from_slice(b"O\r");
+    let res = kinds(buf.advance_by(255));
+    assert_eq!(res, [Err(ErrorKind::Overflow), Ok(Command::Open)]);
+
+    // The bytes that fit into the buffer are kept.
+    assert_eq!(buf.tail_mut().len(), 2);
+
+    let mut buf = CommandBuf::new();
+    buf.set_resync(true);
+    buf.tail_mut()[..2].copy_from_slice(b"C\r");
+    assert_eq!(kinds(buf.advance_by(255)), [Ok(Command::Close)]);
+    assert_eq!(buf.take_discarded(), 255 - CommandBuf::LEN);
+
+    let mut storage = [0; 8];
+    let mut ring = CommandRing::with_buffer(&mut storage[..], DecodeOptions::new());
+    ring.tail_mut()[..2].copy_from_slice(b"O\r");
+    let mut cmds = ring.advance_by(10);
+    assert_eq!(cmds.next().unwrap().unwrap_err().bytes_discarded(), Some(2));
+    assert_eq!(cmds.next().unwrap().unwrap(), Command::Open);
+
+    let mut buf = HostBuf::new();
+    let len = buf.tail_mut().len();
+    let err = buf.advance_by(255).next().unwrap().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Overflow);
+    assert_eq!(err.bytes_discarded(), Some(255 - len));
+}
+
+#[test]
+fn stream_decoder() {
+    fn push(decoder: &mut StreamDecoder, input: &[u8]) -> Vec<Result<Command, ErrorKind>> {
+        input
+            .iter()
+            .filter_map(|byte| match decoder.push(*byte) {
+                Poll::Ready(res) => Some(res.map_err(|e| e.kind())),
+                Poll::Pending => None,
+            })
+            .collect()
+    }
+
+    // Errors only affect their own line.
+    let mut decoder = StreamDecoder::new();
+    assert_eq!(
+        push(&mut decoder, b"t12X1AA\rO\r\rt1232AABBCC\rC\r"),
+        [
+            Err(ErrorKind::InvalidHexDigit),
+            Ok(Command::Open),
+            Err(ErrorKind::UnsupportedCommand),
+            Err(ErrorKind::MissingTerminator),
+            Ok(Command::Close),
+        ],
+    );
+
+    static VENDOR: &[VendorCommand] = &[VendorCommand::any_payload(b't')];
+    let options = DecodeOptions::new()
+        .allow_long_dlc(true)
+        .vendor_commands(VENDOR);
+    let mut decoder = StreamDecoder::with_options(options);
+    let res = push(&mut decoder, b"T00000123F0001020304050607\rtxyz\r");
+    match &res[..] {
+        [Ok(Command::TxExt { frame, .. }), Ok(Command::Vendor { opcode, payload })] => {
+            assert_eq!(frame.dlc(), 15);
+            assert_eq!(frame.data(), [0, 1, 2, 3, 4, 5, 6, 7]);
+            assert_eq!(*opcode, b't');
+            assert_eq!(payload.as_bytes(), b"xyz");
+        }
+        _ => panic!("unexpected results {:?}", res),
+    }
+
+    // Payloads of other commands are limited to `RawPayload::MAX_LENGTH` bytes.
+    let options = DecodeOptions::new().allow_unknown(true);
+    let mut decoder = StreamDecoder::with_options(options);
+    let mut line = [b'?'; RawPayload::MAX_LENGTH + 3];
+    line[RawPayload::MAX_LENGTH + 2] = b'\r';
+    assert!(push(&mut decoder, &line[1..])[0].is_ok());
+    assert_eq!(push(&mut decoder, &line), [Err(ErrorKind::InvalidLength)]);
+    let mut line = [b'?'; 67];
+    line[66] = b'\r';
+    assert_eq!(push(&mut decoder, &line), [Err(ErrorKind::InvalidLength)]);
+}
+
+#[test]
+fn decode_filter_cmds() {
+    ok(
+        "M00000000\r",
+        Command::SetAcceptanceCode(FilterValue::from_raw(0)),
+    );
+    ok(
+        "MFFFFFFFF\r",
+        Command::SetAcceptanceCode(FilterValue::from_raw(0xFFFF_FFFF)),
+    );
+    ok(
+        "m12345678\r",
+        Command::SetAcceptanceMask(FilterValue::from_registers([0x12, 0x34, 0x56, 0x78])),
+    );
+
+    err("M0000000\r", ErrorKind::InvalidLength);
+    err("M000000000\r", ErrorKind::MissingTerminator);
+    err("m0000000G\r", ErrorKind::InvalidHexDigit);
+    err("m0000", ErrorKind::Eof);
+
+    ok("W0\r", Command::SetFilterMode(FilterMode::Dual));
+    ok("W1\r", Command::SetFilterMode(FilterMode::Single));
+    err("W2\r", ErrorKind::InvalidValue);
+}
+
+#[test]
+fn decode_serial_baud_cmd() {
+    ok(
+        "U0\r",
+        Command::SetSerialBaudrate {
+            baud: SerialBaud::_230400,
+        },
+    );
+    ok(
+        "U6\r",
+        Command::SetSerialBaudrate {
+            baud: SerialBaud::_2400,
+        },
+    );
+
+    err("U7\r", ErrorKind::InvalidValue);
+    err("U\r", ErrorKind::InvalidLength);
+}
+
+#[test]
+fn decode_flag_cmds() {
+    ok(
+        "Z0\r",
+        Command::SetRxTimestamp {
+            mode: TimestampMode::Off,
+        },
+    );
+    ok(
+        "Z1\r",
+        Command::SetRxTimestamp {
+            mode: TimestampMode::Milliseconds,
+        },
+    );
+    ok(
+        "Z2\r",
+        Command::SetRxTimestamp {
+            mode: TimestampMode::Microseconds,
+        },
+    );
+    err("Z3\r", ErrorKind::InvalidValue);
+
+    ok("X0\r", Command::SetAutoPoll { enabled: false });
+    ok("X1\r", Command::SetAutoPoll { enabled: true });
+    err("X2\r", ErrorKind::InvalidValue);
+    err("X\r", ErrorKind::InvalidLength);
+
+    ok("Q0\r", Command::SetAutoStartup(AutoStartup::Off));
+    ok("Q1\r", Command::SetAutoStartup(AutoStartup::OpenNormal));
+    ok("Q2\r", Command::SetAutoStartup(AutoStartup::OpenListenOnly));
+    err("Q3\r", ErrorKind::InvalidValue);
+}
+
+#[test]
+fn decode_unknown() {
+    err("!\r", ErrorKind::UnsupportedCommand);
+    err("!abc\r", ErrorKind::UnsupportedCommand);
+
+    let options = DecodeOptions::new().allow_unknown(true);
+    let cmd = Command::decode_with(b"!abc\r", options).unwrap();
+    match cmd {
+        Command::Unknown { opcode, payload } => {
+            assert_eq!(opcode, b'!');
+            assert_eq!(payload.as_bytes(), b"abc");
+        }
+        _ => panic!("unexpected command {:?}", cmd),
+    }
+
+    let cmd = Command::decode_with(b"!\r", options).unwrap();
+    match cmd {
+        Command::Unknown { opcode, payload } => {
+            assert_eq!(opcode, b'!');
+            assert_eq!(payload.as_bytes(), b"");
+        }
+        _ => panic!("unexpected command {:?}", cmd),
+    }
+
+    // Known commands are still decoded normally.
+    assert_eq!(
+        Command::decode_with(b"O\r", options).unwrap(),
+        Command::Open
+    );
+    assert_eq!(
+        Command::decode_with(b"S9\r", options).unwrap_err().kind(),
+        ErrorKind::InvalidValue,
+    );
+
+    assert_eq!(
+        Command::decode_with(b"!abc", options).unwrap_err().kind(),
+        ErrorKind::Eof,
+    );
+    assert_eq!(
+        Command::decode_with(b"!\r\r", options).unwrap_err().kind(),
+        ErrorKind::TrailingData,
+    );
+}
+
+#[test]
+fn decode_vendor() {
+    static VENDOR: &[VendorCommand] = &[
+        VendorCommand::any_payload(b'!'),
+        VendorCommand::new(b'E', |payload| payload == b"x"),
+    ];
+    let options = DecodeOptions::new().vendor_commands(VENDOR);
+
+    let cmd = Command::decode_with(b"!abc\r", options).unwrap();
+    match cmd {
+        Command::Vendor { opcode, payload } => {
+            assert_eq!(opcode, b'!');
+            assert_eq!(payload.as_bytes(), b"abc");
+        }
+        _ => panic!("unexpected command {:?}", cmd),
+    }
+
+    // Vendor commands take precedence over standard ones.
+    let cmd = Command::decode_with(b"Ex\r", options).unwrap();
+    match cmd {
+        Command::Vendor { opcode, payload } => {
+            assert_eq!(opcode, b'E');
+            assert_eq!(payload.as_bytes(), b"x");
+        }
+        _ => panic!("unexpected command {:?}", cmd),
+    }
+    assert_eq!(
+        Command::decode_with(b"E\r", options).unwrap_err().kind(),
+        ErrorKind::Decode,
+    );
+
+    // Other opcodes are unaffected.
+    assert_eq!(
+        Command::decode_with(b"O\r", options).unwrap(),
+        Command::Open
+    );
+    assert_eq!(
+        Command::decode_with(b"?\r", options).unwrap_err().kind(),
+        ErrorKind::UnsupportedCommand,
+    );
+}
+
+#[test]
+#[cfg(feature = "usbtin")]
+fn decode_usbtin_cmds() {
+    ok(
+        "W2A05\r",
+        Command::WriteRegister {
+            register: 0x2A,
+            value: 0x05,
+        },
+    );
+    ok("G0E\r", Command::ReadRegister { register: 0x0E });
+
+    // The filter mode command is still accepted.
+    ok("W1\r", Command::SetFilterMode(FilterMode::Single));
+
+    err("W2A0\r", ErrorKind::InvalidLength);
+    err("W2A050\r", ErrorKind::MissingTerminator);
+    err("G0\r", ErrorKind::InvalidLength);
+
+    // The terminator, not the input length, tells the commands apart.
+    let options = DecodeOptions::new().allow_lf(true);
+    assert_eq!(
+        Command::decode_with(b"W1\r\n", options).unwrap(),
+        Command::SetFilterMode(FilterMode::Single)
+    );
+    assert_eq!(
+        Command::decode_with(b"W0\n", options).unwrap(),
+        Command::SetFilterMode(FilterMode::Dual)
+    );
+    assert_eq!(
+        Command::decode_with(b"W2A05\r\n", options).unwrap(),
+        Command::WriteRegister {
+            register: 0x2A,
+            value: 0x05,
+        }
+    );
+    assert_eq!(Command::decode(b"W1").unwrap_err().kind(), ErrorKind::Eof);
+}
+
+#[test]
+fn decode_channeled() {
+    assert_eq!(
+        ChanneledCommand::decode(b"0O\r").unwrap(),
+        ChanneledCommand {
+            channel: 0,
+            cmd: Command::Open,
+        },
+    );
+    assert_eq!(
+        ChanneledCommand::decode(b"9t1230\r").unwrap(),
+        ChanneledCommand {
+            channel: 9,
+            cmd: Command::TxStandard {
+                identifier: Identifier::from_raw(0x123).unwrap(),
+                frame: [].into(),
+            },
+        },
+    );
+    let err = ChanneledCommand::decode(b"O\r").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidValue);
+    assert_eq!(err.offset(), Some(0));
+    assert_eq!(err.field(), Some(Field::Channel));
+    assert_eq!(err.byte(), Some(b'O'));
+    assert_eq!(
+        err.to_string(),
+        "invalid value in channel at offset 0 (got 0x4F)"
+    );
+
+    // The options apply to the channel prefix as well.
+    let options = DecodeOptions::new().allow_lf(true);
+    assert_eq!(
+        ChanneledCommand::decode_with(b"\n", options)
+            .unwrap_err()
+            .kind(),
+        ErrorKind::InvalidLength,
+    );
+    assert_eq!(
+        ChanneledCommand::decode(b"1").unwrap_err().kind(),
+        ErrorKind::Eof,
+    );
+    assert_eq!(
+        ChanneledCommand::decode(b"").unwrap_err().kind(),
+        ErrorKind::Eof,
+    );
+
+    let mut buf = CommandBuf::new();
+    let mut decoded = Vec::new();
+    for chunk in &[&b"1T111111118414243444"[..], b"5464748\r", b"0C\r"] {
+        buf.tail_mut()[..chunk.len()].copy_from_slice(chunk);
+        decoded.extend(
+            buf.advance_by_channeled(chunk.len() as u8)
+                .map(Result::unwrap),
+        );
+    }
+    assert_eq!(
+        decoded,
+        [
+            ChanneledCommand {
+                channel: 1,
+                cmd: Command::TxExt {
+                    identifier: ExtIdentifier::from_raw(0x1111_1111).unwrap(),
+                    frame: (*b"ABCDEFGH").into(),
+                },
+            },
+            ChanneledCommand {
+                channel: 0,
+                cmd: Command::Close,
+            },
+        ]
+    );
+}
+
+#[test]
+fn decode_long_dlc() {
+    err("t1239000102030405060708\r", ErrorKind::InvalidLength);
+    err("r123F\r", ErrorKind::InvalidLength);
+
+    let options = DecodeOptions::new().allow_long_dlc(true);
+    let cmd = Command::decode_with(b"t123F0001020304050607\r", options).unwrap();
+    match cmd {
+        Command::TxStandard { frame, .. } => {
+            assert_eq!(frame.dlc(), 15);
+            assert_eq!(frame.len(), 8);
+            assert_eq!(frame.data(), [0, 1, 2, 3, 4, 5, 6, 7]);
+        }
+        _ => panic!("unexpected command {:?}", cmd),
+    }
+    assert_eq!(
+        Command::decode_with(b"R000000009\r", options).unwrap(),
+        Command::TxExtRtr {
+            identifier: ExtIdentifier::from_raw(0).unwrap(),
+            len: 9,
+        },
+    );
+    assert_eq!(
+        Command::decode_with(b"t1239\r", options)
+            .unwrap_err()
+            .kind(),
+        ErrorKind::InvalidLength,
+    );
+}
+
+#[test]
+fn decode_lf() {
+    err("O\n", ErrorKind::MissingTerminator);
+    err("O\r\n", ErrorKind::TrailingData);
+
+    let options = DecodeOptions::new().allow_lf(true);
+    for input in [&b"O\r"[..], b"O\n", b"O\r\n"].iter() {
+        assert_eq!(Command::decode_with(input, options).unwrap(), Command::Open);
+    }
+    for input in [&b"O\n\r"[..], b"O\r\n\n", b"O\n\n", b"O\r\r"].iter() {
+        assert_eq!(
+            Command::decode_with(input, options).unwrap_err().kind(),
+            ErrorKind::TrailingData,
+        );
+    }
+    let cmd = Command::decode_with(b"t1232AABB\r\n", options).unwrap();
+    assert_eq!(cmd, Command::decode(b"t1232AABB\r").unwrap());
+
+    let input = b"O\r\nt1232AABB\nC\r\n\r\n";
+    let expected = [
+        Ok(Command::Open),
+        Ok(cmd),
+        Ok(Command::Close),
+        Err(ErrorKind::UnsupportedCommand),
+    ];
+
+    let mut buf = CommandBuf::with_options(options);
+    let res = buf.feed(input).map(|res| res.map_err(|e| e.kind()));
+    assert_eq!(res.collect::<Vec<_>>(), expected);
+
+    let mut buf = CommandBuf::with_options(options);
+    buf.tail_mut()[..input.len()].copy_from_slice(input);
+    let res = buf.advance_by(input.len() as u8);
+    assert_eq!(
+        res.map(|res| res.map_err(|e| e.kind())).collect::<Vec<_>>(),
+        expected
+    );
+
+    let mut buf = CommandBuf::with_options(options);
+    let res = input.iter().filter_map(|byte| buf.push_byte(*byte));
+    assert_eq!(
+        res.map(|res| res.map_err(|e| e.kind())).collect::<Vec<_>>(),
+        expected
+    );
+
+    let mut ring = CommandRing::with_options(options);
+    ring.tail_mut()[..input.len()].copy_from_slice(input);
+    let res = ring.advance_by(input.len() as u8);
+    assert_eq!(
+        res.map(|res| res.map_err(|e| e.kind())).collect::<Vec<_>>(),
+        expected
+    );
+
+    let mut decoder = StreamDecoder::with_options(options);
+    let res = input.iter().filter_map(|byte| match decoder.push(*byte) {
+        Poll::Ready(res) => Some(res.map_err(|e| e.kind())),
+        Poll::Pending => None,
+    });
+    assert_eq!(res.collect::<Vec<_>>(), expected);
+}
+
+#[test]
+fn decode_partial() {
+    let (res, len) = Command::decode_partial(b"t1232AABB\rO\r");
+    assert_eq!(res.unwrap(), Command::decode(b"t1232AABB\r").unwrap());
+    assert_eq!(len, 10);
+
+    let (res, len) = Command::decode_partial(b"t1232AABB");
+    assert_eq!(res.unwrap_err().kind(), ErrorKind::Eof);
+    assert_eq!(len, 0);
+
+    let (res, len) = Command::decode_partial(b"t1232AA\rO\r");
+    assert_eq!(res.unwrap_err().kind(), ErrorKind::InvalidLength);
+    assert_eq!(len, 8);
+
+    let (res, len) = Command::decode_partial(b"O\r\nC\r");
+    assert_eq!(res.unwrap(), Command::Open);
+    assert_eq!(len, 2);
+
+    let options = DecodeOptions::new().allow_lf(true);
+    let mut input = &b"O\r\nC\n\nO\r"[..];
+    let mut cmds = Vec::new();
+    loop {
+        match Command::decode_partial_with(input, options) {
+            (_, 0) => break,
+            (res, len) => {
+                cmds.push(res.unwrap());
+                input = &input[len..];
+            }
+        }
+    }
+    assert_eq!(cmds, [Command::Open, Command::Close, Command::Open]);
+    assert!(input.is_empty());
+}
+
+#[test]
+fn error_size() {
+    // `Error` is returned on every decoding path, so it has to stay small.
+    assert!(core::mem::size_of::<Error>() <= 12);
+
+    // Counts that do not fit are saturated.
+    let err = Error::overflow(100_000);
+    assert_eq!(err.bytes_discarded(), Some(usize::from(u16::MAX)));
+    assert_eq!(
+        Error::eof().at(70_000).offset(),
+        Some(usize::from(u16::MAX))
+    );
+}
+
+#[test]
+fn error_offsets() {
+    fn check(input: &[u8], offset: usize, field: Option<Field>) {
+        let err = Command::decode(input).unwrap_err();
+        assert_eq!(err.offset(), Some(offset), "{:?}", input);
+        assert_eq!(err.field(), field, "{:?}", input);
+    }
+
+    check(b"?\r", 0, Some(Field::Opcode));
+    check(b"t12X1AA\r", 3, Some(Field::Identifier));
+    check(b"t8001AA\r", 1, Some(Field::Identifier));
+    check(b"T2000000011AA\r", 1, Some(Field::Identifier));
+    check(b"t123X\r", 4, Some(Field::Dlc));
+    check(b"t1239\r", 4, Some(Field::Dlc));
+    check(b"t1232AAXB\r", 7, Some(Field::Data));
+    check(b"t1232AA\r", 7, Some(Field::Data));
+    check(b"t1231AABB\r", 7, Some(Field::Terminator));
+    check(b"O\rC\r", 2, Some(Field::Terminator));
+    check(b"t123", 4, Some(Field::Dlc));
+    check(b"S9\r", 1, None);
+
+    fn check_byte(input: &[u8], byte: Option<u8>) {
+        let err = Command::decode(input).unwrap_err();
+        assert_eq!(err.byte(), byte, "{:?}", input);
+    }
+
+    check_byte(b"?\r", Some(b'?'));
+    check_byte(b"t12X1AA\r", Some(b'X'));
+    check_byte(b"t1232AA\r", Some(b'\r'));
+    check_byte(b"t1231AABB\r", Some(b'B'));
+    check_byte(b"O\n", Some(b'\n'));
+    check_byte(b"O\rC\r", Some(b'C'));
+    check_byte(b"t8001AA\r", None);
+    check_byte(b"t1239\r", None);
+    check_byte(b"t123", None);
+    check_byte(b"S9\r", Some(b'9'));
+    check_byte(b"X2\r", Some(b'2'));
+    check_byte(b"U\r", Some(b'\r'));
+
+    let err = Command::decode(b"O\rC\r").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "trailing data after terminator at offset 2 (got 0x43)"
+    );
+    let err = Command::decode(b"t12X1AA\r").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid hex digit in identifier at offset 3 (got 0x58)"
+    );
+
+    let err = ChanneledCommand::decode(b"1t12X1AA\r").unwrap_err();
+    assert_eq!(err.offset(), Some(4));
+
+    let err = Notification::decode(b"t1232AAXB\r").unwrap_err();
+    assert_eq!(err.offset(), Some(7));
+    assert_eq!(err.field(), Some(Field::Data));
+
+    let err = Notification::decode(b"e2aX\r").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidValue);
+    assert_eq!(err.offset(), Some(3));
+    assert_eq!(err.byte(), Some(b'X'));
+    assert_eq!(err.to_string(), "invalid value at offset 3 (got 0x58)");
+
+    let err = Notification::decode(b"x\r").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UnsupportedCommand);
+    assert_eq!(err.field(), Some(Field::Opcode));
+    assert_eq!(err.byte(), Some(b'x'));
+
+    let err = Response::decode(b"x\r").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UnsupportedCommand);
+    assert_eq!(err.offset(), Some(0));
+    assert_eq!(err.byte(), Some(b'x'));
+
+    let mut buf = HostBuf::new();
+    buf.tail_mut()[..4].copy_from_slice(b"t12\x07");
+    let err = buf.advance_by(4).next().unwrap().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::MissingTerminator);
+    assert_eq!(err.offset(), Some(3));
+    assert_eq!(err.byte(), Some(7));
+
+    let mut decoder = StreamDecoder::new();
+    let res = b"t8001AA\r"
+        .iter()
+        .find_map(|byte| match decoder.push(*byte) {
+            Poll::Ready(res) => Some(res),
+            Poll::Pending => None,
+        });
+    assert_eq!(res.unwrap().unwrap_err().offset(), Some(1));
+}
+
+#[test]
+#[cfg(feature = "core-error")]
+fn core_error() {
+    fn decode(input: &[u8]) -> Result<Command, Box<dyn core::error::Error>> {
+        Ok(Command::decode(input)?)
+    }
+
+    assert_eq!(decode(b"O\r").unwrap(), Command::Open);
+    let err = decode(b"t12X1AA\r").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid hex digit in identifier at offset 3 (got 0x58)"
+    );
+    assert!(err.source().is_none());
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn serde_commands() {
+    let options = DecodeOptions::new()
+        .allow_unknown(true)
+        .allow_long_dlc(true);
+    for input in [
+        &b"O\r"[..],
+        b"S4\r",
+        b"t1232AABB\r",
+        b"T1234567881122334455667788\r",
+        b"t7FFF0102030405060708\r",
+        b"r7FF8\r",
+        b"b1239AABBCCDDEEFF001122334455\r",
+        b"M12345678\r",
+        b"?abc\r",
+    ]
+    .iter()
+    {
+        let cmd = Command::decode_with(input, options).unwrap();
+        let json = serde_json::to_string(&cmd).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Command>(&json).unwrap(),
+            cmd,
+            "{}",
+            json
+        );
+    }
+
+    let cmd = Command::decode(b"t1232AABB\r").unwrap();
+    assert_eq!(
+        serde_json::to_string(&cmd).unwrap(),
+        r#"{"TxStandard":{"identifier":291,"frame":{"dlc":2,"data":[170,187]}}}"#,
+    );
+
+    for json in [
+        r#"{"TxStandard":{"identifier":2048,"frame":{"dlc":0,"data":[]}}}"#,
+        r#"{"TxStandard":{"identifier":1,"frame":{"dlc":9,"data":[]}}}"#,
+        r#"{"TxStandard":{"identifier":1,"frame":{"dlc":9,"data":[1,2,3,4,5,6,7,8,9]}}}"#,
+        r#"{"TxFd":{"identifier":1,"frame":[1,2,3,4,5,6,7,8,9],"flags":0}}"#,
+        r#"{"TxFd":{"identifier":1,"frame":[],"flags":4}}"#,
+        r#"{"TxStandardRtr":{"identifier":1,"len":16}}"#,
+        r#"{"SetupWithBitrate":{"bitrate":{"NonStandard":{"code":52,"bps":83333}}}}"#,
+        r#"{"Unknown":{"opcode":63,"payload":[13]}}"#,
+    ]
+    .iter()
+    {
+        assert!(serde_json::from_str::<Command>(json).is_err(), "{}", json);
+    }
+
+    let json = r#"{"channel":9,"cmd":"Open"}"#;
+    let cmd = serde_json::from_str::<ChanneledCommand>(json).unwrap();
+    assert_eq!(cmd.channel, 9);
+    let json = r#"{"channel":42,"cmd":"Open"}"#;
+    assert!(serde_json::from_str::<ChanneledCommand>(json).is_err());
+}
+
+#[test]
+#[cfg(feature = "arbitrary")]
+fn arbitrary_commands() {
+    use crate::write::{CommandEncodeBuf, NotificationBuf, ResponseBuf, TimestampedNotification};
+    use arbitrary::{Arbitrary, Unstructured};
+
+    // Simple xorshift generator, so that the test inputs are deterministic.
+    let mut state = 0x2545_F491_u32;
+    let mut data = [0; 512];
+    for _ in 0..256 {
+        for byte in data.iter_mut() {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            *byte = state as u8;
+        }
+
+        let mut u = Unstructured::new(&data);
+        let identifier = Identifier::arbitrary(&mut u).unwrap();
+        assert!(identifier.as_raw() <= 0x7FF);
+        let ext = ExtIdentifier::arbitrary(&mut u).unwrap();
+        assert!(ext.as_raw() <= 0x1FFF_FFFF);
+        let frame = CanFrame::arbitrary(&mut u).unwrap();
+        assert!(frame.dlc() <= 15);
+        assert!(frame.data().len() <= CanFrame::MAX_LENGTH);
+        let fd = CanFdFrame::arbitrary(&mut u).unwrap();
+        assert_eq!(CanFdFrame::from_slice(fd.data()).unwrap(), fd);
+
+        let cmd = Command::TxStandard { identifier, frame };
+        let mut buf = CommandEncodeBuf::new();
+        let encoded = cmd.encode(&mut buf).unwrap();
+        let options = DecodeOptions::new().allow_long_dlc(true);
+        assert_eq!(Command::decode_with(encoded, options).unwrap(), cmd);
+
+        // Any generated command has to be encodable, and classic CAN commands have to fit the
+        // default buffers.
+        let cmd = Command::arbitrary(&mut u).unwrap();
+        let mut buf = CommandEncodeBuf::new();
+        assert_eq!(cmd.encode(&mut buf).unwrap().len(), cmd.encoded_len());
+        if !matches!(cmd.to_frame(), Some(frame) if frame.is_fd()) {
+            assert!(cmd.encoded_len() <= Command::MAX_ENCODED_LEN, "{:?}", cmd);
+        }
+
+        // Fields with a limited range of valid values stay within it.
+        let cmd = ChanneledCommand::arbitrary(&mut u).unwrap();
+        assert_eq!(cmd.encode(&mut buf).unwrap().len(), cmd.encoded_len());
+        let notif = TimestampedNotification::arbitrary(&mut u).unwrap();
+        let mut buf = NotificationBuf::new_fd();
+        assert_eq!(notif.encode(&mut buf).unwrap().len(), notif.encoded_len());
+        let resp = Response::arbitrary(&mut u).unwrap();
+        let mut buf = ResponseBuf::new();
+        assert_eq!(resp.encode(&mut buf).unwrap().len(), resp.encoded_len());
+    }
+}
+
+#[cfg(feature = "testutil")]
+proptest::proptest! {
+    #[test]
+    fn testutil_roundtrip(
+        identifier in crate::testutil::identifier(),
+        ext in crate::testutil::ext_identifier(),
+        frame in crate::testutil::can_frame(),
+        fd in crate::testutil::can_fd_frame(),
+        cmd in crate::testutil::command(),
+        channeled in crate::testutil::channeled_command(),
+        notif in crate::testutil::notification(),
+        resp in crate::testutil::response(),
+    ) {
+        use crate::write::{CommandEncodeBuf, NotificationBuf, ResponseBuf};
+
+        let options = DecodeOptions::new().allow_long_dlc(true);
+        for expected in [
+            Command::TxStandard { identifier, frame },
+            Command::TxExt { identifier: ext, frame },
+            Command::TxFd { identifier, frame: fd, flags: FdFlags::BRS },
+            Command::TxExtFd { identifier: ext, frame: fd, flags: FdFlags::empty() },
+        ]
+        .iter()
+        {
+            let mut buf = CommandEncodeBuf::new();
+            let encoded = expected.encode(&mut buf).unwrap();
+            proptest::prop_assert_eq!(&Command::decode_with(encoded, options).unwrap(), expected);
+        }
+
+        let mut buf = CommandEncodeBuf::new();
+        let encoded = cmd.encode(&mut buf).unwrap();
+        proptest::prop_assert_eq!(encoded.len(), cmd.encoded_len());
+        let decoded = Command::decode_with(encoded, roundtrip_options(&cmd)).unwrap();
+        proptest::prop_assert_eq!(decoded, cmd);
+
+        let encoded = channeled.encode(&mut buf).unwrap();
+        let options = roundtrip_options(&channeled.cmd);
+        proptest::prop_assert_eq!(ChanneledCommand::decode_with(encoded, options).unwrap(), channeled);
+
+        let mut buf = NotificationBuf::new_fd();
+        let encoded = notif.encode(&mut buf).unwrap();
+        proptest::prop_assert_eq!(Notification::decode_with(encoded, options).unwrap(), notif);
+
+        let mut buf = ResponseBuf::new();
+        let encoded = resp.encode(&mut buf).unwrap();
+        let decoded = match resp {
+            #[cfg(feature = "usbtin")]
+            Response::RegisterValue(_) => {
+                ResponseDecoder::for_command(&Command::ReadRegister { register: 0 }).decode(encoded)
+            }
+            _ => Response::decode(encoded),
+        };
+        proptest::prop_assert_eq!(decoded.unwrap(), resp);
+    }
+}
+
+/// Returns the `DecodeOptions` needed to decode `cmd` after encoding it.
+#[cfg(feature = "testutil")]
+fn roundtrip_options(cmd: &Command) -> DecodeOptions {
+    use std::boxed::Box;
+
+    let options = DecodeOptions::new()
+        .allow_long_dlc(true)
+        .allow_unknown(true);
+    match *cmd {
+        Command::SetupWithBitrate {
+            bitrate: bitrate @ Bitrate::NonStandard { .. },
+        } => options.extra_bitrates(Box::leak(Box::new([bitrate]))),
+        Command::Vendor { opcode, .. } => {
+            options.vendor_commands(Box::leak(Box::new([VendorCommand::any_payload(opcode)])))
+        }
+        _ => options,
+    }
+}
+
+#[test]
+fn decode_all() {
+    let decode = |input: &[u8], options| {
+        Command::decode_all_with(input, options)
+            .map(|res| res.map_err(|e| e.kind()))
+            .collect::<Vec<_>>()
+    };
+
+    let options = DecodeOptions::new();
+    assert_eq!(decode(b"", options), []);
+    assert_eq!(decode(b"O\r", options), [Ok(Command::Open)]);
+    assert_eq!(
+        decode(b"\rO\rC\rt1232AABB", options),
+        [
+            Err(ErrorKind::UnsupportedCommand),
+            Ok(Command::Open),
+            Ok(Command::Close),
+            Err(ErrorKind::Eof),
+        ],
+    );
+
+    let options = DecodeOptions::new().allow_lf(true);
+    assert_eq!(
+        decode(b"O\r\nC\n\n", options),
+        [Ok(Command::Open), Ok(Command::Close)],
+    );
+}
+
+#[test]
+fn decode_lowercase_hex() {
+    err("t7ff1aa\r", ErrorKind::InvalidHexDigit);
+    err("T1234abcd0\r", ErrorKind::InvalidHexDigit);
+
+    let options = DecodeOptions::new().allow_lowercase_hex(true);
+    for (lower, upper) in [
+        (&b"t7ff2aabb\r"[..], &b"t7FF2AABB\r"[..]),
+        (b"T1234abcd1eF\r", b"T1234ABCD1EF\r"),
+        (b"r7ff8\r", b"r7FF8\r"),
+        (
+            b"d1239000102030405060708090a0b\r",
+            b"d1239000102030405060708090A0B\r",
+        ),
+        (b"m0000abcd\r", b"m0000ABCD\r"),
+        (b"s01dc\r", b"s01DC\r"),
+    ]
+    .iter()
+    {
+        assert_eq!(
+            Command::decode_with(lower, options).unwrap(),
+            Command::decode(upper).unwrap(),
+        );
+
+        let mut decoder = StreamDecoder::with_options(options);
+        let res = lower.iter().find_map(|byte| match decoder.push(*byte) {
+            Poll::Ready(res) => Some(res),
+            Poll::Pending => None,
+        });
+        assert_eq!(res.unwrap().unwrap(), Command::decode(upper).unwrap());
+    }
+    assert_eq!(
+        Command::decode_with(b"t7fg1aa\r", options)
+            .unwrap_err()
+            .kind(),
+        ErrorKind::InvalidHexDigit,
+    );
+
+    assert_eq!(
+        Notification::decode_with(b"t7ff1aa\r", options).unwrap(),
+        Notification::decode(b"t7FF1AA\r").unwrap(),
+    );
+    assert_eq!(
+        Reader::new(b"ab")
+            .allow_lowercase_hex(true)
+            .read_hex_u8()
+            .unwrap(),
+        0xAB
+    );
+}
+
+/// Decodes `input` as a `Notification` and checks that it encodes back to `input`.
+fn notif(input: &str) -> Notification {
+    let notif = Notification::decode(input.as_bytes()).unwrap();
+    let mut buf = crate::write::NotificationBuf::new();
+    assert_eq!(notif.encode(&mut buf).unwrap(), input.as_bytes());
+    notif
+}
+
+fn notif_err(input: &str, expected: ErrorKind) {
+    assert_eq!(
+        Notification::decode(input.as_bytes()).unwrap_err().kind(),
+        expected,
+    );
+}
+
+#[test]
+fn decode_notifs() {
+    match notif("t10021133\r") {
+        Notification::Rx { identifier, frame } => {
+            assert_eq!(identifier.as_raw(), 0x100);
+            assert_eq!(frame.data(), [0x11, 0x33]);
+        }
+        n => panic!("unexpected notification {:?}", n),
+    }
+    match notif("T1FFFFFFF80001020304050607\r") {
+        Notification::RxExt { identifier, frame } => {
+            assert_eq!(identifier.as_raw(), 0x1FFF_FFFF);
+            assert_eq!(frame.data(), [0, 1, 2, 3, 4, 5, 6, 7]);
+        }
+        n => panic!("unexpected notification {:?}", n),
+    }
+    match notif("r7FF8\r") {
+        Notification::RxRtr { identifier, len } => {
+            assert_eq!(identifier.as_raw(), 0x7FF);
+            assert_eq!(len, 8);
+        }
+        n => panic!("unexpected notification {:?}", n),
+    }
+    match notif("R000000005\r") {
+        Notification::RxExtRtr { identifier, len } => {
+            assert_eq!(identifier.as_raw(), 0);
+            assert_eq!(len, 5);
+        }
+        n => panic!("unexpected notification {:?}", n),
+    }
+    match notif("b1239000102030405060708090A0B\r") {
+        Notification::RxFd {
+            identifier,
+            frame,
+            flags,
+        } => {
+            assert_eq!(identifier.as_raw(), 0x123);
+            assert_eq!(frame.data(), [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]);
+            assert_eq!(flags, FdFlags::BRS);
+        }
+        n => panic!("unexpected notification {:?}", n),
+    }
+    match notif("D000000011AA\r") {
+        Notification::RxExtFd {
+            identifier,
+            frame,
+            flags,
+        } => {
+            assert_eq!(identifier.as_raw(), 1);
+            assert_eq!(frame.data(), [0xAA]);
+            assert_eq!(flags, FdFlags::empty());
+        }
+        n => panic!("unexpected notification {:?}", n),
+    }
+    match notif("e3bcO\r") {
+        Notification::ErrorFrame(errors) => {
+            assert_eq!(
+                errors,
+                BusErrors::BIT0 | BusErrors::CRC | BusErrors::TX_OVERRUN
+            );
+        }
+        n => panic!("unexpected notification {:?}", n),
+    }
+
+    notif_err("t1002113\r", ErrorKind::InvalidLength);
+    notif_err("t10021133", ErrorKind::Eof);
+    notif_err("t8000\r", ErrorKind::IdentifierOutOfRange);
+    notif_err("t1009\r", ErrorKind::InvalidLength);
+    notif_err("e2a\r", ErrorKind::InvalidLength);
+    notif_err("e1x\r", ErrorKind::InvalidValue);
+    notif_err("z\r", ErrorKind::UnsupportedCommand);
+}
+
+#[test]
+fn decode_responses() {
+    fn resp(input: &[u8], expected: Response) {
+        assert_eq!(Response::decode(input).unwrap(), expected);
+
+        // Encoding the decoded response must yield the original input.
+        let mut buf = crate::write::ResponseBuf::new();
+        assert_eq!(expected.encode(&mut buf).unwrap(), input);
+    }
+
+    resp(b"\x07", Response::Error);
+    resp(b"\r", Response::Ack);
+    resp(b"z\r", Response::TxAck);
+    resp(b"Z\r", Response::ExtTxAck);
+    resp(b"A\r", Response::PollAllEnd);
+    resp(
+        b"F81\r",
+        Response::Status(Status::RX_FIFO_FULL | Status::BUS_ERROR),
+    );
+    resp(b"E7F01\r", Response::ErrorCounters { tec: 0x7F, rec: 1 });
+    resp(
+        b"I0A0000000100000002\r",
+        Response::Busload {
+            load: 10,
+            rx_frames: 1,
+            tx_frames: 2,
+        },
+    );
+    resp(
+        b"V1013\r",
+        Response::Version {
+            hardware_version: 0x10,
+            software_version: 0x13,
+        },
+    );
+    resp(
+        b"v0123456789abcdef0123456789abcdef\r",
+        Response::DetailedVersion(VersionString::new("0123456789abcdef0123456789abcdef").unwrap()),
+    );
+    resp(
+        b"NA123\r",
+        Response::Serial(SerialNumber::new(*b"A123").unwrap()),
+    );
+
+    for input in &[&b"\x07\r"[..], b"\r\r", b"N1 23\r", b"V10\r", b"t1230\r"] {
+        assert!(
+            Response::decode(input).unwrap_err().kind().is_malformed(),
+            "{:?}",
+            input
+        );
+    }
+    assert_eq!(Response::decode(b"z").unwrap_err().kind(), ErrorKind::Eof);
+
+    // Reserved and vendor-specific status bits are ignored.
+    assert_eq!(
+        Response::decode(b"F14\r").unwrap(),
+        Response::Status(Status::ERROR_WARNING)
+    );
+}
+
+/// The longest classic CAN messages have to fit into the default buffers without truncation.
+#[test]
+fn classic_buffer_sizes() {
+    let cmd = b"9T1FFFFFFF8DEADBEEFDEADBEEF\r";
+    assert_eq!(cmd.len(), ChanneledCommand::MAX_ENCODED_LEN);
+    assert_eq!(cmd.len(), CommandBuf::LEN);
+    let mut buf = CommandBuf::new();
+    buf.tail_mut().copy_from_slice(cmd);
+    let decoded = buf
+        .advance_by_channeled(cmd.len() as u8)
+        .map(Result::unwrap)
+        .collect::<Vec<_>>();
+    assert_eq!(decoded.len(), 1);
+}
+
+/// The longest CAN FD messages have to fit into the FD buffers without truncation.
+#[test]
+fn fd_buffer_sizes() {
+    let data = "5A".repeat(CanFdFrame::MAX_LENGTH);
+
+    let cmd = format!("9B1FFFFFFFF{}\r", data);
+    assert_eq!(cmd.len(), ChanneledCommand::MAX_FD_ENCODED_LEN);
+    let mut buf = CommandBuf::new_fd();
+    buf.tail_mut().copy_from_slice(cmd.as_bytes());
+    let decoded = buf
+        .advance_by_channeled(cmd.len() as u8)
+        .map(Result::unwrap)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        decoded,
+        [ChanneledCommand {
+            channel: 9,
+            cmd: Command::TxExtFd {
+                identifier: ExtIdentifier::from_raw(0x1FFF_FFFF).unwrap(),
+                frame: CanFdFrame::from_slice(&[0x5A; 64]).unwrap(),
+                flags: FdFlags::BRS,
+            },
+        }]
+    );
+
+    let notif = format!("B1FFFFFFFF{}DEADBEEF\r", data);
+    let mut buf = HostBuf::new();
+    buf.set_timestamp_mode(TimestampMode::Microseconds);
+    let res = hostbuf_decode(&mut buf, &[notif.as_bytes()]);
+    match &res[..] {
+        [Ok(Incoming::Notification {
+            notif: Notification::RxExtFd { frame, .. },
+            timestamp: Some(Timestamp::Micros(0xDEAD_BEEF)),
+        })] => assert_eq!(frame.len(), 64),
+        r => panic!("unexpected result {:?}", r),
+    }
+}
+
+fn hostbuf_decode(buf: &mut HostBuf, chunks: &[&[u8]]) -> Vec<Result<Incoming, ErrorKind>> {
+    let mut res = Vec::new();
+    for chunk in chunks {
+        buf.tail_mut()[..chunk.len()].copy_from_slice(chunk);
+        res.extend(
+            buf.advance_by(chunk.len() as u8)
+                .map(|r| r.map_err(|e| e.kind())),
+        );
+    }
+    res
+}
+
+#[test]
+fn hostbuf() {
+    let mut buf = HostBuf::new();
+    let res = hostbuf_decode(
+        &mut buf,
+        &[b"z\rt1", b"230\r\x07\x07\r", b"INVALID\rF0", b"0\r"],
+    );
+    assert_eq!(res.len(), 7);
+    assert!(matches!(res[0], Ok(Incoming::Response(Response::TxAck))));
+    match &res[1] {
+        Ok(Incoming::Notification {
+            notif: Notification::Rx { identifier, frame },
+            timestamp: None,
+        }) => {
+            assert_eq!(identifier.as_raw(), 0x123);
+            assert!(frame.is_empty());
+        }
+        r => panic!("unexpected result {:?}", r),
+    }
+    assert!(matches!(res[2], Ok(Incoming::Response(Response::Error))));
+    assert!(matches!(res[3], Ok(Incoming::Response(Response::Error))));
+    assert!(matches!(res[4], Ok(Incoming::Response(Response::Ack))));
+    assert!(matches!(res[5], Err(ErrorKind::InvalidHexDigit)));
+    assert!(matches!(res[6], Ok(Incoming::Response(Response::Status(s))) if s.is_empty()));
+
+    // BELL in the middle of a line.
+    let res = hostbuf_decode(&mut buf, &[b"t12\x07\r"]);
+    assert_eq!(res.len(), 2);
+    assert!(matches!(res[0], Err(ErrorKind::MissingTerminator)));
+    assert!(matches!(res[1], Ok(Incoming::Response(Response::Ack))));
+
+    // Timestamps.
+    buf.set_timestamp_mode(TimestampMode::Milliseconds);
+    let res = hostbuf_decode(&mut buf, &[b"r1230EA5F\r", b"e1a0001\r"]);
+    assert_eq!(res.len(), 2);
+    assert!(matches!(
+        res[0],
+        Ok(Incoming::Notification {
+            notif: Notification::RxRtr { len: 0, .. },
+            timestamp: Some(Timestamp::Millis(0xEA5F)),
+        })
+    ));
+    assert!(matches!(
+        res[1],
+        Ok(Incoming::Notification {
+            notif: Notification::ErrorFrame(BusErrors::ACK),
+            timestamp: Some(Timestamp::Millis(1)),
+        })
+    ));
+
+    // Overlong lines.
+    let res = hostbuf_decode(&mut buf, &[&[b'v'; HOST_BUF_LEN], b"\r"]);
+    assert_eq!(res.len(), 2);
+    assert!(matches!(res[0], Err(ErrorKind::Overflow)));
+    assert!(matches!(res[1], Ok(Incoming::Response(Response::Ack))));
+}
+
+#[test]
+fn response_decoder() {
+    let decode = |cmd: &str, input: &[u8]| {
+        let cmd = Command::decode(cmd.as_bytes()).unwrap();
+        ResponseDecoder::for_command(&cmd)
+            .decode(input)
+            .map_err(|e| e.kind())
+    };
+
+    assert_eq!(decode("O\r", b"\r"), Ok(Response::Ack));
+    assert_eq!(decode("O\r", b"\x07"), Ok(Response::Error));
+    assert_eq!(decode("O\r", b"z\r"), Err(ErrorKind::Decode));
+    assert_eq!(decode("t1230\r", b"z\r"), Ok(Response::TxAck));
+    assert_eq!(decode("t1230\r", b"Z\r"), Err(ErrorKind::Decode));
+    assert_eq!(decode("t1230\r", b"\r"), Err(ErrorKind::Decode));
+    assert_eq!(decode("R000000000\r", b"Z\r"), Ok(Response::ExtTxAck));
+    assert_eq!(decode("A\r", b"A\r"), Ok(Response::PollAllEnd));
+    assert_eq!(decode("P\r", b"\r"), Ok(Response::Ack));
+    assert_eq!(
+        decode("F\r", b"F00\r"),
+        Ok(Response::Status(Status::empty()))
+    );
+    assert_eq!(decode("F\r", b"V0000\r"), Err(ErrorKind::Decode));
+    assert_eq!(
+        decode("N\r", b"NABCD\r"),
+        Ok(Response::Serial(SerialNumber::new(*b"ABCD").unwrap()))
+    );
+    assert_eq!(decode("N\r", b"NABC"), Err(ErrorKind::Eof));
+}
+
+#[test]
+#[cfg(feature = "usbtin")]
+fn response_decoder_usbtin() {
+    let decoder = ResponseDecoder::for_command(&Command::ReadRegister { register: 0x0E });
+    assert_eq!(
+        decoder.decode(b"A5\r").unwrap(),
+        Response::RegisterValue(0xA5)
+    );
+    assert_eq!(decoder.decode(b"\x07").unwrap(), Response::Error);
+    assert_eq!(
+        decoder.decode(b"\r").unwrap_err().kind(),
+        ErrorKind::InvalidLength
+    );
+}
+
+#[test]
+fn demux() {
+    let mut demux = Demux::new();
+    assert!(matches!(
+        demux.classify(b"z\r"),
+        Ok(Incoming::Response(Response::TxAck))
+    ));
+
+    demux.command_sent(&Command::ReadStatus);
+    assert!(demux.is_awaiting_response());
+    assert!(matches!(
+        demux.classify(b"t1230\r"),
+        Ok(Incoming::Notification { .. })
+    ));
+    assert!(demux.is_awaiting_response());
+    assert!(matches!(
+        demux.classify(b"F04\r"),
+        Ok(Incoming::Response(Response::Status(Status::ERROR_WARNING)))
+    ));
+    assert!(!demux.is_awaiting_response());
+
+    // Mismatched responses are rejected.
+    demux.command_sent(&Command::Open);
+    assert_eq!(
+        demux.classify(b"z\r").unwrap_err().kind(),
+        ErrorKind::Decode
+    );
+    assert!(!demux.is_awaiting_response());
+
+    // A polled frame answers `PollOne`.
+    demux.command_sent(&Command::PollOne);
+    assert!(matches!(
+        demux.classify(b"r1230\r"),
+        Ok(Incoming::Notification { .. })
+    ));
+    assert!(!demux.is_awaiting_response());
+    demux.command_sent(&Command::PollOne);
+    assert!(matches!(
+        demux.classify(b"\r"),
+        Ok(Incoming::Response(Response::Ack))
+    ));
+}
+
+#[test]
+#[cfg(feature = "usbtin")]
+fn demux_register_value() {
+    let mut demux = Demux::new();
+    for (msg, value) in [(&b"B3\r"[..], 0xB3), (b"D0\r", 0xD0), (b"1F\r", 0x1F)] {
+        demux.command_sent(&Command::ReadRegister { register: 0x2A });
+        assert!(matches!(
+            demux.classify(msg),
+            Ok(Incoming::Response(Response::RegisterValue(v))) if v == value
+        ));
+        assert!(!demux.is_awaiting_response());
+    }
+
+    // Notifications are still recognized while a register read is pending.
+    demux.command_sent(&Command::ReadRegister { register: 0x2A });
+    assert!(matches!(
+        demux.classify(b"t1230\r"),
+        Ok(Incoming::Notification { .. })
+    ));
+    assert!(demux.is_awaiting_response());
+}
+
+#[test]
+fn demux_error_frame_timestamps() {
+    use crate::write::{NotificationBuf, TimestampedNotification};
+
+    let notif = Notification::ErrorFrame(BusErrors::ACK | BusErrors::CRC);
+    let mut demux = Demux::new();
+    assert!(matches!(
+        demux.classify(b"e2ac\r"),
+        Ok(Incoming::Notification {
+            timestamp: None,
+            ..
+        })
+    ));
+
+    // Error frames carry timestamps like frame notifications.
+    for (timestamped, mode) in [
+        (
+            TimestampedNotification::new(notif.clone(), 0x1234),
+            TimestampMode::Milliseconds,
+        ),
+        (
+            TimestampedNotification::new_micros(notif.clone(), 0xDEAD_BEEF),
+            TimestampMode::Microseconds,
+        ),
+    ]
+    .iter()
+    {
+        let mut buf = NotificationBuf::new();
+        let encoded = timestamped.encode(&mut buf).unwrap();
+        demux.set_timestamp_mode(*mode);
+        match demux.classify(encoded) {
+            Ok(Incoming::Notification {
+                notif: decoded,
+                timestamp: Some(timestamp),
+            }) => {
+                assert_eq!(decoded, notif);
+                assert_eq!(timestamp, timestamped.timestamp());
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+}
+
+#[test]
+fn host_fd() {
+    let mut buf = HostBuf::new();
+    buf.set_timestamp_mode(TimestampMode::Milliseconds);
+
+    let cmd = Command::decode(b"B000000010\r").unwrap();
+    buf.command_sent(&cmd);
+    let res = hostbuf_decode(
+        &mut buf,
+        &[b"d12300001\rb1239000102030405", b"060708090A0B1234\rZ\r"],
+    );
+    assert_eq!(res.len(), 3);
+    match &res[0] {
+        Ok(Incoming::Notification {
+            notif: Notification::RxFd { frame, flags, .. },
+            timestamp: Some(Timestamp::Millis(1)),
+        }) => {
+            assert!(frame.is_empty());
+            assert_eq!(*flags, FdFlags::empty());
+        }
+        r => panic!("unexpected result {:?}", r),
+    }
+    match &res[1] {
+        Ok(Incoming::Notification {
+            notif:
+                Notification::RxFd {
+                    identifier,
+                    frame,
+                    flags,
+                },
+            timestamp: Some(Timestamp::Millis(0x1234)),
+        }) => {
+            assert_eq!(identifier.as_raw(), 0x123);
+            assert_eq!(frame.len(), 12);
+            assert_eq!(*flags, FdFlags::BRS);
+        }
+        r => panic!("unexpected result {:?}", r),
+    }
+    assert!(matches!(res[2], Ok(Incoming::Response(Response::ExtTxAck))));
+
+    // FD frames answer `PollOne` just like classic frames.
+    let mut demux = Demux::new();
+    demux.command_sent(&Command::PollOne);
+    assert!(matches!(
+        demux.classify(b"D000000011AA\r"),
+        Ok(Incoming::Notification { .. })
+    ));
+    assert!(!demux.is_awaiting_response());
+}
+
+#[test]
+fn reader() {
+    fn needed<T: core::fmt::Debug>(result: Result<T, Error>) -> Option<usize> {
+        let err = result.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Eof);
+        err.bytes_needed()
+    }
+
+    let mut reader = Reader::new(b"1");
+    assert_eq!(needed(reader.read_ext_identifier()), Some(7));
+    assert_eq!(needed(reader.read_hex_u16()), Some(3));
+    assert_eq!(reader.read_hex_u4().unwrap(), 1);
+    assert_eq!(needed(reader.read_byte()), Some(1));
+    assert_eq!(needed(reader.finish()), Some(1));
+
+    // Frames are only consumed once all data is available.
+    let mut reader = Reader::new(b"3AABB");
+    assert_eq!(needed(reader.read_frame()), Some(2));
+    assert_eq!(reader.remaining(), b"3AABB");
+    let mut reader = Reader::new(b"9AABB");
+    assert_eq!(needed(reader.read_fd_frame()), Some(20));
+    assert_eq!(reader.remaining(), b"9AABB");
+    assert_eq!(
+        reader.read_frame().unwrap_err().kind(),
+        ErrorKind::InvalidLength
+    );
+
+    let mut reader = Reader::new(b"vendor");
+    assert_eq!(needed(reader.read_until_cr()), Some(1));
+    let mut reader = Reader::new(b"x1\r");
+    assert_eq!(
+        reader.read_bool().unwrap_err().kind(),
+        ErrorKind::InvalidValue
+    );
+    assert_eq!(reader.read_byte().unwrap(), b'x');
+    assert!(reader.read_bool().unwrap());
+    reader.finish().unwrap();
+
+    // Numbers wider than a `u32` are rejected instead of panicking.
+    let mut reader = Reader::new(b"123456789\r");
+    assert_eq!(
+        reader.read_hex(9).unwrap_err().kind(),
+        ErrorKind::InvalidLength
+    );
+    assert_eq!(reader.read_hex(8).unwrap(), 0x12345678);
+
+    // Errors that are not caused by the decoder have no hint.
+    assert_eq!(
+        CanFrame::from([0; 8]).push(0).unwrap_err().bytes_needed(),
+        None
+    );
+    assert_eq!(needed(Command::decode(b"t12")), Some(1));
 }
@@ -0,0 +1,310 @@
+//! `serde` support for types that have to be validated when deserialized.
+//!
+//! Types without invariants derive `Serialize` and `Deserialize` where they are defined. Fields of
+//! those types that only have a limited range of valid values are checked by the functions at the
+//! end of this module.
+
+use crate::read::RawPayload;
+use crate::{
+    BusErrors, CanFdFrame, CanFrame, ExtIdentifier, FdFlags, Identifier, SerialNumber, Status,
+    VersionString,
+};
+use core::convert::TryFrom;
+use core::fmt;
+use serde::de::{self, Deserializer, SeqAccess, Unexpected, Visitor};
+use serde::{Deserialize, Serialize, Serializer};
+
+/// Up to `N` bytes, serialized as a byte string.
+struct Bytes<const N: usize> {
+    bytes: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> Bytes<N> {
+    fn new(data: &[u8]) -> Self {
+        let mut bytes = [0; N];
+        bytes[..data.len()].copy_from_slice(data);
+        Self {
+            bytes,
+            len: data.len(),
+        }
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+}
+
+impl<const N: usize> Serialize for Bytes<N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.as_slice())
+    }
+}
+
+impl<'de, const N: usize> Deserialize<'de> for Bytes<N> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_bytes(BytesVisitor)
+    }
+}
+
+struct BytesVisitor<const N: usize>;
+
+impl<'de, const N: usize> Visitor<'de> for BytesVisitor<N> {
+    type Value = Bytes<N>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at most {} bytes", N)
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        if v.len() > N {
+            return Err(E::invalid_length(v.len(), &self));
+        }
+
+        Ok(Bytes::new(v))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut bytes = Bytes::new(&[]);
+        while let Some(byte) = seq.next_element()? {
+            if bytes.len == N {
+                return Err(de::Error::invalid_length(N + 1, &self));
+            }
+
+            bytes.bytes[bytes.len] = byte;
+            bytes.len += 1;
+        }
+        Ok(bytes)
+    }
+}
+
+/// Deserializes a `T` from a string, using `parse` to validate it.
+struct StrVisitor<T> {
+    parse: fn(&str) -> Option<T>,
+    expected: &'static str,
+}
+
+impl<'de, T> Visitor<'de> for StrVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.expected)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        (self.parse)(v).ok_or_else(|| E::invalid_value(Unexpected::Str(v), &self))
+    }
+}
+
+/// Identifiers are serialized as their raw value.
+impl Serialize for Identifier {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.as_raw().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Identifier {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = u16::deserialize(deserializer)?;
+        Identifier::from_raw(raw).ok_or_else(|| {
+            de::Error::invalid_value(Unexpected::Unsigned(raw.into()), &"an 11-bit identifier")
+        })
+    }
+}
+
+/// Identifiers are serialized as their raw value.
+impl Serialize for ExtIdentifier {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.as_raw().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ExtIdentifier {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = u32::deserialize(deserializer)?;
+        ExtIdentifier::from_raw(raw).ok_or_else(|| {
+            de::Error::invalid_value(Unexpected::Unsigned(raw.into()), &"a 29-bit identifier")
+        })
+    }
+}
+
+macro_rules! impl_flags {
+    ($($flags:ident),+) => {
+        $(
+            /// Flags are serialized as their raw bits.
+            impl Serialize for $flags {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    self.bits().serialize(serializer)
+                }
+            }
+
+            impl<'de> Deserialize<'de> for $flags {
+                fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    let bits = u8::deserialize(deserializer)?;
+                    $flags::from_bits(bits).ok_or_else(|| {
+                        de::Error::invalid_value(
+                            Unexpected::Unsigned(bits.into()),
+                            &concat!("valid `", stringify!($flags), "` bits"),
+                        )
+                    })
+                }
+            }
+        )+
+    };
+}
+
+impl_flags!(Status, BusErrors, FdFlags);
+
+/// Serial numbers are serialized as a string of 4 alphanumeric characters.
+impl Serialize for SerialNumber {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // The constructors only accept ASCII characters.
+        serializer.serialize_str(core::str::from_utf8(&self.0).unwrap())
+    }
+}
+
+impl<'de> Deserialize<'de> for SerialNumber {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(StrVisitor {
+            parse: |s| SerialNumber::new(<[u8; 4]>::try_from(s.as_bytes()).ok()?),
+            expected: "4 alphanumeric ASCII characters",
+        })
+    }
+}
+
+impl Serialize for VersionString {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for VersionString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(StrVisitor {
+            parse: VersionString::new,
+            expected: "at most 32 printable ASCII characters",
+        })
+    }
+}
+
+/// Classic CAN frames are serialized with their DLC, since it can exceed the data length.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "CanFrame")]
+struct CanFrameRepr {
+    dlc: u8,
+    data: Bytes<{ CanFrame::MAX_LENGTH }>,
+}
+
+impl Serialize for CanFrame {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        CanFrameRepr {
+            dlc: self.dlc(),
+            data: Bytes::new(self.data()),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for CanFrame {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = CanFrameRepr::deserialize(deserializer)?;
+        // Can not fail, `Bytes` limits the length.
+        let frame = CanFrame::from_slice(repr.data.as_slice()).unwrap();
+        if repr.dlc == frame.dlc() {
+            return Ok(frame);
+        }
+
+        frame.with_dlc(repr.dlc).ok_or_else(|| {
+            de::Error::invalid_value(
+                Unexpected::Unsigned(repr.dlc.into()),
+                &"the data length, or 9 to 15 for frames with 8 data bytes",
+            )
+        })
+    }
+}
+
+/// CAN FD frames are serialized as their data bytes.
+impl Serialize for CanFdFrame {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.data())
+    }
+}
+
+impl<'de> Deserialize<'de> for CanFdFrame {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = Bytes::<{ CanFdFrame::MAX_LENGTH }>::deserialize(deserializer)?;
+        CanFdFrame::from_slice(data.as_slice())
+            .map_err(|_| de::Error::invalid_length(data.len, &"a valid CAN FD frame length"))
+    }
+}
+
+impl Serialize for RawPayload {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.as_bytes())
+    }
+}
+
+impl<'de> Deserialize<'de> for RawPayload {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = Bytes::<{ RawPayload::MAX_LENGTH }>::deserialize(deserializer)?;
+        if data.as_slice().contains(&b'\r') {
+            return Err(de::Error::invalid_value(
+                Unexpected::Bytes(data.as_slice()),
+                &"a payload without `CR`",
+            ));
+        }
+
+        // Can not fail, `Bytes` limits the length.
+        Ok(RawPayload::from_slice(data.as_slice()).unwrap())
+    }
+}
+
+// The functions below are used via `deserialize_with` for fields of derived types that are stored
+// as plain integers, but only have a limited range of valid values.
+
+/// Deserializes a `T` and checks that it is at most `max`.
+fn at_most<'de, D, T>(deserializer: D, max: T, expected: &'static str) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + PartialOrd + Into<u64>,
+{
+    let value = T::deserialize(deserializer)?;
+    if value > max {
+        return Err(de::Error::invalid_value(
+            Unexpected::Unsigned(value.into()),
+            &expected,
+        ));
+    }
+
+    Ok(value)
+}
+
+pub(crate) fn channel<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u8, D::Error> {
+    at_most(deserializer, 9, "a channel number in range 0..=9")
+}
+
+pub(crate) fn dlc<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u8, D::Error> {
+    at_most(deserializer, 15, "a data length code in range 0..=15")
+}
+
+pub(crate) fn busload<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u8, D::Error> {
+    at_most(deserializer, 100, "a percentage in range 0..=100")
+}
+
+pub(crate) fn millis<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u16, D::Error> {
+    at_most(deserializer, 0xEA5F, "a millisecond timestamp below 60000")
+}
+
+/// Non-standard bitrate codes must not collide with the standard codes `0` to `8`, or terminate
+/// the command.
+pub(crate) fn bitrate_code<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u8, D::Error> {
+    let code = u8::deserialize(deserializer)?;
+    if matches!(code, b'0'..=b'8' | b'\r') {
+        return Err(de::Error::invalid_value(
+            Unexpected::Unsigned(code.into()),
+            &"a non-standard bitrate code",
+        ));
+    }
+
+    Ok(code)
+}
@@ -0,0 +1,93 @@
+//! Extraction of bit-field signals from frame payloads.
+
+use crate::{Error, ErrorKind};
+use defmt::Format;
+
+/// Byte order of a signal in a frame payload.
+///
+/// Bits are numbered like in DBC files: bit `n` is bit `n % 8` (counted from the least
+/// significant bit) of data byte `n / 8`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Format)]
+pub enum ByteOrder {
+    /// Little endian ("Intel"). The start bit is the least significant bit of the signal, and
+    /// the signal extends towards higher bit numbers.
+    LittleEndian,
+
+    /// Big endian ("Motorola"). The start bit is the most significant bit of the signal, and
+    /// the signal continues towards the least significant bit of the same byte, then with the
+    /// most significant bit of the next byte.
+    BigEndian,
+}
+
+/// Returns the bit position following `pos` when walking a signal from its start bit.
+fn next_bit(pos: usize, order: ByteOrder) -> usize {
+    match (order, pos % 8) {
+        (ByteOrder::LittleEndian, _) => pos + 1,
+        // Continue with the most significant bit of the next byte.
+        (ByteOrder::BigEndian, 0) => pos + 15,
+        (ByteOrder::BigEndian, _) => pos - 1,
+    }
+}
+
+/// Checks that a signal is between 1 and 64 bits long and fits into `data`.
+fn check(data: &[u8], start_bit: usize, length: usize, order: ByteOrder) -> Result<(), Error> {
+    // Checking the start bit first keeps `next_bit` from overflowing.
+    if length == 0 || length > 64 || start_bit / 8 >= data.len() {
+        return Err(Error::new(ErrorKind::InvalidLength));
+    }
+
+    let mut pos = start_bit;
+    for _ in 1..length {
+        pos = next_bit(pos, order);
+    }
+
+    if pos / 8 < data.len() {
+        Ok(())
+    } else {
+        Err(Error::new(ErrorKind::InvalidLength))
+    }
+}
+
+pub(crate) fn get(data: &[u8], start_bit: usize, length: usize, order: ByteOrder) -> Option<u64> {
+    check(data, start_bit, length, order).ok()?;
+
+    let mut value = 0;
+    let mut pos = start_bit;
+    for i in 0..length {
+        let bit = u64::from(data[pos / 8] >> (pos % 8) & 1);
+        match order {
+            ByteOrder::LittleEndian => value |= bit << i,
+            ByteOrder::BigEndian => value = value << 1 | bit,
+        }
+        pos = next_bit(pos, order);
+    }
+
+    Some(value)
+}
+
+pub(crate) fn set(
+    data: &mut [u8],
+    start_bit: usize,
+    length: usize,
+    order: ByteOrder,
+    value: u64,
+) -> Result<(), Error> {
+    check(data, start_bit, length, order)?;
+
+    let mut pos = start_bit;
+    for i in 0..length {
+        let shift = match order {
+            ByteOrder::LittleEndian => i,
+            ByteOrder::BigEndian => length - 1 - i,
+        };
+        let mask = 1 << (pos % 8);
+        if value >> shift & 1 != 0 {
+            data[pos / 8] |= mask;
+        } else {
+            data[pos / 8] &= !mask;
+        }
+        pos = next_bit(pos, order);
+    }
+
+    Ok(())
+}
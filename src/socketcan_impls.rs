@@ -0,0 +1,112 @@
+//! Conversions between frames and `socketcan` frames.
+//!
+//! Identifiers convert via the `embedded_can` identifier types, which `socketcan` re-exports.
+//!
+//! SocketCAN's classic frames have no room for a DLC above 8, so data frames with such a DLC are
+//! converted to frames with a DLC of 8, and remote frames have their DLC clamped to 8.
+
+use crate::{CanFdFrame, CanFrame, FdFlags, Frame, FrameKind};
+use core::convert::TryFrom;
+use socketcan::frame::FdFlags as SocketFdFlags;
+use socketcan::{CanAnyFrame, CanDataFrame, CanRemoteFrame, EmbeddedFrame};
+
+fn fd_flags(flags: SocketFdFlags) -> FdFlags {
+    let mut converted = FdFlags::empty();
+    converted.set(FdFlags::BRS, flags.contains(SocketFdFlags::BRS));
+    converted.set(FdFlags::ESI, flags.contains(SocketFdFlags::ESI));
+    converted
+}
+
+fn socket_fd_flags(flags: FdFlags) -> SocketFdFlags {
+    let mut converted = SocketFdFlags::empty();
+    converted.set(SocketFdFlags::BRS, flags.contains(FdFlags::BRS));
+    converted.set(SocketFdFlags::ESI, flags.contains(FdFlags::ESI));
+    converted
+}
+
+impl From<CanDataFrame> for Frame {
+    fn from(frame: CanDataFrame) -> Self {
+        Self {
+            id: frame.id().into(),
+            // `CanDataFrame` holds at most 8 bytes.
+            kind: FrameKind::Data(CanFrame::from_slice(frame.data()).unwrap()),
+        }
+    }
+}
+
+impl From<CanRemoteFrame> for Frame {
+    fn from(frame: CanRemoteFrame) -> Self {
+        Self {
+            id: frame.id().into(),
+            kind: FrameKind::Remote(frame.dlc() as u8),
+        }
+    }
+}
+
+impl From<socketcan::CanFdFrame> for Frame {
+    fn from(frame: socketcan::CanFdFrame) -> Self {
+        Self {
+            id: frame.id().into(),
+            kind: FrameKind::Fd {
+                // SocketCAN pads FD frames to a valid length.
+                frame: CanFdFrame::from_slice_padded(frame.data()).unwrap(),
+                flags: fd_flags(frame.flags()),
+            },
+        }
+    }
+}
+
+/// Converts a data or remote frame, handing back error frames as the error.
+impl TryFrom<socketcan::CanFrame> for Frame {
+    type Error = socketcan::CanFrame;
+
+    fn try_from(frame: socketcan::CanFrame) -> Result<Self, Self::Error> {
+        match frame {
+            socketcan::CanFrame::Data(frame) => Ok(frame.into()),
+            socketcan::CanFrame::Remote(frame) => Ok(frame.into()),
+            socketcan::CanFrame::Error(_) => Err(frame),
+        }
+    }
+}
+
+/// Converts a data, remote or CAN FD frame, handing back error frames as the error.
+impl TryFrom<CanAnyFrame> for Frame {
+    type Error = CanAnyFrame;
+
+    fn try_from(frame: CanAnyFrame) -> Result<Self, Self::Error> {
+        match frame {
+            CanAnyFrame::Normal(frame) => Ok(frame.into()),
+            CanAnyFrame::Remote(frame) => Ok(frame.into()),
+            CanAnyFrame::Fd(frame) => Ok(frame.into()),
+            CanAnyFrame::Error(_) => Err(frame),
+        }
+    }
+}
+
+impl From<Frame> for CanAnyFrame {
+    fn from(frame: Frame) -> Self {
+        let id = socketcan::Id::from(frame.id);
+        match frame.kind {
+            FrameKind::Data(data) => CanAnyFrame::Normal(CanDataFrame::new(id, &data).unwrap()),
+            FrameKind::Remote(dlc) => CanAnyFrame::Remote(
+                CanRemoteFrame::new_remote(id, usize::from(dlc.min(8))).unwrap(),
+            ),
+            FrameKind::Fd { frame, flags } => CanAnyFrame::Fd(
+                socketcan::CanFdFrame::with_flags(id, &frame, socket_fd_flags(flags)).unwrap(),
+            ),
+        }
+    }
+}
+
+/// Converts a classic CAN frame, handing back CAN FD frames as the error.
+impl TryFrom<Frame> for socketcan::CanFrame {
+    type Error = Frame;
+
+    fn try_from(frame: Frame) -> Result<Self, Frame> {
+        match CanAnyFrame::from(frame) {
+            CanAnyFrame::Normal(frame) => Ok(frame.into()),
+            CanAnyFrame::Remote(frame) => Ok(frame.into()),
+            _ => Err(frame),
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! `proptest` strategies for the protocol types.
+//!
+//! These generate valid values of the types in this crate, which allows firmware and host
+//! applications to property-test their command and response handling.
+//!
+//! Generated messages survive an encoding round trip: decoding them yields the same value, as long
+//! as `DecodeOptions::allow_long_dlc` and `allow_unknown` are enabled, and the vendor command or
+//! non-standard bitrate a command uses is registered.
+//!
+//! Identifiers and frames are generated from plain integer and byte ranges, and shrink towards
+//! small values. All other types are generated through their `arbitrary::Arbitrary`
+//! implementations from a byte string, which shrinks towards the simplest value of the type.
+//!
+//! # Examples
+//!
+//! ```
+//! use mini_slcan::testutil;
+//! use mini_slcan::write::CommandEncodeBuf;
+//! use proptest::proptest;
+//!
+//! proptest!(|(cmd in testutil::command())| {
+//!     let mut buf = CommandEncodeBuf::new();
+//!     assert_eq!(cmd.encode(&mut buf).unwrap().len(), cmd.encoded_len());
+//! });
+//! ```
+
+use crate::read::{ChanneledCommand, Command, Incoming};
+use crate::write::{Notification, Response, TimestampedNotification};
+use crate::{CanFdFrame, CanFrame, ExtIdentifier, Frame, Id, Identifier};
+use arbitrary::{Arbitrary, Unstructured};
+use proptest::collection::vec;
+use proptest::prelude::*;
+use std::vec::Vec;
+
+/// Generates values of `T` through its `arbitrary::Arbitrary` implementation.
+///
+/// Byte strings that `T::arbitrary` rejects are filtered out.
+pub fn arbitrary<T>() -> impl Strategy<Value = T>
+where
+    T: for<'a> Arbitrary<'a> + core::fmt::Debug,
+{
+    any::<Vec<u8>>().prop_filter_map("rejected by `Arbitrary`", |bytes| {
+        T::arbitrary_take_rest(Unstructured::new(&bytes)).ok()
+    })
+}
+
+/// Generates standard 11-bit identifiers.
+pub fn identifier() -> impl Strategy<Value = Identifier> {
+    (0..=0x7FF_u16).prop_map(|raw| Identifier::from_raw(raw).unwrap())
+}
+
+/// Generates extended 29-bit identifiers.
+pub fn ext_identifier() -> impl Strategy<Value = ExtIdentifier> {
+    (0..=0x1FFF_FFFF_u32).prop_map(|raw| ExtIdentifier::from_raw(raw).unwrap())
+}
+
+/// Generates standard and extended identifiers.
+pub fn id() -> impl Strategy<Value = Id> {
+    prop_oneof![
+        identifier().prop_map(Id::Standard),
+        ext_identifier().prop_map(Id::Extended)
+    ]
+}
+
+/// Generates classic CAN frames, including frames with 8 data bytes and a DLC of 9 to 15.
+pub fn can_frame() -> impl Strategy<Value = CanFrame> {
+    (vec(any::<u8>(), 0..=CanFrame::MAX_LENGTH), 8..=15_u8).prop_map(|(data, dlc)| {
+        let frame = CanFrame::from_slice(&data).unwrap();
+        frame.with_dlc(dlc).unwrap_or(frame)
+    })
+}
+
+/// Generates CAN FD frames of every valid length.
+pub fn can_fd_frame() -> impl Strategy<Value = CanFdFrame> {
+    (0..=15_u8)
+        .prop_flat_map(|dlc| vec(any::<u8>(), CanFdFrame::dlc_to_len(dlc).unwrap()))
+        .prop_map(|data| CanFdFrame::from_slice(&data).unwrap())
+}
+
+/// Generates data, remote and CAN FD frames.
+pub fn frame() -> impl Strategy<Value = Frame> {
+    arbitrary()
+}
+
+/// Generates commands sent from a host to an SLCAN device.
+pub fn command() -> impl Strategy<Value = Command> {
+    arbitrary()
+}
+
+/// Generates commands addressed to a specific channel.
+pub fn channeled_command() -> impl Strategy<Value = ChanneledCommand> {
+    arbitrary()
+}
+
+/// Generates responses sent by an SLCAN device.
+pub fn response() -> impl Strategy<Value = Response> {
+    arbitrary()
+}
+
+/// Generates notifications sent by an SLCAN device.
+pub fn notification() -> impl Strategy<Value = Notification> {
+    arbitrary()
+}
+
+/// Generates notifications, with or without a timestamp.
+pub fn timestamped_notification() -> impl Strategy<Value = TimestampedNotification> {
+    arbitrary()
+}
+
+/// Generates messages received by a host.
+pub fn incoming() -> impl Strategy<Value = Incoming> {
+    arbitrary()
+}
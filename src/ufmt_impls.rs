@@ -0,0 +1,103 @@
+//! `ufmt` implementations for types that can not use `derive(uDebug)`.
+//!
+//! Identifiers are formatted like their `Debug` implementations, and frames like `CanFrame`'s
+//! `Display` implementation.
+
+use crate::read::RawPayload;
+use crate::{CanFdFrame, CanFrame, ExtIdentifier, FdFlags, Identifier};
+use ufmt::{uDebug, uDisplay, uWrite, uwrite, Formatter};
+
+impl uDebug for Identifier {
+    fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+        uwrite!(f, "0x{:03X}", self.as_raw())
+    }
+}
+
+/// Formats the identifier as a hexadecimal number, like its `Debug` implementation.
+impl uDisplay for Identifier {
+    fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+        uDebug::fmt(self, f)
+    }
+}
+
+impl uDebug for ExtIdentifier {
+    fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+        uwrite!(f, "0x{:08X}", self.as_raw())
+    }
+}
+
+/// Formats the identifier as a hexadecimal number, like its `Debug` implementation.
+impl uDisplay for ExtIdentifier {
+    fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+        uDebug::fmt(self, f)
+    }
+}
+
+/// Writes the length of a frame in brackets, followed by its data bytes, like `candump` does.
+fn fmt_frame<W: uWrite + ?Sized>(f: &mut Formatter<'_, W>, data: &[u8]) -> Result<(), W::Error> {
+    uwrite!(f, "[{}]", data.len())?;
+    for byte in data {
+        uwrite!(f, " {:02X}", *byte)?;
+    }
+    Ok(())
+}
+
+impl uDebug for CanFrame {
+    fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+        f.debug_struct("CanFrame")?
+            .field("data", &self.data())?
+            .field("dlc", &self.dlc)?
+            .finish()
+    }
+}
+
+/// Formats the frame like its `Display` implementation.
+impl uDisplay for CanFrame {
+    fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+        fmt_frame(f, self.data())
+    }
+}
+
+impl uDebug for CanFdFrame {
+    fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+        f.debug_struct("CanFdFrame")?
+            .field("data", &self.data())?
+            .field("dlc", &self.dlc)?
+            .finish()
+    }
+}
+
+/// Formats the frame like `CanFrame`'s `Display` implementation.
+impl uDisplay for CanFdFrame {
+    fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+        fmt_frame(f, self.data())
+    }
+}
+
+impl uDebug for FdFlags {
+    fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+        let mut first = true;
+        for (flag, name) in [(FdFlags::BRS, "BRS"), (FdFlags::ESI, "ESI")].iter() {
+            if self.contains(*flag) {
+                if !first {
+                    f.write_str(" | ")?;
+                }
+                f.write_str(name)?;
+                first = false;
+            }
+        }
+
+        if first {
+            f.write_str("(empty)")?;
+        }
+        Ok(())
+    }
+}
+
+impl uDebug for RawPayload {
+    fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+        f.debug_struct("RawPayload")?
+            .field("bytes", &self.as_bytes())?
+            .finish()
+    }
+}
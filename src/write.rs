@@ -3,16 +3,21 @@
 #[cfg(test)]
 mod tests;
 
-use core::mem;
-
-use crate::{CanFrame, Error, ExtIdentifier, Identifier, SerialNumber, Status};
+use crate::read::{ChanneledCommand, Command, ResponseKind};
+use crate::{
+    AutoStartup, Bitrate, BusErrors, CanFdFrame, CanFrame, DataBitrate, Error, ErrorKind,
+    ExtIdentifier, FdFlags, Field, FilterMode, Frame, FrameKind, Id, Identifier, SerialBaud,
+    SerialNumber, Status, TimestampMode, VersionString,
+};
 use defmt::Format;
 
-const MAX_RESPONSE_LEN: usize = 6;
-const MAX_NOTIF_LEN: usize = 1 + 8 + 1 + 16 + 4 + 1; // Tiiiiiiiilddddddddddddddddssss\r
+// Both include a leading channel number for multi-channel devices.
+const MAX_RESPONSE_LEN: usize = 1 + 1 + VersionString::MAX_LENGTH + 1; // nv<version string>\r
+const MAX_NOTIF_LEN: usize = 1 + 1 + 8 + 1 + 16 + 8 + 1; // nTiiiiiiiildd..ddssssssss\r
+const MAX_FD_NOTIF_LEN: usize = 1 + 1 + 8 + 1 + 128 + 8 + 1; // nDiiiiiiiildd..ddssssssss\r
 
 /// A byte buffer that can hold any `Response`.
-#[derive(Default, Debug)]
+#[derive(Debug)]
 pub struct ResponseBuf([u8; MAX_RESPONSE_LEN]);
 
 impl ResponseBuf {
@@ -31,8 +36,16 @@ impl ResponseBuf {
     }
 }
 
+impl Default for ResponseBuf {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// A response to a `Command`.
 #[derive(Debug, Clone, Eq, PartialEq, Format)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 #[non_exhaustive]
 pub enum Response {
     /// General error response (ASCII BELL).
@@ -47,22 +60,199 @@ pub enum Response {
     /// Extended CAN frame enqueued for transmission.
     ExtTxAck,
 
+    /// Terminates the list of CAN frames sent in response to the `PollAll` command (`A`).
+    PollAllEnd,
+
     /// Status flags response.
     Status(Status),
 
+    /// Response to the `ReadErrorCounters` command.
+    ErrorCounters {
+        /// Transmit error counter.
+        tec: u8,
+        /// Receive error counter.
+        rec: u8,
+    },
+
+    /// Response to the `ReadBusload` command.
+    Busload {
+        /// Bus load in percent. Must be in range 0..=100.
+        #[cfg_attr(
+            feature = "serde",
+            serde(deserialize_with = "crate::serde_impls::busload")
+        )]
+        #[cfg_attr(feature = "arbitrary", arbitrary(with = crate::arbitrary_impls::busload))]
+        load: u8,
+        /// Number of frames received since the channel was opened.
+        rx_frames: u32,
+        /// Number of frames transmitted since the channel was opened.
+        tx_frames: u32,
+    },
+
     /// Response to the `ReadVersion` command.
     Version {
         hardware_version: u8,
         software_version: u8,
     },
 
+    /// Response to the `ReadDetailedVersion` command.
+    DetailedVersion(VersionString),
+
     /// Response to the `ReadSerial` command.
     Serial(SerialNumber),
+
+    /// Response to the `ReadRegister` command (USBtin only).
+    #[cfg(feature = "usbtin")]
+    RegisterValue(u8),
 }
 
 impl Response {
+    /// Returns the response to send after executing `cmd`, given the outcome of the execution.
+    ///
+    /// If `result` is an error, this returns `Response::Error`. Otherwise, this returns the
+    /// acknowledgement appropriate for `cmd`: `TxAck` for standard frames, `ExtTxAck` for extended
+    /// frames, and `Ack` for all other commands that are answered without data.
+    ///
+    /// Returns `None` if `cmd` was executed successfully, but is answered with data (like
+    /// `ReadStatus`) or CAN frames (like `PollAll`), or if the response to `cmd` is unknown. The
+    /// caller has to construct the response in that case.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_slcan::read::Command;
+    /// use mini_slcan::write::Response;
+    ///
+    /// let cmd = Command::decode(b"T000000000\r").unwrap();
+    /// assert_eq!(Response::for_result(&cmd, Ok::<_, ()>(())), Some(Response::ExtTxAck));
+    /// assert_eq!(Response::for_result(&cmd, Err(())), Some(Response::Error));
+    /// assert_eq!(Response::for_result(&Command::ReadStatus, Ok::<_, ()>(())), None);
+    /// ```
+    pub fn for_result<E>(cmd: &Command, result: Result<(), E>) -> Option<Self> {
+        if result.is_err() {
+            return Some(Response::Error);
+        }
+
+        match cmd.expected_response() {
+            ResponseKind::Ack => Some(Response::Ack),
+            ResponseKind::TxAck => Some(Response::TxAck),
+            ResponseKind::ExtTxAck => Some(Response::ExtTxAck),
+            _ => None,
+        }
+    }
+
+    /// Returns the letter identifying this response, or `None` if the response has none.
+    ///
+    /// `Error` and `Ack` consist of only a single byte (BELL or `CR`), and USBtin's
+    /// `RegisterValue` response only contains the register value.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_slcan::write::Response;
+    ///
+    /// assert_eq!(Response::TxAck.opcode(), Some(b'z'));
+    /// assert_eq!(Response::Ack.opcode(), None);
+    /// ```
+    pub fn opcode(&self) -> Option<u8> {
+        Some(match self {
+            Response::Error | Response::Ack => return None,
+            Response::TxAck => b'z',
+            Response::ExtTxAck => b'Z',
+            Response::PollAllEnd => b'A',
+            Response::Status(_) => b'F',
+            Response::ErrorCounters { .. } => b'E',
+            Response::Busload { .. } => b'I',
+            Response::Version { .. } => b'V',
+            Response::DetailedVersion(_) => b'v',
+            Response::Serial(_) => b'N',
+            #[cfg(feature = "usbtin")]
+            Response::RegisterValue(_) => return None,
+        })
+    }
+
     pub fn encode<'a>(&self, buf: &'a mut ResponseBuf) -> Result<&'a [u8], Error> {
-        let mut writer = Writer { buf: &mut buf.0 };
+        encode_with(&mut buf.0, |writer| self.write_to(writer))
+    }
+
+    /// Returns the number of bytes `encode` writes for this response, without encoding it.
+    ///
+    /// `encode_channeled` writes one additional byte.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_slcan::write::{Response, ResponseBuf};
+    ///
+    /// let resp = Response::Version { hardware_version: 1, software_version: 2 };
+    /// assert_eq!(resp.encoded_len(), 6);
+    /// assert_eq!(resp.encoded_len(), resp.encode(&mut ResponseBuf::new()).unwrap().len());
+    /// ```
+    pub fn encoded_len(&self) -> usize {
+        measure_with(|writer| self.write_to(writer))
+    }
+
+    /// Encodes this response and writes it to `writer`, including the terminating `CR`.
+    ///
+    /// The response is encoded on the stack and passed to `writer` with a single `write_all`
+    /// call. `writer` is not flushed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_slcan::write::Response;
+    ///
+    /// // `&mut [u8]` implements `embedded_io::Write`. Firmware would pass its UART or USB driver
+    /// // instead.
+    /// let mut out = [0; 8];
+    /// Response::TxAck.encode_to(&mut out[..]).unwrap();
+    /// assert_eq!(&out[..2], b"z\r");
+    /// ```
+    #[cfg(feature = "embedded-io")]
+    pub fn encode_to<W: embedded_io::Write>(&self, mut writer: W) -> Result<(), W::Error> {
+        let mut buf = ResponseBuf::new();
+        // Can not fail, the buffer fits every response.
+        writer.write_all(self.encode(&mut buf).unwrap())
+    }
+
+    /// Encodes this response and writes it to a `std::io::Write` implementation, including the
+    /// terminating `CR`.
+    ///
+    /// Like `encode_to`, this passes the encoded response to `writer` with a single `write_all`
+    /// call, and does not flush `writer`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_slcan::write::Response;
+    ///
+    /// let mut out = Vec::new();
+    /// Response::TxAck.encode_to_std(&mut out).unwrap();
+    /// assert_eq!(out, b"z\r");
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn encode_to_std<W: std::io::Write>(&self, mut writer: W) -> std::io::Result<()> {
+        let mut buf = ResponseBuf::new();
+        // Can not fail, the buffer fits every response.
+        writer.write_all(self.encode(&mut buf).unwrap())
+    }
+
+    /// Encodes this response, prefixed with the number of the CAN channel it refers to.
+    ///
+    /// This is used by devices with multiple CAN channels. Returns an error of kind
+    /// `InvalidValue` if `channel` is greater than 9.
+    pub fn encode_channeled<'a>(
+        &self,
+        channel: u8,
+        buf: &'a mut ResponseBuf,
+    ) -> Result<&'a [u8], Error> {
+        encode_with(&mut buf.0, |writer| {
+            writer.write_channel(channel)?;
+            self.write_to(writer)
+        })
+    }
+
+    fn write_to(&self, writer: &mut Writer<'_>) -> Result<(), Error> {
         match self {
             Response::Error => {
                 // BELL (ASCII 7) - not followed by CR
@@ -77,9 +267,30 @@ impl Response {
                 writer.write(b'Z')?;
                 writer.write(b'\r')?;
             }
+            Response::PollAllEnd => {
+                writer.write(b'A')?;
+                writer.write(b'\r')?;
+            }
             Response::Status(flags) => {
                 writer.write(b'F')?;
-                writer.write_hex_u8(flags.bits().into())?;
+                writer.write_hex_u8(flags.bits())?;
+                writer.write(b'\r')?;
+            }
+            Response::ErrorCounters { tec, rec } => {
+                writer.write(b'E')?;
+                writer.write_hex_u8(*tec)?;
+                writer.write_hex_u8(*rec)?;
+                writer.write(b'\r')?;
+            }
+            Response::Busload {
+                load,
+                rx_frames,
+                tx_frames,
+            } => {
+                writer.write(b'I')?;
+                writer.write_hex_u8(*load)?;
+                writer.write_hex(*rx_frames, 8)?;
+                writer.write_hex(*tx_frames, 8)?;
                 writer.write(b'\r')?;
             }
             Response::Version {
@@ -87,8 +298,15 @@ impl Response {
                 software_version,
             } => {
                 writer.write(b'V')?;
-                writer.write_hex_u8((*hardware_version).into())?;
-                writer.write_hex_u8((*software_version).into())?;
+                writer.write_hex_u8(*hardware_version)?;
+                writer.write_hex_u8(*software_version)?;
+                writer.write(b'\r')?;
+            }
+            Response::DetailedVersion(version) => {
+                writer.write(b'v')?;
+                for byte in version.as_bytes() {
+                    writer.write(*byte)?;
+                }
                 writer.write(b'\r')?;
             }
             Response::Serial(serial) => {
@@ -98,25 +316,50 @@ impl Response {
                 }
                 writer.write(b'\r')?;
             }
+            #[cfg(feature = "usbtin")]
+            Response::RegisterValue(value) => {
+                writer.write_hex_u8(*value)?;
+                writer.write(b'\r')?;
+            }
         }
 
-        let remaining = writer.buf.len();
-        let used = buf.0.len() - remaining;
-        Ok(&buf.0[..used])
+        Ok(())
     }
 }
 
-#[derive(Default, Debug)]
-pub struct NotificationBuf([u8; MAX_NOTIF_LEN]);
+/// A byte buffer that can hold a `Notification` or `TimestampedNotification`.
+///
+/// The buffer created by `NotificationBuf::new` can hold any classic CAN notification. CAN FD
+/// notifications require the larger buffer created by `NotificationBuf::new_fd`, and fail to encode
+/// with an `ErrorKind::Eof` error otherwise.
+#[derive(Debug)]
+pub struct NotificationBuf<const N: usize = MAX_NOTIF_LEN>([u8; N]);
 
 impl NotificationBuf {
+    pub const LEN: usize = MAX_NOTIF_LEN;
+    pub const FD_LEN: usize = MAX_FD_NOTIF_LEN;
+
     pub const fn new() -> Self {
         Self([0; MAX_NOTIF_LEN])
     }
 }
 
+impl NotificationBuf<MAX_FD_NOTIF_LEN> {
+    pub const fn new_fd() -> Self {
+        Self([0; MAX_FD_NOTIF_LEN])
+    }
+}
+
+impl Default for NotificationBuf {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// An unprompted message sent by the SLCAN device.
-#[derive(Debug, Format)]
+#[derive(Debug, Clone, Eq, PartialEq, Format)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum Notification {
     Rx {
         identifier: Identifier,
@@ -130,20 +373,200 @@ pub enum Notification {
 
     RxRtr {
         identifier: Identifier,
-        /// Must be in range 0..=8.
+        /// Data length code. Must be in range 0..=15.
+        #[cfg_attr(feature = "serde", serde(deserialize_with = "crate::serde_impls::dlc"))]
+        #[cfg_attr(feature = "arbitrary", arbitrary(with = crate::arbitrary_impls::dlc))]
         len: u8,
     },
 
     RxExtRtr {
         identifier: ExtIdentifier,
-        /// Must be in range 0..=8.
+        /// Data length code. Must be in range 0..=15.
+        #[cfg_attr(feature = "serde", serde(deserialize_with = "crate::serde_impls::dlc"))]
+        #[cfg_attr(feature = "arbitrary", arbitrary(with = crate::arbitrary_impls::dlc))]
         len: u8,
     },
+
+    /// A received standard CAN FD frame (`d`, or `b` if `FdFlags::BRS` is set).
+    ///
+    /// The notification letters only convey the `BRS` flag, so `ESI` is not encoded and never set
+    /// by the decoder.
+    RxFd {
+        identifier: Identifier,
+        frame: CanFdFrame,
+        #[cfg_attr(feature = "arbitrary", arbitrary(with = crate::arbitrary_impls::fd_flags))]
+        flags: FdFlags,
+    },
+
+    /// A received extended CAN FD frame (`D`, or `B` if `FdFlags::BRS` is set).
+    RxExtFd {
+        identifier: ExtIdentifier,
+        frame: CanFdFrame,
+        #[cfg_attr(feature = "arbitrary", arbitrary(with = crate::arbitrary_impls::fd_flags))]
+        flags: FdFlags,
+    },
+
+    /// Reports CAN bus errors as an error frame.
+    ///
+    /// This uses the error frame format understood by the Linux `slcan` driver: `e`, followed by
+    /// the number of errors and one letter per reported error.
+    ErrorFrame(BusErrors),
 }
 
 impl Notification {
-    pub fn encode<'a>(&self, buf: &'a mut NotificationBuf) -> Result<&'a [u8], Error> {
-        let mut writer = Writer { buf: &mut buf.0 };
+    /// Creates the notification reporting the reception of `frame`.
+    ///
+    /// The notification variant is chosen according to the identifier type and whether `frame` is
+    /// a remote frame.
+    ///
+    /// Returns an error with kind `InvalidLength` if `frame` is a data frame whose DLC differs from
+    /// its data length or which carries more than 8 bytes, or a remote frame with a DLC above 15.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # struct Frame;
+    /// # impl embedded_can::Frame for Frame {
+    /// #     fn new(_: impl Into<embedded_can::Id>, _: &[u8]) -> Option<Self> { None }
+    /// #     fn new_remote(_: impl Into<embedded_can::Id>, _: usize) -> Option<Self> { None }
+    /// #     fn is_extended(&self) -> bool { false }
+    /// #     fn is_remote_frame(&self) -> bool { false }
+    /// #     fn id(&self) -> embedded_can::Id { embedded_can::StandardId::new(0x123).unwrap().into() }
+    /// #     fn dlc(&self) -> usize { 1 }
+    /// #     fn data(&self) -> &[u8] { &[0xAA] }
+    /// # }
+    /// # fn receive() -> Frame { Frame }
+    /// use mini_slcan::write::{Notification, NotificationBuf};
+    ///
+    /// let frame = receive(); // from the CAN peripheral
+    /// let notif = Notification::from_frame(&frame)?;
+    /// let mut buf = NotificationBuf::new();
+    /// assert_eq!(notif.encode(&mut buf)?, b"t1231AA\r");
+    /// # Ok::<(), mini_slcan::Error>(())
+    /// ```
+    #[cfg(feature = "embedded-can")]
+    pub fn from_frame<F: embedded_can::Frame>(frame: &F) -> Result<Self, Error> {
+        use embedded_can::Id;
+
+        if frame.is_remote_frame() {
+            if frame.dlc() > 15 {
+                return Err(Error::new(ErrorKind::InvalidLength));
+            }
+            let len = frame.dlc() as u8;
+            return Ok(match frame.id() {
+                Id::Standard(id) => Notification::RxRtr {
+                    identifier: id.into(),
+                    len,
+                },
+                Id::Extended(id) => Notification::RxExtRtr {
+                    identifier: id.into(),
+                    len,
+                },
+            });
+        }
+
+        if frame.dlc() != frame.data().len() {
+            return Err(Error::new(ErrorKind::InvalidLength));
+        }
+        let data = CanFrame::from_slice(frame.data())?;
+        Ok(match frame.id() {
+            Id::Standard(id) => Notification::Rx {
+                identifier: id.into(),
+                frame: data,
+            },
+            Id::Extended(id) => Notification::RxExt {
+                identifier: id.into(),
+                frame: data,
+            },
+        })
+    }
+
+    /// Returns the received CAN frame reported by this notification, if any.
+    pub fn to_frame(&self) -> Option<Frame> {
+        let (id, kind) = match *self {
+            Notification::Rx { identifier, frame } => (identifier.into(), FrameKind::Data(frame)),
+            Notification::RxExt { identifier, frame } => {
+                (identifier.into(), FrameKind::Data(frame))
+            }
+            Notification::RxRtr { identifier, len } => (identifier.into(), FrameKind::Remote(len)),
+            Notification::RxExtRtr { identifier, len } => {
+                (identifier.into(), FrameKind::Remote(len))
+            }
+            Notification::RxFd {
+                identifier,
+                frame,
+                flags,
+            } => (identifier.into(), FrameKind::Fd { frame, flags }),
+            Notification::RxExtFd {
+                identifier,
+                frame,
+                flags,
+            } => (identifier.into(), FrameKind::Fd { frame, flags }),
+            Notification::ErrorFrame(_) => return None,
+        };
+
+        Some(Frame { id, kind })
+    }
+
+    pub fn encode<'a, const N: usize>(
+        &self,
+        buf: &'a mut NotificationBuf<N>,
+    ) -> Result<&'a [u8], Error> {
+        encode_with(&mut buf.0, |writer| self.write_to(writer))
+    }
+
+    /// Returns the number of bytes `encode` writes for this notification, without encoding it.
+    ///
+    /// `encode_channeled` writes one additional byte.
+    pub fn encoded_len(&self) -> usize {
+        measure_with(|writer| self.write_to(writer))
+    }
+
+    /// Encodes this notification and writes it to `writer`, including the terminating `CR`.
+    ///
+    /// The notification is encoded on the stack and passed to `writer` with a single `write_all`
+    /// call. `writer` is not flushed.
+    #[cfg(feature = "embedded-io")]
+    pub fn encode_to<W: embedded_io::Write>(&self, mut writer: W) -> Result<(), W::Error> {
+        let mut buf = NotificationBuf::new_fd();
+        // Can not fail, the buffer fits every notification.
+        writer.write_all(self.encode(&mut buf).unwrap())
+    }
+
+    /// Encodes this notification and writes it to a `std::io::Write` implementation, including the
+    /// terminating `CR`.
+    ///
+    /// Like `encode_to`, this passes the encoded notification to `writer` with a single `write_all`
+    /// call, and does not flush `writer`.
+    #[cfg(feature = "std")]
+    pub fn encode_to_std<W: std::io::Write>(&self, mut writer: W) -> std::io::Result<()> {
+        let mut buf = NotificationBuf::new_fd();
+        // Can not fail, the buffer fits every notification.
+        writer.write_all(self.encode(&mut buf).unwrap())
+    }
+
+    /// Encodes this notification, prefixed with the number of the CAN channel it refers to.
+    ///
+    /// This is used by devices with multiple CAN channels. Returns an error of kind
+    /// `InvalidValue` if `channel` is greater than 9.
+    pub fn encode_channeled<'a, const N: usize>(
+        &self,
+        channel: u8,
+        buf: &'a mut NotificationBuf<N>,
+    ) -> Result<&'a [u8], Error> {
+        encode_with(&mut buf.0, |writer| {
+            writer.write_channel(channel)?;
+            self.write_to(writer)
+        })
+    }
+
+    fn write_to(&self, writer: &mut Writer<'_>) -> Result<(), Error> {
+        self.write_body(writer)?;
+        writer.write(b'\r')
+    }
+
+    /// Writes the notification without the terminating `CR`.
+    fn write_body(&self, writer: &mut Writer<'_>) -> Result<(), Error> {
         match self {
             Notification::Rx { identifier, frame } => {
                 writer.write(b't')?;
@@ -165,89 +588,546 @@ impl Notification {
                 writer.write_ext_identifier(*identifier)?;
                 writer.write_hex_u4(*len)?;
             }
+            Notification::RxFd {
+                identifier,
+                frame,
+                flags,
+            } => {
+                writer.write(if flags.contains(FdFlags::BRS) {
+                    b'b'
+                } else {
+                    b'd'
+                })?;
+                writer.write_identifier(*identifier)?;
+                writer.write_fd_frame(frame)?;
+            }
+            Notification::RxExtFd {
+                identifier,
+                frame,
+                flags,
+            } => {
+                writer.write(if flags.contains(FdFlags::BRS) {
+                    b'B'
+                } else {
+                    b'D'
+                })?;
+                writer.write_ext_identifier(*identifier)?;
+                writer.write_fd_frame(frame)?;
+            }
+            Notification::ErrorFrame(errors) => {
+                writer.write(b'e')?;
+                writer.write_hex_u4(errors.bits().count_ones() as u8)?;
+                for (flag, code) in &crate::BUS_ERROR_CODES {
+                    if errors.contains(*flag) {
+                        writer.write(*code)?;
+                    }
+                }
+            }
         }
 
-        let remaining = writer.buf.len();
-        let used = buf.0.len() - remaining;
-        Ok(&buf.0[..used])
+        Ok(())
+    }
+}
+
+impl From<Frame> for Notification {
+    /// Creates the notification reporting the reception of `frame`.
+    fn from(frame: Frame) -> Self {
+        match (frame.id, frame.kind) {
+            (Id::Standard(identifier), FrameKind::Data(frame)) => {
+                Notification::Rx { identifier, frame }
+            }
+            (Id::Extended(identifier), FrameKind::Data(frame)) => {
+                Notification::RxExt { identifier, frame }
+            }
+            (Id::Standard(identifier), FrameKind::Remote(len)) => {
+                Notification::RxRtr { identifier, len }
+            }
+            (Id::Extended(identifier), FrameKind::Remote(len)) => {
+                Notification::RxExtRtr { identifier, len }
+            }
+            (Id::Standard(identifier), FrameKind::Fd { frame, flags }) => Notification::RxFd {
+                identifier,
+                frame,
+                flags,
+            },
+            (Id::Extended(identifier), FrameKind::Fd { frame, flags }) => Notification::RxExtFd {
+                identifier,
+                frame,
+                flags,
+            },
+        }
     }
 }
 
-/// A notification with 16-bit timestamp.
+/// A timestamp attached to a `TimestampedNotification`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Format)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum Timestamp {
+    /// 16-bit millisecond timestamp, encoded as 4 hex digits. Must be in range `0..=0xEA5F`.
+    Millis(
+        #[cfg_attr(
+            feature = "serde",
+            serde(deserialize_with = "crate::serde_impls::millis")
+        )]
+        #[cfg_attr(feature = "arbitrary", arbitrary(with = crate::arbitrary_impls::millis))]
+        u16,
+    ),
+
+    /// 32-bit microsecond timestamp, encoded as 8 hex digits.
+    Micros(u32),
+}
+
+/// A notification with a timestamp.
 ///
 /// Timestamps are disabled by default, and are turned on by the host by sending a `SetRxTimestamp`
-/// command.
-#[derive(Debug)]
+/// command. Depending on the requested `TimestampMode`, either a 16-bit millisecond or a 32-bit
+/// microsecond timestamp is appended.
+///
+/// Classic and CAN FD frame notifications carry timestamps in the same way. The buffer created by
+/// `NotificationBuf::new_fd` is large enough for a timestamped CAN FD frame with 64 data bytes.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct TimestampedNotification {
     notif: Notification,
-    timestamp: u16,
+    timestamp: Timestamp,
 }
 
 impl TimestampedNotification {
+    /// Creates a notification with a 16-bit millisecond timestamp.
+    ///
     /// `timestamp` must be in range `0..=0xEA5F`.
     pub fn new(notif: Notification, timestamp: u16) -> Self {
+        Self::with_timestamp(notif, Timestamp::Millis(timestamp))
+    }
+
+    /// Creates a notification with a 32-bit microsecond timestamp.
+    pub fn new_micros(notif: Notification, timestamp: u32) -> Self {
+        Self::with_timestamp(notif, Timestamp::Micros(timestamp))
+    }
+
+    pub fn with_timestamp(notif: Notification, timestamp: Timestamp) -> Self {
         Self { notif, timestamp }
     }
 
-    pub fn encode<'a>(&self, buf: &'a mut NotificationBuf) -> Result<&'a [u8], Error> {
-        let used = self.notif.encode(buf)?.len();
-        let mut writer = Writer {
-            buf: &mut buf.0[used..],
-        };
-        writer.write_hex_u16(self.timestamp)?;
+    /// Returns the notification without its timestamp.
+    pub fn notification(&self) -> &Notification {
+        &self.notif
+    }
 
-        let remaining = writer.buf.len();
-        let used = buf.0.len() - remaining;
-        Ok(&buf.0[..used])
+    pub fn timestamp(&self) -> Timestamp {
+        self.timestamp
+    }
+
+    /// Creates a timestamped notification reporting the reception of `frame`.
+    ///
+    /// See `Notification::from_frame`.
+    #[cfg(feature = "embedded-can")]
+    pub fn from_frame<F: embedded_can::Frame>(
+        frame: &F,
+        timestamp: Timestamp,
+    ) -> Result<Self, Error> {
+        Notification::from_frame(frame).map(|notif| Self::with_timestamp(notif, timestamp))
+    }
+
+    pub fn encode<'a, const N: usize>(
+        &self,
+        buf: &'a mut NotificationBuf<N>,
+    ) -> Result<&'a [u8], Error> {
+        encode_with(&mut buf.0, |writer| self.write_to(writer))
+    }
+
+    /// Returns the number of bytes `encode` writes for this notification, without encoding it.
+    ///
+    /// `encode_channeled` writes one additional byte.
+    pub fn encoded_len(&self) -> usize {
+        measure_with(|writer| self.write_to(writer))
+    }
+
+    /// Encodes this notification and writes it to `writer`, including the terminating `CR`.
+    ///
+    /// The notification is encoded on the stack and passed to `writer` with a single `write_all`
+    /// call. `writer` is not flushed.
+    #[cfg(feature = "embedded-io")]
+    pub fn encode_to<W: embedded_io::Write>(&self, mut writer: W) -> Result<(), W::Error> {
+        let mut buf = NotificationBuf::new_fd();
+        // Can not fail, the buffer fits every notification.
+        writer.write_all(self.encode(&mut buf).unwrap())
+    }
+
+    /// Encodes this notification and writes it to a `std::io::Write` implementation, including the
+    /// terminating `CR`.
+    ///
+    /// Like `encode_to`, this passes the encoded notification to `writer` with a single `write_all`
+    /// call, and does not flush `writer`.
+    #[cfg(feature = "std")]
+    pub fn encode_to_std<W: std::io::Write>(&self, mut writer: W) -> std::io::Result<()> {
+        let mut buf = NotificationBuf::new_fd();
+        // Can not fail, the buffer fits every notification.
+        writer.write_all(self.encode(&mut buf).unwrap())
+    }
+
+    /// Encodes this notification, prefixed with the number of the CAN channel it refers to.
+    ///
+    /// This is used by devices with multiple CAN channels. Returns an error of kind
+    /// `InvalidValue` if `channel` is greater than 9.
+    pub fn encode_channeled<'a, const N: usize>(
+        &self,
+        channel: u8,
+        buf: &'a mut NotificationBuf<N>,
+    ) -> Result<&'a [u8], Error> {
+        encode_with(&mut buf.0, |writer| {
+            writer.write_channel(channel)?;
+            self.write_to(writer)
+        })
+    }
+
+    fn write_to(&self, writer: &mut Writer<'_>) -> Result<(), Error> {
+        self.notif.write_body(writer)?;
+        match self.timestamp {
+            Timestamp::Millis(ms) => writer.write_hex_u16(ms)?,
+            Timestamp::Micros(us) => writer.write_hex(us, 8)?,
+        }
+        writer.write(b'\r')
     }
 }
 
-struct Writer<'a> {
-    buf: &'a mut [u8],
+/// A byte buffer that can hold any encoded `Command` or `ChanneledCommand`.
+///
+/// Unlike the device-side buffers, this is sized for CAN FD commands, since hosts have to be able to
+/// send every command.
+#[derive(Debug)]
+pub struct CommandEncodeBuf([u8; ChanneledCommand::MAX_FD_ENCODED_LEN]);
+
+impl CommandEncodeBuf {
+    pub const fn new() -> Self {
+        Self([0; ChanneledCommand::MAX_FD_ENCODED_LEN])
+    }
 }
 
-impl<'a> Writer<'a> {
-    fn write(&mut self, byte: u8) -> Result<(), Error> {
-        let buf = mem::replace(&mut self.buf, &mut []);
-        match buf {
-            [] => Err(Error::eof()),
-            [b, rest @ ..] => {
-                *b = byte;
-                self.buf = rest;
-                Ok(())
+impl Default for CommandEncodeBuf {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Command {
+    /// Encodes this command, including the terminating `CR`.
+    ///
+    /// This is meant to be used by host applications that talk to an SLCAN device.
+    pub fn encode<'a>(&self, buf: &'a mut CommandEncodeBuf) -> Result<&'a [u8], Error> {
+        encode_with(&mut buf.0, |writer| self.write_to(writer))
+    }
+
+    /// Returns the number of bytes `encode` writes for this command, without encoding it.
+    pub fn encoded_len(&self) -> usize {
+        measure_with(|writer| self.write_to(writer))
+    }
+
+    fn write_to(&self, writer: &mut Writer<'_>) -> Result<(), Error> {
+        writer.write(self.opcode())?;
+        match self {
+            Command::SetupWithBitrate { bitrate } => {
+                writer.write(match bitrate {
+                    Bitrate::_10kbit => b'0',
+                    Bitrate::_20kbit => b'1',
+                    Bitrate::_50kbit => b'2',
+                    Bitrate::_100kbit => b'3',
+                    Bitrate::_125kbit => b'4',
+                    Bitrate::_250kbit => b'5',
+                    Bitrate::_500kbit => b'6',
+                    Bitrate::_800kbit => b'7',
+                    Bitrate::_1mbit => b'8',
+                    Bitrate::NonStandard { code, .. } => *code,
+                })?;
+            }
+            Command::SetupWithBtr { btr0, btr1 } => {
+                writer.write_hex_u8(*btr0)?;
+                writer.write_hex_u8(*btr1)?;
+            }
+            Command::SetupDataBitrate { bitrate } => {
+                writer.write(match bitrate {
+                    DataBitrate::_2mbit => b'2',
+                    DataBitrate::_4mbit => b'4',
+                    DataBitrate::_5mbit => b'5',
+                    DataBitrate::_8mbit => b'8',
+                })?;
+            }
+            Command::TxStandard { identifier, frame } => {
+                writer.write_identifier(*identifier)?;
+                writer.write_frame(frame)?;
+            }
+            Command::TxExt { identifier, frame } => {
+                writer.write_ext_identifier(*identifier)?;
+                writer.write_frame(frame)?;
+            }
+            Command::TxStandardRtr { identifier, len } => {
+                writer.write_identifier(*identifier)?;
+                writer.write_hex_u4(*len)?;
+            }
+            Command::TxExtRtr { identifier, len } => {
+                writer.write_ext_identifier(*identifier)?;
+                writer.write_hex_u4(*len)?;
+            }
+            Command::TxFd {
+                identifier, frame, ..
+            } => {
+                writer.write_identifier(*identifier)?;
+                writer.write_fd_frame(frame)?;
+            }
+            Command::TxExtFd {
+                identifier, frame, ..
+            } => {
+                writer.write_ext_identifier(*identifier)?;
+                writer.write_fd_frame(frame)?;
+            }
+            Command::SetRxTimestamp { mode } => {
+                writer.write(match mode {
+                    TimestampMode::Off => b'0',
+                    TimestampMode::Milliseconds => b'1',
+                    TimestampMode::Microseconds => b'2',
+                })?;
+            }
+            Command::SetAutoPoll { enabled } => {
+                writer.write_bool(*enabled)?;
+            }
+            Command::SetAcceptanceCode(value) => {
+                writer.write_hex(value.as_raw(), 8)?;
+            }
+            Command::SetAcceptanceMask(value) => {
+                writer.write_hex(value.as_raw(), 8)?;
+            }
+            Command::SetFilterMode(mode) => {
+                writer.write(match mode {
+                    FilterMode::Dual => b'0',
+                    FilterMode::Single => b'1',
+                })?;
+            }
+            Command::SetAutoStartup(mode) => {
+                writer.write(match mode {
+                    AutoStartup::Off => b'0',
+                    AutoStartup::OpenNormal => b'1',
+                    AutoStartup::OpenListenOnly => b'2',
+                })?;
+            }
+            Command::SetSerialBaudrate { baud } => {
+                writer.write(match baud {
+                    SerialBaud::_230400 => b'0',
+                    SerialBaud::_115200 => b'1',
+                    SerialBaud::_57600 => b'2',
+                    SerialBaud::_38400 => b'3',
+                    SerialBaud::_19200 => b'4',
+                    SerialBaud::_9600 => b'5',
+                    SerialBaud::_2400 => b'6',
+                })?;
+            }
+            #[cfg(feature = "usbtin")]
+            Command::WriteRegister { register, value } => {
+                writer.write_hex_u8(*register)?;
+                writer.write_hex_u8(*value)?;
+            }
+            #[cfg(feature = "usbtin")]
+            Command::ReadRegister { register } => {
+                writer.write_hex_u8(*register)?;
+            }
+            Command::Open
+            | Command::OpenListenOnly
+            | Command::Close
+            | Command::PollOne
+            | Command::PollAll
+            | Command::ReadStatus
+            | Command::ReadErrorCounters
+            | Command::ReadBusload
+            | Command::ReadVersion
+            | Command::ReadDetailedVersion
+            | Command::ReadSerial => {}
+            Command::Unknown { payload, .. } | Command::Vendor { payload, .. } => {
+                writer.write_bytes(payload.as_bytes())?;
             }
         }
+
+        writer.write(b'\r')
+    }
+}
+
+impl ChanneledCommand {
+    /// Encodes this command, prefixed with its channel number and including the terminating `CR`.
+    pub fn encode<'a>(&self, buf: &'a mut CommandEncodeBuf) -> Result<&'a [u8], Error> {
+        encode_with(&mut buf.0, |writer| {
+            writer.write_channel(self.channel)?;
+            self.cmd.write_to(writer)
+        })
     }
 
-    fn write_hex_u4(&mut self, val: u8) -> Result<(), Error> {
+    /// Returns the number of bytes `encode` writes for this command, without encoding it.
+    pub fn encoded_len(&self) -> usize {
+        1 + self.cmd.encoded_len()
+    }
+}
+
+/// Invokes `f` with a `Writer` writing to `buf`, and returns the part of `buf` that was written to.
+fn encode_with(
+    buf: &mut [u8],
+    f: impl FnOnce(&mut Writer<'_>) -> Result<(), Error>,
+) -> Result<&[u8], Error> {
+    let mut writer = Writer::new(buf);
+    f(&mut writer)?;
+    Ok(writer.into_bytes())
+}
+
+/// Invokes `f` with a `Writer` that discards its output, and returns the number of bytes written.
+fn measure_with(f: impl FnOnce(&mut Writer<'_>) -> Result<(), Error>) -> usize {
+    let mut writer = Writer { buf: None, len: 0 };
+    match f(&mut writer) {
+        Ok(()) => writer.len,
+        Err(_) => unreachable!("writer without buffer cannot run out of space"),
+    }
+}
+
+/// Low-level encoder for SLCAN messages.
+///
+/// This is used to implement the `encode` methods in this module, and can be used to emit
+/// vendor-specific messages that use the same formatting for hex numbers, identifiers and frames.
+///
+/// All methods return an error when the buffer is full.
+///
+/// # Example
+///
+/// ```
+/// use mini_slcan::{CanFrame, Identifier};
+/// use mini_slcan::write::Writer;
+///
+/// let mut buf = [0; 32];
+/// let mut writer = Writer::new(&mut buf);
+/// writer.write(b'x').unwrap();
+/// writer.write_identifier(Identifier::new_const(0x123)).unwrap();
+/// writer.write_frame(&CanFrame::from([0xAB])).unwrap();
+/// assert_eq!(writer.finish().unwrap(), b"x1231AB\r");
+/// ```
+#[derive(Debug)]
+pub struct Writer<'a> {
+    /// The output buffer, or `None` if the output is only counted.
+    buf: Option<&'a mut [u8]>,
+    /// Number of bytes written so far.
+    len: usize,
+}
+
+impl<'a> Writer<'a> {
+    /// Creates a `Writer` that writes to the start of `buf`.
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        Self {
+            buf: Some(buf),
+            len: 0,
+        }
+    }
+
+    /// Returns the number of bytes written so far.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Terminates the message with `CR` and returns the encoded message.
+    pub fn finish(mut self) -> Result<&'a [u8], Error> {
+        self.write(b'\r')?;
+        Ok(self.into_bytes())
+    }
+
+    /// Returns the bytes written so far, without terminating the message.
+    pub fn into_bytes(self) -> &'a [u8] {
+        match self.buf {
+            Some(buf) => &buf[..self.len],
+            None => &[],
+        }
+    }
+
+    /// Writes a single byte.
+    pub fn write(&mut self, byte: u8) -> Result<(), Error> {
+        if let Some(buf) = self.buf.as_deref_mut() {
+            *buf.get_mut(self.len).ok_or_else(Error::eof)? = byte;
+        }
+
+        self.len += 1;
+        Ok(())
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        for byte in bytes {
+            self.write(*byte)?;
+        }
+        Ok(())
+    }
+
+    /// Writes `1` or `0`.
+    pub fn write_bool(&mut self, value: bool) -> Result<(), Error> {
+        self.write(if value { b'1' } else { b'0' })
+    }
+
+    /// Writes the channel number prefix used by multi-channel devices.
+    ///
+    /// Returns an error of kind `InvalidValue` if `channel` is greater than 9.
+    pub fn write_channel(&mut self, channel: u8) -> Result<(), Error> {
+        if channel > 9 {
+            return Err(Error::new(ErrorKind::InvalidValue).in_field(Field::Channel));
+        }
+        self.write(b'0' + channel)
+    }
+
+    /// Writes the low 4 bits of `val` as a single hex digit.
+    pub fn write_hex_u4(&mut self, val: u8) -> Result<(), Error> {
         self.write_hex(val.into(), 1)
     }
 
-    fn write_hex_u8(&mut self, val: u8) -> Result<(), Error> {
+    pub fn write_hex_u8(&mut self, val: u8) -> Result<(), Error> {
         self.write_hex(val.into(), 2)
     }
 
-    fn write_hex_u16(&mut self, val: u16) -> Result<(), Error> {
+    pub fn write_hex_u16(&mut self, val: u16) -> Result<(), Error> {
         self.write_hex(val.into(), 4)
     }
 
-    fn write_identifier(&mut self, id: Identifier) -> Result<(), Error> {
+    /// Writes a standard identifier as 3 hex digits.
+    pub fn write_identifier(&mut self, id: Identifier) -> Result<(), Error> {
         self.write_hex(id.as_raw().into(), 3)
     }
 
-    fn write_ext_identifier(&mut self, id: ExtIdentifier) -> Result<(), Error> {
-        self.write_hex(id.as_raw().into(), 8)
+    /// Writes an extended identifier as 8 hex digits.
+    pub fn write_ext_identifier(&mut self, id: ExtIdentifier) -> Result<(), Error> {
+        self.write_hex(id.as_raw(), 8)
+    }
+
+    /// Writes the DLC of `frame` as a single hex digit, followed by its data.
+    pub fn write_frame(&mut self, frame: &CanFrame) -> Result<(), Error> {
+        self.write_hex_u4(frame.dlc())?;
+        for b in frame.data() {
+            self.write_hex_u8(*b)?;
+        }
+        Ok(())
     }
 
-    fn write_frame(&mut self, frame: &CanFrame) -> Result<(), Error> {
-        self.write_hex_u4(frame.len() as u8)?;
+    /// Writes the DLC of `frame` as a single hex digit, followed by its data.
+    pub fn write_fd_frame(&mut self, frame: &CanFdFrame) -> Result<(), Error> {
+        self.write_hex_u4(frame.dlc())?;
         for b in frame.data() {
             self.write_hex_u8(*b)?;
         }
         Ok(())
     }
 
-    fn write_hex(&mut self, value: u32, digits: u8) -> Result<(), Error> {
+    /// Writes the lowest `digits` nibbles of `value` as uppercase hex digits, most significant
+    /// first.
+    ///
+    /// Returns an error of kind `InvalidLength` if `digits` is greater than 8, the number of
+    /// nibbles in a `u32`.
+    pub fn write_hex(&mut self, value: u32, digits: u8) -> Result<(), Error> {
+        if digits > 8 {
+            return Err(Error::new(ErrorKind::InvalidLength));
+        }
+
         let mut shift = digits * 4;
 
         for _ in 0..digits {
@@ -1,15 +1,24 @@
 use super::*;
+use crate::{ErrorKind, Id};
 
 fn enc_response(response: Response, expected: &[u8]) {
     let mut buf = ResponseBuf::new();
     let buf = response.encode(&mut buf).unwrap();
     assert_eq!(expected, buf);
+    assert_eq!(response.encoded_len(), buf.len());
 }
 
 fn enc_notif(notif: Notification, expected: &[u8]) {
-    let mut buf = NotificationBuf::new();
+    let mut buf = NotificationBuf::new_fd();
     let buf = notif.encode(&mut buf).unwrap();
     assert_eq!(expected, buf);
+    assert_eq!(notif.encoded_len(), buf.len());
+
+    // Classic CAN notifications also fit into the default buffer.
+    if !matches!(notif.to_frame(), Some(frame) if frame.is_fd()) {
+        let mut buf = NotificationBuf::new();
+        assert_eq!(notif.encode(&mut buf).unwrap(), expected);
+    }
 }
 
 #[test]
@@ -18,6 +27,7 @@ fn encode_simple_responses() {
     enc_response(Response::Ack, b"\r");
     enc_response(Response::TxAck, b"z\r");
     enc_response(Response::ExtTxAck, b"Z\r");
+    enc_response(Response::PollAllEnd, b"A\r");
 }
 
 #[test]
@@ -25,6 +35,30 @@ fn encode_status() {
     enc_response(Response::Status(Status::RX_FIFO_FULL), b"F01\r");
 }
 
+#[test]
+fn encode_error_counters() {
+    enc_response(Response::ErrorCounters { tec: 0, rec: 0 }, b"E0000\r");
+    enc_response(
+        Response::ErrorCounters {
+            tec: 0xFF,
+            rec: 0x7F,
+        },
+        b"EFF7F\r",
+    );
+}
+
+#[test]
+fn encode_busload() {
+    enc_response(
+        Response::Busload {
+            load: 100,
+            rx_frames: 0x1234,
+            tx_frames: 0xFFFF_FFFF,
+        },
+        b"I6400001234FFFFFFFF\r",
+    );
+}
+
 #[test]
 fn encode_version() {
     enc_response(
@@ -36,6 +70,25 @@ fn encode_version() {
     );
 }
 
+#[test]
+fn encode_detailed_version() {
+    enc_response(
+        Response::DetailedVersion(VersionString::new("1.0.3-abc").unwrap()),
+        b"v1.0.3-abc\r",
+    );
+    enc_response(
+        Response::DetailedVersion(VersionString::new("").unwrap()),
+        b"v\r",
+    );
+    enc_response(
+        Response::DetailedVersion(VersionString::new("0123456789abcdef0123456789abcdef").unwrap()),
+        b"v0123456789abcdef0123456789abcdef\r",
+    );
+
+    assert!(VersionString::new("0123456789abcdef0123456789abcdef0").is_none());
+    assert!(VersionString::new("1.0\r").is_none());
+}
+
 #[test]
 fn encode_serial() {
     enc_response(
@@ -44,6 +97,12 @@ fn encode_serial() {
     );
 }
 
+#[test]
+#[cfg(feature = "usbtin")]
+fn encode_register_value() {
+    enc_response(Response::RegisterValue(0x0E), b"0E\r");
+}
+
 #[test]
 fn encode_notifs() {
     enc_notif(
@@ -51,20 +110,940 @@ fn encode_notifs() {
             identifier: Identifier::from_raw(0x100).unwrap(),
             frame: [0x11, 0x33].into(),
         },
-        b"t10021133",
+        b"t10021133\r",
     );
     enc_notif(
         Notification::Rx {
             identifier: Identifier::from_raw(0x7FF).unwrap(),
             frame: [].into(),
         },
-        b"t7FF0",
+        b"t7FF0\r",
     );
     enc_notif(
         Notification::RxExtRtr {
             identifier: ExtIdentifier::from_raw(0).unwrap(),
             len: 5,
         },
-        b"R000000005",
+        b"R000000005\r",
+    );
+}
+
+#[test]
+fn encode_fd_notifs() {
+    enc_notif(
+        Notification::RxFd {
+            identifier: Identifier::from_raw(0x123).unwrap(),
+            frame: CanFdFrame::from_slice(&[0xAA; 12]).unwrap(),
+            flags: FdFlags::empty(),
+        },
+        b"d1239AAAAAAAAAAAAAAAAAAAAAAAA\r",
+    );
+    enc_notif(
+        Notification::RxExtFd {
+            identifier: ExtIdentifier::from_raw(0x1FFF_FFFF).unwrap(),
+            frame: CanFdFrame::new(),
+            flags: FdFlags::BRS | FdFlags::ESI,
+        },
+        b"B1FFFFFFF0\r",
+    );
+
+    // The longest notification still fits into a CAN FD `NotificationBuf`, but not into the
+    // default one.
+    let notif = TimestampedNotification::new_micros(
+        Notification::RxExtFd {
+            identifier: ExtIdentifier::from_raw(0x1FFF_FFFF).unwrap(),
+            frame: CanFdFrame::from_slice(&[0x55; 64]).unwrap(),
+            flags: FdFlags::BRS,
+        },
+        0xDEAD_BEEF,
+    );
+    let mut buf = NotificationBuf::new_fd();
+    let encoded = notif.encode_channeled(9, &mut buf).unwrap();
+    assert_eq!(encoded.len(), NotificationBuf::FD_LEN);
+    let mut buf = NotificationBuf::new();
+    let err = notif.encode_channeled(9, &mut buf).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Eof);
+
+    // The longest classic notification fits into the default buffer.
+    let notif = TimestampedNotification::new_micros(
+        Notification::RxExt {
+            identifier: ExtIdentifier::from_raw(0x1FFF_FFFF).unwrap(),
+            frame: CanFrame::from_slice(&[0x55; 8]).unwrap(),
+        },
+        0xDEAD_BEEF,
+    );
+    let mut buf = NotificationBuf::new();
+    let encoded = notif.encode_channeled(9, &mut buf).unwrap();
+    assert_eq!(encoded.len(), NotificationBuf::LEN);
+}
+
+#[test]
+fn encode_error_frames() {
+    enc_notif(Notification::ErrorFrame(BusErrors::ACK), b"e1a\r");
+    enc_notif(
+        Notification::ErrorFrame(BusErrors::BIT0 | BusErrors::CRC | BusErrors::TX_OVERRUN),
+        b"e3bcO\r",
+    );
+    enc_notif(Notification::ErrorFrame(BusErrors::all()), b"e8abBcfoOs\r");
+    enc_notif(Notification::ErrorFrame(BusErrors::empty()), b"e0\r");
+}
+
+#[test]
+fn encode_timestamped_notifs() {
+    fn enc(notif: TimestampedNotification, expected: &[u8]) {
+        let mut buf = NotificationBuf::new();
+        let buf = notif.encode(&mut buf).unwrap();
+        assert_eq!(expected, buf);
+        assert_eq!(notif.encoded_len(), buf.len());
+    }
+
+    enc(
+        TimestampedNotification::new(
+            Notification::Rx {
+                identifier: Identifier::from_raw(0x100).unwrap(),
+                frame: [0x11, 0x33].into(),
+            },
+            0xEA5F,
+        ),
+        b"t10021133EA5F\r",
+    );
+    enc(
+        TimestampedNotification::new_micros(
+            Notification::RxExt {
+                identifier: ExtIdentifier::from_raw(0x1FFF_FFFF).unwrap(),
+                frame: [0, 1, 2, 3, 4, 5, 6, 7].into(),
+            },
+            0xDEAD_BEEF,
+        ),
+        b"T1FFFFFFF80001020304050607DEADBEEF\r",
+    );
+    enc(
+        TimestampedNotification::new(
+            Notification::RxFd {
+                identifier: Identifier::from_raw(0x123).unwrap(),
+                frame: CanFdFrame::from_slice(&[0xAA; 12]).unwrap(),
+                flags: FdFlags::BRS,
+            },
+            0x1234,
+        ),
+        b"b1239AAAAAAAAAAAAAAAAAAAAAAAA1234\r",
+    );
+}
+
+#[test]
+fn timestamped_notif_accessors() {
+    let notif = Notification::Rx {
+        identifier: Identifier::from_raw(0x100).unwrap(),
+        frame: [0x11, 0x33].into(),
+    };
+    let timestamped = TimestampedNotification::new(notif.clone(), 0x1234);
+    assert_eq!(timestamped.notification(), &notif);
+    assert_eq!(timestamped.timestamp(), Timestamp::Millis(0x1234));
+    assert_eq!(timestamped.clone(), timestamped);
+    assert_ne!(
+        timestamped,
+        TimestampedNotification::new_micros(notif, 0x1234)
+    );
+}
+
+#[test]
+fn encode_channeled() {
+    let mut buf = ResponseBuf::new();
+    assert_eq!(
+        Response::TxAck.encode_channeled(1, &mut buf).unwrap(),
+        b"1z\r"
+    );
+
+    let mut buf = NotificationBuf::new();
+    let notif = Notification::RxExt {
+        identifier: ExtIdentifier::from_raw(0x1FFF_FFFF).unwrap(),
+        frame: [0, 1, 2, 3, 4, 5, 6, 7].into(),
+    };
+    assert_eq!(
+        notif.encode_channeled(9, &mut buf).unwrap(),
+        b"9T1FFFFFFF80001020304050607\r",
+    );
+    assert_eq!(
+        TimestampedNotification::new_micros(notif, 0xDEAD_BEEF)
+            .encode_channeled(0, &mut buf)
+            .unwrap(),
+        b"0T1FFFFFFF80001020304050607DEADBEEF\r",
+    );
+
+    // Channel numbers above 9 can not be encoded.
+    let mut buf = ResponseBuf::new();
+    let err = Response::TxAck.encode_channeled(10, &mut buf).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidValue);
+    assert_eq!(err.field(), Some(Field::Channel));
+    let mut buf = CommandEncodeBuf::new();
+    let cmd = ChanneledCommand {
+        channel: 42,
+        cmd: Command::Open,
+    };
+    assert_eq!(
+        cmd.encode(&mut buf).unwrap_err().kind(),
+        ErrorKind::InvalidValue
+    );
+}
+
+#[test]
+fn encode_long_dlc() {
+    let frame = CanFrame::from([0, 1, 2, 3, 4, 5, 6, 7])
+        .with_dlc(12)
+        .unwrap();
+    enc_notif(
+        Notification::Rx {
+            identifier: Identifier::from_raw(0x123).unwrap(),
+            frame,
+        },
+        b"t123C0001020304050607\r",
+    );
+    enc_notif(
+        Notification::RxRtr {
+            identifier: Identifier::from_raw(0x123).unwrap(),
+            len: 15,
+        },
+        b"r123F\r",
+    );
+
+    assert!(CanFrame::from([0, 1, 2]).with_dlc(9).is_none());
+    assert!(frame.with_dlc(16).is_none());
+    assert_eq!(frame.with_dlc(8).unwrap().dlc(), 8);
+}
+
+#[test]
+fn encode_cmds() {
+    fn roundtrip(encoded: &[u8]) {
+        let cmd = Command::decode(encoded).unwrap();
+        let mut buf = CommandEncodeBuf::new();
+        assert_eq!(cmd.encode(&mut buf).unwrap(), encoded, "{:?}", cmd);
+        assert_eq!(cmd.opcode(), encoded[0]);
+        assert_eq!(cmd.encoded_len(), encoded.len());
+    }
+
+    for cmd in &[
+        "S0\r",
+        "S8\r",
+        "s031C\r",
+        "Y4\r",
+        "Y5\r",
+        "O\r",
+        "L\r",
+        "C\r",
+        "t7FF0\r",
+        "t1231AA\r",
+        "T1FFFFFFF80001020304050607\r",
+        "r1238\r",
+        "R000000000\r",
+        "d1230\r",
+        "b1239000102030405060708090A0B\r",
+        "D1FFFFFFF1AA\r",
+        "B000000000\r",
+        "P\r",
+        "A\r",
+        "F\r",
+        "E\r",
+        "I\r",
+        "V\r",
+        "v\r",
+        "N\r",
+        "Z0\r",
+        "Z1\r",
+        "Z2\r",
+        "X0\r",
+        "X1\r",
+        "M12345678\r",
+        "mFFFFFFFF\r",
+        "W0\r",
+        "W1\r",
+        "Q0\r",
+        "Q2\r",
+        "U0\r",
+        "U6\r",
+    ] {
+        roundtrip(cmd.as_bytes());
+    }
+
+    let mut buf = CommandEncodeBuf::new();
+    let cmd = ChanneledCommand {
+        channel: 1,
+        cmd: Command::TxExt {
+            identifier: ExtIdentifier::from_raw(0x1111_1111).unwrap(),
+            frame: (*b"ABCDEFGH").into(),
+        },
+    };
+    assert_eq!(
+        cmd.encode(&mut buf).unwrap(),
+        b"1T1111111184142434445464748\r",
+    );
+    assert_eq!(cmd.encoded_len(), 28);
+
+    roundtrip(format!("D1FFFFFFFF{}\r", "55".repeat(64)).as_bytes());
+
+    let options = crate::read::DecodeOptions::new().allow_unknown(true);
+    let cmd = Command::decode_with(b"!abc\r", options).unwrap();
+    assert_eq!(cmd.encode(&mut buf).unwrap(), b"!abc\r");
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn serde_notifs() {
+    let notifs = [
+        Notification::Rx {
+            identifier: Identifier::from_raw(0x123).unwrap(),
+            frame: CanFrame::from([0xAA, 0xBB]),
+        },
+        Notification::RxExtRtr {
+            identifier: ExtIdentifier::from_raw(0x1234_5678).unwrap(),
+            len: 4,
+        },
+        Notification::ErrorFrame(BusErrors::ACK | BusErrors::CRC),
+    ];
+    for notif in notifs.iter() {
+        let json = serde_json::to_string(notif).unwrap();
+        assert_eq!(&serde_json::from_str::<Notification>(&json).unwrap(), notif);
+    }
+
+    let responses = [
+        Response::Status(Status::BUS_ERROR),
+        Response::Serial(SerialNumber::new(*b"A1B2").unwrap()),
+        Response::DetailedVersion(VersionString::new("v1.2.3").unwrap()),
+    ];
+    for response in responses.iter() {
+        let json = serde_json::to_string(response).unwrap();
+        assert_eq!(&serde_json::from_str::<Response>(&json).unwrap(), response);
+    }
+    assert_eq!(
+        serde_json::to_string(&responses[1]).unwrap(),
+        r#"{"Serial":"A1B2"}"#
     );
+
+    assert!(serde_json::from_str::<Response>(r#"{"Serial":"A1-2"}"#).is_err());
+    assert!(serde_json::from_str::<Response>(r#"{"Status":16}"#).is_err());
+
+    let json = r#"{"Busload":{"load":101,"rx_frames":0,"tx_frames":0}}"#;
+    assert!(serde_json::from_str::<Response>(json).is_err());
+    let json = r#"{"RxRtr":{"identifier":1,"len":16}}"#;
+    assert!(serde_json::from_str::<Notification>(json).is_err());
+    assert_eq!(
+        serde_json::from_str::<Timestamp>(r#"{"Millis":59999}"#).unwrap(),
+        Timestamp::Millis(0xEA5F),
+    );
+    assert!(serde_json::from_str::<Timestamp>(r#"{"Millis":60000}"#).is_err());
+}
+
+#[test]
+fn response_for_result() {
+    fn resp(cmd: &str, ok: bool) -> Option<Response> {
+        let cmd = Command::decode(cmd.as_bytes()).unwrap();
+        Response::for_result(&cmd, if ok { Ok(()) } else { Err(()) })
+    }
+
+    assert_eq!(resp("O\r", true), Some(Response::Ack));
+    assert_eq!(resp("O\r", false), Some(Response::Error));
+    assert_eq!(resp("Z1\r", true), Some(Response::Ack));
+    assert_eq!(resp("t1230\r", true), Some(Response::TxAck));
+    assert_eq!(resp("r1230\r", true), Some(Response::TxAck));
+    assert_eq!(resp("R000000000\r", true), Some(Response::ExtTxAck));
+    assert_eq!(resp("F\r", true), None);
+    assert_eq!(resp("F\r", false), Some(Response::Error));
+    assert_eq!(resp("A\r", true), None);
+}
+
+#[test]
+fn response_opcodes() {
+    use crate::read::ResponseKind;
+
+    for (resp, kind) in &[
+        (Response::TxAck, ResponseKind::TxAck),
+        (Response::ExtTxAck, ResponseKind::ExtTxAck),
+        (Response::PollAllEnd, ResponseKind::PollAllEnd),
+        (Response::Status(Status::empty()), ResponseKind::Status),
+        (
+            Response::ErrorCounters { tec: 0, rec: 0 },
+            ResponseKind::ErrorCounters,
+        ),
+        (
+            Response::Version {
+                hardware_version: 1,
+                software_version: 2,
+            },
+            ResponseKind::Version,
+        ),
+        (
+            Response::Serial(SerialNumber::new(*b"ABCD").unwrap()),
+            ResponseKind::Serial,
+        ),
+    ] {
+        let mut buf = ResponseBuf::new();
+        assert_eq!(resp.opcode(), Some(resp.encode(&mut buf).unwrap()[0]));
+        assert!(kind.accepts(resp));
+        assert!(!kind.accepts(&Response::Ack));
+        assert!(!kind.accepts(&Response::Error));
+    }
+
+    assert_eq!(Response::Error.opcode(), None);
+    assert!(ResponseKind::PollOne.accepts(&Response::Ack));
+    assert!(ResponseKind::Any.accepts(&Response::Error));
+
+    assert_eq!(Command::Open.expected_response(), ResponseKind::Ack);
+    assert_eq!(
+        Command::PollAll.expected_response(),
+        ResponseKind::PollAllEnd
+    );
+    assert_eq!(
+        Command::ReadSerial.expected_response(),
+        ResponseKind::Serial
+    );
+}
+
+#[cfg(feature = "embedded-can")]
+#[test]
+fn notif_from_frame() {
+    use embedded_can::{ExtendedId, Id, StandardId};
+
+    struct Frame {
+        id: Id,
+        remote: bool,
+        data: &'static [u8],
+        dlc: usize,
+    }
+
+    impl embedded_can::Frame for Frame {
+        fn new(_: impl Into<Id>, _: &[u8]) -> Option<Self> {
+            unimplemented!()
+        }
+
+        fn new_remote(_: impl Into<Id>, _: usize) -> Option<Self> {
+            unimplemented!()
+        }
+
+        fn is_extended(&self) -> bool {
+            matches!(self.id, Id::Extended(_))
+        }
+
+        fn is_remote_frame(&self) -> bool {
+            self.remote
+        }
+
+        fn id(&self) -> Id {
+            self.id
+        }
+
+        fn dlc(&self) -> usize {
+            self.dlc
+        }
+
+        fn data(&self) -> &[u8] {
+            self.data
+        }
+    }
+
+    let std_id = Id::Standard(StandardId::new(0x7FF).unwrap());
+    let ext_id = Id::Extended(ExtendedId::new(0x1234).unwrap());
+    let frame = |id, remote, data: &'static [u8], dlc| Frame {
+        id,
+        remote,
+        data,
+        dlc,
+    };
+
+    enc_notif(
+        Notification::from_frame(&frame(std_id, false, &[1, 2], 2)).unwrap(),
+        b"t7FF20102\r",
+    );
+    enc_notif(
+        Notification::from_frame(&frame(ext_id, false, &[], 0)).unwrap(),
+        b"T000012340\r",
+    );
+    enc_notif(
+        Notification::from_frame(&frame(std_id, true, &[], 8)).unwrap(),
+        b"r7FF8\r",
+    );
+    enc_notif(
+        Notification::from_frame(&frame(ext_id, true, &[], 3)).unwrap(),
+        b"R000012343\r",
+    );
+
+    let mut buf = NotificationBuf::new();
+    let notif = TimestampedNotification::from_frame(
+        &frame(std_id, false, &[0xAA], 1),
+        Timestamp::Millis(0x1234),
+    )
+    .unwrap();
+    assert_eq!(notif.encode(&mut buf).unwrap(), b"t7FF1AA1234\r");
+
+    enc_notif(
+        Notification::from_frame(&frame(std_id, true, &[], 15)).unwrap(),
+        b"r7FFF\r",
+    );
+    for frame in &[
+        frame(std_id, true, &[], 16),
+        frame(std_id, false, &[1, 2], 1),
+        frame(std_id, false, &[1, 2], 3),
+        frame(std_id, false, &[0; 9], 9),
+    ] {
+        assert_eq!(
+            Notification::from_frame(frame).unwrap_err().kind(),
+            ErrorKind::InvalidLength
+        );
+    }
+}
+
+#[test]
+fn frame_conversions() {
+    for input in &[
+        &b"t1231AA\r"[..],
+        b"T1FFFFFFF80001020304050607\r",
+        b"r7FF8\r",
+        b"R000000005\r",
+        b"b1239000102030405060708090A0B\r",
+        b"D000000011AA\r",
+    ] {
+        let frame = Notification::decode(input).unwrap().to_frame().unwrap();
+
+        let mut buf = NotificationBuf::new();
+        let notif = Notification::from(frame);
+        assert_eq!(notif.encode(&mut buf).unwrap(), *input);
+
+        let cmd = Command::from(frame);
+        assert_eq!(cmd.to_frame(), Some(frame));
+    }
+
+    let frame = Command::decode(b"b1239000102030405060708090A0B\r")
+        .unwrap()
+        .to_frame()
+        .unwrap();
+    assert_eq!(frame.id.raw(), 0x123);
+    assert!(!frame.is_extended());
+    assert!(frame.is_fd());
+    assert!(!frame.is_rtr());
+    assert_eq!(frame.dlc(), 9);
+    assert_eq!(frame.data().len(), 12);
+
+    let frame = Command::decode(b"R1FFFFFFF8\r")
+        .unwrap()
+        .to_frame()
+        .unwrap();
+    assert_eq!(
+        frame.id,
+        Id::Extended(ExtIdentifier::from_raw(0x1FFF_FFFF).unwrap())
+    );
+    assert!(frame.is_extended());
+    assert!(frame.is_rtr());
+    assert_eq!(frame.dlc(), 8);
+    assert!(frame.data().is_empty());
+
+    assert!(Notification::ErrorFrame(BusErrors::ACK)
+        .to_frame()
+        .is_none());
+}
+
+#[cfg(feature = "embedded-can")]
+#[test]
+fn frame_embedded_can() {
+    use embedded_can::{ExtendedId, Frame as _, StandardId};
+
+    let frame = Frame::new(StandardId::new(0x123).unwrap(), &[0xAA]).unwrap();
+    assert_eq!(
+        embedded_can::Frame::id(&frame),
+        embedded_can::Id::Standard(StandardId::new(0x123).unwrap())
+    );
+    assert!(frame.is_data_frame());
+    enc_notif(Notification::from_frame(&frame).unwrap(), b"t1231AA\r");
+
+    let frame = Frame::new_remote(ExtendedId::new(0x1234).unwrap(), 8).unwrap();
+    assert!(frame.is_extended());
+    assert!(frame.is_remote_frame());
+    assert_eq!(embedded_can::Frame::dlc(&frame), 8);
+    enc_notif(Notification::from_frame(&frame).unwrap(), b"R000012348\r");
+
+    assert!(Frame::new(StandardId::ZERO, &[0; 9]).is_none());
+    assert!(Frame::new_remote(StandardId::ZERO, 16).is_none());
+}
+
+#[cfg(all(feature = "socketcan", target_os = "linux"))]
+#[test]
+fn frame_socketcan() {
+    use core::convert::TryFrom;
+    use socketcan::{CanAnyFrame, EmbeddedFrame as _, Frame as _};
+
+    let frame = Frame {
+        id: Identifier::new_const(0x123).into(),
+        kind: FrameKind::Data(CanFrame::from([0xAA, 0xBB])),
+    };
+    let socket_frame = socketcan::CanFrame::try_from(frame).unwrap();
+    assert_eq!(socket_frame.raw_id(), 0x123);
+    assert_eq!(socket_frame.data(), [0xAA, 0xBB]);
+    assert_eq!(Frame::try_from(socket_frame), Ok(frame));
+
+    let frame = Frame {
+        id: ExtIdentifier::new_const(0x1234).into(),
+        kind: FrameKind::Remote(4),
+    };
+    let socket_frame = CanAnyFrame::from(frame);
+    assert!(socket_frame.is_extended());
+    assert!(socket_frame.is_remote_frame());
+    assert_eq!(socket_frame.dlc(), 4);
+    assert_eq!(Frame::try_from(socket_frame).unwrap(), frame);
+
+    // SocketCAN has no classic DLCs above 8.
+    let frame = Frame {
+        kind: FrameKind::Remote(15),
+        ..frame
+    };
+    assert_eq!(CanAnyFrame::from(frame).dlc(), 8);
+
+    let frame = Frame {
+        id: Identifier::new_const(0x7FF).into(),
+        kind: FrameKind::Fd {
+            frame: CanFdFrame::from_slice(&[0x11; 12]).unwrap(),
+            flags: FdFlags::BRS,
+        },
+    };
+    assert_eq!(socketcan::CanFrame::try_from(frame), Err(frame));
+    let socket_frame = match CanAnyFrame::from(frame) {
+        CanAnyFrame::Fd(frame) => frame,
+        other => panic!("unexpected frame: {:?}", other),
+    };
+    assert!(socket_frame.is_brs());
+    assert!(!socket_frame.is_esi());
+    assert_eq!(socket_frame.dlc(), 9);
+    assert_eq!(Frame::from(socket_frame), frame);
+
+    let error =
+        socketcan::CanFrame::Error(socketcan::CanErrorFrame::from(socketcan::CanError::BusOff));
+    assert!(Frame::try_from(error).is_err());
+}
+
+#[cfg(feature = "embedded-can")]
+#[test]
+fn embedded_can_ids() {
+    use core::convert::TryFrom;
+    use embedded_can::{ExtendedId, StandardId};
+
+    let std = Identifier::from_raw(0x7FF).unwrap();
+    let ext = ExtIdentifier::from_raw(0x1FFF_FFFF).unwrap();
+    assert_eq!(StandardId::from(std), StandardId::MAX);
+    assert_eq!(ExtendedId::from(ext), ExtendedId::MAX);
+    assert_eq!(
+        embedded_can::Id::from(Id::Extended(ext)),
+        embedded_can::Id::Extended(ExtendedId::MAX)
+    );
+    assert_eq!(
+        Id::from(embedded_can::Id::Standard(StandardId::MAX)),
+        Id::Standard(std)
+    );
+
+    let id = embedded_can::Id::Standard(StandardId::MAX);
+    assert_eq!(Identifier::try_from(id), Ok(std));
+    assert_eq!(ExtIdentifier::try_from(id), Err(id));
+
+    // Arbitration priority ordering agrees with `embedded-can`.
+    let ids = [
+        Id::Standard(Identifier::new_const(0)),
+        Id::Standard(Identifier::new_const(0x100)),
+        Id::Standard(std),
+        Id::Extended(ExtIdentifier::new_const(0)),
+        Id::Extended(ExtIdentifier::new_const(0x100 << 18)),
+        Id::Extended(ExtIdentifier::new_const(0x100 << 18 | 1)),
+        Id::Extended(ext),
+    ];
+    for a in &ids {
+        for b in &ids {
+            let (ea, eb) = (embedded_can::Id::from(*a), embedded_can::Id::from(*b));
+            assert_eq!(a.cmp(b), ea.cmp(&eb), "{:?} <=> {:?}", a, b);
+        }
+    }
+}
+
+#[test]
+fn frame_from_slice() {
+    use core::convert::TryFrom;
+
+    assert_eq!(
+        CanFrame::try_from(&[1, 2][..]).unwrap(),
+        CanFrame::from([1, 2])
+    );
+    assert_eq!(CanFrame::from_slice(&[]).unwrap(), CanFrame::new());
+    assert_eq!(
+        CanFrame::from_slice(&[0; 8]).unwrap().len(),
+        CanFrame::MAX_LENGTH
+    );
+    assert_eq!(
+        CanFrame::try_from(&[0; 9][..]).unwrap_err().kind(),
+        crate::ErrorKind::InvalidLength
+    );
+
+    assert_eq!(
+        CanFdFrame::try_from(&[0xAA; 12][..]).unwrap().dlc(),
+        CanFdFrame::len_to_dlc(12).unwrap()
+    );
+    assert_eq!(
+        CanFdFrame::try_from(&[0xAA; 10][..]).unwrap_err().kind(),
+        crate::ErrorKind::InvalidLength
+    );
+    assert_eq!(
+        CanFdFrame::from_slice_padded(&[0; 65]).unwrap_err().kind(),
+        crate::ErrorKind::InvalidLength
+    );
+}
+
+#[test]
+fn frame_extend_truncate() {
+    let mut frame = CanFrame::from([1, 2]);
+    frame.extend_from_slice(&[3, 4, 5]).unwrap();
+    assert_eq!(frame.data(), [1, 2, 3, 4, 5]);
+    assert_eq!(
+        frame.extend_from_slice(&[0; 4]).unwrap_err().kind(),
+        crate::ErrorKind::InvalidLength
+    );
+    assert_eq!(frame.len(), 5);
+    frame.extend_from_slice(&[6, 7, 8]).unwrap();
+    assert_eq!(frame, CanFrame::from([1, 2, 3, 4, 5, 6, 7, 8]));
+
+    // Extending a full frame with nothing keeps its long DLC.
+    let mut long = frame.with_dlc(12).unwrap();
+    long.extend_from_slice(&[]).unwrap();
+    assert_eq!(long.dlc(), 12);
+    assert_eq!(
+        long.extend_from_slice(&[9]).unwrap_err().kind(),
+        crate::ErrorKind::InvalidLength
+    );
+    assert_eq!(long.dlc(), 12);
+
+    frame.truncate(9);
+    assert_eq!(frame.len(), 8);
+    frame.truncate(3);
+    assert_eq!(frame, CanFrame::from([1, 2, 3]));
+
+    frame.clear();
+    assert!(frame.is_empty());
+    assert_eq!(frame, CanFrame::new());
+}
+
+#[test]
+fn frame_display() {
+    assert_eq!(CanFrame::new().to_string(), "[0]");
+    assert_eq!(format!("{:x}", CanFrame::new()), "");
+
+    let frame = CanFrame::from([0x01, 0x23, 0xab]);
+    assert_eq!(frame.to_string(), "[3] 01 23 AB");
+    assert_eq!(format!("{:x}", frame), "0123ab");
+    assert_eq!(format!("{:X}", frame), "0123AB");
+
+    // Long DLCs still print the 8 bytes that are actually transmitted.
+    let frame = CanFrame::from([0; 8]).with_dlc(15).unwrap();
+    assert_eq!(frame.to_string(), "[8] 00 00 00 00 00 00 00 00");
+}
+
+#[test]
+#[cfg(feature = "embedded-io")]
+fn encode_to_writer() {
+    let notif = Notification::Rx {
+        identifier: Identifier::from_raw(0x100).unwrap(),
+        frame: [0x11, 0x33].into(),
+    };
+
+    let mut out = [0; 16];
+    let mut writer = &mut out[..];
+    Response::TxAck.encode_to(&mut writer).unwrap();
+    notif.encode_to(&mut writer).unwrap();
+    TimestampedNotification::with_timestamp(notif.clone(), Timestamp::Millis(0x1234))
+        .encode_to(&mut writer)
+        .unwrap_err();
+    // `write_all` fails once the slice is full, after writing as much as fits.
+    assert_eq!(out, *b"z\rt10021133\rt100");
+
+    let mut out = [0; 14];
+    TimestampedNotification::with_timestamp(notif, Timestamp::Millis(0x1234))
+        .encode_to(&mut out[..])
+        .unwrap();
+    assert_eq!(out, *b"t100211331234\r");
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn encode_to_std_writer() {
+    let notif = Notification::Rx {
+        identifier: Identifier::from_raw(0x100).unwrap(),
+        frame: [0x11, 0x33].into(),
+    };
+
+    let mut out = Vec::new();
+    Response::TxAck.encode_to_std(&mut out).unwrap();
+    notif.encode_to_std(&mut out).unwrap();
+    TimestampedNotification::with_timestamp(notif, Timestamp::Millis(0x1234))
+        .encode_to_std(&mut out)
+        .unwrap();
+    assert_eq!(out, b"z\rt10021133\rt100211331234\r");
+
+    let mut out = [0; 4];
+    let err = Response::Version {
+        hardware_version: 1,
+        software_version: 2,
+    }
+    .encode_to_std(&mut out[..])
+    .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::WriteZero);
+}
+
+#[test]
+#[cfg(feature = "ufmt")]
+fn frame_ufmt() {
+    use ufmt::{uDebug, uDisplay, uWrite};
+
+    struct Buf(String);
+
+    impl uWrite for Buf {
+        type Error = core::convert::Infallible;
+
+        fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+            self.0.push_str(s);
+            Ok(())
+        }
+    }
+
+    fn display(value: &impl uDisplay) -> String {
+        let mut buf = Buf(String::new());
+        ufmt::uwrite!(buf, "{}", value).unwrap();
+        buf.0
+    }
+
+    fn debug(value: &impl uDebug) -> String {
+        let mut buf = Buf(String::new());
+        ufmt::uwrite!(buf, "{:?}", value).unwrap();
+        buf.0
+    }
+
+    let frame = CanFrame::from([0x01, 0x23, 0xab]);
+    assert_eq!(display(&frame), frame.to_string());
+    assert_eq!(debug(&frame), "CanFrame { data: [1, 35, 171], dlc: 3 }");
+
+    let frame = CanFdFrame::from_slice(&[0xAA; 12]).unwrap();
+    assert_eq!(display(&frame), "[12] AA AA AA AA AA AA AA AA AA AA AA AA");
+
+    let id = Identifier::from_raw(0x12).unwrap();
+    assert_eq!(display(&id), "0x012");
+    assert_eq!(debug(&id), format!("{:?}", id));
+    let id = ExtIdentifier::from_raw(0x1234_5678).unwrap();
+    assert_eq!(display(&id), format!("{:?}", id));
+
+    let cmd = Command::decode(b"T12345678200FF\r").unwrap();
+    assert_eq!(
+        debug(&cmd),
+        "TxExt { identifier: 0x12345678, frame: CanFrame { data: [0, 255], dlc: 2 } }"
+    );
+    let cmd = Command::decode(b"b1230\r").unwrap();
+    assert_eq!(
+        debug(&cmd),
+        "TxFd { identifier: 0x123, frame: CanFdFrame { data: [], dlc: 0 }, flags: BRS }"
+    );
+    assert_eq!(debug(&Command::Open), "Open");
+}
+
+#[test]
+fn frame_signals() {
+    use crate::ByteOrder::{BigEndian, LittleEndian};
+
+    let mut frame = CanFrame::from([0; 8]);
+    frame.set_signal(4, 12, LittleEndian, 0xABC).unwrap();
+    assert_eq!(frame.data(), [0xC0, 0xAB, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(frame.get_signal(4, 12, LittleEndian), Some(0xABC));
+    assert_eq!(frame.get_signal(8, 4, LittleEndian), Some(0xB));
+
+    // Big endian signals continue at the MSB of the following byte.
+    frame.clear();
+    frame.extend_from_slice(&[0; 8]).unwrap();
+    frame.set_signal(19, 12, BigEndian, 0xABC).unwrap();
+    assert_eq!(frame.data(), [0, 0, 0x0A, 0xBC, 0, 0, 0, 0]);
+    assert_eq!(frame.get_signal(19, 12, BigEndian), Some(0xABC));
+    assert_eq!(
+        frame.get_signal(7, 64, BigEndian),
+        Some(0x0000_0ABC_0000_0000)
+    );
+
+    // Excess value bits are ignored, neighbouring bits are preserved.
+    frame.set_signal(17, 2, LittleEndian, 0xFF).unwrap();
+    assert_eq!(frame.data()[2], 0x0E);
+
+    // Out of range signals.
+    assert_eq!(frame.get_signal(0, 0, LittleEndian), None);
+    assert_eq!(frame.get_signal(0, 65, LittleEndian), None);
+    assert_eq!(frame.get_signal(60, 8, LittleEndian), None);
+    assert_eq!(frame.get_signal(3, 5, BigEndian), Some(0));
+    assert_eq!(frame.get_signal(59, 5, BigEndian), None);
+    assert_eq!(
+        frame.set_signal(63, 2, LittleEndian, 0).unwrap_err().kind(),
+        crate::ErrorKind::InvalidLength
+    );
+    assert_eq!(CanFrame::new().get_signal(0, 1, LittleEndian), None);
+    assert_eq!(frame.get_signal(usize::MAX, 2, LittleEndian), None);
+    assert_eq!(frame.get_signal(usize::MAX - 7, 2, BigEndian), None);
+    assert_eq!(
+        frame
+            .set_signal(usize::MAX, 64, LittleEndian, 0)
+            .unwrap_err()
+            .kind(),
+        crate::ErrorKind::InvalidLength
+    );
+
+    let mut frame = CanFdFrame::from_slice(&[0; 12]).unwrap();
+    frame.set_signal(80, 16, LittleEndian, 0x1234).unwrap();
+    assert_eq!(frame.data()[10..], [0x34, 0x12]);
+    assert_eq!(frame.get_signal(87, 16, BigEndian), Some(0x3412));
+    assert_eq!(frame.get_signal(88, 16, LittleEndian), None);
+}
+
+#[test]
+fn frame_macros() {
+    use crate::read::Command;
+
+    const ID: u16 = 0x7FF;
+    assert_eq!(
+        frame!(ID, []),
+        Frame {
+            id: Id::Standard(Identifier::from_raw(0x7FF).unwrap()),
+            kind: FrameKind::Data(CanFrame::new()),
+        }
+    );
+    assert_eq!(
+        frame!(ext 0x1FFF_FFFF, [1, 2, 3, 4, 5, 6, 7, 8,])
+            .data()
+            .len(),
+        8
+    );
+    assert_eq!(frame!(0x100, rtr 8).kind, FrameKind::Remote(8));
+
+    assert_eq!(
+        cmd!(tx ext 0x100, rtr 2),
+        Command::TxExtRtr {
+            identifier: ExtIdentifier::from_raw(0x100).unwrap(),
+            len: 2,
+        }
+    );
+    assert_eq!(cmd!(ReadSerial), Command::ReadSerial);
+}
+
+#[test]
+fn writer() {
+    let mut buf = [0; 8];
+    let mut writer = Writer::new(&mut buf);
+    assert!(writer.is_empty());
+    writer.write_channel(3).unwrap();
+    writer.write_hex(0xABCDE, 5).unwrap();
+    assert_eq!(
+        writer.write_hex(0, 9).unwrap_err().kind(),
+        ErrorKind::InvalidLength
+    );
+    writer.write_bool(true).unwrap();
+    assert_eq!(writer.len(), 7);
+    assert_eq!(writer.finish().unwrap(), b"3ABCDE1\r");
+
+    // Running out of space is an error.
+    let mut buf = [0; 2];
+    let mut writer = Writer::new(&mut buf);
+    writer.write_hex_u8(0x12).unwrap();
+    assert_eq!(writer.write(b'x').unwrap_err().kind(), ErrorKind::Eof);
+    assert_eq!(writer.finish().unwrap_err().kind(), ErrorKind::Eof);
 }